@@ -5,7 +5,9 @@ echo "Building for WebGPU..."
 RUSTFLAGS=--cfg=web_sys_unstable_apis cargo build \
     --target wasm32-unknown-unknown \
     --release \
-    --lib
+    --lib \
+    --no-default-features \
+    --features wasm-viewer
 
 echo "Running wasm-bindgen..."
 wasm-bindgen \
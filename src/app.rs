@@ -1,202 +1,1939 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::sync::Arc;
 
-use glam::Vec2;
+use glam::{Vec2, Vec3};
 use winit::application::ApplicationHandler;
-use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::event::{DeviceEvent, DeviceId, ElementState, MouseScrollDelta, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, EventLoop};
 use winit::keyboard::{KeyCode, PhysicalKey};
-use winit::window::{Window, WindowId};
+use winit::window::{CursorGrabMode, Fullscreen, Window, WindowId};
 
-use crate::camera::Camera;
-use crate::gpu::GpuState;
-use crate::input::InputState;
+use crate::attract::Attract;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::bench::{Bench, BenchOptions};
+#[cfg(target_arch = "wasm32")]
+use crate::builder::DEFAULT_SEED;
+use crate::builder::{VendekBuilder, MAX_PHASES};
+use crate::camera::{CameraMode, CameraPose};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::config::{Config, FullscreenMode, PresentMode};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::control::{ControlCommand, StdinControl};
+use crate::engine::VendekEngine;
+use crate::gpu::{AdapterOptions, GpuError, GpuState, LoadStage};
+use crate::hud::{Hud, HudConfig};
+use crate::input::{self, Action, Binding, InputMap, InputState};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::osc::{OscCommand, OscListener};
+use crate::overlay::FrameHistory;
+use crate::pacer::FramePacer;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::params::MAX_CLIP_PLANES;
+use crate::params::{ClipPlane, RenderMode, RuntimeParams};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::preset::Preset;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::reload::WatchedFile;
+use crate::resolution::DynamicResolution;
+use crate::script::{Frame, Script};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::session::{SessionRecorder, SessionWorld};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::settings::{Settings, WindowPlacement};
+use crate::timeline::Timeline;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::video::{RecordingConfig, VideoRecorder};
 use crate::world::HoneycombWorld;
 
-// World generation constants
-const CELL_COUNT: usize = 128;
-const PHASE_COUNT: usize = 12;
-const WORLD_SEED: u64 = 42;
+/// Most cells a page may ask for; beyond this the world outgrows the 64 KiB
+/// of uniforms adapters commonly allow
+#[cfg(target_arch = "wasm32")]
+const MAX_CELLS: usize = 4000;
+
+// Fly camera movement speed in world units per second
+const FLY_SPEED: f32 = 6.0;
+
+// Factor the cell count keys grow and shrink the world by
+const CELL_COUNT_STEP: f32 = 1.25;
+
+// Clipping plane sweep speed in world units per second
+const SWEEP_SPEED: f32 = 4.0;
 
 struct AppState {
     window: Arc<Window>,
-    gpu: GpuState,
-    camera: Camera,
+    /// The world, its simulations, the camera and the parameters, drawn
+    /// into the window
+    engine: VendekEngine,
     input: InputState,
-    #[allow(dead_code)]
-    world: HoneycombWorld,
-    time: f32,
+    input_map: InputMap,
+    resolution: DynamicResolution,
+    /// Frame script moving parameters and the camera
+    script: Option<Script>,
+    /// Where the native viewer's script comes from
+    #[cfg(not(target_arch = "wasm32"))]
+    script_file: Option<WatchedFile>,
+    /// Keyframed animation playing, and how far into it
+    timeline: Option<Timeline>,
+    timeline_time: f32,
+    /// Where the native viewer's timeline comes from
+    #[cfg(not(target_arch = "wasm32"))]
+    timeline_file: Option<WatchedFile>,
+    /// Takes over the camera when nobody has touched the viewer for a while
+    attract: Attract,
+    /// Camera poses to come back to, and the one to visit next
+    bookmarks: Vec<CameraPose>,
+    next_bookmark: usize,
+    /// Camera as it was at startup, for resetting
+    startup_pose: CameraPose,
+    /// Exponentially smoothed frames per second
+    fps: f32,
+    frame_history: FrameHistory,
+    show_stats: bool,
+    /// Caption and readouts over the frame
+    hud: Hud,
     last_frame: web_time::Instant,
+    /// Schedules frames under `max_fps`
+    pacer: FramePacer,
+    /// The window is covered, or on the web its tab is hidden or the canvas
+    /// scrolled out of view
+    occluded: bool,
+    minimized: bool,
+    /// Input or a message arrived that the next frame should answer;
+    /// cleared by drawing it
+    dirty: bool,
+    /// Frames were skipped since the last one, rendering on demand
+    idle: bool,
+    /// What the fullscreen toggle switches to; browsers only have
+    /// borderless fullscreen
+    #[cfg(not(target_arch = "wasm32"))]
+    fullscreen_mode: FullscreenMode,
+    /// Window size before going fullscreen, restored when leaving it
+    #[cfg(not(target_arch = "wasm32"))]
+    windowed_size: Option<winit::dpi::PhysicalSize<u32>>,
+    /// Scripted camera and timing collection for `vendek bench`
+    #[cfg(not(target_arch = "wasm32"))]
+    bench: Option<Bench>,
+    /// Control messages from show-control software
+    #[cfg(not(target_arch = "wasm32"))]
+    osc: Option<OscListener>,
+    /// JSON commands from stdin
+    #[cfg(not(target_arch = "wasm32"))]
+    control: Option<StdinControl>,
+    /// Screenshots asked for on stdin, written after the next frame: the
+    /// command's id and the path, if it gave one
+    #[cfg(not(target_arch = "wasm32"))]
+    control_screenshots: Vec<(Option<serde_json::Value>, Option<std::path::PathBuf>)>,
+    /// Video being recorded, and how recordings are made
+    #[cfg(not(target_arch = "wasm32"))]
+    recording: Option<VideoRecorder>,
+    #[cfg(not(target_arch = "wasm32"))]
+    recording_config: RecordingConfig,
+    /// Session log being written
+    #[cfg(not(target_arch = "wasm32"))]
+    session: Option<SessionRecorder>,
+    #[cfg(target_arch = "wasm32")]
+    web: WebViewer,
 }
 
-enum AppPhase {
-    Uninitialized,
-    Initializing { window: Arc<Window> },
-    Running(AppState),
-}
+impl AppState {
+    fn new(
+        window: Arc<Window>,
+        gpu: GpuState,
+        world: HoneycombWorld,
+        builder: &VendekBuilder,
+        input_map: InputMap,
+        #[cfg(target_arch = "wasm32")] web: WebViewer,
+    ) -> Self {
+        Self {
+            window,
+            engine: VendekEngine::from_parts(gpu, world, builder.clone()),
+            input: InputState::new(),
+            input_map,
+            resolution: DynamicResolution::new(),
+            script: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            script_file: None,
+            timeline: None,
+            timeline_time: 0.0,
+            #[cfg(not(target_arch = "wasm32"))]
+            timeline_file: None,
+            attract: Attract::new(0.0),
+            bookmarks: Vec::new(),
+            next_bookmark: 0,
+            startup_pose: builder.camera_pose(),
+            fps: 0.0,
+            frame_history: FrameHistory::new(),
+            show_stats: false,
+            hud: Hud::new(HudConfig::default()),
+            last_frame: web_time::Instant::now(),
+            pacer: FramePacer::default(),
+            occluded: false,
+            minimized: false,
+            dirty: true,
+            idle: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            fullscreen_mode: FullscreenMode::Borderless,
+            #[cfg(not(target_arch = "wasm32"))]
+            windowed_size: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            bench: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            osc: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            control: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            control_screenshots: Vec::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            recording: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            recording_config: RecordingConfig::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            session: None,
+            #[cfg(target_arch = "wasm32")]
+            web,
+        }
+    }
 
-struct App {
-    phase: AppPhase,
-}
+    fn handle_action(&mut self, action: Action, event_loop: &ActiveEventLoop) {
+        match action {
+            Action::Quit => event_loop.exit(),
+            Action::TogglePause => {
+                self.engine.paused = !self.engine.paused;
+                tracing::info!(
+                    "{}",
+                    if self.engine.paused {
+                        "Paused"
+                    } else {
+                        "Resumed"
+                    }
+                );
+            }
+            Action::Screenshot => self.save_screenshot(),
+            Action::ToggleRecording => self.toggle_recording(),
+            Action::ToggleSession => self.toggle_session(),
+            Action::ToggleStats => self.show_stats = !self.show_stats,
+            Action::ToggleFullscreen => self.toggle_fullscreen(),
+            Action::ToggleSplitView => {
+                self.update_params(|params| params.split_view = !params.split_view)
+            }
+            Action::ToggleAxes => self.update_params(|params| params.show_axes = !params.show_axes),
+            Action::ToggleBounds => {
+                self.update_params(|params| params.show_bounds = !params.show_bounds)
+            }
+            Action::ToggleGrid => self.update_params(|params| params.show_grid = !params.show_grid),
+            Action::ToggleLowPower => {
+                self.update_params(|params| params.low_power = !params.low_power)
+            }
+            Action::SweepClipPlane => self.toggle_sweep(),
+            Action::CycleRenderMode => {
+                self.update_params(|params| params.render_mode = params.render_mode.next())
+            }
+            Action::ToggleMembranes => self.update_params(|params| {
+                params.render_mode = match params.render_mode {
+                    RenderMode::Membranes => RenderMode::Volume,
+                    _ => RenderMode::Membranes,
+                }
+            }),
+            Action::CycleDebugView => {
+                self.update_params(|params| params.debug_view = params.debug_view.next())
+            }
+            Action::AddBookmark => {
+                self.bookmarks.push(self.engine.camera.target_pose());
+                tracing::info!("Bookmarked the view ({} in all)", self.bookmarks.len());
+                #[cfg(target_arch = "wasm32")]
+                if self.web.storage.is_some() {
+                    crate::storage::save_bookmarks(&self.bookmarks);
+                }
+            }
+            Action::NextBookmark => {
+                if let Some(&pose) = self.bookmarks.get(self.next_bookmark) {
+                    self.engine.camera.glide_to(pose);
+                    self.next_bookmark = (self.next_bookmark + 1) % self.bookmarks.len();
+                }
+            }
+            Action::NewWorld => self.regenerate(self.engine.recipe.clone().seed(rand::random())),
+            Action::MoreCells => {
+                let (cells, _) = self.engine.recipe.counts();
+                self.set_cell_count(((cells as f32 * CELL_COUNT_STEP) as usize).max(cells + 1));
+            }
+            Action::FewerCells => {
+                let cells = self.engine.recipe.counts().0 as f32;
+                self.set_cell_count((cells / CELL_COUNT_STEP) as usize);
+            }
+            Action::MorePhases => self.set_phase_count(self.engine.world.phases.len() + 1),
+            Action::FewerPhases => {
+                self.set_phase_count(self.engine.world.phases.len().saturating_sub(1))
+            }
+            Action::ResetView => {
+                let params = self.engine.initial_params;
+                self.update_params(|current| *current = params);
+                self.engine.camera.glide_to(self.startup_pose);
+            }
+            Action::ToggleFly => {
+                self.engine.camera.mode = match self.engine.camera.mode {
+                    CameraMode::Orbit => CameraMode::Fly,
+                    CameraMode::Fly => CameraMode::Orbit,
+                };
+                self.set_pointer_lock(self.engine.camera.mode == CameraMode::Fly);
+            }
+            // Held actions are polled each frame in update_camera
+            Action::Orbit
+            | Action::Pan
+            | Action::Zoom
+            | Action::MoveForward
+            | Action::MoveBack
+            | Action::MoveLeft
+            | Action::MoveRight
+            | Action::MoveUp
+            | Action::MoveDown
+            | Action::OrbitLeft
+            | Action::OrbitRight
+            | Action::OrbitUp
+            | Action::OrbitDown
+            | Action::ZoomIn
+            | Action::ZoomOut => {}
+        }
+        if let Some(text) = self.readout(action) {
+            self.hud.show_readout(text);
+        }
+    }
 
-impl App {
-    fn new() -> Self {
-        Self {
-            phase: AppPhase::Uninitialized,
+    /// What `action`, just taken, changed, for the readout
+    fn readout(&self, action: Action) -> Option<String> {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        let params = self.engine.params.get();
+        Some(match action {
+            Action::TogglePause => (if self.engine.paused {
+                "Paused"
+            } else {
+                "Playing"
+            })
+            .to_string(),
+            Action::ToggleSplitView => format!("Split view {}", on_off(params.split_view)),
+            Action::ToggleAxes => format!("Axes {}", on_off(params.show_axes)),
+            Action::ToggleBounds => format!("Bounds {}", on_off(params.show_bounds)),
+            Action::ToggleGrid => format!("Grid {}", on_off(params.show_grid)),
+            Action::ToggleLowPower => format!("Low power {}", on_off(params.low_power)),
+            Action::CycleRenderMode | Action::ToggleMembranes => {
+                format!("Render mode: {:?}", params.render_mode)
+            }
+            Action::CycleDebugView => format!("Debug view: {:?}", params.debug_view),
+            Action::AddBookmark => format!("Bookmark {} added", self.bookmarks.len()),
+            Action::NextBookmark if !self.bookmarks.is_empty() => {
+                let shown = (self.next_bookmark + self.bookmarks.len() - 1) % self.bookmarks.len();
+                format!("Bookmark {} of {}", shown + 1, self.bookmarks.len())
+            }
+            Action::NewWorld => format!("Seed {}", self.engine.world.seed),
+            Action::MoreCells | Action::FewerCells => {
+                format!("{} cells", self.engine.recipe.counts().0)
+            }
+            Action::MorePhases | Action::FewerPhases => {
+                format!("{} phases", self.engine.world.phases.len())
+            }
+            Action::ResetView => "View reset".to_string(),
+            Action::ToggleFly => match self.engine.camera.mode {
+                CameraMode::Orbit => "Orbit camera".to_string(),
+                CameraMode::Fly => "Fly camera".to_string(),
+            },
+            _ => return None,
+        })
+    }
+
+    /// Enter or leave fullscreen. On the web winit goes through the
+    /// Fullscreen API, which the browser only allows from a key or click.
+    fn toggle_fullscreen(&mut self) {
+        if self.window.fullscreen().is_some() {
+            self.window.set_fullscreen(None);
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(size) = self.windowed_size.take() {
+                let _ = self.window.request_inner_size(size);
+            }
+            return;
         }
+
+        #[cfg(target_arch = "wasm32")]
+        let exclusive = None;
+        #[cfg(not(target_arch = "wasm32"))]
+        let exclusive = {
+            self.windowed_size = Some(self.window.inner_size());
+            match self.fullscreen_mode {
+                FullscreenMode::Exclusive => self.window.current_monitor().and_then(|monitor| {
+                    monitor.video_modes().max_by_key(|mode| {
+                        let size = mode.size();
+                        (size.width * size.height, mode.refresh_rate_millihertz())
+                    })
+                }),
+                FullscreenMode::Borderless => None,
+            }
+        };
+        self.window.set_fullscreen(Some(match exclusive {
+            Some(mode) => Fullscreen::Exclusive(mode),
+            None => Fullscreen::Borderless(None),
+        }));
     }
-}
 
-impl ApplicationHandler for App {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        // Only initialize if we haven't started yet
-        if !matches!(self.phase, AppPhase::Uninitialized) {
+    /// Hide and lock the cursor so mouse-look is unbounded. On the web winit
+    /// forwards `Locked` to the Pointer Lock API; platforms without locking
+    /// fall back to confining the cursor to the window.
+    fn set_pointer_lock(&self, locked: bool) {
+        if locked {
+            if self.window.set_cursor_grab(CursorGrabMode::Locked).is_err() {
+                if let Err(e) = self.window.set_cursor_grab(CursorGrabMode::Confined) {
+                    tracing::warn!("Pointer lock unavailable: {}", e);
+                }
+            }
+        } else {
+            let _ = self.window.set_cursor_grab(CursorGrabMode::None);
+        }
+        self.window.set_cursor_visible(!locked);
+    }
+
+    /// Apply this frame's accumulated mouse motion and held movement keys
+    /// to where the camera is heading
+    fn steer_camera(&mut self, dt: f32) {
+        let delta = self.input.mouse_delta;
+        let held = |action| self.input_map.is_held(&self.input, action);
+        let axis = |positive, negative| (held(positive) as i32 - held(negative) as i32) as f32;
+        let movement = Vec3::new(
+            axis(Action::MoveRight, Action::MoveLeft),
+            axis(Action::MoveUp, Action::MoveDown),
+            axis(Action::MoveForward, Action::MoveBack),
+        );
+
+        match self.engine.camera.mode {
+            CameraMode::Orbit => {
+                if held(Action::Orbit) {
+                    self.engine.camera.orbit(delta);
+                } else if held(Action::Pan) {
+                    self.engine.camera.pan(delta);
+                } else if held(Action::Zoom) {
+                    self.engine.camera.zoom(-delta.y * 0.05);
+                }
+
+                let orbit = Vec2::new(
+                    axis(Action::OrbitRight, Action::OrbitLeft),
+                    axis(Action::OrbitUp, Action::OrbitDown),
+                );
+                let zoom = axis(Action::ZoomIn, Action::ZoomOut);
+                self.engine.camera.navigate(movement, orbit, zoom, dt);
+            }
+            CameraMode::Fly => {
+                self.engine.camera.look(delta);
+                self.engine.camera.translate(movement * FLY_SPEED * dt);
+            }
+        }
+    }
+
+    /// Replace the world with one generated from `recipe`, and start the
+    /// simulations over on it. On error the current world stays.
+    fn regenerate(&mut self, recipe: VendekBuilder) {
+        #[cfg(target_arch = "wasm32")]
+        let counts = (
+            self.engine.world.cells.len(),
+            self.engine.world.phases.len(),
+        );
+        if let Err(e) = self.engine.regenerate(recipe) {
+            tracing::warn!("Keeping the current world: {}", e);
+            #[cfg(target_arch = "wasm32")]
+            self.web.link.notify_error(&e.to_string());
+            return;
+        }
+        let world = &self.engine.world;
+        #[cfg(target_arch = "wasm32")]
+        {
+            let resized = (world.cells.len(), world.phases.len()) != counts;
+            if let Some(hash) = &mut self.web.hash {
+                hash.set_world(
+                    world.seed,
+                    resized.then_some((world.cells.len(), world.phases.len())),
+                );
+            }
+            self.web
+                .link
+                .set_world(world.seed, world.cells.len(), world.phases.len());
+        }
+        tracing::info!(
+            "Generated a world of {} cells in {} phases from seed {}",
+            world.cells.len(),
+            world.phases.len(),
+            world.seed
+        );
+        // A session plays back in the world it started in
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.session.is_some() {
+            tracing::warn!("The world changed; ending the session recording");
+            self.stop_session();
+        }
+    }
+
+    /// Regenerate the world with `cells` cells, per chunk in a streamed
+    /// world, up to what the platform allows
+    fn set_cell_count(&mut self, cells: usize) {
+        #[cfg(target_arch = "wasm32")]
+        let cells = cells.min(MAX_CELLS);
+        let cells = cells.max(1);
+        if cells != self.engine.recipe.counts().0 {
+            self.regenerate(self.engine.recipe.clone().cells(cells));
+        }
+    }
+
+    /// Regenerate the world with `phases` phases, 1 to `MAX_PHASES`
+    fn set_phase_count(&mut self, phases: usize) {
+        let phases = phases.clamp(1, MAX_PHASES);
+        if phases != self.engine.world.phases.len() {
+            self.regenerate(self.engine.recipe.clone().phases(phases));
+        }
+    }
+
+    /// Start a clipping plane facing the camera at the near side of the
+    /// volume, moving away, or remove it if one is already there
+    fn toggle_sweep(&mut self) {
+        let toward_camera =
+            (self.engine.camera.position() - self.engine.camera.focus).normalize_or(Vec3::Z);
+        let bounds = self.engine.world.bounds;
+        self.update_params(|params| {
+            params.clip_planes[0] = match params.clip_planes[0] {
+                Some(_) => None,
+                None => {
+                    let mut plane = ClipPlane {
+                        position: bounds.center(),
+                        normal: toward_camera,
+                        speed: SWEEP_SPEED,
+                    };
+                    // Past the near side, where the plane turns around and sweeps away
+                    plane.advance(bounds.size().max_element(), bounds.min, bounds.max);
+                    Some(plane)
+                }
+            };
+        });
+    }
+
+    /// Apply the OSC commands received since the last frame
+    #[cfg(not(target_arch = "wasm32"))]
+    fn apply_osc(&mut self) {
+        let Some(osc) = &self.osc else {
+            return;
+        };
+        for command in osc.take_commands() {
+            match command {
+                OscCommand::SetParam(name, value) => {
+                    if let Err(e) = self.engine.params.set(&name, value) {
+                        tracing::warn!("OSC: {}", e);
+                    }
+                }
+                OscCommand::Orbit(delta) => self.engine.camera.orbit(delta),
+                OscCommand::Pan(delta) => self.engine.camera.pan(delta),
+                OscCommand::Zoom(amount) => self.engine.camera.zoom(amount),
+            }
+        }
+    }
+
+    /// Apply the commands read from stdin since the last frame, answering
+    /// each but screenshots, which wait for the frame
+    #[cfg(not(target_arch = "wasm32"))]
+    fn apply_control(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(control) = &self.control else {
             return;
+        };
+        for request in control.take_requests() {
+            let result = match request.command {
+                ControlCommand::Set { params } => {
+                    // Set on a copy, so an unknown name changes nothing
+                    let mut updated = self.engine.params.get();
+                    params
+                        .iter()
+                        .try_for_each(|(name, &value)| updated.set(name, value))
+                        .map(|()| self.engine.params.replace(updated))
+                        .map_err(|e| e.to_string())
+                }
+                ControlCommand::Camera {
+                    focus,
+                    yaw,
+                    pitch,
+                    distance,
+                    glide,
+                } => {
+                    let current = self.engine.camera.target_pose();
+                    // Held where input can go, as `glide_to` holds it, so a
+                    // pitch straight up or a zero distance can't leave the
+                    // view matrix degenerate
+                    let pose = CameraPose {
+                        focus: focus.map_or(current.focus, Vec3::from_array),
+                        yaw: yaw.unwrap_or(current.yaw),
+                        pitch: pitch.unwrap_or(current.pitch),
+                        distance: distance.unwrap_or(current.distance),
+                    }
+                    .clamped();
+                    if glide {
+                        self.engine.camera.glide_to(pose);
+                    } else {
+                        self.engine
+                            .camera
+                            .jump_to(pose.focus, pose.yaw, pose.pitch, pose.distance);
+                    }
+                    Ok(())
+                }
+                ControlCommand::Screenshot { path } => {
+                    self.control_screenshots.push((request.id, path));
+                    continue;
+                }
+                ControlCommand::Quit => {
+                    crate::control::reply(request.id.as_ref(), Ok(Default::default()));
+                    event_loop.exit();
+                    continue;
+                }
+            };
+            crate::control::reply(request.id.as_ref(), result.map(|()| Default::default()));
         }
+    }
 
-        let window_attributes = Window::default_attributes().with_title("Vendek - Far Side Explorer");
+    /// Write the screenshots asked for on stdin from the frame just drawn
+    #[cfg(not(target_arch = "wasm32"))]
+    fn finish_control_screenshots(&mut self) {
+        if self.control_screenshots.is_empty() {
+            return;
+        }
+        let pixels = self.engine.gpu.capture_frame();
+        let size = self.engine.gpu.size;
+        for (id, path) in std::mem::take(&mut self.control_screenshots) {
+            let path = path.unwrap_or_else(crate::capture::screenshot_path);
+            let result = crate::capture::save_png(&path, size.width, size.height, &pixels)
+                .map(|()| {
+                    let mut fields = serde_json::Map::new();
+                    fields.insert("path".to_string(), path.display().to_string().into());
+                    fields
+                })
+                .map_err(|e| format!("could not write {}: {}", path.display(), e));
+            crate::control::reply(id.as_ref(), result);
+        }
+    }
 
+    /// Let the frame script change parameters and steer the camera
+    fn run_script(&mut self, dt: f32) {
         #[cfg(not(target_arch = "wasm32"))]
-        let window_attributes =
-            window_attributes.with_inner_size(winit::dpi::PhysicalSize::new(1280, 720));
+        if let Some(script) = self
+            .script_file
+            .as_mut()
+            .and_then(|file| file.poll(Script::parse))
+        {
+            self.script = Some(script);
+        }
+        #[cfg(target_arch = "wasm32")]
+        if let Some(script) = self.web.link.take_script() {
+            self.script = script;
+        }
+        let Some(script) = &mut self.script else {
+            return;
+        };
+
+        let mut params = self.engine.params.get();
+        let mut pose = self.engine.camera.target_pose();
+        script.run(&mut Frame {
+            time: self.engine.time,
+            dt,
+            params: &mut params,
+            camera: &mut pose,
+            world: &mut self.engine.world,
+        });
+        if params != self.engine.params.get() {
+            self.update_params(|current| *current = params);
+        }
+        if pose != self.engine.camera.target_pose() {
+            self.engine.camera.glide_to(pose);
+        }
+    }
+
+    /// Play the keyframe timeline, from the start whenever a new one arrives.
+    /// Once a timeline that doesn't loop is over, what it set stays and the
+    /// controls are free again.
+    fn play_timeline(&mut self, dt: f32) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(timeline) = self
+            .timeline_file
+            .as_mut()
+            .and_then(|file| file.poll(Timeline::parse))
+        {
+            self.timeline = Some(timeline);
+            self.timeline_time = 0.0;
+        }
+        #[cfg(target_arch = "wasm32")]
+        if let Some(timeline) = self.web.link.take_timeline() {
+            self.timeline = timeline;
+            self.timeline_time = 0.0;
+        }
+        let Some(timeline) = &self.timeline else {
+            return;
+        };
+        let length = timeline.length();
+        if self.timeline_time > length {
+            return;
+        }
+        let time = self.timeline_time;
+        let next = self.timeline_time + dt;
+        self.timeline_time = if timeline.is_looping() && length > 0.0 {
+            next % length
+        } else if self.timeline_time < length {
+            // The end itself is always played, however large the step over it
+            next.min(length)
+        } else {
+            next
+        };
 
-        let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
+        let mut params = self.engine.params.get();
+        let mut pose = self.engine.camera.target_pose();
+        timeline.apply(time, &mut params, &mut pose);
+        let moves_camera = timeline.moves_camera();
+        if params != self.engine.params.get() {
+            self.update_params(|current| *current = params);
+        }
+        // Exactly where the keys say, so renders of a timeline repeat
+        if moves_camera && pose != self.engine.camera.target_pose() {
+            self.engine
+                .camera
+                .jump_to(pose.focus, pose.yaw, pose.pitch, pose.distance);
+        }
+    }
 
+    /// Let attract mode take over once the viewer has been left alone
+    fn run_attract(&mut self, dt: f32) {
         #[cfg(target_arch = "wasm32")]
+        if let Some(seconds) = self.web.link.take_attract() {
+            if let Some(params) = self.attract.set_idle_after(seconds) {
+                self.update_params(|current| *current = params);
+            }
+        }
+        let mut params = self.engine.params.get();
+        let mut pose = self.engine.camera.target_pose();
+        if !self
+            .attract
+            .update(dt, &mut params, &mut pose, &self.engine.world.bounds)
         {
-            use winit::platform::web::WindowExtWebSys;
+            return;
+        }
+        if params != self.engine.params.get() {
+            self.update_params(|current| *current = params);
+        }
+        if self.engine.camera.mode == CameraMode::Fly {
+            self.engine.camera.mode = CameraMode::Orbit;
+            self.set_pointer_lock(false);
+        }
+        self.engine.camera.glide_to(pose);
+    }
 
-            let canvas = window.canvas().unwrap();
+    /// Note that someone used the viewer, ending attract mode and putting
+    /// the parameters back as they were. Returns whether it ended, in which
+    /// case the input only wakes the viewer.
+    fn user_input(&mut self) -> bool {
+        let Some(params) = self.attract.input() else {
+            return false;
+        };
+        self.update_params(|current| *current = params);
+        tracing::info!("Attract mode ended");
+        true
+    }
 
-            // Get target container
-            let web_window = web_sys::window().unwrap();
-            let document = web_window.document().unwrap();
+    /// Let the page know when input moved the camera, so it can share the view
+    #[cfg(target_arch = "wasm32")]
+    fn report_camera(&mut self) {
+        let pose = self.engine.camera.target_pose();
+        self.web.link.set_camera_pose(pose);
+        if !pose.is_close(&self.web.camera_pose) {
+            self.web.camera_pose = pose;
+            self.web.link.notify_camera(pose);
+        }
+    }
 
-            // Set canvas size BEFORE attaching to DOM
-            let width = web_window.inner_width().unwrap().as_f64().unwrap() as u32;
-            let height = web_window.inner_height().unwrap().as_f64().unwrap() as u32;
-            let width = width.max(100);
-            let height = height.max(100);
+    /// Change parameters from within the viewer
+    fn update_params(&mut self, change: impl FnOnce(&mut RuntimeParams)) {
+        self.engine.params.update(change);
+        #[cfg(target_arch = "wasm32")]
+        self.web.link.set_params(self.engine.params.get());
+    }
 
-            canvas.set_width(width);
-            canvas.set_height(height);
+    /// Whether none of the viewer can be seen, in which case it draws
+    /// nothing and its clock stops
+    fn hidden(&self) -> bool {
+        self.occluded || self.minimized
+    }
 
-            // Set explicit style dimensions too
-            let style = canvas.style();
-            let _ = style.set_property("width", &format!("{}px", width));
-            let _ = style.set_property("height", &format!("{}px", height));
+    /// Note the window being covered or minimized, or showing again. The
+    /// clock picks up where it stopped rather than jumping ahead by the
+    /// time away.
+    fn set_hidden(&mut self, occluded: bool, minimized: bool) {
+        let was_hidden = self.hidden();
+        self.occluded = occluded;
+        self.minimized = minimized;
+        if self.hidden() == was_hidden {
+            return;
+        }
+        if self.hidden() {
+            tracing::debug!("Hidden; pausing rendering");
+        } else {
+            tracing::debug!("Visible again; resuming rendering");
+            self.last_frame = web_time::Instant::now();
+            self.window.request_redraw();
+        }
+    }
+
+    /// Whether to draw another frame: always, unless rendering on demand
+    /// and nothing on screen would change
+    fn needs_frame(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.recording.is_some() {
+            return true;
+        }
+        !self.engine.params.get().on_demand
+            || self.dirty
+            || !self.engine.paused
+            || self.benchmarking()
+            || !self.engine.camera.is_settled()
+            || self.input.any_held()
+            || self.hud.has_readout()
+            || !self.engine.gpu.converged()
+    }
 
-            if let Some(container) = document.get_element_by_id("canvas-container") {
-                // Append canvas to container
-                container.append_child(&canvas).unwrap();
+    /// Advance the clock, update the camera and render one frame
+    fn redraw(&mut self, event_loop: &ActiveEventLoop) {
+        let _span = tracing::trace_span!("frame").entered();
+        self.dirty = false;
+        // Calculate delta time
+        let now = web_time::Instant::now();
+        // Time spent idle on demand isn't a frame's worth of motion
+        if std::mem::take(&mut self.idle) {
+            self.last_frame = now;
+        }
+        let dt = (now - self.last_frame).as_secs_f32();
+        self.last_frame = now;
+        if dt > 0.0 {
+            let instant_fps = 1.0 / dt;
+            self.fps = if self.fps == 0.0 {
+                instant_fps
             } else {
-                // Append to body
-                document.body().unwrap().append_child(&canvas).unwrap();
-            }
+                self.fps + (instant_fps - self.fps) * 0.1
+            };
+        }
+        self.frame_history.push(dt);
+        self.hud.update(dt);
+        // Frames on the pacer's schedule step the world by a whole interval
+        let dt = self
+            .pacer
+            .start_frame(now, dt, self.engine.params.get().power_limited().max_fps);
+        // A recording moves the world on by one video frame per frame
+        #[cfg(not(target_arch = "wasm32"))]
+        let dt = self
+            .recording
+            .as_ref()
+            .map_or(dt, |recorder| recorder.frame_time());
+        if !self.benchmarking() {
+            self.engine.advance_time(dt);
+        }
 
-            let _ = window.request_inner_size(winit::dpi::PhysicalSize::new(width, height));
+        // Update camera, or follow the benchmark's path
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(bench) = &self.bench {
+            let (focus, yaw, pitch, distance) =
+                crate::bench::camera_pose(bench.frame(), bench.total_frames());
+            self.engine.camera.jump_to(focus, yaw, pitch, distance);
+            self.engine.time = bench.frame() as f32 * crate::bench::FRAME_TIME;
+        }
+        // Follow camera moves pushed through the JS API
+        #[cfg(target_arch = "wasm32")]
+        if let Some(pose) = self.web.link.take_camera() {
+            self.engine.camera.glide_to(pose);
+            self.web.camera_pose = self.engine.camera.target_pose();
+        }
+        let animate = tracing::trace_span!("animate").entered();
+        if !self.engine.paused && !self.benchmarking() {
+            self.play_timeline(dt);
+            self.run_script(dt);
+            self.run_attract(dt);
         }
+        if !self.benchmarking() {
+            self.steer_camera(dt);
+        }
+        drop(animate);
+        self.engine.advance_world(dt);
+        #[cfg(target_arch = "wasm32")]
+        self.report_camera();
 
-        // Start async GPU initialization
-        let window_clone = window.clone();
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.apply_osc();
+            self.apply_control(event_loop);
+        }
 
+        // Pick up parameter and palette changes pushed through the JS API
         #[cfg(target_arch = "wasm32")]
         {
-            self.phase = AppPhase::Initializing { window: window.clone() };
-
-            // Use a static to communicate back to the app
-            // This is a workaround for WASM's async limitations with winit
-            wasm_bindgen_futures::spawn_local(async move {
-                let world = HoneycombWorld::generate(WORLD_SEED, CELL_COUNT, PHASE_COUNT);
-                let gpu = GpuState::new(window_clone.clone(), &world).await;
-
-                // Store in thread-local for retrieval
-                PENDING_STATE.with(|cell| {
-                    *cell.borrow_mut() = Some(PendingState {
-                        window: window_clone,
-                        gpu,
-                        world,
-                    });
-                });
+            if let Some(params) = self.web.link.take_params() {
+                self.engine.params.replace(params);
+            }
+            if let Some(paused) = self.web.link.take_paused() {
+                self.engine.paused = paused;
+            }
+            if let Some(change) = self.web.link.take_world_change() {
+                let mut recipe = self.engine.recipe.clone();
+                if let Some(seed) = change.seed {
+                    recipe = recipe.seed(seed);
+                }
+                if let Some(cells) = change.cells {
+                    recipe = recipe.cells(cells.min(MAX_CELLS));
+                }
+                if let Some(phases) = change.phases {
+                    recipe = recipe.phases(phases);
+                }
+                self.regenerate(recipe);
+            }
+            if let Some(gradient) = self.web.link.take_gradient() {
+                self.engine.gpu.set_custom_gradient(gradient);
+            }
+            for (phase, function) in self.web.link.take_transfer_functions() {
+                self.engine.gpu.set_transfer_function(phase, function);
+            }
+            if let Some(hash) = &mut self.web.hash {
+                hash.update(&self.engine.camera.target_pose(), &self.engine.params.get());
+            }
+            if let Some(storage) = &mut self.web.storage {
+                storage.update(&self.engine.params.get());
+            }
+        }
+        if self.show_stats {
+            let lines = self.stats_lines();
+            self.engine.gpu.update_overlay(&lines, &self.frame_history);
+        }
+        #[cfg(target_arch = "wasm32")]
+        if let Some(caption) = self.web.link.take_caption() {
+            self.hud.set_caption(caption);
+        }
+        let size = self.engine.gpu.size;
+        let scale = self.window.scale_factor() as f32;
+        for label in self.hud.labels(size.width, size.height, scale) {
+            self.engine.gpu.draw_text(label);
+        }
+
+        // Render
+        let render = tracing::trace_span!("render").entered();
+        let result = self.engine.render();
+        drop(render);
+        match result {
+            Ok(_) => {
+                self.update_resolution(dt);
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    self.record_frame();
+                    self.record_session();
+                    self.finish_control_screenshots();
+                }
+
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Some(bench) = &mut self.bench {
+                    if bench.record(dt, self.engine.gpu.timings()) {
+                        self.finish_bench();
+                        event_loop.exit();
+                    }
+                }
+
+                #[cfg(target_arch = "wasm32")]
+                {
+                    let captures = self.web.link.take_captures();
+                    if !captures.is_empty() {
+                        let size = self.engine.gpu.size;
+                        self.engine.gpu.capture_frame_async(move |frame| {
+                            crate::web::finish_captures(captures, size.width, size.height, frame)
+                        });
+                    }
+                    self.web.link.notify_frame(
+                        self.engine.time,
+                        self.fps,
+                        self.engine.gpu.converged(),
+                    );
+                }
+            }
+            Err(wgpu::SurfaceError::Lost) => {
+                self.engine.gpu.resize(self.engine.gpu.size);
+            }
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                tracing::error!("Out of memory");
+                #[cfg(target_arch = "wasm32")]
+                {
+                    crate::report::show_gpu_error("out of memory");
+                    self.web.link.notify_error("Out of GPU memory");
+                }
+                event_loop.exit();
+            }
+            Err(e) => {
+                tracing::warn!("Surface error: {:?}", e);
+                #[cfg(target_arch = "wasm32")]
+                self.web.link.notify_error(&format!("Surface error: {}", e));
+            }
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        for error in self.engine.gpu.take_errors() {
+            crate::report::show_gpu_error(&error);
+            self.web.link.notify_error(&error);
+        }
+
+        // Clear frame input state
+        self.input.end_frame();
+    }
+
+    /// When the next frame is due under the frame cap, or `None` if uncapped
+    fn next_frame_at(&self) -> Option<web_time::Instant> {
+        self.pacer
+            .next_frame_at(self.engine.params.get().power_limited().max_fps)
+    }
+
+    /// Start dynamic resolution at `scale` rather than full resolution
+    fn start_at_scale(&mut self, scale: f32) {
+        if self.engine.gpu.scales_resolution() {
+            self.resolution = DynamicResolution::starting_at(scale);
+            self.engine.gpu.set_dynamic_scale(self.resolution.scale());
+        }
+    }
+
+    /// Adjust the raymarch resolution toward the target frame rate
+    fn update_resolution(&mut self, dt: f32) {
+        let target_fps = self.engine.params.get().power_limited().target_fps;
+        if target_fps <= 0.0 || !self.engine.gpu.scales_resolution() {
+            if self.resolution.scale() != 1.0 {
+                self.resolution.reset();
+                self.engine.gpu.set_dynamic_scale(1.0);
+            }
+            return;
+        }
+
+        // GPU time isn't capped by vsync, so it shows headroom wall time can't
+        let frame_ms = match self.engine.gpu.timings() {
+            Some(timings) => timings.passes.iter().map(|(_, ms)| ms).sum(),
+            None => dt * 1000.0,
+        };
+        if let Some(scale) = self.resolution.update(frame_ms, target_fps) {
+            tracing::debug!("Dynamic resolution scale {:.1}", scale);
+            self.engine.gpu.set_dynamic_scale(scale);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn benchmarking(&self) -> bool {
+        self.bench.is_some()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn benchmarking(&self) -> bool {
+        false
+    }
+
+    /// Print the benchmark report and write it to the requested file
+    #[cfg(not(target_arch = "wasm32"))]
+    fn finish_bench(&mut self) {
+        let Some(bench) = self.bench.take() else {
+            return;
+        };
+        let size = (self.engine.gpu.size.width, self.engine.gpu.size.height);
+        let report = bench.report(
+            &self.engine.gpu.adapter_info,
+            self.engine.gpu.raymarch_path(),
+            size,
+        );
+        report.print();
+
+        let output = &bench.options().output;
+        match report.write(output) {
+            Ok(()) => tracing::info!("Wrote benchmark report to {}", output.display()),
+            Err(e) => tracing::error!("Failed to write {}: {}", output.display(), e),
+        }
+    }
+
+    fn stats_lines(&self) -> Vec<String> {
+        let size = self.engine.gpu.size;
+        let params = self.engine.params.get().power_limited();
+        let dispatch = match (
+            self.engine.gpu.isosurface_triangles(),
+            self.engine.gpu.dispatch_size(),
+            self.engine.gpu.workgroup_size(),
+        ) {
+            (Some(triangles), _, _) => format!("ISOSURFACE {} TRIS", triangles),
+            (None, Some((x, y)), Some(workgroup)) => {
+                format!("DISPATCH {}x{} ({})", x, y, workgroup)
+            }
+            _ => "FRAGMENT RAYMARCH".to_string(),
+        };
+        let mut lines = vec![
+            format!(
+                "{:.0} FPS  {:.2} MS",
+                self.fps,
+                self.frame_history.average() * 1000.0
+            ),
+            format!(
+                "{}x{}  MAX {:.1} MS",
+                size.width,
+                size.height,
+                self.frame_history.max() * 1000.0
+            ),
+            dispatch,
+            format!("STEPS {} x {:.2}", params.max_steps, params.step_size),
+        ];
+        if params.low_power {
+            lines.push("LOW POWER".to_string());
+        }
+        if self.resolution.scale() != 1.0 {
+            lines.push(format!("DYNAMIC SCALE {:.1}", self.resolution.scale()));
+        }
+        if self.engine.gpu.accumulated_samples() > 1 {
+            lines.push(format!("SAMPLES {}", self.engine.gpu.accumulated_samples()));
+        }
+        if self.engine.gpu.converged() {
+            lines.push("CONVERGED".to_string());
+        }
+        if let Some(timings) = self.engine.gpu.timings() {
+            let mut line = String::from("GPU");
+            for (pass, ms) in &timings.passes {
+                line.push_str(&format!(" {} {:.2}", pass.name(), ms));
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// Pick up where the last run left off
+    #[cfg(not(target_arch = "wasm32"))]
+    fn restore_settings(&mut self, settings: &Settings) {
+        self.engine
+            .params
+            .update(|params| settings.restore_params(params));
+        if let Some(pose) = settings.camera {
+            let pose = CameraPose::from(pose);
+            self.engine
+                .camera
+                .jump_to(pose.focus, pose.yaw, pose.pitch, pose.distance);
+        }
+        self.bookmarks = settings.bookmarks.iter().map(|&pose| pose.into()).collect();
+    }
+
+    /// Record the viewer's state for the next run
+    #[cfg(not(target_arch = "wasm32"))]
+    fn store_settings(&self, settings: &mut Settings) {
+        settings.store_params(&self.engine.params.get(), &self.engine.initial_params);
+        settings.camera = Some(self.engine.camera.target_pose().into());
+        settings.bookmarks = self.bookmarks.iter().map(|&pose| pose.into()).collect();
+        // A fullscreen or maximized window keeps the placement it had before
+        if self.window.fullscreen().is_none() && !self.window.is_maximized() {
+            let size = self.window.inner_size();
+            let position = self.window.outer_position().ok();
+            settings.window = Some(WindowPlacement {
+                width: size.width,
+                height: size.height,
+                x: position.map(|position| position.x),
+                y: position.map(|position| position.y),
             });
         }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_screenshot(&mut self) {
+        let pixels = self.engine.gpu.capture_frame();
+        let path = crate::capture::screenshot_path();
+        match crate::capture::save_png(
+            &path,
+            self.engine.gpu.size.width,
+            self.engine.gpu.size.height,
+            &pixels,
+        ) {
+            Ok(()) => {
+                tracing::info!("Saved screenshot to {}", path.display());
+                self.hud.show_readout(format!("Saved {}", path.display()));
+            }
+            Err(e) => tracing::error!("Failed to save screenshot: {}", e),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn save_screenshot(&mut self) {
+        tracing::warn!("Screenshots are not supported in the browser");
+    }
+
+    /// Start recording the window, or finish the recording under way
+    #[cfg(not(target_arch = "wasm32"))]
+    fn toggle_recording(&mut self) {
+        if self.recording.is_some() {
+            self.stop_recording();
+            return;
+        }
+        let config = &self.recording_config;
+        let path = crate::video::video_path(config.format);
+        let size = self.engine.gpu.size;
+        match VideoRecorder::start(config, &path, size.width, size.height) {
+            Ok(recorder) => {
+                tracing::info!("Recording to {}", path.display());
+                self.hud.show_readout("Recording");
+                self.recording = Some(recorder);
+            }
+            Err(e) => tracing::error!("Could not run {}: {}", config.ffmpeg.display(), e),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn toggle_recording(&mut self) {
+        tracing::warn!("In the browser, record with viewer.start_recording()");
+    }
 
+    /// Let the encoder finish the file
+    #[cfg(not(target_arch = "wasm32"))]
+    fn stop_recording(&mut self) {
+        let Some(recorder) = self.recording.take() else {
+            return;
+        };
+        match recorder.finish() {
+            Ok((path, frames)) => {
+                tracing::info!("Saved {} frames of video to {}", frames, path.display());
+                self.hud.show_readout(format!("Saved {}", path.display()));
+            }
+            Err(e) => tracing::error!("Failed to save the recording: {}", e),
+        }
+    }
+
+    /// Start logging the session, or finish the log under way
+    #[cfg(not(target_arch = "wasm32"))]
+    fn toggle_session(&mut self) {
+        if self.session.is_some() {
+            self.stop_session();
+            return;
+        }
+        let path = crate::session::session_path();
+        let (cells, phases) = self.engine.recipe.counts();
+        let world = SessionWorld {
+            seed: self.engine.world.seed,
+            cells,
+            phases,
+        };
+        match SessionRecorder::start(&path, world) {
+            Ok(recorder) => {
+                tracing::info!("Recording the session to {}", path.display());
+                self.hud.show_readout("Recording session");
+                self.session = Some(recorder);
+            }
+            Err(e) => tracing::error!("Could not create {}: {}", path.display(), e),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn toggle_session(&mut self) {
+        tracing::warn!("Sessions can only be recorded in the native viewer");
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn stop_session(&mut self) {
+        let Some(recorder) = self.session.take() else {
+            return;
+        };
+        match recorder.finish() {
+            Ok((path, frames)) => {
+                tracing::info!("Saved {} frames of session to {}", frames, path.display());
+                self.hud.show_readout(format!("Saved {}", path.display()));
+            }
+            Err(e) => tracing::error!("Failed to save the session: {}", e),
+        }
+    }
+
+    /// Log the state the frame just drawn had
+    #[cfg(not(target_arch = "wasm32"))]
+    fn record_session(&mut self) {
+        let Some(recorder) = &mut self.session else {
+            return;
+        };
+        if let Err(e) = recorder.record(
+            self.engine.time,
+            &self.engine.camera,
+            &self.engine.params.get(),
+        ) {
+            tracing::error!("Session recording failed: {}", e);
+            self.stop_session();
+        }
+    }
+
+    /// Hand the frame just drawn, without the gizmo or overlay, to the
+    /// recording. Resizing the window ends it, since every frame of a video
+    /// has one size.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn record_frame(&mut self) {
+        let Some(recorder) = &mut self.recording else {
+            return;
+        };
+        if recorder.size() != (self.engine.gpu.size.width, self.engine.gpu.size.height) {
+            tracing::warn!("The window changed size; ending the recording");
+            self.stop_recording();
+            return;
+        }
+        if let Err(e) = recorder.push(&self.engine.gpu.capture_frame()) {
+            tracing::error!("Recording failed: {}", e);
+            self.stop_recording();
+        }
+    }
+}
+
+/// Per-viewer browser state: the link to its JS handle and the canvas size watcher
+#[cfg(target_arch = "wasm32")]
+struct WebViewer {
+    link: crate::web::ViewerLink,
+    /// Camera pose the page last heard about
+    camera_pose: crate::camera::CameraPose,
+    /// Present when the canvas follows its container's size
+    _observer: Option<crate::web::CanvasObserver>,
+    /// Present when the view is kept in the URL fragment
+    hash: Option<crate::url::HashSync>,
+    /// Present when settings are kept in localStorage
+    storage: Option<crate::storage::ParamStorage>,
+}
+
+/// A request to open a viewer, queued until the event loop can create windows
+#[cfg(target_arch = "wasm32")]
+pub struct ViewerRequest {
+    pub options: crate::web::ViewerOptions,
+    pub link: crate::web::ViewerLink,
+}
+
+/// Events delivered to the event loop from outside winit
+#[cfg(target_arch = "wasm32")]
+pub enum UserEvent {
+    /// Open another viewer (a later `wasm_main` call)
+    CreateViewer(ViewerRequest),
+    /// The page changed something through the JS API, which an idle
+    /// viewer rendering on demand should draw
+    Wake,
+    /// A viewer's canvas changed size, in device pixels
+    CanvasResized {
+        window_id: WindowId,
+        width: u32,
+        height: u32,
+    },
+    /// Async GPU initialization for a viewer finished
+    GpuReady {
+        window_id: WindowId,
+        gpu: Box<GpuState>,
+        world: HoneycombWorld,
+        builder: VendekBuilder,
+    },
+    /// Async GPU initialization for a viewer failed
+    GpuFailed {
+        window_id: WindowId,
+        error: GpuError,
+    },
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub type UserEvent = ();
+
+/// Why the viewer could not start
+#[derive(Debug)]
+pub enum StartupError {
+    EventLoop(winit::error::EventLoopError),
+    /// The page's event loop has already shut down
+    #[cfg(target_arch = "wasm32")]
+    EventLoopClosed,
+    Window(winit::error::OsError),
+    Gpu(GpuError),
+}
+
+impl fmt::Display for StartupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartupError::EventLoop(e) => write!(f, "Could not start the event loop: {}", e),
+            #[cfg(target_arch = "wasm32")]
+            StartupError::EventLoopClosed => write!(f, "The event loop has exited"),
+            StartupError::Window(e) => write!(f, "Could not create a window: {}", e),
+            StartupError::Gpu(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for StartupError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StartupError::EventLoop(e) => Some(e),
+            StartupError::Window(e) => Some(e),
+            StartupError::Gpu(e) => Some(e),
+            #[cfg(target_arch = "wasm32")]
+            StartupError::EventLoopClosed => None,
+        }
+    }
+}
+
+impl From<winit::error::EventLoopError> for StartupError {
+    fn from(e: winit::error::EventLoopError) -> Self {
+        StartupError::EventLoop(e)
+    }
+}
+
+impl From<winit::error::OsError> for StartupError {
+    fn from(e: winit::error::OsError) -> Self {
+        StartupError::Window(e)
+    }
+}
+
+impl From<GpuError> for StartupError {
+    fn from(e: GpuError) -> Self {
+        StartupError::Gpu(e)
+    }
+}
+
+enum ViewerPhase {
+    #[cfg(target_arch = "wasm32")]
+    Initializing {
+        window: Arc<Window>,
+        web: WebViewer,
+    },
+    Running(Box<AppState>),
+}
+
+struct App {
+    viewers: HashMap<WindowId, ViewerPhase>,
+    /// Viewer receiving raw device motion
+    focused: Option<WindowId>,
+    #[cfg(not(target_arch = "wasm32"))]
+    config: Config,
+    /// Backend and adapter choice for new viewers
+    adapter: AdapterOptions,
+    /// Run a benchmark instead of the interactive viewer
+    #[cfg(not(target_arch = "wasm32"))]
+    bench: Option<BenchOptions>,
+    /// Where to take control messages from
+    #[cfg(not(target_arch = "wasm32"))]
+    remote: RemoteControl,
+    /// Frame script file to run
+    #[cfg(not(target_arch = "wasm32"))]
+    script_path: Option<std::path::PathBuf>,
+    /// Keyframe timeline file to play
+    #[cfg(not(target_arch = "wasm32"))]
+    timeline_path: Option<std::path::PathBuf>,
+    /// Set when the native viewer failed to start, reported once the loop exits
+    #[cfg(not(target_arch = "wasm32"))]
+    startup_error: Option<StartupError>,
+    /// State to save on exit; None for benchmarks, which shouldn't disturb it
+    #[cfg(not(target_arch = "wasm32"))]
+    settings: Option<Settings>,
+    /// Viewers requested before the event loop first resumed
+    #[cfg(target_arch = "wasm32")]
+    pending: Vec<ViewerRequest>,
+    /// For waking the event loop from background threads and async tasks
+    proxy: winit::event_loop::EventLoopProxy<UserEvent>,
+}
+
+impl App {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new(
+        config: Config,
+        adapter: AdapterOptions,
+        bench: Option<BenchOptions>,
+        remote: RemoteControl,
+        script_path: Option<std::path::PathBuf>,
+        timeline_path: Option<std::path::PathBuf>,
+        proxy: winit::event_loop::EventLoopProxy<UserEvent>,
+    ) -> Self {
+        Self {
+            viewers: HashMap::new(),
+            focused: None,
+            config,
+            adapter,
+            bench,
+            remote,
+            script_path,
+            timeline_path,
+            startup_error: None,
+            settings: None,
+            proxy,
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn new(proxy: winit::event_loop::EventLoopProxy<UserEvent>, first: ViewerRequest) -> Self {
+        Self {
+            viewers: HashMap::new(),
+            focused: None,
+            adapter: AdapterOptions::default(),
+            pending: vec![first],
+            proxy,
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn create_viewer(&mut self, event_loop: &ActiveEventLoop) -> Result<(), StartupError> {
+        let settings = self.bench.is_none().then(Settings::load);
+        let mut window_attributes = Window::default_attributes()
+            .with_title("Vendek - Far Side Explorer")
+            .with_inner_size(winit::dpi::PhysicalSize::new(1280, 720));
+        if let Some(placement) = settings.as_ref().and_then(|settings| settings.window) {
+            window_attributes = window_attributes.with_inner_size(winit::dpi::PhysicalSize::new(
+                placement.width.max(1),
+                placement.height.max(1),
+            ));
+            // Only onto a monitor that is still connected
+            if let (Some(x), Some(y)) = (placement.x, placement.y) {
+                let visible = event_loop.available_monitors().any(|monitor| {
+                    let position = monitor.position();
+                    let size = monitor.size();
+                    (position.x..position.x + size.width as i32).contains(&x)
+                        && (position.y..position.y + size.height as i32).contains(&y)
+                });
+                if visible {
+                    window_attributes =
+                        window_attributes.with_position(winit::dpi::PhysicalPosition::new(x, y));
+                }
+            }
+        }
+        if let Some(bench) = &self.bench {
+            window_attributes = window_attributes
+                .with_title("Vendek - Benchmark")
+                .with_inner_size(winit::dpi::PhysicalSize::new(bench.width, bench.height))
+                .with_resizable(false);
+        }
+        let window = Arc::new(event_loop.create_window(window_attributes)?);
+
+        let progress = |stage: LoadStage| tracing::debug!("Starting up: {}", stage.name());
+        progress(LoadStage::World);
+        let builder = self.config.world_builder();
+        let world = builder.generate_world();
+        let gpu = pollster::block_on(GpuState::new(
+            window.clone(),
+            &world,
+            &self.adapter,
+            progress,
+        ))?;
+
+        // Benchmarks compare adapters at the same quality and resolution
+        let class = self.bench.is_none().then(|| gpu.device_class());
+        let builder = match class {
+            Some(class) => builder.for_device(class),
+            None => builder,
+        };
+        let mut state = AppState::new(window, gpu, world, &builder, self.config.bindings.clone());
+        if let Some(class) = class {
+            state.start_at_scale(class.render_scale());
+        }
+        if let Some(palette) = &self.config.palette {
+            state.engine.gpu.set_custom_gradient(palette.stops.clone());
+        }
+        let low_power = match self.config.render.low_power {
+            Some(low_power) => low_power,
+            None if self.bench.is_none() && crate::power::on_battery() => {
+                tracing::info!("Running on battery; starting in low-power mode");
+                true
+            }
+            None => false,
+        };
+        state.engine.params.update(|params| {
+            self.config.apply_params(params);
+            params.low_power = low_power;
+        });
+        for transfer in &self.config.transfer_functions {
+            state
+                .engine
+                .gpu
+                .set_transfer_function(transfer.phase, Some(transfer.points.clone()));
+        }
+        if self.config.clip_planes.len() > MAX_CLIP_PLANES {
+            tracing::warn!("Only the first {} clip planes are used", MAX_CLIP_PLANES);
+        }
+        if let Some(port) = self.remote.osc {
+            // Wake the event loop for each message, in case it is idle
+            let proxy = self.proxy.clone();
+            match OscListener::bind(port, move || {
+                let _ = proxy.send_event(());
+            }) {
+                Ok(listener) => {
+                    tracing::info!("Listening for OSC on UDP port {}", port);
+                    state.osc = Some(listener);
+                }
+                Err(e) => tracing::error!("Could not listen for OSC on port {}: {}", port, e),
+            }
+        }
+        if self.remote.stdin {
+            let proxy = self.proxy.clone();
+            match StdinControl::spawn(move || {
+                let _ = proxy.send_event(());
+            }) {
+                Ok(control) => {
+                    tracing::info!("Reading commands from stdin");
+                    state.control = Some(control);
+                }
+                Err(e) => tracing::error!("Could not read commands from stdin: {}", e),
+            }
+        }
+        state.script_file = self
+            .script_path
+            .clone()
+            .map(|path| WatchedFile::new(path, "script"));
+        state.timeline_file = self
+            .timeline_path
+            .clone()
+            .map(|path| WatchedFile::new(path, "timeline"));
+        state.attract = Attract::new(self.config.attract.idle_seconds);
+        state.hud = Hud::new(self.config.hud.clone());
         #[cfg(not(target_arch = "wasm32"))]
         {
-            let world = HoneycombWorld::generate(WORLD_SEED, CELL_COUNT, PHASE_COUNT);
-            let gpu = pollster::block_on(GpuState::new(window_clone, &world));
-
-            self.phase = AppPhase::Running(AppState {
-                window,
-                gpu,
-                camera: Camera::new(),
-                input: InputState::new(),
-                world,
-                time: 0.0,
-                last_frame: web_time::Instant::now(),
+            state.recording_config = self.config.recording.clone();
+        }
+        state.engine.initial_params = state.engine.params.get();
+        if let Some(settings) = &settings {
+            state.restore_settings(settings);
+        }
+        state.fullscreen_mode = self.config.window.fullscreen;
+        match self.config.render.workgroup {
+            Some(workgroup) => state.engine.gpu.set_workgroup_size(workgroup),
+            None => {
+                // Time the workgroup sizes on the view and parameters the
+                // viewer starts with
+                state.engine.gpu.set_params(&state.engine.params.get());
+                state.engine.gpu.autotune_workgroup(&state.engine.camera);
+            }
+        }
+        if self.config.render.present_mode != PresentMode::Vsync {
+            state
+                .engine
+                .gpu
+                .set_present_mode(self.config.render.present_mode.to_wgpu());
+        }
+        if let Some(options) = self.bench.take() {
+            // Vsync or a frame cap would hold every frame to a fixed interval,
+            // and a changing resolution would make runs incomparable
+            state
+                .engine
+                .gpu
+                .set_present_mode(wgpu::PresentMode::AutoNoVsync);
+            state.engine.params.update(|params| {
+                params.target_fps = 0.0;
+                params.max_fps = 0.0;
+                params.low_power = false;
             });
+            tracing::info!(
+                "Benchmarking {} frames after {} warmup frames",
+                options.frames,
+                options.warmup
+            );
+            state.bench = Some(Bench::new(options));
+        }
+        self.focused = Some(state.window.id());
+        self.viewers
+            .insert(state.window.id(), ViewerPhase::Running(Box::new(state)));
+        self.settings = settings;
+        Ok(())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn create_viewer(&mut self, event_loop: &ActiveEventLoop, request: ViewerRequest) {
+        use winit::platform::web::WindowExtWebSys;
+
+        let window_attributes =
+            Window::default_attributes().with_title("Vendek - Far Side Explorer");
+        let window = match event_loop.create_window(window_attributes) {
+            Ok(window) => Arc::new(window),
+            Err(e) => {
+                let message = StartupError::from(e).to_string();
+                tracing::error!("{}", message);
+                request.link.notify_error(&message);
+                return;
+            }
+        };
+
+        let options = request.options;
+        let canvas = window.canvas().unwrap();
+
+        // Set canvas size BEFORE attaching to DOM. The backing store is in device
+        // pixels so HiDPI displays render sharply; the style is in CSS pixels.
+        let (width, height) = options.initial_size();
+        let ratio = crate::web::device_pixel_ratio();
+        canvas.set_width((width as f64 * ratio).round() as u32);
+        canvas.set_height((height as f64 * ratio).round() as u32);
+
+        // Auto-resizing canvases fill their container; fixed ones keep an explicit size
+        let style = canvas.style();
+        if options.auto_resize {
+            let _ = style.set_property("width", "100%");
+            let _ = style.set_property("height", "100%");
+        } else {
+            let _ = style.set_property("width", &format!("{}px", width));
+            let _ = style.set_property("height", &format!("{}px", height));
+        }
+
+        options.container.append_child(&canvas).unwrap();
+        request.link.set_canvas(canvas.clone());
+
+        // Track the element's size rather than winit's window resize events
+        let observer = if options.auto_resize {
+            let proxy = self.proxy.clone();
+            let observed = canvas.clone();
+            let window_id = window.id();
+            crate::web::CanvasObserver::new(&canvas, move |width, height| {
+                observed.set_width(width);
+                observed.set_height(height);
+                let _ = proxy.send_event(UserEvent::CanvasResized {
+                    window_id,
+                    width,
+                    height,
+                });
+            })
+            .map_err(|e| tracing::warn!("ResizeObserver unavailable: {:?}", e))
+            .ok()
+        } else {
+            None
+        };
+
+        // GPU initialization is async on the web; the result comes back as a user event
+        let seed = options.seed.unwrap_or(DEFAULT_SEED);
+        let mut builder = VendekBuilder::new();
+        if let Some(preset) = options.preset {
+            builder = builder.preset(preset);
         }
+        let mut builder = builder.seed(seed);
+        if let Some(cells) = options.cells {
+            builder = builder.cells(cells.clamp(1, MAX_CELLS));
+        }
+        if let Some(phases) = options.phases {
+            builder = builder.phases(phases);
+        }
+        let (cells, phases) = builder.counts();
+        request.link.set_world(seed, cells, phases);
+        let proxy = self.proxy.clone();
+        let adapter = self.adapter.clone();
+        let window_clone = window.clone();
+        let link = request.link.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            // Let the page show the first stage before generation blocks
+            link.notify_progress(LoadStage::World);
+            crate::web::yield_to_browser().await;
+            let world = builder.generate_world();
+            let window_id = window_clone.id();
+            let progress = |stage| link.notify_progress(stage);
+            let event = match GpuState::new(window_clone, &world, &adapter, progress).await {
+                Ok(gpu) => UserEvent::GpuReady {
+                    window_id,
+                    gpu: Box::new(gpu),
+                    world,
+                    builder,
+                },
+                Err(error) => UserEvent::GpuFailed { window_id, error },
+            };
+            let _ = proxy.send_event(event);
+        });
+
+        let web = WebViewer {
+            link: request.link,
+            camera_pose: Default::default(),
+            _observer: observer,
+            storage: options.persist.then(crate::storage::ParamStorage::new),
+            hash: options
+                .sync_hash
+                .then(|| crate::url::HashSync::new(seed, options.cells, options.phases)),
+        };
+        self.viewers
+            .insert(window.id(), ViewerPhase::Initializing { window, web });
     }
+}
+
+impl ApplicationHandler<UserEvent> for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.viewers.is_empty() && self.startup_error.is_none() {
+            if let Err(e) = self.create_viewer(event_loop) {
+                tracing::error!("{}", e);
+                self.startup_error = Some(e);
+                event_loop.exit();
+            }
+        }
 
-    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
-        // Check for pending WASM initialization
         #[cfg(target_arch = "wasm32")]
-        if matches!(self.phase, AppPhase::Initializing { .. }) {
-            PENDING_STATE.with(|cell| {
-                if let Some(pending) = cell.borrow_mut().take() {
-                    self.phase = AppPhase::Running(AppState {
-                        window: pending.window,
-                        gpu: pending.gpu,
-                        camera: Camera::new(),
-                        input: InputState::new(),
-                        world: pending.world,
-                        time: 0.0,
-                        last_frame: web_time::Instant::now(),
-                    });
+        for request in std::mem::take(&mut self.pending) {
+            self.create_viewer(event_loop, request);
+        }
+    }
+
+    /// A background thread has news for the viewers, such as OSC messages
+    #[cfg(not(target_arch = "wasm32"))]
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, _event: UserEvent) {
+        for ViewerPhase::Running(state) in self.viewers.values_mut() {
+            state.dirty = true;
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        match event {
+            UserEvent::Wake => {
+                for viewer in self.viewers.values_mut() {
+                    if let ViewerPhase::Running(state) = viewer {
+                        state.dirty = true;
+                    }
                 }
-            });
+            }
+            UserEvent::CreateViewer(request) => self.create_viewer(event_loop, request),
+            UserEvent::CanvasResized {
+                window_id,
+                width,
+                height,
+            } => {
+                // Resizes during initialization are picked up from the canvas in GpuReady
+                if let Some(ViewerPhase::Running(state)) = self.viewers.get_mut(&window_id) {
+                    state
+                        .engine
+                        .gpu
+                        .resize(winit::dpi::PhysicalSize::new(width, height));
+                }
+            }
+            UserEvent::GpuReady {
+                window_id,
+                gpu,
+                world,
+                builder,
+            } => {
+                if let Some(ViewerPhase::Initializing { window, web }) =
+                    self.viewers.remove(&window_id)
+                {
+                    use winit::platform::web::WindowExtWebSys;
+
+                    let link = web.link.clone();
+                    let class = gpu.device_class();
+                    let builder = builder.for_device(class);
+                    let mut state =
+                        AppState::new(window, *gpu, world, &builder, InputMap::default(), web);
+                    state.start_at_scale(class.render_scale());
+                    link.start_from(&state.engine.params.get());
+                    // Before anything stored or in the URL is applied, so
+                    // resetting goes back to the defaults
+                    state.engine.initial_params = state.engine.params.get();
+                    if state.web.storage.is_some() {
+                        state.bookmarks = crate::storage::load_bookmarks();
+                    }
+                    if let Some(canvas) = state.window.canvas() {
+                        let size = winit::dpi::PhysicalSize::new(canvas.width(), canvas.height());
+                        if size != state.engine.gpu.size {
+                            state.engine.gpu.resize(size);
+                        }
+                    }
+                    self.viewers
+                        .insert(window_id, ViewerPhase::Running(Box::new(state)));
+                    link.notify_ready();
+                }
+            }
+            UserEvent::GpuFailed { window_id, error } => {
+                if let Some(ViewerPhase::Initializing { window, web }) =
+                    self.viewers.remove(&window_id)
+                {
+                    use winit::platform::web::WindowExtWebSys;
+
+                    let message = error.to_string();
+                    tracing::error!("{}", message);
+                    if let Some(canvas) = window.canvas() {
+                        crate::web::show_error_panel(&canvas, &message);
+                    }
+                    web.link.notify_error(&message);
+                }
+            }
         }
+    }
 
-        let state = match &mut self.phase {
-            AppPhase::Running(s) => s,
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        let state = match self.viewers.get_mut(&id) {
+            Some(ViewerPhase::Running(s)) => s,
             _ => return,
         };
+        if !matches!(event, WindowEvent::RedrawRequested) {
+            state.dirty = true;
+        }
 
         match event {
             WindowEvent::CloseRequested => {
-                event_loop.exit();
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Some(settings) = &mut self.settings {
+                    state.store_settings(settings);
+                }
+                self.viewers.remove(&id);
+                if self.viewers.is_empty() {
+                    event_loop.exit();
+                }
             }
 
+            // On the web the canvas ResizeObserver drives resizes instead
+            #[cfg(not(target_arch = "wasm32"))]
             WindowEvent::Resized(physical_size) => {
-                state.gpu.resize(physical_size);
+                // Minimizing shrinks the window to nothing on some platforms
+                let minimized = physical_size.width == 0 || physical_size.height == 0;
+                state.set_hidden(state.occluded, minimized);
+                state.engine.gpu.resize(physical_size);
             }
 
+            WindowEvent::Occluded(occluded) => state.set_hidden(occluded, state.minimized),
+
             WindowEvent::KeyboardInput { event, .. } => {
                 if let PhysicalKey::Code(code) = event.physical_key {
+                    let woke = state.user_input();
                     state.input.handle_key(code, event.state);
 
-                    // Close on Escape
-                    if code == KeyCode::Escape && event.state == ElementState::Pressed {
-                        event_loop.exit();
+                    if event.state == ElementState::Pressed && !event.repeat && !woke {
+                        let actions: Vec<Action> =
+                            state.input_map.actions_for(Binding::Key(code)).collect();
+                        for action in actions {
+                            state.handle_action(action, event_loop);
+                        }
+
+                        // Alt+Enter is the other conventional fullscreen shortcut
+                        let alt = state.input.is_key_held(KeyCode::AltLeft)
+                            || state.input.is_key_held(KeyCode::AltRight);
+                        if code == KeyCode::Enter && alt {
+                            state.handle_action(Action::ToggleFullscreen, event_loop);
+                        }
+
+                        // Digits show and hide phases; with Shift, show one alone
+                        if let Some(phase) = input::phase_key(code) {
+                            let shift = state.input.is_key_held(KeyCode::ShiftLeft)
+                                || state.input.is_key_held(KeyCode::ShiftRight);
+                            state.update_params(|params| {
+                                if shift {
+                                    params.solo_phase(phase)
+                                } else {
+                                    params.toggle_phase(phase)
+                                }
+                            });
+                        }
                     }
                 }
             }
 
-            WindowEvent::MouseInput { state: btn_state, button, .. } => {
+            WindowEvent::MouseInput {
+                state: btn_state,
+                button,
+                ..
+            } => {
+                let woke = state.user_input();
                 state.input.handle_mouse_button(button, btn_state);
+
+                if btn_state == ElementState::Pressed && !woke {
+                    let actions: Vec<Action> = state
+                        .input_map
+                        .actions_for(Binding::Mouse(button))
+                        .collect();
+                    for action in actions {
+                        state.handle_action(action, event_loop);
+                    }
+                }
             }
 
             WindowEvent::CursorMoved { position, .. } => {
                 let new_pos = Vec2::new(position.x as f32, position.y as f32);
-                let old_pos = state.input.mouse_position;
+                state.user_input();
                 state.input.handle_mouse_move(new_pos);
+            }
 
-                // Handle camera controls
-                if state.input.is_mouse_held(MouseButton::Left) {
-                    let delta = new_pos - old_pos;
-                    state.camera.orbit(delta);
-                } else if state.input.is_mouse_held(MouseButton::Right) {
-                    let delta = new_pos - old_pos;
-                    state.camera.pan(delta);
+            WindowEvent::Focused(focused) => {
+                if focused {
+                    self.focused = Some(id);
+                } else if self.focused == Some(id) {
+                    self.focused = None;
+                }
+
+                // The OS or browser releases the lock when focus leaves
+                if state.engine.camera.mode == CameraMode::Fly {
+                    state.set_pointer_lock(focused);
                 }
             }
 
@@ -206,71 +1943,146 @@ impl ApplicationHandler for App {
                     MouseScrollDelta::PixelDelta(pos) => pos.y as f32 * 0.01,
                 };
                 state.input.handle_scroll(scroll);
-                state.camera.zoom(scroll);
+                if !state.user_input() {
+                    state.engine.camera.zoom(scroll);
+                }
             }
 
-            WindowEvent::RedrawRequested => {
-                // Calculate delta time
-                let now = web_time::Instant::now();
-                let dt = (now - state.last_frame).as_secs_f32();
-                state.last_frame = now;
-                state.time += dt;
+            WindowEvent::RedrawRequested if !state.hidden() => state.redraw(event_loop),
 
-                // Update camera
-                state.camera.update(dt);
+            _ => {}
+        }
+    }
 
-                // Render
-                match state.gpu.render(&state.camera, state.time) {
-                    Ok(_) => {}
-                    Err(wgpu::SurfaceError::Lost) => {
-                        state.gpu.resize(state.gpu.size);
-                    }
-                    Err(wgpu::SurfaceError::OutOfMemory) => {
-                        log::error!("Out of memory");
-                        event_loop.exit();
-                    }
-                    Err(e) => {
-                        log::warn!("Surface error: {:?}", e);
-                    }
-                }
+    fn device_event(&mut self, _event_loop: &ActiveEventLoop, _id: DeviceId, event: DeviceEvent) {
+        // Raw motion deltas instead of CursorMoved positions, so mouse-look
+        // keeps working when the cursor is locked or pinned to a screen edge
+        let Some(focused) = self.focused else {
+            return;
+        };
+        if let (Some(ViewerPhase::Running(state)), DeviceEvent::MouseMotion { delta }) =
+            (self.viewers.get_mut(&focused), event)
+        {
+            state.dirty = true;
+            state.user_input();
+            state
+                .input
+                .handle_mouse_motion(Vec2::new(delta.0 as f32, delta.1 as f32));
+        }
+    }
 
-                // Clear frame input state
-                state.input.end_frame();
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        // Viewers under a frame cap sleep until their next frame is due, and
+        // hidden ones, or ones rendering on demand with nothing to draw,
+        // until something happens. The rest ask for a redraw, which wakes
+        // the loop without it polling in between.
+        let now = web_time::Instant::now();
+        let mut wake: Option<web_time::Instant> = None;
+        for viewer in self.viewers.values_mut() {
+            match viewer {
+                ViewerPhase::Running(state) if state.hidden() => {}
+                ViewerPhase::Running(state) if !state.needs_frame() => state.idle = true,
+                ViewerPhase::Running(state) => match state.next_frame_at() {
+                    Some(at) if at > now => wake = Some(wake.map_or(at, |wake| wake.min(at))),
+                    _ => state.window.request_redraw(),
+                },
+                #[cfg(target_arch = "wasm32")]
+                ViewerPhase::Initializing { window, .. } => window.request_redraw(),
             }
-
-            _ => {}
         }
+        event_loop.set_control_flow(match wake {
+            Some(at) => winit::event_loop::ControlFlow::WaitUntil(at),
+            None => winit::event_loop::ControlFlow::Wait,
+        });
     }
 
-    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
-        match &self.phase {
-            AppPhase::Running(state) => {
-                state.window.request_redraw();
-            }
-            AppPhase::Initializing { window } => {
-                window.request_redraw();
-            }
-            _ => {}
+    #[cfg(not(target_arch = "wasm32"))]
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        for ViewerPhase::Running(state) in self.viewers.values_mut() {
+            state.stop_recording();
+            state.stop_session();
         }
+        let Some(settings) = &mut self.settings else {
+            return;
+        };
+        // Native viewers start up synchronously, so every one is running
+        for ViewerPhase::Running(state) in self.viewers.values() {
+            state.store_settings(settings);
+        }
+        settings.save();
     }
 }
 
-#[cfg(target_arch = "wasm32")]
-struct PendingState {
-    window: Arc<Window>,
-    gpu: GpuState,
-    world: HoneycombWorld,
+/// Where the native viewer takes control messages from, besides its window
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RemoteControl {
+    /// UDP port to listen for OSC on
+    pub osc: Option<u16>,
+    /// Read JSON commands from stdin
+    pub stdin: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn run(
+    adapter: AdapterOptions,
+    preset: Option<Preset>,
+    stream: Option<u32>,
+    bench: Option<BenchOptions>,
+    remote: RemoteControl,
+    script: Option<std::path::PathBuf>,
+    timeline: Option<std::path::PathBuf>,
+) -> Result<(), StartupError> {
+    let event_loop = EventLoop::new()?;
+    event_loop.set_control_flow(winit::event_loop::ControlFlow::Wait);
+
+    let mut config = Config::load();
+    if preset.is_some() {
+        config.world.preset = preset;
+    }
+    if stream.is_some() {
+        config.world.stream = stream;
+    }
+    let proxy = event_loop.create_proxy();
+    let mut app = App::new(config, adapter, bench, remote, script, timeline, proxy);
+    event_loop.run_app(&mut app)?;
+    app.startup_error.map_or(Ok(()), Err)
 }
 
 #[cfg(target_arch = "wasm32")]
 thread_local! {
-    static PENDING_STATE: std::cell::RefCell<Option<PendingState>> = const { std::cell::RefCell::new(None) };
+    /// Set once the page's single event loop is running; later viewers are sent through it
+    static PROXY: std::cell::RefCell<Option<winit::event_loop::EventLoopProxy<UserEvent>>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Wake the page's event loop after a change through the JS API, so an
+/// idle viewer draws it
+#[cfg(target_arch = "wasm32")]
+pub fn wake() {
+    if let Some(proxy) = PROXY.with(|p| p.borrow().clone()) {
+        let _ = proxy.send_event(UserEvent::Wake);
+    }
 }
 
-pub async fn run() {
-    let event_loop = EventLoop::new().expect("Failed to create event loop");
-    event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
+/// Open a viewer without blocking, so `wasm_main` can return to the page. winit
+/// allows one event loop per page, so the first call starts it and later calls
+/// hand their request to the running loop.
+#[cfg(target_arch = "wasm32")]
+pub fn open_viewer(request: ViewerRequest) -> Result<(), StartupError> {
+    use winit::platform::web::EventLoopExtWebSys;
+
+    if let Some(proxy) = PROXY.with(|p| p.borrow().clone()) {
+        return proxy
+            .send_event(UserEvent::CreateViewer(request))
+            .map_err(|_| StartupError::EventLoopClosed);
+    }
+
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    event_loop.set_control_flow(winit::event_loop::ControlFlow::Wait);
 
-    let mut app = App::new();
-    event_loop.run_app(&mut app).expect("Event loop error");
+    let proxy = event_loop.create_proxy();
+    PROXY.with(|p| *p.borrow_mut() = Some(proxy.clone()));
+    event_loop.spawn_app(App::new(proxy, request));
+    Ok(())
 }
@@ -0,0 +1,155 @@
+//! Attract mode for gallery and kiosk installations: after a stretch without
+//! input the camera wanders through the world on its own and a few
+//! parameters drift around their settings, until someone touches a key or
+//! the mouse. Each run follows a different path, generated from a handful of
+//! slow sine waves with random rates and phases.
+
+use glam::Vec3;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::camera::CameraPose;
+use crate::params::RuntimeParams;
+use crate::world::Bounds;
+
+/// Seconds to ease from the pose the viewer was left in onto the path
+const BLEND_TIME: f32 = 4.0;
+/// Orbit speed in radians per second
+const ORBIT_SPEED: f32 = 0.06;
+/// Share of the volume's half-size the focus point wanders over
+const FOCUS_RANGE: f32 = 0.35;
+
+pub struct Attract {
+    /// Seconds without input before it starts; 0 keeps it off
+    idle_after: f32,
+    idle: f32,
+    /// Runs so far, which seeds each run's path
+    runs: u64,
+    running: Option<Run>,
+}
+
+struct Run {
+    time: f32,
+    /// Where the camera was when the run started
+    from: CameraPose,
+    /// Parameters to drift around, and to restore afterwards
+    params: RuntimeParams,
+    /// Rate and phase of each wave
+    waves: [(f32, f32); 8],
+    /// Orbit direction, 1 or -1
+    direction: f32,
+}
+
+impl Attract {
+    pub fn new(idle_after: f32) -> Self {
+        Self {
+            idle_after,
+            idle: 0.0,
+            runs: 0,
+            running: None,
+        }
+    }
+
+    /// Change the idle time; 0 turns attract mode off, ending a run
+    #[cfg(target_arch = "wasm32")]
+    pub fn set_idle_after(&mut self, seconds: f32) -> Option<RuntimeParams> {
+        self.idle_after = seconds.max(0.0);
+        if self.idle_after == 0.0 {
+            self.running.take().map(|run| run.params)
+        } else {
+            None
+        }
+    }
+
+    /// Someone used the viewer. Ends a run, returning the parameters to
+    /// restore.
+    pub fn input(&mut self) -> Option<RuntimeParams> {
+        self.idle = 0.0;
+        self.running.take().map(|run| run.params)
+    }
+
+    /// Count idle time, starting a run once there has been enough, and move
+    /// the camera and parameters along the run, within `bounds`. Returns
+    /// whether one is going.
+    pub fn update(
+        &mut self,
+        dt: f32,
+        params: &mut RuntimeParams,
+        camera: &mut CameraPose,
+        bounds: &Bounds,
+    ) -> bool {
+        self.idle += dt;
+        if self.running.is_none() && self.idle_after > 0.0 && self.idle >= self.idle_after {
+            self.running = Some(Run::new(self.runs, *params, *camera));
+            self.runs += 1;
+            tracing::info!("Attract mode started");
+        }
+        let Some(run) = &mut self.running else {
+            return false;
+        };
+        run.time += dt;
+        *camera = run.pose(bounds);
+        *params = run.params();
+        true
+    }
+}
+
+impl Run {
+    fn new(seed: u64, params: RuntimeParams, from: CameraPose) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        Self {
+            time: 0.0,
+            from,
+            params,
+            // Periods of roughly half a minute to two minutes
+            waves: std::array::from_fn(|_| {
+                (
+                    rng.gen_range(0.05..0.2),
+                    rng.gen_range(0.0..std::f32::consts::TAU),
+                )
+            }),
+            direction: if rng.gen_bool(0.5) { 1.0 } else { -1.0 },
+        }
+    }
+
+    /// A wave, -1 to 1
+    fn wave(&self, index: usize) -> f32 {
+        let (rate, phase) = self.waves[index];
+        (self.time * rate + phase).sin()
+    }
+
+    /// 0 to 1 over the first few seconds, easing in and out
+    fn blend(&self) -> f32 {
+        let blend = (self.time / BLEND_TIME).clamp(0.0, 1.0);
+        blend * blend * (3.0 - 2.0 * blend)
+    }
+
+    fn pose(&self, bounds: &Bounds) -> CameraPose {
+        let half_size = bounds.size() * 0.5 * FOCUS_RANGE;
+        let path = CameraPose {
+            focus: bounds.center()
+                + Vec3::new(self.wave(0), 0.5 * self.wave(1), self.wave(2)) * half_size,
+            yaw: self.from.yaw + self.direction * ORBIT_SPEED * self.time,
+            pitch: 0.35 + 0.25 * self.wave(3),
+            distance: 28.0 + 6.0 * self.wave(4),
+        };
+        let blend = self.blend();
+        CameraPose {
+            focus: self.from.focus.lerp(path.focus, blend),
+            yaw: path.yaw,
+            pitch: self.from.pitch + (path.pitch - self.from.pitch) * blend,
+            distance: self.from.distance + (path.distance - self.from.distance) * blend,
+        }
+    }
+
+    /// The run's starting parameters with the glow, density and ripples
+    /// swelling and fading around them
+    fn params(&self) -> RuntimeParams {
+        let mut params = self.params;
+        let blend = self.blend();
+        params.membrane_glow *= 1.0 + 0.3 * blend * self.wave(5);
+        params.density *= 1.0 + 0.2 * blend * self.wave(6);
+        params.ripple_amplitude *= 1.0 + 0.5 * blend * self.wave(7);
+        params
+    }
+}
@@ -0,0 +1,119 @@
+//! Phase transitions as a cellular automaton over the cell adjacency graph:
+//! each tick every cell may take on another phase depending on the phases
+//! of the cells it shares a membrane with, so the honeycomb evolves like a
+//! 3D CA. Cells never move; only their phases change, and the cell buffers
+//! are rewritten after each tick that changed any.
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::params::AutomatonRule;
+use crate::world::{HoneycombCell, HoneycombWorld};
+
+/// Ticks run in one frame at most, so a stalled frame doesn't snowball
+const MAX_TICKS_PER_FRAME: u32 = 4;
+/// Chance per tick that a cell under the majority rule takes a random phase,
+/// so the domains it settles into keep shifting
+const MUTATION: f64 = 0.01;
+/// Neighbours in the successor phase it takes to advance under the cyclic rule
+const CYCLIC_THRESHOLD: usize = 1;
+
+/// Default ticks per second
+pub const RATE: f32 = 2.0;
+
+pub struct PhaseAutomaton {
+    neighbours: Vec<Vec<usize>>,
+    phase_count: usize,
+    /// Time not yet simulated, in seconds
+    pending: f32,
+    rng: ChaCha8Rng,
+}
+
+impl PhaseAutomaton {
+    /// Ticking with draws from `seed`, with the cells' neighbours as
+    /// `HoneycombWorld::neighbours` finds them
+    pub fn new(world: &HoneycombWorld, neighbours: Vec<Vec<usize>>, seed: u64) -> Self {
+        Self {
+            neighbours,
+            phase_count: world.phases.len(),
+            pending: 0.0,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Run the ticks `dt` seconds are worth at `rate` ticks per second.
+    /// Returns whether any cell changed phase.
+    pub fn advance(
+        &mut self,
+        cells: &mut [HoneycombCell],
+        rule: AutomatonRule,
+        rate: f32,
+        dt: f32,
+    ) -> bool {
+        if rule == AutomatonRule::Off || rate <= 0.0 {
+            self.pending = 0.0;
+            return false;
+        }
+        self.pending += dt;
+        let ticks = ((self.pending * rate) as u32).min(MAX_TICKS_PER_FRAME);
+        self.pending = (self.pending - ticks as f32 / rate).min(1.0 / rate);
+        let mut changed = false;
+        for _ in 0..ticks {
+            changed |= self.tick(cells, rule);
+        }
+        changed
+    }
+
+    /// Apply `rule` to every cell at once. Returns whether any changed.
+    fn tick(&mut self, cells: &mut [HoneycombCell], rule: AutomatonRule) -> bool {
+        let phases: Vec<u32> = cells.iter().map(|cell| cell.phase_index).collect();
+        let mut changed = false;
+        for (i, cell) in cells.iter_mut().enumerate() {
+            let neighbours = self.neighbours[i].iter().map(|&j| phases[j]);
+            let next = match rule {
+                AutomatonRule::Off => phases[i],
+                AutomatonRule::Majority => {
+                    if self.rng.gen_bool(MUTATION) {
+                        self.rng.gen_range(0..self.phase_count as u32)
+                    } else {
+                        majority(phases[i], neighbours, self.phase_count, &mut self.rng)
+                    }
+                }
+                AutomatonRule::Cyclic => {
+                    let successor = (phases[i] + 1) % self.phase_count as u32;
+                    if neighbours.filter(|&phase| phase == successor).count() >= CYCLIC_THRESHOLD {
+                        successor
+                    } else {
+                        phases[i]
+                    }
+                }
+            };
+            changed |= next != cell.phase_index;
+            cell.phase_index = next;
+        }
+        changed
+    }
+}
+
+/// The phase most common among a cell and its neighbours. A tie the cell's
+/// own phase is part of keeps it; other ties are broken at random.
+fn majority(
+    own: u32,
+    neighbours: impl Iterator<Item = u32>,
+    phase_count: usize,
+    rng: &mut ChaCha8Rng,
+) -> u32 {
+    let mut counts = vec![0usize; phase_count];
+    counts[own as usize] += 1;
+    for phase in neighbours {
+        counts[phase as usize] += 1;
+    }
+    let most = counts.iter().copied().max().unwrap_or(0);
+    if counts[own as usize] == most {
+        return own;
+    }
+    let leaders: Vec<u32> = (0..phase_count as u32)
+        .filter(|&phase| counts[phase as usize] == most)
+        .collect();
+    leaders[rng.gen_range(0..leaders.len())]
+}
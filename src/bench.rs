@@ -0,0 +1,215 @@
+//! `vendek bench`: render a fixed camera path for a set number of frames and
+//! report frame-time statistics, so performance can be compared between
+//! commits. Animation time advances by a fixed step per frame, so every run
+//! renders the same images regardless of how fast they are produced.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use glam::Vec3;
+use serde::Serialize;
+
+use crate::profiler::GpuTimings;
+
+/// Animation time per frame, in seconds
+pub const FRAME_TIME: f32 = 1.0 / 60.0;
+
+#[derive(Clone, Debug)]
+pub struct BenchOptions {
+    /// Measured frames
+    pub frames: u32,
+    /// Frames rendered before measuring starts, to let pipelines and clocks settle
+    pub warmup: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Where the JSON report is written
+    pub output: PathBuf,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            frames: 600,
+            warmup: 60,
+            width: 1280,
+            height: 720,
+            output: PathBuf::from("vendek-bench.json"),
+        }
+    }
+}
+
+/// Orbit camera pose for `frame` of `frames`: one full turn around the world
+/// while bobbing in pitch and diving toward the centre halfway through
+pub fn camera_pose(frame: u32, frames: u32) -> (Vec3, f32, f32, f32) {
+    let t = frame as f32 / frames.max(1) as f32;
+    let tau = std::f32::consts::TAU;
+    let yaw = 0.3 + tau * t;
+    let pitch = 0.4 + 0.25 * (2.0 * tau * t).sin();
+    let distance = 35.0 - 17.0 * (std::f32::consts::PI * t).sin();
+    (Vec3::ZERO, yaw, pitch, distance)
+}
+
+/// Frame and GPU timings collected over a run
+pub struct Bench {
+    options: BenchOptions,
+    /// Frames rendered so far, including warmup
+    frame: u32,
+    /// Milliseconds between consecutive measured frames
+    frame_ms: Vec<f32>,
+    gpu: Vec<GpuTimings>,
+}
+
+impl Bench {
+    pub fn new(options: BenchOptions) -> Self {
+        Self {
+            frame_ms: Vec::with_capacity(options.frames as usize),
+            options,
+            frame: 0,
+            gpu: Vec::new(),
+        }
+    }
+
+    pub fn options(&self) -> &BenchOptions {
+        &self.options
+    }
+
+    /// Index of the frame about to be rendered, counting warmup frames
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Total frames to render, including warmup
+    pub fn total_frames(&self) -> u32 {
+        self.options.warmup + self.options.frames
+    }
+
+    /// Record a rendered frame. `dt` is the wall time since the previous frame
+    /// and `timings` the most recent GPU timings, which lag a frame or two
+    /// behind. Returns true once every frame has been rendered.
+    pub fn record(&mut self, dt: f32, timings: Option<&GpuTimings>) -> bool {
+        // The first measured interval still spans the last warmup frame
+        if self.frame > self.options.warmup {
+            self.frame_ms.push(dt * 1000.0);
+        }
+        if let Some(timings) = timings {
+            let warm = timings.frame >= self.options.warmup as u64;
+            let new = self
+                .gpu
+                .last()
+                .is_none_or(|last| last.frame != timings.frame);
+            if warm && new {
+                self.gpu.push(timings.clone());
+            }
+        }
+
+        self.frame += 1;
+        self.frame >= self.total_frames()
+    }
+
+    pub fn report(
+        &self,
+        adapter: &wgpu::AdapterInfo,
+        raymarch_path: &str,
+        size: (u32, u32),
+    ) -> Report {
+        let mut gpu_ms: BTreeMap<&'static str, Vec<f32>> = BTreeMap::new();
+        for timings in &self.gpu {
+            for (pass, ms) in &timings.passes {
+                gpu_ms.entry(pass.name()).or_default().push(*ms);
+            }
+        }
+
+        Report {
+            version: env!("CARGO_PKG_VERSION"),
+            adapter: adapter.name.clone(),
+            backend: format!("{:?}", adapter.backend),
+            raymarch_path: raymarch_path.to_string(),
+            width: size.0,
+            height: size.1,
+            frames: self.frame_ms.len(),
+            frame_ms: Stats::new(&self.frame_ms),
+            gpu_samples: self.gpu.len(),
+            gpu_ms: gpu_ms
+                .into_iter()
+                .filter_map(|(pass, samples)| Some((pass.to_string(), Stats::new(&samples)?)))
+                .collect(),
+        }
+    }
+}
+
+/// Summary of one set of samples, in milliseconds
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Stats {
+    pub min: f32,
+    pub avg: f32,
+    pub p99: f32,
+    pub max: f32,
+}
+
+impl Stats {
+    /// `None` for an empty sample set
+    fn new(samples: &[f32]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f32::total_cmp);
+        // Nearest-rank percentile
+        let rank = (sorted.len() as f32 * 0.99).ceil() as usize;
+        Some(Self {
+            min: sorted[0],
+            avg: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            p99: sorted[rank.clamp(1, sorted.len()) - 1],
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub version: &'static str,
+    pub adapter: String,
+    pub backend: String,
+    pub raymarch_path: String,
+    pub width: u32,
+    pub height: u32,
+    pub frames: usize,
+    pub frame_ms: Option<Stats>,
+    /// Frames with GPU timings; absent without timestamp query support
+    pub gpu_samples: usize,
+    pub gpu_ms: BTreeMap<String, Stats>,
+}
+
+impl Report {
+    /// Print a human-readable summary to stdout
+    pub fn print(&self) {
+        println!(
+            "{} ({}, {} raymarch) at {}x{}, {} frames",
+            self.adapter, self.backend, self.raymarch_path, self.width, self.height, self.frames
+        );
+        println!(
+            "{:<10} {:>8} {:>8} {:>8} {:>8}",
+            "ms", "min", "avg", "p99", "max"
+        );
+        let row = |name: &str, stats: &Stats| {
+            println!(
+                "{:<10} {:>8.3} {:>8.3} {:>8.3} {:>8.3}",
+                name, stats.min, stats.avg, stats.p99, stats.max
+            );
+        };
+        if let Some(stats) = &self.frame_ms {
+            row("frame", stats);
+        }
+        for (pass, stats) in &self.gpu_ms {
+            row(&format!("gpu {}", pass), stats);
+        }
+        if self.gpu_samples == 0 {
+            println!("(no GPU timings: the adapter lacks timestamp queries)");
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, json + "\n")
+    }
+}
@@ -0,0 +1,377 @@
+//! What a world and its renderer start from, gathered in one place.
+//! `VendekBuilder` takes the seed, cell and phase counts, the volume, the
+//! starting camera, a quality tier, the optional features, the emitting
+//! cells and whether the world streams through fluent setters, or starts
+//! from a named preset, and builds an embedded engine or a headless
+//! renderer from them.
+//! The viewer starts from one as well, so its defaults live here.
+
+use glam::Vec3;
+
+use crate::camera::CameraPose;
+use crate::engine::{EngineConfig, VendekEngine};
+use crate::gpu::{GpuError, GpuState};
+use crate::palette::Palette;
+use crate::params::{RuntimeParams, MAX_STEPS, STEP_SIZE};
+use crate::preset::Preset;
+use crate::stream::{WorldStream, MAX_STREAM_RADIUS};
+use crate::world::{Bounds, EmitterChoice, HoneycombWorld, WorldStyle};
+
+pub const DEFAULT_SEED: u64 = 42;
+pub const DEFAULT_CELLS: usize = 128;
+pub const DEFAULT_PHASES: usize = 12;
+/// Phases a world may have at most; `visiblePhases` has a bit for each
+pub const MAX_PHASES: usize = 32;
+/// Shortest side the volume may have, in world units
+const MIN_EXTENT: f32 = 1.0;
+
+/// How much detail the raymarch goes into, traded against frame rate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Quality {
+    /// Few, long steps, longer still and plainer with distance, for
+    /// integrated GPUs and software renderers
+    Low,
+    /// The default, and what the viewer starts with on a browser's WebGPU
+    #[default]
+    Medium,
+    /// Finer steps that resolve thin membranes
+    High,
+    /// Finer still, and supersampled on the compute path
+    Ultra,
+}
+
+impl Quality {
+    /// Set the step count, step size, level-of-detail falloff and
+    /// supersampling of the tier
+    fn apply(self, params: &mut RuntimeParams) {
+        let (max_steps, step_size, lod_falloff, supersample) = match self {
+            Quality::Low => (64, 0.3, 1.0, 1.0),
+            Quality::Medium => (MAX_STEPS, STEP_SIZE, 0.0, 1.0),
+            Quality::High => (256, 0.08, 0.0, 1.0),
+            Quality::Ultra => (512, 0.05, 0.0, 1.5),
+        };
+        params.max_steps = max_steps;
+        params.step_size = step_size;
+        params.lod_falloff = lod_falloff;
+        params.supersample = supersample;
+    }
+}
+
+/// Broad kind of adapter, which the viewer's starting quality and
+/// resolution follow when the builder doesn't set a tier
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceClass {
+    /// A GPU with memory of its own
+    Discrete,
+    /// A GPU sharing the CPU's memory, or one the driver doesn't describe
+    Integrated,
+    /// WebGPU in a browser, which doesn't say what GPU is behind it
+    Browser,
+    /// An adapter on the fragment path, such as WebGL2, or one whose
+    /// textures are too small for a desktop GPU
+    Downlevel,
+    /// A software renderer running on the CPU
+    Software,
+}
+
+/// Largest 2D texture every desktop GPU and WebGPU implementation allows
+const DESKTOP_TEXTURE_DIMENSION: u32 = 8192;
+
+impl DeviceClass {
+    /// Classify an adapter by its type and backend, and by the `limits` of
+    /// its device, on which `compute` says whether the compute path runs
+    pub fn of(info: &wgpu::AdapterInfo, limits: &wgpu::Limits, compute: bool) -> Self {
+        match info.device_type {
+            wgpu::DeviceType::Cpu => DeviceClass::Software,
+            wgpu::DeviceType::DiscreteGpu => DeviceClass::Discrete,
+            _ if !compute || limits.max_texture_dimension_2d < DESKTOP_TEXTURE_DIMENSION => {
+                DeviceClass::Downlevel
+            }
+            _ if info.backend == wgpu::Backend::BrowserWebGpu => DeviceClass::Browser,
+            _ => DeviceClass::Integrated,
+        }
+    }
+
+    /// Quality tier the viewer starts at
+    pub fn quality(self) -> Quality {
+        match self {
+            DeviceClass::Discrete => Quality::High,
+            DeviceClass::Browser => Quality::Medium,
+            DeviceClass::Integrated | DeviceClass::Downlevel | DeviceClass::Software => {
+                Quality::Low
+            }
+        }
+    }
+
+    /// Raymarch resolution dynamic resolution starts from, relative to the
+    /// window. The fragment path always draws at the window's.
+    pub fn render_scale(self) -> f32 {
+        match self {
+            DeviceClass::Software => 0.5,
+            DeviceClass::Integrated => 0.8,
+            _ => 1.0,
+        }
+    }
+}
+
+impl std::fmt::Display for DeviceClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DeviceClass::Discrete => "discrete GPU",
+            DeviceClass::Integrated => "integrated GPU",
+            DeviceClass::Browser => "browser WebGPU",
+            DeviceClass::Downlevel => "downlevel GPU",
+            DeviceClass::Software => "software renderer",
+        })
+    }
+}
+
+/// Optional parts of the picture, on or off at the start. All of them can
+/// be switched later through their parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Features {
+    /// Membranes oscillating with their neighbouring cells' phases
+    pub coupling: bool,
+    /// Orientation axes in the corner of the main view
+    pub axes: bool,
+    /// Wireframe of the volume's bounding box
+    pub bounds: bool,
+    /// An overview of the whole world beside the main view
+    pub split_view: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        let params = RuntimeParams::default();
+        Self {
+            coupling: params.enable_coupling,
+            axes: params.show_axes,
+            bounds: params.show_bounds,
+            split_view: params.split_view,
+        }
+    }
+}
+
+/// Settings for a world and its renderer; see the module docs
+#[derive(Clone, Debug)]
+pub struct VendekBuilder {
+    seed: u64,
+    cells: usize,
+    phases: usize,
+    style: WorldStyle,
+    /// Palette to start with, if not the parameters' default
+    palette: Option<Palette>,
+    bounds: Bounds,
+    camera: CameraPose,
+    /// Quality tier, if chosen rather than left to the default or the
+    /// adapter
+    quality: Option<Quality>,
+    features: Features,
+    emitters: EmitterChoice,
+    /// Chunks loaded around the camera on each side, if streamed
+    stream: Option<u32>,
+}
+
+impl VendekBuilder {
+    /// The viewer's defaults
+    pub fn new() -> Self {
+        Self {
+            seed: DEFAULT_SEED,
+            cells: DEFAULT_CELLS,
+            phases: DEFAULT_PHASES,
+            style: WorldStyle::default(),
+            palette: None,
+            bounds: Bounds::default(),
+            camera: CameraPose::default(),
+            quality: None,
+            features: Features::default(),
+            emitters: EmitterChoice::default(),
+            stream: None,
+        }
+    }
+
+    /// Start from a named preset: its seed layout, cell and phase counts,
+    /// phase densities and palette. Setters called afterwards override it.
+    pub fn preset(mut self, preset: Preset) -> Self {
+        self.cells = preset.cells();
+        self.phases = preset.phases();
+        self.style = preset.style();
+        self.palette = Some(preset.palette());
+        self
+    }
+
+    /// Seed for world generation and the simulations; the same seed gives
+    /// the same world
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Number of honeycomb cells, at least one
+    pub fn cells(mut self, cells: usize) -> Self {
+        self.cells = cells.max(1);
+        self
+    }
+
+    /// Number of vendek phases, 1 to 32
+    pub fn phases(mut self, phases: usize) -> Self {
+        self.phases = phases.clamp(1, MAX_PHASES);
+        self
+    }
+
+    /// Corners of the box the world fills, in either order. Sides shorter
+    /// than a world unit are lengthened to one.
+    pub fn volume(mut self, a: [f32; 3], b: [f32; 3]) -> Self {
+        let bounds = Bounds::new(Vec3::from_array(a), Vec3::from_array(b));
+        self.bounds = Bounds {
+            min: bounds.min,
+            max: bounds.max.max(bounds.min + MIN_EXTENT),
+        };
+        self
+    }
+
+    /// Where the orbit camera starts, and where resetting the view returns
+    /// it: the point it looks at, angles in radians and the distance from
+    /// the point
+    pub fn camera(mut self, focus: [f32; 3], yaw: f32, pitch: f32, distance: f32) -> Self {
+        self.camera = CameraPose {
+            focus: Vec3::from_array(focus),
+            yaw,
+            pitch,
+            distance,
+        };
+        self
+    }
+
+    pub fn quality(mut self, quality: Quality) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    /// Start at the quality tier suited to a `class` of adapter, unless a
+    /// tier was set
+    pub(crate) fn for_device(mut self, class: DeviceClass) -> Self {
+        let quality = *self.quality.get_or_insert(class.quality());
+        tracing::info!("Starting at {:?} quality on a {}", quality, class);
+        self
+    }
+
+    pub fn features(mut self, features: Features) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Cells that glow from within, lighting the volume around them; none
+    /// by default. See `EmitterChoice`.
+    pub fn emitters(mut self, emitters: EmitterChoice) -> Self {
+        self.emitters = emitters;
+        self
+    }
+
+    /// Stream a world larger than the volume: the volume becomes one chunk
+    /// of the cell count, tiling space, and the chunks within `radius` of
+    /// the one the camera's focus is in stay loaded, up to
+    /// `MAX_STREAM_RADIUS`. A radius of one loads 27 chunks. The raymarch
+    /// only covers the loaded chunks, and streamed worlds have no emitters.
+    pub fn stream(mut self, radius: u32) -> Self {
+        self.stream = Some(radius.min(MAX_STREAM_RADIUS));
+        self
+    }
+
+    /// Generate the world and set it up on a host application's device
+    pub fn build(self, config: EngineConfig) -> Result<VendekEngine, GpuError> {
+        let world = self.generate_world();
+        let gpu = GpuState::from_device(
+            config.adapter,
+            config.device,
+            config.queue,
+            config.format,
+            config.width,
+            config.height,
+            &world,
+        )?;
+        Ok(VendekEngine::from_parts(gpu, world, self))
+    }
+
+    /// Generate the world and set it up to render `width`×`height` frames
+    /// without a window, on any backend including OpenGL
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn build_headless(
+        self,
+        width: u32,
+        height: u32,
+    ) -> Result<crate::headless::HeadlessRenderer, GpuError> {
+        let world = self.generate_world();
+        let options = crate::gpu::AdapterOptions {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        };
+        let gpu = GpuState::new_headless(&world, &options, width, height)?;
+        Ok(crate::headless::HeadlessRenderer::from_parts(
+            gpu,
+            self.initial_params(),
+            self.camera,
+        ))
+    }
+
+    pub(crate) fn generate_world(&self) -> HoneycombWorld {
+        self.generate_world_around(self.camera.focus).0
+    }
+
+    /// The world, with a streamed world's chunks loaded around `focus`
+    /// rather than the starting camera's, and the stream keeping them
+    /// loaded
+    pub(crate) fn generate_world_around(
+        &self,
+        focus: Vec3,
+    ) -> (HoneycombWorld, Option<WorldStream>) {
+        if let Some(stream) = self.world_stream(focus) {
+            return (stream.generate(self.seed, self.phases), Some(stream));
+        }
+        let mut world = HoneycombWorld::generate_styled(
+            self.seed,
+            self.cells,
+            self.phases,
+            self.bounds,
+            &self.style,
+        );
+        world.choose_emitters(&self.emitters);
+        (world, None)
+    }
+
+    /// For a streamed world, which chunks are loaded with the camera's
+    /// focus at `focus`
+    pub(crate) fn world_stream(&self, focus: Vec3) -> Option<WorldStream> {
+        self.stream
+            .map(|radius| WorldStream::new(self.bounds, self.cells, self.style, radius, focus))
+    }
+
+    /// Parameters for the quality tier and features
+    pub(crate) fn initial_params(&self) -> RuntimeParams {
+        let mut params = RuntimeParams::default();
+        self.quality.unwrap_or_default().apply(&mut params);
+        params.enable_coupling = self.features.coupling;
+        params.show_axes = self.features.axes;
+        params.show_bounds = self.features.bounds;
+        params.split_view = self.features.split_view;
+        if let Some(palette) = self.palette {
+            params.palette = palette.index();
+        }
+        params
+    }
+
+    pub(crate) fn camera_pose(&self) -> CameraPose {
+        self.camera
+    }
+
+    /// Cells, per chunk when streamed, and phases the world will have
+    pub(crate) fn counts(&self) -> (usize, usize) {
+        (self.cells, self.phases)
+    }
+}
+
+impl Default for VendekBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
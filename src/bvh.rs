@@ -0,0 +1,95 @@
+//! Bounding volume hierarchy over the Voronoi seeds. Every raymarch sample
+//! looks up the two seeds nearest it, which means testing each seed in
+//! turn; that is fine for a few hundred cells but grows with the count.
+//! The hierarchy is built on the CPU with median splits along the longest
+//! axis and uploaded as a flat array, and the compute path walks it to
+//! skip the boxes that can't hold a nearer seed, so lookups scale to tens
+//! of thousands of cells.
+
+use glam::Vec3;
+
+use crate::world::HoneycombCell;
+
+/// Seeds a leaf holds at most
+pub const LEAF_SIZE: usize = 4;
+/// Cells from which the raymarch looks seeds up through the hierarchy
+/// instead of testing every one
+pub const MIN_CELLS: usize = 512;
+
+/// A node as the shaders read it. Nodes are stored depth first, so an inner
+/// node's first child directly follows it.
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct BvhNode {
+    /// Box around the seeds below the node
+    pub min: Vec3,
+    /// Index of an inner node's second child; 0 for leaves
+    pub right: u32,
+    pub max: Vec3,
+    /// Seeds in a leaf
+    pub count: u32,
+    /// Indices of a leaf's seeds in the world's cells
+    pub cells: [u32; LEAF_SIZE],
+}
+
+/// The hierarchy over a world's seeds
+#[derive(Clone, Debug)]
+pub struct SeedBvh {
+    pub nodes: Vec<BvhNode>,
+}
+
+impl SeedBvh {
+    /// Build over `cells`; an empty slice gives a single empty leaf
+    pub fn build(cells: &[HoneycombCell]) -> Self {
+        let _span = tracing::info_span!("build_bvh", cells = cells.len()).entered();
+        let mut indices: Vec<u32> = (0..cells.len() as u32).collect();
+        let mut nodes = Vec::with_capacity(2 * cells.len().div_ceil(LEAF_SIZE));
+        Self::build_node(&mut nodes, cells, &mut indices);
+        Self { nodes }
+    }
+
+    /// Append the node over `indices` and everything below it, returning its
+    /// index
+    fn build_node(nodes: &mut Vec<BvhNode>, cells: &[HoneycombCell], indices: &mut [u32]) -> u32 {
+        let (min, max) = indices.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), &i| {
+                let position = cells[i as usize].position;
+                (min.min(position), max.max(position))
+            },
+        );
+        let index = nodes.len() as u32;
+        let mut node = BvhNode {
+            min: if indices.is_empty() { Vec3::ZERO } else { min },
+            right: 0,
+            max: if indices.is_empty() { Vec3::ZERO } else { max },
+            count: 0,
+            cells: [0; LEAF_SIZE],
+        };
+        if indices.len() <= LEAF_SIZE {
+            node.count = indices.len() as u32;
+            node.cells[..indices.len()].copy_from_slice(indices);
+            nodes.push(node);
+            return index;
+        }
+
+        let extent = max - min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        let middle = indices.len() / 2;
+        indices.select_nth_unstable_by(middle, |&a, &b| {
+            cells[a as usize].position[axis].total_cmp(&cells[b as usize].position[axis])
+        });
+
+        nodes.push(node);
+        let (left, right) = indices.split_at_mut(middle);
+        Self::build_node(nodes, cells, left);
+        nodes[index as usize].right = Self::build_node(nodes, cells, right);
+        index
+    }
+}
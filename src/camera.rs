@@ -1,6 +1,109 @@
 use glam::{Mat4, Vec2, Vec3};
 
+/// Furthest the orbit camera tilts up or down, short of straight overhead
+/// where its view matrix has no up direction
+const MAX_PITCH: f32 = 1.5;
+/// Closest and furthest the orbit camera comes to its focus under input
+const MIN_DISTANCE: f32 = 5.0;
+const MAX_DISTANCE: f32 = 50.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Rotate around a focus point
+    Orbit,
+    /// First-person mouse look from a fixed eye position
+    Fly,
+}
+
+/// Where the camera is heading: the pose its smoothing settles on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraPose {
+    pub focus: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+}
+
+impl CameraPose {
+    /// The pose with its pitch and distance kept where input can take them
+    pub fn clamped(self) -> Self {
+        Self {
+            pitch: self.pitch.clamp(-MAX_PITCH, MAX_PITCH),
+            distance: self.distance.clamp(MIN_DISTANCE, MAX_DISTANCE),
+            ..self
+        }
+    }
+
+    /// Whether the two differ by less than anyone would see, so motion
+    /// coasting to a stop doesn't count as moving
+    pub fn is_close(&self, other: &CameraPose) -> bool {
+        const EPSILON: f32 = 1e-4;
+        self.focus.distance(other.focus) < EPSILON * self.distance.max(1.0)
+            && (self.yaw - other.yaw).abs() < EPSILON
+            && (self.pitch - other.pitch).abs() < EPSILON
+            && (self.distance - other.distance).abs() < EPSILON * self.distance.max(1.0)
+    }
+}
+
+/// One number of a pose, as scripts and timelines name them: `camera.yaw`,
+/// `camera.pitch`, `camera.distance`, or the focus's `camera.x`, `camera.y`
+/// and `camera.z`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PoseField {
+    Yaw,
+    Pitch,
+    Distance,
+    X,
+    Y,
+    Z,
+}
+
+impl PoseField {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "camera.yaw" => PoseField::Yaw,
+            "camera.pitch" => PoseField::Pitch,
+            "camera.distance" => PoseField::Distance,
+            "camera.x" => PoseField::X,
+            "camera.y" => PoseField::Y,
+            "camera.z" => PoseField::Z,
+            _ => return None,
+        })
+    }
+}
+
+impl CameraPose {
+    pub fn get(&self, field: PoseField) -> f32 {
+        match field {
+            PoseField::Yaw => self.yaw,
+            PoseField::Pitch => self.pitch,
+            PoseField::Distance => self.distance,
+            PoseField::X => self.focus.x,
+            PoseField::Y => self.focus.y,
+            PoseField::Z => self.focus.z,
+        }
+    }
+
+    pub fn set(&mut self, field: PoseField, value: f32) {
+        match field {
+            PoseField::Yaw => self.yaw = value,
+            PoseField::Pitch => self.pitch = value,
+            PoseField::Distance => self.distance = value,
+            PoseField::X => self.focus.x = value,
+            PoseField::Y => self.focus.y = value,
+            PoseField::Z => self.focus.z = value,
+        }
+    }
+}
+
+impl Default for CameraPose {
+    fn default() -> Self {
+        Camera::new().target_pose()
+    }
+}
+
 pub struct Camera {
+    pub mode: CameraMode,
     pub focus: Vec3,
     pub distance: f32,
     pub yaw: f32,   // radians
@@ -14,11 +117,17 @@ pub struct Camera {
     target_distance: f32,
     target_yaw: f32,
     target_pitch: f32,
+
+    // Keyboard navigation velocities, eased toward the held-key input
+    move_velocity: Vec3,
+    orbit_velocity: Vec2,
+    zoom_velocity: f32,
 }
 
 impl Camera {
     pub fn new() -> Self {
         Self {
+            mode: CameraMode::Orbit,
             focus: Vec3::ZERO,
             distance: 35.0,
             yaw: 0.3,
@@ -30,14 +139,22 @@ impl Camera {
             target_distance: 35.0,
             target_yaw: 0.3,
             target_pitch: 0.4,
+            move_velocity: Vec3::ZERO,
+            orbit_velocity: Vec2::ZERO,
+            zoom_velocity: 0.0,
         }
     }
 
+    /// Offset from the focus point to the eye for the given angles
+    fn offset(yaw: f32, pitch: f32, distance: f32) -> Vec3 {
+        let x = distance * pitch.cos() * yaw.sin();
+        let y = distance * pitch.sin();
+        let z = distance * pitch.cos() * yaw.cos();
+        Vec3::new(x, y, z)
+    }
+
     pub fn position(&self) -> Vec3 {
-        let x = self.distance * self.pitch.cos() * self.yaw.sin();
-        let y = self.distance * self.pitch.sin();
-        let z = self.distance * self.pitch.cos() * self.yaw.cos();
-        self.focus + Vec3::new(x, y, z)
+        self.focus + Self::offset(self.yaw, self.pitch, self.distance)
     }
 
     pub fn view_matrix(&self) -> Mat4 {
@@ -50,11 +167,12 @@ impl Camera {
 
     pub fn orbit(&mut self, delta: Vec2) {
         self.target_yaw += delta.x * 0.01;
-        self.target_pitch = (self.target_pitch + delta.y * 0.01).clamp(-1.5, 1.5);
+        self.target_pitch = (self.target_pitch + delta.y * 0.01).clamp(-MAX_PITCH, MAX_PITCH);
     }
 
     pub fn zoom(&mut self, delta: f32) {
-        self.target_distance = (self.target_distance * (1.0 - delta * 0.1)).clamp(5.0, 50.0);
+        self.target_distance =
+            (self.target_distance * (1.0 - delta * 0.1)).clamp(MIN_DISTANCE, MAX_DISTANCE);
     }
 
     pub fn pan(&mut self, delta: Vec2) {
@@ -63,6 +181,94 @@ impl Camera {
         self.target_focus += right * delta.x * 0.02 + up * delta.y * 0.02;
     }
 
+    /// Rotate the view direction about the eye, keeping the eye fixed
+    pub fn look(&mut self, delta: Vec2) {
+        let eye = self.target_focus
+            + Self::offset(self.target_yaw, self.target_pitch, self.target_distance);
+        self.target_yaw -= delta.x * 0.003;
+        self.target_pitch = (self.target_pitch + delta.y * 0.003).clamp(-MAX_PITCH, MAX_PITCH);
+        self.target_focus =
+            eye - Self::offset(self.target_yaw, self.target_pitch, self.target_distance);
+    }
+
+    /// Move eye and focus together along the camera's axes
+    /// (x = right, y = world up, z = view direction)
+    pub fn translate(&mut self, local: Vec3) {
+        let forward = -Self::offset(self.target_yaw, self.target_pitch, 1.0);
+        let right = Vec3::new(self.target_yaw.cos(), 0.0, -self.target_yaw.sin());
+        self.target_focus += right * local.x + Vec3::Y * local.y + forward * local.z;
+    }
+
+    /// Keyboard navigation for orbit mode. Inputs are in -1..=1: `movement` moves
+    /// the focus (x = right, y = up, z = forward along the ground plane), `orbit`
+    /// turns yaw/pitch and `zoom` dollies in. Velocities ease toward the input so
+    /// motion ramps up and coasts to a stop instead of snapping.
+    pub fn navigate(&mut self, movement: Vec3, orbit: Vec2, zoom: f32, dt: f32) {
+        let ease = 1.0 - (-8.0 * dt).exp();
+        self.move_velocity = self.move_velocity.lerp(movement, ease);
+        self.orbit_velocity = self.orbit_velocity.lerp(orbit, ease);
+        self.zoom_velocity += (zoom - self.zoom_velocity) * ease;
+
+        // Scale movement with distance so it feels the same zoomed in or out
+        let forward = Vec3::new(-self.target_yaw.sin(), 0.0, -self.target_yaw.cos());
+        let right = Vec3::new(self.target_yaw.cos(), 0.0, -self.target_yaw.sin());
+        let speed = self.target_distance * 0.5 * dt;
+        let v = self.move_velocity;
+        self.target_focus += (right * v.x + Vec3::Y * v.y + forward * v.z) * speed;
+
+        self.target_yaw += self.orbit_velocity.x * 1.5 * dt;
+        self.target_pitch =
+            (self.target_pitch + self.orbit_velocity.y * 1.5 * dt).clamp(-MAX_PITCH, MAX_PITCH);
+        self.zoom(self.zoom_velocity * 10.0 * dt);
+    }
+
+    /// Place the orbit camera immediately, skipping the smoothing
+    pub fn jump_to(&mut self, focus: Vec3, yaw: f32, pitch: f32, distance: f32) {
+        self.mode = CameraMode::Orbit;
+        self.focus = focus;
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.distance = distance;
+        self.target_focus = focus;
+        self.target_yaw = yaw;
+        self.target_pitch = pitch;
+        self.target_distance = distance;
+    }
+
+    pub fn target_pose(&self) -> CameraPose {
+        CameraPose {
+            focus: self.target_focus,
+            yaw: self.target_yaw,
+            pitch: self.target_pitch,
+            distance: self.target_distance,
+        }
+    }
+
+    /// Head for `pose`, smoothing the way there as mouse input does
+    pub fn glide_to(&mut self, pose: CameraPose) {
+        let pose = pose.clamped();
+        self.target_focus = pose.focus;
+        self.target_yaw = pose.yaw;
+        self.target_pitch = pose.pitch;
+        self.target_distance = pose.distance;
+    }
+
+    /// Whether the camera has come to rest on where it was heading, with
+    /// no keyboard motion still coasting
+    pub fn is_settled(&self) -> bool {
+        const EPSILON: f32 = 1e-3;
+        let pose = CameraPose {
+            focus: self.focus,
+            yaw: self.yaw,
+            pitch: self.pitch,
+            distance: self.distance,
+        };
+        pose.is_close(&self.target_pose())
+            && self.move_velocity.length() < EPSILON
+            && self.orbit_velocity.length() < EPSILON
+            && self.zoom_velocity.abs() < EPSILON
+    }
+
     pub fn update(&mut self, dt: f32) {
         let smoothing = 1.0 - (-10.0 * dt).exp();
         self.focus = self.focus.lerp(self.target_focus, smoothing);
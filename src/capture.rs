@@ -0,0 +1,76 @@
+//! PNG encoding for screenshots and the browser capture API, and OpenEXR
+//! encoding for HDR stills
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::File;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::BufWriter;
+use std::io::Write;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
+
+#[cfg(not(target_arch = "wasm32"))]
+use exr::prelude::f16;
+
+/// Timestamped output path for a screenshot in the working directory
+#[cfg(not(target_arch = "wasm32"))]
+pub fn screenshot_path() -> PathBuf {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    PathBuf::from(format!("vendek-{}.png", secs))
+}
+
+/// Write tightly packed RGBA8 pixels as an sRGB PNG
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_png(
+    path: &Path,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+) -> Result<(), png::EncodingError> {
+    let file = File::create(path)?;
+    write_png(BufWriter::new(file), width, height, rgba)
+}
+
+/// Write RGBA half floats, given as their raw bits and tightly packed, as
+/// a losslessly compressed OpenEXR image with linear values, so the HDR
+/// range survives for grading elsewhere
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_exr(path: &Path, width: u32, height: u32, rgba: &[u16]) -> std::io::Result<()> {
+    let width = width as usize;
+    let texel = |x: usize, y: usize| {
+        let [r, g, b, a] =
+            [0, 1, 2, 3].map(|channel| f16::from_bits(rgba[(y * width + x) * 4 + channel]));
+        (r, g, b, a)
+    };
+    exr::prelude::write_rgba_file(path, width, height as usize, texel).map_err(|e| match e {
+        exr::error::Error::Io(e) => e,
+        e => std::io::Error::other(e),
+    })
+}
+
+/// Encode tightly packed RGBA8 pixels as an in-memory sRGB PNG
+#[cfg(target_arch = "wasm32")]
+pub fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>, png::EncodingError> {
+    let mut bytes = Vec::new();
+    write_png(&mut bytes, width, height, rgba)?;
+    Ok(bytes)
+}
+
+fn write_png<W: Write>(
+    out: W,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+) -> Result<(), png::EncodingError> {
+    let mut encoder = png::Encoder::new(out, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+    Ok(())
+}
@@ -0,0 +1,389 @@
+//! Command-line options for the native viewer. Each option can also be set
+//! through a `VENDEK_*` environment variable; the command line wins.
+
+use std::path::PathBuf;
+
+use crate::bench::BenchOptions;
+use crate::gpu::{AdapterOptions, RaymarchChoice};
+use crate::loop_export::LoopOptions;
+use crate::preset::Preset;
+use crate::session::SessionOptions;
+use crate::still::StillOptions;
+
+pub const USAGE: &str = "\
+Usage: vendek [OPTIONS]
+       vendek bench [OPTIONS] [BENCH OPTIONS]
+       vendek render-still [OPTIONS] [STILL OPTIONS]
+       vendek export-loop [OPTIONS] [LOOP OPTIONS]
+       vendek render-session <FILE> [OPTIONS] [SESSION OPTIONS]
+
+Commands:
+  bench              Render a fixed camera path uncapped, print frame and
+                     GPU pass timings and write them as a JSON report
+  render-still       Render the viewer's last view offscreen, accumulating
+                     samples per pixel, and write it as a PNG or EXR
+  export-loop        Render one period of the membranes' oscillation
+                     offscreen and write it as a looping GIF or WebP
+  render-session     Render a session recorded with F8 offscreen, frame
+                     by frame, and encode it as a video
+
+Options:
+  --backend <LIST>   Graphics backends to try, comma separated:
+                     vulkan, metal, dx12, gl, primary or all [env: VENDEK_BACKEND]
+  --power <PREF>     Adapter preference: high, low or none [env: VENDEK_POWER]
+  --adapter <NAME>   Use the first adapter whose name contains NAME
+                     (case-insensitive) [env: VENDEK_ADAPTER]
+  --list-adapters    Print the available adapters and exit
+  --raymarch <PATH>  Raymarch path: compute, fragment (fullscreen fragment
+                     shader, no storage textures) or auto [default: auto]
+                     [env: VENDEK_RAYMARCH]
+  --preset <NAME>    Start from a world preset: foam, crystal, sparse-nebula
+                     or dense-hive [env: VENDEK_PRESET]
+  --stream <RADIUS>  Stream an unbounded world, keeping the chunks within
+                     RADIUS (0 to 2) of the camera loaded [env: VENDEK_STREAM]
+  --osc <PORT>       Listen for OSC control messages on this UDP port
+                     [env: VENDEK_OSC]
+  --stdin            Take newline-delimited JSON commands on stdin
+                     [env: VENDEK_STDIN]
+  --script <FILE>    Run a frame script, reloading it when the file
+                     changes [env: VENDEK_SCRIPT]
+  --timeline <FILE>  Play a keyframe timeline, reloading it when the file
+                     changes [env: VENDEK_TIMELINE]
+  --trace <FILE>     Write a Chrome trace of startup and every frame to
+                     FILE [env: VENDEK_TRACE]
+  -h, --help         Print this help and exit
+
+Bench options:
+  --frames <N>       Frames to measure [default: 600]
+  --warmup <N>       Frames to render before measuring [default: 60]
+  --size <WxH>       Window size in pixels [default: 1280x720]
+  --output <FILE>    JSON report path [default: vendek-bench.json]
+
+Still options:
+  --samples <N>      Samples per pixel [default: 256]
+  --res <WxH>        Image size in pixels [default: 3840x2160]
+  --time <SECONDS>   Seconds into the world's animation [default: 0]
+  --output <FILE>    Image path; a .exr extension writes the HDR image
+                     as OpenEXR [default: vendek-still.png]
+
+Loop options:
+  --length <SECONDS> Loop length [default: the period the membranes
+                     come closest to repeating after, 2 to 12 seconds]
+  --fps <N>          Frames per second [default: 20]
+  --samples <N>      Samples per pixel in each frame [default: 1]
+  --res <WxH>        Frame size in pixels [default: 480x270]
+  --time <SECONDS>   Seconds into the world's animation the loop starts
+                     [default: 0]
+  --output <FILE>    Animation path; a .gif extension writes a GIF and
+                     .webp an animated WebP through ffmpeg
+                     [default: vendek-loop.gif]
+
+Session options:
+  --fps <N>          Frames per second [default: 30]
+  --samples <N>      Samples per pixel in each frame [default: 16]
+  --res <WxH>        Frame size in pixels [default: 1920x1080]
+  --output <FILE>    Video path; the extension, .mp4, .webm or .webp,
+                     picks the format [default: vendek-session.mp4]";
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub adapter: AdapterOptions,
+    pub list_adapters: bool,
+    /// World preset, overriding the config's
+    pub preset: Option<Preset>,
+    /// Chunk radius of a streamed world, overriding the config's
+    pub stream: Option<u32>,
+    /// UDP port to receive OSC messages on
+    pub osc: Option<u16>,
+    /// Read JSON commands from stdin
+    pub stdin: bool,
+    /// Frame script to run
+    pub script: Option<PathBuf>,
+    /// Keyframe timeline to play
+    pub timeline: Option<PathBuf>,
+    /// Chrome trace file to write
+    pub trace: Option<PathBuf>,
+    /// Set by the `bench` command
+    pub bench: Option<BenchOptions>,
+    /// Set by the `render-still` command
+    pub still: Option<StillOptions>,
+    /// Set by the `export-loop` command
+    pub loop_export: Option<LoopOptions>,
+    /// Set by the `render-session` command
+    pub session: Option<SessionOptions>,
+}
+
+impl Args {
+    /// Parse the process arguments on top of the environment. Prints usage and
+    /// exits for `--help`.
+    pub fn parse() -> Result<Self, String> {
+        let mut args = Self::default();
+
+        if let Ok(value) = std::env::var("VENDEK_BACKEND") {
+            args.adapter.backends = parse_backends(&value)?;
+        }
+        if let Ok(value) = std::env::var("VENDEK_POWER") {
+            args.adapter.power_preference = parse_power(&value)?;
+        }
+        if let Ok(value) = std::env::var("VENDEK_ADAPTER") {
+            args.adapter.name = Some(value);
+        }
+        if let Ok(value) = std::env::var("VENDEK_RAYMARCH") {
+            args.adapter.raymarch = parse_raymarch(&value)?;
+        }
+        if let Ok(value) = std::env::var("VENDEK_PRESET") {
+            args.preset = Some(parse_preset(&value)?);
+        }
+        if let Ok(value) = std::env::var("VENDEK_STREAM") {
+            args.stream = Some(parse_count("VENDEK_STREAM", &value)?);
+        }
+        if let Ok(value) = std::env::var("VENDEK_OSC") {
+            args.osc = Some(parse_port("VENDEK_OSC", &value)?);
+        }
+        if let Ok(value) = std::env::var("VENDEK_STDIN") {
+            args.stdin = parse_switch("VENDEK_STDIN", &value)?;
+        }
+        if let Ok(value) = std::env::var("VENDEK_SCRIPT") {
+            args.script = Some(value.into());
+        }
+        if let Ok(value) = std::env::var("VENDEK_TIMELINE") {
+            args.timeline = Some(value.into());
+        }
+        if let Ok(value) = std::env::var("VENDEK_TRACE") {
+            args.trace = Some(value.into());
+        }
+
+        let mut argv = std::env::args().skip(1).peekable();
+        match argv.peek().map(String::as_str) {
+            Some("bench") => args.bench = Some(BenchOptions::default()),
+            Some("render-still") => args.still = Some(StillOptions::default()),
+            Some("export-loop") => args.loop_export = Some(LoopOptions::default()),
+            Some("render-session") => args.session = Some(SessionOptions::default()),
+            _ => {}
+        }
+        if args.bench.is_some()
+            || args.still.is_some()
+            || args.loop_export.is_some()
+            || args.session.is_some()
+        {
+            argv.next();
+        }
+        if let Some(session) = &mut args.session {
+            match argv.next_if(|arg| !arg.starts_with('-')) {
+                Some(input) => session.input = input.into(),
+                None => return Err("`render-session` needs a session file".to_string()),
+            }
+        }
+
+        while let Some(arg) = argv.next() {
+            // Accept both `--flag value` and `--flag=value`
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| argv.next())
+                    .ok_or_else(|| format!("{} needs a value", flag))
+            };
+
+            match flag.as_str() {
+                "--backend" => args.adapter.backends = parse_backends(&value()?)?,
+                "--power" => args.adapter.power_preference = parse_power(&value()?)?,
+                "--adapter" => args.adapter.name = Some(value()?),
+                "--list-adapters" => args.list_adapters = true,
+                "--raymarch" => args.adapter.raymarch = parse_raymarch(&value()?)?,
+                "--preset" => args.preset = Some(parse_preset(&value()?)?),
+                "--stream" => args.stream = Some(parse_count(&flag, &value()?)?),
+                "--osc" => args.osc = Some(parse_port(&flag, &value()?)?),
+                "--stdin" => args.stdin = true,
+                "--script" => args.script = Some(value()?.into()),
+                "--timeline" => args.timeline = Some(value()?.into()),
+                "--trace" => args.trace = Some(value()?.into()),
+                "--frames" | "--warmup" | "--size" => {
+                    let Some(bench) = &mut args.bench else {
+                        return Err(format!("{} is only valid with `bench`", flag));
+                    };
+                    let value = value()?;
+                    match flag.as_str() {
+                        "--frames" => bench.frames = parse_count(&flag, &value)?,
+                        "--warmup" => bench.warmup = parse_count(&flag, &value)?,
+                        _ => (bench.width, bench.height) = parse_size(&flag, &value)?,
+                    }
+                }
+                "--samples" | "--res" => {
+                    let value = value()?;
+                    let (samples, size) =
+                        match (&mut args.still, &mut args.loop_export, &mut args.session) {
+                            (Some(still), _, _) => {
+                                (&mut still.samples, (&mut still.width, &mut still.height))
+                            }
+                            (_, Some(export), _) => {
+                                (&mut export.samples, (&mut export.width, &mut export.height))
+                            }
+                            (_, _, Some(session)) => (
+                                &mut session.samples,
+                                (&mut session.width, &mut session.height),
+                            ),
+                            _ => {
+                                return Err(format!(
+                                    "{} is only valid with `render-still`, `export-loop` or `render-session`",
+                                    flag
+                                ))
+                            }
+                        };
+                    match flag.as_str() {
+                        "--samples" => *samples = parse_count(&flag, &value)?.max(1),
+                        _ => (*size.0, *size.1) = parse_size(&flag, &value)?,
+                    }
+                }
+                "--time" => {
+                    // A session's clock is the one it recorded
+                    let time = match (&mut args.still, &mut args.loop_export) {
+                        (Some(still), _) => &mut still.time,
+                        (_, Some(export)) => &mut export.time,
+                        _ => {
+                            return Err(format!(
+                                "{} is only valid with `render-still` or `export-loop`",
+                                flag
+                            ))
+                        }
+                    };
+                    *time = parse_seconds(&flag, &value()?)?;
+                }
+                "--fps" => {
+                    let fps = parse_count(&flag, &value()?)?.max(1);
+                    if let Some(export) = &mut args.loop_export {
+                        export.fps = fps;
+                    } else if let Some(session) = &mut args.session {
+                        session.fps = fps;
+                    } else {
+                        return Err(format!(
+                            "{} is only valid with `export-loop` or `render-session`",
+                            flag
+                        ));
+                    }
+                }
+                "--length" => {
+                    let Some(export) = &mut args.loop_export else {
+                        return Err(format!("{} is only valid with `export-loop`", flag));
+                    };
+                    match parse_seconds(&flag, &value()?)? {
+                        length if length > 0.0 => export.length = Some(length),
+                        _ => return Err(format!("{} must be positive", flag)),
+                    }
+                }
+                "--output" => {
+                    let value = value()?;
+                    if let Some(bench) = &mut args.bench {
+                        bench.output = value.into();
+                    } else if let Some(still) = &mut args.still {
+                        still.output = value.into();
+                    } else if let Some(export) = &mut args.loop_export {
+                        export.output = value.into();
+                    } else if let Some(session) = &mut args.session {
+                        session.output = value.into();
+                    } else {
+                        return Err(format!(
+                            "{} is only valid with `bench`, `render-still`, `export-loop` or `render-session`",
+                            flag
+                        ));
+                    }
+                }
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                _ => return Err(format!("unknown argument `{}`", flag)),
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+fn parse_backends(list: &str) -> Result<wgpu::Backends, String> {
+    let mut backends = wgpu::Backends::empty();
+    for name in list.split(',').map(|name| name.trim().to_lowercase()) {
+        backends |= match name.as_str() {
+            "vulkan" | "vk" => wgpu::Backends::VULKAN,
+            "metal" | "mtl" => wgpu::Backends::METAL,
+            "dx12" | "d3d12" => wgpu::Backends::DX12,
+            "gl" | "gles" | "opengl" => wgpu::Backends::GL,
+            "primary" => wgpu::Backends::PRIMARY,
+            "all" => wgpu::Backends::all(),
+            _ => return Err(format!("unknown backend `{}`", name)),
+        };
+    }
+    Ok(backends)
+}
+
+fn parse_count(flag: &str, value: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a whole number, got `{}`", flag, value))
+}
+
+fn parse_port(flag: &str, value: &str) -> Result<u16, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a port number, got `{}`", flag, value))
+}
+
+fn parse_switch(flag: &str, value: &str) -> Result<bool, String> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "on" => Ok(true),
+        "0" | "false" | "off" | "" => Ok(false),
+        _ => Err(format!("{} expects 1 or 0, got `{}`", flag, value)),
+    }
+}
+
+fn parse_seconds(flag: &str, value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(seconds) if seconds.is_finite() => Ok(seconds),
+        _ => Err(format!(
+            "{} expects a number of seconds, got `{}`",
+            flag, value
+        )),
+    }
+}
+
+fn parse_size(flag: &str, value: &str) -> Result<(u32, u32), String> {
+    let parsed = value
+        .split_once(['x', 'X'])
+        .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)));
+    match parsed {
+        Some((w, h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err(format!("{} expects WIDTHxHEIGHT, got `{}`", flag, value)),
+    }
+}
+
+fn parse_preset(name: &str) -> Result<Preset, String> {
+    Preset::from_name(name).ok_or_else(|| {
+        let names: Vec<&str> = Preset::ALL.iter().map(|preset| preset.name()).collect();
+        format!(
+            "unknown preset `{}`; expected one of {}",
+            name,
+            names.join(", ")
+        )
+    })
+}
+
+fn parse_power(name: &str) -> Result<wgpu::PowerPreference, String> {
+    match name.trim().to_lowercase().as_str() {
+        "high" | "high-performance" => Ok(wgpu::PowerPreference::HighPerformance),
+        "low" | "low-power" => Ok(wgpu::PowerPreference::LowPower),
+        "none" => Ok(wgpu::PowerPreference::None),
+        _ => Err(format!("unknown power preference `{}`", name)),
+    }
+}
+
+fn parse_raymarch(name: &str) -> Result<RaymarchChoice, String> {
+    match name.trim().to_lowercase().as_str() {
+        "auto" => Ok(RaymarchChoice::Auto),
+        "compute" => Ok(RaymarchChoice::Compute),
+        "fragment" => Ok(RaymarchChoice::Fragment),
+        _ => Err(format!("unknown raymarch path `{}`", name)),
+    }
+}
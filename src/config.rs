@@ -0,0 +1,256 @@
+//! `vendek.toml`, the native viewer's configuration: key bindings, the
+//! custom palette, the starting render settings and world, emitters, clip
+//! planes, transfer functions, recording, the HUD and attract mode. Every
+//! section is optional. The `[render]` values start the runtime parameters
+//! of the same meaning, over the quality tier the viewer picks for its
+//! adapter; settings saved by the last run and later changes override
+//! them.
+
+use serde::Deserialize;
+
+use crate::builder::VendekBuilder;
+use crate::input::InputMap;
+use crate::palette::{Gradient, Palette};
+use crate::params::{Background, ClipPlane, DebugView, RenderMode, RuntimeParams, TARGET_FPS};
+use crate::preset::Preset;
+use crate::tiles::WorkgroupSize;
+use crate::transfer::TransferFunction;
+use crate::world::EmitterChoice;
+
+/// Config file read from the working directory unless `VENDEK_CONFIG` is set
+const CONFIG_FILE: &str = "vendek.toml";
+
+/// User configuration. Every section is optional; missing values use defaults.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub bindings: InputMap,
+    /// Custom palette, selected at startup when present
+    pub palette: Option<PaletteConfig>,
+    pub render: RenderConfig,
+    pub window: WindowConfig,
+    pub attract: AttractConfig,
+    pub world: WorldConfig,
+    pub emitters: EmittersConfig,
+    /// `[[clip_plane]]` entries, applied at startup
+    #[serde(rename = "clip_plane")]
+    pub clip_planes: Vec<ClipPlaneConfig>,
+    pub recording: crate::video::RecordingConfig,
+    pub hud: crate::hud::HudConfig,
+    /// `[[transfer]]` entries, applied at startup in order
+    #[serde(rename = "transfer")]
+    pub transfer_functions: Vec<TransferConfig>,
+}
+
+/// `[palette]` section: gradient stops for the custom palette
+#[derive(Clone, Debug, Deserialize)]
+pub struct PaletteConfig {
+    pub stops: Gradient,
+}
+
+/// A `[[clip_plane]]` entry
+#[derive(Clone, Debug, Deserialize)]
+pub struct ClipPlaneConfig {
+    pub position: [f32; 3],
+    /// Points toward the part that is cut away
+    pub normal: [f32; 3],
+    /// World units per second along the normal; 0 for a still plane
+    #[serde(default)]
+    pub speed: f32,
+}
+
+impl ClipPlaneConfig {
+    pub fn to_plane(&self) -> ClipPlane {
+        ClipPlane {
+            position: self.position.into(),
+            normal: self.normal.into(),
+            speed: self.speed,
+        }
+    }
+}
+
+/// A `[[transfer]]` entry: a transfer function for one phase, or for every
+/// phase when `phase` is left out
+#[derive(Clone, Debug, Deserialize)]
+pub struct TransferConfig {
+    pub phase: Option<usize>,
+    pub points: TransferFunction,
+}
+
+/// `[render]` section: quality settings applied at startup
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RenderConfig {
+    /// Raymarched volume, rasterized isosurface, or both
+    pub mode: RenderMode,
+    /// What rays that miss the volume show
+    pub background: Background,
+    /// Raymarch resolution relative to the window, 1 to 2
+    pub supersample: f32,
+    /// Frame rate dynamic resolution holds; 0 turns it off
+    pub target_fps: f32,
+    pub present_mode: PresentMode,
+    /// Frame rate cap applied on the CPU; 0 for none
+    pub max_fps: f32,
+    /// Start in low-power mode; when unset, the viewer does so on battery
+    pub low_power: Option<bool>,
+    /// Draw frames only when something changed
+    pub on_demand: bool,
+    /// Diagnostic view drawn instead of the image
+    pub debug_view: DebugView,
+    /// Raymarch workgroup size on the compute path; timed at startup to
+    /// pick the fastest when unset
+    pub workgroup: Option<WorkgroupSize>,
+}
+
+/// `[window]` section
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub fullscreen: FullscreenMode,
+}
+
+/// `[attract]` section
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AttractConfig {
+    /// Seconds without input before attract mode starts; 0 turns it off
+    pub idle_seconds: f32,
+}
+
+/// `[world]` section
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct WorldConfig {
+    /// Preset the world starts from
+    pub preset: Option<Preset>,
+    /// Stream an unbounded world, keeping the chunks within this many of
+    /// the camera's loaded
+    pub stream: Option<u32>,
+}
+
+/// `[emitters]` section: cells that glow from within
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct EmittersConfig {
+    /// Chance that a cell of each phase emits, by phase index
+    pub phase_probability: Vec<f32>,
+    /// Cells that emit whatever their phase
+    pub cells: Vec<usize>,
+}
+
+impl EmittersConfig {
+    pub fn to_choice(&self) -> EmitterChoice {
+        EmitterChoice {
+            phase_probability: self.phase_probability.clone(),
+            cells: self.cells.clone(),
+        }
+    }
+}
+
+/// What the fullscreen toggle switches to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FullscreenMode {
+    /// A borderless window covering the current monitor
+    #[default]
+    Borderless,
+    /// Take over the monitor at its highest resolution and refresh rate
+    Exclusive,
+}
+
+/// How frames are handed to the display
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PresentMode {
+    /// Wait for vertical blank; never tears
+    #[default]
+    Vsync,
+    /// Lowest latency the platform offers without waiting for vblank
+    NoVsync,
+    /// Present immediately, tearing included
+    Immediate,
+    /// Replace the queued frame with the newest one; low latency without tearing
+    Mailbox,
+}
+
+impl PresentMode {
+    pub fn to_wgpu(self) -> wgpu::PresentMode {
+        match self {
+            PresentMode::Vsync => wgpu::PresentMode::AutoVsync,
+            PresentMode::NoVsync => wgpu::PresentMode::AutoNoVsync,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+        }
+    }
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            mode: RenderMode::Volume,
+            background: Background::Solid,
+            supersample: 1.0,
+            target_fps: TARGET_FPS,
+            present_mode: PresentMode::Vsync,
+            max_fps: 0.0,
+            low_power: None,
+            on_demand: false,
+            debug_view: DebugView::Off,
+            workgroup: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let path = std::env::var("VENDEK_CONFIG").unwrap_or_else(|_| CONFIG_FILE.to_string());
+
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+
+        match toml::from_str(&contents) {
+            Ok(config) => {
+                tracing::info!("Loaded config from {}", path);
+                config
+            }
+            Err(e) => {
+                tracing::warn!("Ignoring invalid config {}: {}", path, e);
+                Self::default()
+            }
+        }
+    }
+
+    /// The world the world and emitters sections describe
+    pub fn world_builder(&self) -> VendekBuilder {
+        let mut builder = VendekBuilder::new();
+        if let Some(preset) = self.world.preset {
+            builder = builder.preset(preset);
+        }
+        if let Some(radius) = self.world.stream {
+            builder = builder.stream(radius);
+        }
+        builder.emitters(self.emitters.to_choice())
+    }
+
+    /// Set the parameters the config starts with: the custom palette if it
+    /// has one, the render section's and the clip planes
+    pub fn apply_params(&self, params: &mut RuntimeParams) {
+        if self.palette.is_some() {
+            params.palette = Palette::Custom.index();
+        }
+        let render = &self.render;
+        params.supersample = render.supersample.clamp(1.0, 2.0);
+        params.target_fps = render.target_fps.max(0.0);
+        params.max_fps = render.max_fps.max(0.0);
+        params.on_demand = render.on_demand;
+        params.render_mode = render.mode;
+        params.background = render.background;
+        params.debug_view = render.debug_view;
+        for (slot, plane) in params.clip_planes.iter_mut().zip(&self.clip_planes) {
+            *slot = Some(plane.to_plane());
+        }
+    }
+}
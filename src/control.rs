@@ -0,0 +1,131 @@
+//! Stdin control: newline-delimited JSON commands, so the viewer can be
+//! driven by test scripts, external tools and show-control systems that
+//! would rather write to a pipe than speak OSC. Lines are parsed on a
+//! background thread and queued for the render loop.
+//!
+//! Each line is an object whose `cmd` names the command:
+//!
+//! - `{"cmd": "set", "params": {"density": 1.5}}` sets runtime parameters by
+//!   their JavaScript names; if any name is unknown, none are set
+//! - `{"cmd": "camera", "focus": [0, 0, 0], "yaw": 0.3, "pitch": 0.4,
+//!   "distance": 35}` moves the camera there at once, or eases it there with
+//!   `"glide": true`; fields left out keep their current values, and the
+//!   pitch and distance stay within what mouse input allows
+//! - `{"cmd": "screenshot", "path": "shot.png"}` writes the next frame as a
+//!   PNG, to a timestamped file without a `path`
+//! - `{"cmd": "quit"}` closes the viewer
+//!
+//! Every line is answered with one line on stdout once it has been applied:
+//! `{"ok": true}`, with the `path` written for screenshots, or
+//! `{"ok": false, "error": "..."}`. An `id` on the command, of any JSON
+//! type, is copied into its answer.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// What a line asks the viewer to do
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+pub enum ControlCommand {
+    Set {
+        params: BTreeMap<String, f64>,
+    },
+    Camera {
+        focus: Option<[f32; 3]>,
+        yaw: Option<f32>,
+        pitch: Option<f32>,
+        distance: Option<f32>,
+        #[serde(default)]
+        glide: bool,
+    },
+    Screenshot {
+        path: Option<PathBuf>,
+    },
+    Quit,
+}
+
+/// A command and the id to answer it with
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ControlRequest {
+    pub id: Option<Value>,
+    #[serde(flatten)]
+    pub command: ControlCommand,
+}
+
+/// Reads commands from stdin and queues them
+pub struct StdinControl {
+    requests: Arc<Mutex<Vec<ControlRequest>>>,
+}
+
+impl StdinControl {
+    /// Start reading stdin, calling `wake` after queueing each command
+    pub fn spawn(wake: impl Fn() + Send + 'static) -> std::io::Result<Self> {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let queue = requests.clone();
+        std::thread::Builder::new()
+            .name("stdin-control".to_string())
+            .spawn(move || read(queue, wake))?;
+        Ok(Self { requests })
+    }
+
+    /// Commands received since the last call, oldest first
+    pub fn take_requests(&self) -> Vec<ControlRequest> {
+        std::mem::take(&mut *self.requests.lock().unwrap())
+    }
+}
+
+/// Queue each line until stdin closes, answering the ones that don't parse
+fn read(queue: Arc<Mutex<Vec<ControlRequest>>>, wake: impl Fn()) {
+    for line in std::io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::warn!("Stopped reading commands from stdin: {}", e);
+                return;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<ControlRequest>(&line) {
+            Ok(request) => {
+                queue.lock().unwrap().push(request);
+                wake();
+            }
+            Err(e) => {
+                // Still answer the id of a line that is valid JSON
+                let id = serde_json::from_str::<Value>(&line)
+                    .ok()
+                    .and_then(|value| value.get("id").cloned());
+                reply(id.as_ref(), Err(format!("invalid command: {}", e)));
+            }
+        }
+    }
+    tracing::debug!("Stdin closed; no more commands");
+}
+
+/// Answer a command on stdout: `ok` with `fields`, or the error
+pub fn reply(id: Option<&Value>, result: Result<Map<String, Value>, String>) {
+    let mut answer = Map::new();
+    if let Some(id) = id {
+        answer.insert("id".to_string(), id.clone());
+    }
+    match result {
+        Ok(fields) => {
+            answer.insert("ok".to_string(), Value::Bool(true));
+            answer.extend(fields);
+        }
+        Err(e) => {
+            answer.insert("ok".to_string(), Value::Bool(false));
+            answer.insert("error".to_string(), Value::String(e));
+        }
+    }
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{}", Value::Object(answer));
+    let _ = stdout.flush();
+}
@@ -0,0 +1,262 @@
+//! Edge-aware denoising for the compute path, between the raymarch and the
+//! display pass. An à-trous filter blurs the HDR target over a 5×5 kernel
+//! whose taps spread twice as far each pass, four passes reaching 31
+//! pixels across, and stops at edges found in a guide the raymarch writes
+//! beside it: the distance to the volume each pixel shows and the phase
+//! that contributes most to it. Pixels only mix with neighbours of the same
+//! phase at a similar depth and brightness, so low-sample frames lose their
+//! banding while membranes and silhouettes stay sharp. While a still view
+//! accumulates samples the filter backs off, leaving converged stills
+//! almost untouched.
+
+use crate::graph::{Access, RenderGraph, RenderGraphBuilder, TextureDesc, TextureId};
+use crate::shader::ShaderError;
+
+/// Passes over the image, each with taps twice as far apart. Even, so the
+/// last one writes back into the HDR target.
+const ITERATIONS: usize = 4;
+/// Side of the filter's square workgroups
+const WORKGROUP_SIZE: u32 = 8;
+/// Luminance difference at which taps weigh a third as much, at full
+/// strength and one sample
+const LUMINANCE_SIGMA: f32 = 0.04;
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Settings of one pass, as `DenoisePass` in `denoise.wgsl`
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct DenoisePass {
+    /// Pixels between the taps
+    step: u32,
+    luminance_sigma: f32,
+    _pad: [u32; 2],
+}
+
+/// The guide the raymarch writes for the filter, and the texture the passes
+/// alternate with the HDR target
+#[derive(Clone, Copy, Debug)]
+pub struct DenoiseTargets {
+    /// Depth in x, phase in y, -1 where no volume shows
+    pub guide: TextureId,
+    pub scratch: TextureId,
+}
+
+impl DenoiseTargets {
+    pub fn declare<P: Copy + PartialEq + std::fmt::Debug>(
+        graph: &mut RenderGraphBuilder<P>,
+    ) -> Self {
+        let usage = wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING;
+        Self {
+            guide: graph.texture(TextureDesc {
+                label: "Denoise Guide",
+                format: FORMAT,
+                usage,
+                scale: 1.0,
+            }),
+            scratch: graph.texture(TextureDesc {
+                label: "Denoise Scratch",
+                format: FORMAT,
+                usage,
+                scale: 1.0,
+            }),
+        }
+    }
+}
+
+pub struct Denoiser {
+    pipeline: wgpu::ComputePipeline,
+    layout: wgpu::BindGroupLayout,
+    targets: DenoiseTargets,
+    /// Settings of each pass, rewritten every frame
+    settings: Vec<wgpu::Buffer>,
+    /// For each pass: its input, the guide, its output and its settings
+    bind_groups: Vec<wgpu::BindGroup>,
+    /// Image size the bind groups were made for
+    size: wgpu::Extent3d,
+    /// 0 to 1; 0 skips the pass
+    strength: f32,
+}
+
+impl Denoiser {
+    pub fn new(
+        device: &wgpu::Device,
+        targets: DenoiseTargets,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Result<Self, ShaderError> {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Denoise Bind Group Layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let source = crate::shader::preprocess("denoise.wgsl", &[])?;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Denoise Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Denoise Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Denoise Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache,
+        });
+
+        let settings = (0..ITERATIONS)
+            .map(|pass| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("Denoise Pass {} Settings", pass)),
+                    size: size_of::<DenoisePass>() as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            pipeline,
+            layout,
+            targets,
+            settings,
+            bind_groups: Vec::new(),
+            size: wgpu::Extent3d::default(),
+            strength: 0.0,
+        })
+    }
+
+    pub fn targets(&self) -> DenoiseTargets {
+        self.targets
+    }
+
+    /// Point the passes at the graph's current textures. The first reads
+    /// `hdr_target` and the rest alternate, the last writing back into it.
+    pub fn bind_targets<P: Copy + PartialEq>(
+        &mut self,
+        device: &wgpu::Device,
+        graph: &RenderGraph<P>,
+        hdr_target: TextureId,
+    ) {
+        self.size = graph.texture(hdr_target).size();
+        self.bind_groups = self
+            .settings
+            .iter()
+            .enumerate()
+            .map(|(pass, settings)| {
+                let (input, output) = if pass % 2 == 0 {
+                    (hdr_target, self.targets.scratch)
+                } else {
+                    (self.targets.scratch, hdr_target)
+                };
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some(&format!("Denoise Pass {} Bind Group", pass)),
+                    layout: &self.layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(
+                                graph.view(input, Access::Sampled),
+                            ),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(
+                                graph.view(self.targets.guide, Access::Sampled),
+                            ),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(
+                                graph.view(output, Access::Storage),
+                            ),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: settings.as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect();
+    }
+
+    pub fn set_strength(&mut self, strength: f32) {
+        self.strength = strength.clamp(0.0, 1.0);
+    }
+
+    /// Whether the filter runs at all
+    pub fn active(&self) -> bool {
+        self.strength > 0.0
+    }
+
+    /// Set the passes up for a frame with `samples` accumulated samples
+    /// per pixel, filtering less the more there are
+    pub fn prepare(&self, queue: &wgpu::Queue, samples: u32) {
+        let luminance_sigma = self.strength * LUMINANCE_SIGMA / (samples as f32).sqrt();
+        for (pass, buffer) in self.settings.iter().enumerate() {
+            let settings = DenoisePass {
+                step: 1 << pass,
+                luminance_sigma,
+                _pad: [0; 2],
+            };
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[settings]));
+        }
+    }
+
+    pub fn encode(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        timestamp_writes: Option<wgpu::ComputePassTimestampWrites>,
+    ) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Denoise Pass"),
+            timestamp_writes,
+        });
+        pass.set_pipeline(&self.pipeline);
+        for bind_group in &self.bind_groups {
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.dispatch_workgroups(
+                self.size.width.div_ceil(WORKGROUP_SIZE),
+                self.size.height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+    }
+}
@@ -0,0 +1,365 @@
+//! The visualization as a library, for Rust applications that want it in
+//! their own windows and render loops. The host keeps its adapter, device
+//! and event loop; `VendekEngine` owns the world, its simulations, the
+//! camera and the parameters, and draws into whatever texture view the host
+//! hands it. Input is up to the host, which drives the camera and
+//! parameters through the engine's methods.
+//!
+//! The native and web viewers are built on the engine as well: they keep
+//! the window, input, HUD and recording around it and reach into its state
+//! for what they drive directly.
+
+use glam::{Vec2, Vec3};
+
+use crate::automaton::PhaseAutomaton;
+use crate::builder::VendekBuilder;
+use crate::camera::Camera;
+use crate::gpu::{GpuError, GpuState};
+use crate::heat::HeatDiffusion;
+use crate::params::{ParamError, ParamStore, RuntimeParams};
+use crate::reaction::ReactionDiffusion;
+use crate::stream::WorldStream;
+use crate::text::Label;
+use crate::world::HoneycombWorld;
+
+/// What the engine draws with and into
+pub struct EngineConfig<'a> {
+    pub adapter: &'a wgpu::Adapter,
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    /// Format of the texture views passed to `render_into`, which must
+    /// allow `RENDER_ATTACHMENT`
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl<'a> EngineConfig<'a> {
+    /// Draw at `width`×`height`
+    pub fn new(
+        adapter: &'a wgpu::Adapter,
+        device: &'a wgpu::Device,
+        queue: &'a wgpu::Queue,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self {
+            adapter,
+            device,
+            queue,
+            format,
+            width,
+            height,
+        }
+    }
+}
+
+/// One world, animated and drawn on the host's device
+pub struct VendekEngine {
+    pub(crate) gpu: GpuState,
+    pub(crate) world: HoneycombWorld,
+    /// What the world was generated from, for generating it again
+    pub(crate) recipe: VendekBuilder,
+    /// Which chunks are loaded, if the world is streamed
+    stream: Option<WorldStream>,
+    simulations: Simulations,
+    pub(crate) camera: Camera,
+    /// Second camera for the split view, looking at the whole world
+    overview: Camera,
+    pub(crate) params: ParamStore,
+    /// What `reset_params` goes back to
+    pub(crate) initial_params: RuntimeParams,
+    pub(crate) time: f32,
+    pub(crate) paused: bool,
+}
+
+impl VendekEngine {
+    /// Generate the viewer's world and create its buffers and pipelines on
+    /// the config's device. The device's uncaptured-error handler is left as
+    /// the host set it. `VendekBuilder` starts from other settings.
+    pub fn new(config: EngineConfig) -> Result<Self, GpuError> {
+        VendekBuilder::new().build(config)
+    }
+
+    pub(crate) fn from_parts(gpu: GpuState, world: HoneycombWorld, recipe: VendekBuilder) -> Self {
+        let params = recipe.initial_params();
+        let pose = recipe.camera_pose();
+        let mut camera = Camera::new();
+        camera.jump_to(pose.focus, pose.yaw, pose.pitch, pose.distance);
+        Self {
+            gpu,
+            simulations: Simulations::new(&world),
+            world,
+            stream: recipe.world_stream(pose.focus),
+            recipe,
+            camera,
+            overview: Camera::new(),
+            params: ParamStore::new(params),
+            initial_params: params,
+            time: 0.0,
+            paused: false,
+        }
+    }
+
+    /// Match a new target size; views passed to `render_into` afterwards
+    /// must have it
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.gpu
+            .resize(winit::dpi::PhysicalSize::new(width, height));
+    }
+
+    /// Width and height of the target in pixels
+    pub fn size(&self) -> (u32, u32) {
+        (self.gpu.size.width, self.gpu.size.height)
+    }
+
+    /// Advance the animation and simulations by `dt` seconds, unless paused,
+    /// ease the camera toward where it is heading, and load the chunks
+    /// around it in a streamed world
+    pub fn update(&mut self, dt: f32) {
+        self.advance_time(dt);
+        self.advance_world(dt);
+    }
+
+    /// The first half of `update`: advance the animation clock, unless
+    /// paused. The viewers move the camera and parameters in between, by
+    /// input and scripts that read the new time.
+    pub(crate) fn advance_time(&mut self, dt: f32) {
+        if !self.paused {
+            self.time += dt;
+        }
+    }
+
+    /// The second half of `update`: ease the camera, load the chunks around
+    /// it, and unless paused move the clipping planes and step the
+    /// simulations
+    pub(crate) fn advance_world(&mut self, dt: f32) {
+        self.camera.update(dt);
+        if let Some(stream) = &mut self.stream {
+            if stream.follow(self.camera.focus, &mut self.world) {
+                self.gpu.stream_world(&self.world, &self.params.get());
+                self.simulations = Simulations::new(&self.world);
+            }
+        }
+        if !self.paused {
+            let _span = tracing::trace_span!("simulate").entered();
+            self.params.advance_clip_planes(dt, &self.world.bounds);
+            self.simulations
+                .advance(&mut self.world, &self.params.get(), dt, &mut self.gpu);
+        }
+    }
+
+    /// Draw a frame into `target`, which must be in the config's format and
+    /// of the engine's size. The commands are submitted on the host's queue.
+    pub fn render_into(&mut self, target: &wgpu::TextureView) {
+        let views = self.prepare_render();
+        self.gpu
+            .render_to(&[&self.camera, &self.overview][..views], self.time, target);
+    }
+
+    /// Draw a frame and present it on the viewer's window
+    pub(crate) fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let views = self.prepare_render();
+        self.gpu
+            .render(&[&self.camera, &self.overview][..views], self.time)
+    }
+
+    /// Upload changed parameters, and in the split view place the overview
+    /// on the same side as the main camera, far enough out to take in the
+    /// whole world. Returns how many of the two cameras to draw.
+    fn prepare_render(&mut self) -> usize {
+        if let Some(params) = self.params.take_changed() {
+            self.gpu.set_params(&params);
+        }
+        if !self.params.get().split_view {
+            return 1;
+        }
+        self.overview
+            .jump_to(self.world.bounds.center(), self.camera.yaw, 0.6, 55.0);
+        2
+    }
+
+    /// Draw the last frame `render_into` drew again, without its text,
+    /// offscreen, and read it back as tightly packed RGBA8 rows, top row
+    /// first. Needs a config format with four 8-bit channels. Natively the
+    /// host's device is polled until the pixels arrive; in the browser the
+    /// future waits for the buffer to be mapped.
+    pub async fn read_frame(&self) -> Result<Vec<u8>, wgpu::BufferAsyncError> {
+        self.gpu.read_frame().await
+    }
+
+    /// Whether the last frame `render_into` drew is final: nothing has
+    /// changed since, and on the compute path every sample a still view
+    /// takes has accumulated, so `read_frame` now gives the finished image
+    pub fn converged(&self) -> bool {
+        self.gpu.converged()
+    }
+
+    /// Write `label` over the next frame `render_into` draws. Labels last
+    /// one frame, so queue them again each frame they should stay.
+    pub fn draw_text(&mut self, label: Label) {
+        self.gpu.draw_text(label);
+    }
+
+    /// Width and height in pixels `label` would take up, for laying text
+    /// out around other labels
+    pub fn measure_text(&self, label: &Label) -> [f32; 2] {
+        self.gpu.measure_text(label)
+    }
+
+    /// Seed the world was generated from
+    pub fn seed(&self) -> u64 {
+        self.world.seed
+    }
+
+    /// Cells in the world, in all loaded chunks of a streamed one
+    pub fn cell_count(&self) -> usize {
+        self.world.cells.len()
+    }
+
+    /// Phases in the world
+    pub fn phase_count(&self) -> usize {
+        self.world.phases.len()
+    }
+
+    /// Generate a new world from `seed`, with the cell and phase counts,
+    /// volume, preset and emitters of the current one, and start the
+    /// simulations over on it. Parameters and the camera stay. On error the
+    /// current world is kept.
+    pub fn reseed(&mut self, seed: u64) -> Result<(), GpuError> {
+        self.regenerate(self.recipe.clone().seed(seed))
+    }
+
+    /// Generate the world again with `cells` cells, per chunk in a streamed
+    /// world, in `phases` phases, clamped as `VendekBuilder` clamps them,
+    /// keeping its seed and the rest as `reseed` does
+    pub fn set_counts(&mut self, cells: usize, phases: usize) -> Result<(), GpuError> {
+        self.regenerate(self.recipe.clone().cells(cells).phases(phases))
+    }
+
+    /// Replace the world with one generated from `recipe` around the
+    /// camera, and start the simulations over on it. On error the current
+    /// world stays.
+    pub(crate) fn regenerate(&mut self, recipe: VendekBuilder) -> Result<(), GpuError> {
+        let (world, stream) = recipe.generate_world_around(self.camera.focus);
+        self.gpu.set_world(&world, &self.params.get())?;
+        self.simulations = Simulations::new(&world);
+        self.world = world;
+        self.stream = stream;
+        self.recipe = recipe;
+        Ok(())
+    }
+
+    /// Seconds into the animation
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Stop or restart the animation and simulations; the camera still moves
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Set a runtime parameter by its JavaScript name, as the viewer's
+    /// `set_param`. Takes effect with the next frame.
+    pub fn set_param(&mut self, name: &str, value: f64) -> Result<(), ParamError> {
+        self.params.set(name, value)
+    }
+
+    /// A runtime parameter by its JavaScript name
+    pub fn param(&self, name: &str) -> Option<f64> {
+        self.params.get().get(name)
+    }
+
+    /// Names accepted by `set_param` and `param`
+    pub fn param_names(&self) -> &'static [&'static str] {
+        RuntimeParams::NAMES
+    }
+
+    /// Put every parameter back to how the engine started
+    pub fn reset_params(&mut self) {
+        self.params.replace(self.initial_params);
+    }
+
+    /// Place the orbit camera at once: the point it looks at, angles in
+    /// radians and the distance from the point
+    pub fn set_camera(&mut self, focus: [f32; 3], yaw: f32, pitch: f32, distance: f32) {
+        self.camera
+            .jump_to(Vec3::from_array(focus), yaw, pitch, distance);
+    }
+
+    /// Orbit the camera around its focus, by mouse-drag pixels
+    pub fn orbit(&mut self, dx: f32, dy: f32) {
+        self.camera.orbit(Vec2::new(dx, dy));
+    }
+
+    /// Move the focus across the view, by mouse-drag pixels
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.camera.pan(Vec2::new(dx, dy));
+    }
+
+    /// Move the camera toward its focus, or away for negative amounts, by
+    /// mouse-wheel lines
+    pub fn zoom(&mut self, amount: f32) {
+        self.camera.zoom(amount);
+    }
+}
+
+/// The simulations changing the world as it plays: reaction–diffusion and
+/// heat in each cell, and the automaton changing cells' phases
+pub struct Simulations {
+    reaction: ReactionDiffusion,
+    heat: HeatDiffusion,
+    automaton: PhaseAutomaton,
+}
+
+impl Simulations {
+    /// Simulations over `world`, seeded with its seed. The adjacency is
+    /// found once for all three.
+    pub fn new(world: &HoneycombWorld) -> Self {
+        let neighbours = world.neighbours();
+        Self {
+            reaction: ReactionDiffusion::new(world, neighbours.clone(), world.seed),
+            heat: HeatDiffusion::new(world, neighbours.clone()),
+            automaton: PhaseAutomaton::new(world, neighbours, world.seed),
+        }
+    }
+
+    /// Step the simulations `params` turn on by `dt` seconds, and upload
+    /// the cell states and cells that changed
+    pub fn advance(
+        &mut self,
+        world: &mut HoneycombWorld,
+        params: &RuntimeParams,
+        dt: f32,
+        gpu: &mut GpuState,
+    ) {
+        let reacted = params.reaction_strength > 0.0
+            && self
+                .reaction
+                .advance(dt, params.reaction_feed, params.reaction_kill);
+        let heated = params.heat_strength > 0.0
+            && self
+                .heat
+                .advance(&world.cells, &world.phases, params.heat_diffusion, dt);
+        if reacted || heated {
+            let states: Vec<[f32; 4]> = self
+                .reaction
+                .concentrations()
+                .zip(self.heat.temperatures())
+                .map(|((u, v), temperature)| [u, v, temperature, 0.0])
+                .collect();
+            gpu.set_cell_states(&states);
+        }
+
+        if self.automaton.advance(
+            &mut world.cells,
+            params.automaton_rule,
+            params.automaton_rate,
+            dt,
+        ) {
+            gpu.set_cells(&world.cells);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Animated GIF encoding for looping exports. GIF frames hold at most 256
+//! colors, so one palette is chosen for the whole animation by NeuQuant
+//! over pixels sampled from every frame; sharing it keeps colors from
+//! flickering between frames, which per-frame palettes would. Pixels take
+//! the nearest palette color without dithering, as dither patterns that
+//! shift from frame to frame shimmer in a loop. The file itself is written
+//! by the `gif` crate.
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use ::gif::{DisposalMethod, Encoder, EncodingError, Frame, Repeat};
+use color_quant::NeuQuant;
+
+/// Pixels sampled from all the frames together to choose the palette
+const PALETTE_SAMPLES: usize = 1 << 18;
+/// Colors in the palette, as many as GIF allows
+const PALETTE_SIZE: usize = 256;
+/// NeuQuant's sampling factor, from 1 (slowest, best) to 30; the pixels
+/// are already sampled, so it sees all of them
+const SAMPLE_FACTOR: i32 = 1;
+
+/// Write frames of tightly packed RGBA8 pixels as a GIF that loops forever,
+/// showing each frame for `delay` hundredths of a second. Alpha is ignored.
+pub fn save_gif(
+    path: &Path,
+    width: u32,
+    height: u32,
+    delay: u16,
+    frames: &[Vec<u8>],
+) -> io::Result<()> {
+    let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(width), Ok(height)) => (width, height),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "GIF frames are at most 65535 pixels on a side",
+            ))
+        }
+    };
+    let quantizer = palette(frames);
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder =
+        Encoder::new(file, width, height, &quantizer.color_map_rgb()).map_err(io_error)?;
+    encoder.set_repeat(Repeat::Infinite).map_err(io_error)?;
+
+    let mut indices = Vec::with_capacity(width as usize * height as usize);
+    for frame in frames {
+        indices.clear();
+        indices.extend(
+            frame
+                .as_chunks::<4>()
+                .0
+                .iter()
+                .map(|&[r, g, b, _]| quantizer.index_of(&[r, g, b, u8::MAX]) as u8),
+        );
+        // Each frame replaces the last whole, after `delay`
+        let frame = Frame {
+            delay,
+            dispose: DisposalMethod::Keep,
+            width,
+            height,
+            buffer: Cow::Borrowed(&indices),
+            ..Frame::default()
+        };
+        encoder.write_frame(&frame).map_err(io_error)?;
+    }
+    // Writes the trailer
+    encoder.into_inner()?.flush()
+}
+
+/// The palette for the frames, trained on pixels sampled evenly across
+/// them with alpha made opaque
+fn palette(frames: &[Vec<u8>]) -> NeuQuant {
+    let pixels: usize = frames.iter().map(|frame| frame.len() / 4).sum();
+    let stride = pixels.div_ceil(PALETTE_SAMPLES).max(1);
+    let samples: Vec<u8> = frames
+        .iter()
+        .flat_map(|frame| frame.as_chunks::<4>().0)
+        .step_by(stride)
+        .flat_map(|&[r, g, b, _]| [r, g, b, u8::MAX])
+        .collect();
+    NeuQuant::new(SAMPLE_FACTOR, PALETTE_SIZE, &samples)
+}
+
+fn io_error(e: EncodingError) -> io::Error {
+    match e {
+        EncodingError::Io(e) => e,
+        e => io::Error::other(e),
+    }
+}
@@ -0,0 +1,263 @@
+//! Orientation gizmo: world axes drawn in the bottom-left corner, turned with
+//! the camera, plus an optional wireframe of the volume's bounding box, so
+//! it's clear which way is up once the camera is inside the volume.
+
+use glam::{Mat3, Mat4, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::camera::Camera;
+
+/// Side of the square the axes are drawn in, in pixels
+const SIZE: f32 = 96.0;
+/// Distance from the view's corner, in pixels
+const MARGIN: f32 = 8.0;
+/// Half-width of the orthographic box around the unit axes
+const EXTENT: f32 = 1.3;
+
+const AXIS_COLORS: [[f32; 4]; 3] = [
+    [0.95, 0.3, 0.3, 1.0],
+    [0.4, 0.9, 0.4, 1.0],
+    [0.35, 0.55, 1.0, 1.0],
+];
+/// Negative half of each axis, fainter than the positive one
+const NEGATIVE_ALPHA: f32 = 0.3;
+const BOUNDS_COLOR: [f32; 4] = [0.8, 0.85, 0.9, 0.35];
+
+/// Vertices before this one are the axes; the rest are the bounding box
+const AXES_VERTICES: u32 = 12;
+const BOUNDS_VERTICES: u32 = 24;
+
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct GizmoVertex {
+    /// w selects the transform: 0 for the axes, 1 for world space
+    position: [f32; 4],
+    color: [f32; 4],
+}
+
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct GizmoUniforms {
+    axes: Mat4,
+    world: Mat4,
+}
+
+pub struct Gizmo {
+    vertex_buffer: wgpu::Buffer,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    /// Main view's rectangle in the surface: x, y, width, height in pixels
+    viewport: [f32; 4],
+    pub show_axes: bool,
+    pub show_bounds: bool,
+}
+
+impl Gizmo {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        cache: Option<&wgpu::PipelineCache>,
+        bounds_min: Vec3,
+        bounds_max: Vec3,
+    ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Gizmo Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices(bounds_min, bounds_max)),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Gizmo Uniforms Buffer"),
+            contents: bytemuck::cast_slice(&[GizmoUniforms {
+                axes: Mat4::IDENTITY,
+                world: Mat4::IDENTITY,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Gizmo Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Gizmo Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Gizmo Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/gizmo.wgsl").into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Gizmo Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Gizmo Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<GizmoVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4],
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache,
+        });
+
+        Self {
+            vertex_buffer,
+            uniform_buffer,
+            bind_group,
+            pipeline,
+            viewport: [0.0, 0.0, 1.0, 1.0],
+            show_axes: true,
+            show_bounds: false,
+        }
+    }
+
+    /// Outline a different volume
+    pub fn set_bounds(&self, queue: &wgpu::Queue, bounds_min: Vec3, bounds_max: Vec3) {
+        queue.write_buffer(
+            &self.vertex_buffer,
+            0,
+            bytemuck::cast_slice(&vertices(bounds_min, bounds_max)),
+        );
+    }
+
+    /// Follow `camera`, drawn in the `viewport` rectangle of the surface
+    /// (x, y, width, height in pixels)
+    pub fn update(&mut self, queue: &wgpu::Queue, camera: &Camera, viewport: [f32; 4]) {
+        self.viewport = viewport;
+
+        // Only the camera's rotation, so the axes stay centered in their corner
+        let view = camera.view_matrix();
+        let rotation = Mat4::from_mat3(Mat3::from_mat4(view));
+        let projection = Mat4::orthographic_rh(-EXTENT, EXTENT, -EXTENT, EXTENT, -EXTENT, EXTENT);
+        let aspect = viewport[2] / viewport[3].max(1.0);
+        let uniforms = GizmoUniforms {
+            axes: projection * rotation,
+            world: camera.projection_matrix(aspect) * view,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+    }
+
+    /// Draw into `target` over what is already there
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        if !self.show_axes && !self.show_bounds {
+            return;
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Gizmo Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+
+        let [x, y, width, height] = self.viewport;
+        if self.show_bounds {
+            render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+            render_pass.draw(AXES_VERTICES..AXES_VERTICES + BOUNDS_VERTICES, 0..1);
+        }
+        if self.show_axes && width > SIZE + MARGIN && height > SIZE + MARGIN {
+            render_pass.set_viewport(x + MARGIN, y + height - SIZE - MARGIN, SIZE, SIZE, 0.0, 1.0);
+            render_pass.draw(0..AXES_VERTICES, 0..1);
+        }
+    }
+}
+
+/// The three axes through the origin, then the twelve edges of the box
+fn vertices(bounds_min: Vec3, bounds_max: Vec3) -> Vec<GizmoVertex> {
+    let mut vertices = Vec::with_capacity((AXES_VERTICES + BOUNDS_VERTICES) as usize);
+    for (axis, color) in AXIS_COLORS.iter().enumerate() {
+        let tip = Vec3::AXES[axis];
+        let faint = [color[0], color[1], color[2], NEGATIVE_ALPHA];
+        for (end, color) in [(tip, *color), (-tip, faint)] {
+            vertices.push(GizmoVertex {
+                position: [0.0; 4],
+                color,
+            });
+            vertices.push(GizmoVertex {
+                position: end.extend(0.0).to_array(),
+                color,
+            });
+        }
+    }
+
+    let corner = |i: usize| {
+        let pick = |bit: usize, axis: usize| {
+            if i & bit == 0 {
+                bounds_min[axis]
+            } else {
+                bounds_max[axis]
+            }
+        };
+        [pick(1, 0), pick(2, 1), pick(4, 2), 1.0]
+    };
+    // Corners are numbered by which coordinates are at the max; edges join
+    // corners that differ in one bit
+    for i in 0..8 {
+        for bit in [1, 2, 4] {
+            if i & bit == 0 {
+                for end in [i, i | bit] {
+                    vertices.push(GizmoVertex {
+                        position: corner(end),
+                        color: BOUNDS_COLOR,
+                    });
+                }
+            }
+        }
+    }
+    vertices
+}
@@ -1,209 +1,2387 @@
+use std::fmt;
 use std::sync::Arc;
 
-use bytemuck;
 use glam::Vec3;
+use tracing::Instrument;
 use wgpu::util::DeviceExt;
 use winit::window::Window;
 
+use crate::builder::DeviceClass;
+use crate::bvh::{self, BvhNode, SeedBvh};
 use crate::camera::Camera;
-use crate::world::{FrameUniforms, HoneycombCell, HoneycombWorld, RaymarchParams, VendekPhase};
+use crate::denoise::{DenoiseTargets, Denoiser};
+use crate::gizmo::Gizmo;
+use crate::graph::{Access, RenderGraph, RenderGraphBuilder, Slot, TextureDesc, TextureId};
+use crate::grid_overlay::GridOverlay;
+use crate::isosurface::{self, Isosurface};
+use crate::membrane::{self, MembranePair};
+use crate::overlay::{FrameHistory, Overlay};
+use crate::palette::{Gradient, Palette};
+use crate::params::{
+    Background, ClipPlane, DebugView, RenderMode, RuntimeParams, ALL_PHASES, MAX_CLIP_PLANES,
+    MAX_STEPS, MEMBRANE_GLOW, MEMBRANE_THICKNESS, MIN_TRANSMITTANCE, REFINE_THRESHOLD,
+    RIPPLE_AMPLITUDE, RIPPLE_SPEED, STEP_SIZE,
+};
+use crate::pipeline_cache::DiskPipelineCache;
+use crate::profiler::{GpuProfiler, GpuTimings, Pass};
+use crate::shader::{Permutations, ShaderError, ShaderFeatures};
+use crate::shadow::ShadowMap;
+use crate::text::{Label, TextRenderer};
+use crate::tiles::{TileCulling, WorkgroupSize};
+use crate::transfer::{self, TransferFunction};
+use crate::uniforms::{EmitterLights, FrameUniforms, RaymarchParams};
+use crate::world::{Bounds, HoneycombCell, HoneycombWorld, SpatialGrid, VendekPhase};
+
+/// Views that can be rendered side by side, each with its own camera
+pub const MAX_VIEWS: usize = 2;
+
+/// Color format headless states render in, sRGB like window surfaces
+#[cfg(not(target_arch = "wasm32"))]
+pub const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Per-cell simulation state: four channels, full precision so small
+/// concentrations survive
+const CELL_STATE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+/// Cells per row of the cell state texture, within WebGL2's smallest
+/// texture size; larger worlds wrap onto more rows
+const CELL_STATE_ROW: usize = 2048;
+/// Samples a still view accumulates per pixel at most, unless
+/// `set_sample_limit` says otherwise
+const MAX_SAMPLES: u32 = 64;
+/// Bytes of accumulated mean and variance per pixel
+const ACCUMULATION_TEXEL: u64 = 16;
+/// Raymarch frames timed for each workgroup size when autotuning, after
+/// one to warm up
+#[cfg(not(target_arch = "wasm32"))]
+const AUTOTUNE_FRAMES: u32 = 4;
+/// Time of the first frame after the warm-up past which autotuning gives
+/// up, rather than hold up startup on a slow adapter
+#[cfg(not(target_arch = "wasm32"))]
+const AUTOTUNE_FRAME_LIMIT: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Which backends and adapter to run on. Native builds take these from the
+/// command line or environment; the browser always uses the defaults.
+#[derive(Clone, Debug)]
+pub struct AdapterOptions {
+    pub backends: wgpu::Backends,
+    pub power_preference: wgpu::PowerPreference,
+    /// Prefer the first adapter whose name contains this, case-insensitively.
+    /// Adapters can't be enumerated in the browser.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub name: Option<String>,
+    /// Raymarch path to use, where the adapter supports it
+    pub raymarch: RaymarchChoice,
+}
+
+/// Which raymarch path to run. The fragment path draws straight into the
+/// surface without storage textures, so it runs everywhere; forcing it on
+/// an adapter that could run the compute path is mostly for comparing them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RaymarchChoice {
+    /// The compute path where the adapter supports it, else the fragment path
+    #[default]
+    Auto,
+    // Chosen with `--raymarch`, which the browser viewer doesn't have
+    #[cfg(not(target_arch = "wasm32"))]
+    Compute,
+    #[cfg(not(target_arch = "wasm32"))]
+    Fragment,
+}
+
+impl RaymarchChoice {
+    /// Whether to take the compute path, given whether `supported`
+    fn use_compute(self, supported: bool) -> bool {
+        match self {
+            RaymarchChoice::Auto => supported,
+            #[cfg(not(target_arch = "wasm32"))]
+            RaymarchChoice::Fragment => false,
+            #[cfg(not(target_arch = "wasm32"))]
+            RaymarchChoice::Compute => {
+                if !supported {
+                    tracing::warn!(
+                        "The adapter can't run the compute raymarch path; using the fragment path"
+                    );
+                }
+                supported
+            }
+        }
+    }
+}
+
+impl Default for AdapterOptions {
+    fn default() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            backends: wgpu::Backends::PRIMARY,
+            // Browsers without WebGPU fall back to WebGL2
+            #[cfg(target_arch = "wasm32")]
+            backends: wgpu::Backends::BROWSER_WEBGPU | wgpu::Backends::GL,
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            #[cfg(not(target_arch = "wasm32"))]
+            name: None,
+            raymarch: RaymarchChoice::Auto,
+        }
+    }
+}
+
+/// Steps of starting a viewer, reported as each begins so the page can show
+/// how far along it is rather than a blank canvas
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadStage {
+    /// Generating the world
+    World,
+    /// Finding a GPU
+    Adapter,
+    /// Opening the device
+    Device,
+    /// Compiling shaders and creating pipelines, usually the longest step
+    Pipelines,
+}
+
+impl LoadStage {
+    pub fn name(self) -> &'static str {
+        match self {
+            LoadStage::World => "world",
+            LoadStage::Adapter => "adapter",
+            LoadStage::Device => "device",
+            LoadStage::Pipelines => "pipelines",
+        }
+    }
+
+    /// Rough share of startup done when the stage begins
+    #[cfg(target_arch = "wasm32")]
+    pub fn fraction(self) -> f32 {
+        match self {
+            LoadStage::World => 0.0,
+            LoadStage::Adapter => 0.1,
+            LoadStage::Device => 0.2,
+            LoadStage::Pipelines => 0.3,
+        }
+    }
+}
+
+/// Print every adapter the chosen backends expose, for `--list-adapters`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn list_adapters(options: &AdapterOptions) {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: options.backends,
+        ..Default::default()
+    });
+    for adapter in instance.enumerate_adapters(options.backends) {
+        println!("{}", describe_adapter(&adapter.get_info()));
+    }
+}
+
+fn describe_adapter(info: &wgpu::AdapterInfo) -> String {
+    format!(
+        "{} ({:?}, {:?}, driver: {} {})",
+        info.name, info.backend, info.device_type, info.driver, info.driver_info
+    )
+}
+
+/// How both raymarch paths bind the spatial grid's occupancy
+const GRID_BINDING: wgpu::BindingType = wgpu::BindingType::Texture {
+    sample_type: wgpu::TextureSampleType::Uint,
+    view_dimension: wgpu::TextureViewDimension::D3,
+    multisampled: false,
+};
+
+/// Size of the cell state texture for `count` cells
+fn cell_state_extent(count: usize) -> wgpu::Extent3d {
+    wgpu::Extent3d {
+        width: count.clamp(1, CELL_STATE_ROW) as u32,
+        height: count.div_ceil(CELL_STATE_ROW).max(1) as u32,
+        depth_or_array_layers: 1,
+    }
+}
+
+/// `states` padded with zeros to fill the texture for `count` cells
+fn cell_state_texels(states: &[[f32; 4]], count: usize) -> Vec<[f32; 4]> {
+    let size = cell_state_extent(count);
+    let mut texels = states.to_vec();
+    texels.resize((size.width * size.height) as usize, [0.0; 4]);
+    texels
+}
+
+/// Baked transfer functions for `phases`, each phase's own where `chosen`
+/// has none
+fn transfer_texels(phases: &[VendekPhase], chosen: &[Option<TransferFunction>]) -> Vec<[f32; 4]> {
+    let functions: Vec<TransferFunction> = phases
+        .iter()
+        .zip(chosen)
+        .map(|(phase, function)| {
+            function
+                .clone()
+                .unwrap_or_else(|| TransferFunction::for_phase(phase))
+        })
+        .collect();
+    transfer::bake(&functions)
+}
+
+/// The textures sized by the world's phase and cell counts
+struct WorldTextures {
+    /// Phase colors for the current palette, one texel per phase
+    palette: wgpu::Texture,
+    /// Density to tint and opacity, one row per phase
+    transfer: wgpu::Texture,
+    /// Simulated per-cell state, one texel per cell, zeroed
+    cell_state: wgpu::Texture,
+    /// Seeds each cell of the spatial grid lists, for the grid debug view
+    grid: wgpu::Texture,
+}
+
+impl WorldTextures {
+    fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        phases: &[VendekPhase],
+        colors: &[[u8; 4]],
+        transfer_functions: &[Option<TransferFunction>],
+        cells: usize,
+        grid: &SpatialGrid,
+    ) -> Self {
+        let palette = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Palette Texture"),
+                size: wgpu::Extent3d {
+                    width: phases.len() as u32,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            bytemuck::cast_slice(colors),
+        );
+
+        let transfer = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Transfer Function Texture"),
+                size: wgpu::Extent3d {
+                    width: transfer::SAMPLES,
+                    height: phases.len() as u32,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: transfer::FORMAT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            bytemuck::cast_slice(&transfer_texels(phases, transfer_functions)),
+        );
+
+        let cell_state = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Cell State Texture"),
+                size: cell_state_extent(cells),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: CELL_STATE_FORMAT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            bytemuck::cast_slice(&cell_state_texels(&[], cells)),
+        );
+
+        let grid = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Grid Occupancy Texture"),
+                size: wgpu::Extent3d {
+                    width: grid.grid_size,
+                    height: grid.grid_size,
+                    depth_or_array_layers: grid.grid_size,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D3,
+                format: wgpu::TextureFormat::R8Uint,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &grid.occupancy(),
+        );
+
+        Self {
+            palette,
+            transfer,
+            cell_state,
+            grid,
+        }
+    }
+
+    /// Views of the textures, for bind groups
+    fn views(&self) -> WorldViews {
+        let default = wgpu::TextureViewDescriptor::default();
+        WorldViews {
+            palette: self.palette.create_view(&default),
+            transfer: self.transfer.create_view(&default),
+            cell_state: self.cell_state.create_view(&default),
+            grid: self.grid.create_view(&default),
+        }
+    }
+}
+
+/// Views of the `WorldTextures` the raymarch reads
+struct WorldViews {
+    palette: wgpu::TextureView,
+    transfer: wgpu::TextureView,
+    cell_state: wgpu::TextureView,
+    grid: wgpu::TextureView,
+}
+
+/// Poll the device, ignoring the timeout that is its only error: the
+/// renderer never sets one
+fn poll(device: &wgpu::Device, poll_type: wgpu::PollType) {
+    let _ = device.poll(poll_type);
+}
+
+/// The world's seed folded to 32 bits, for the starfield
+fn background_seed(seed: u64) -> u32 {
+    (seed ^ (seed >> 32)) as u32
+}
+
+/// Configuration for states without a surface, which render into textures
+/// of their own or the host's
+fn offscreen_config(
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+) -> wgpu::SurfaceConfiguration {
+    wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format,
+        width: width.max(1),
+        height: height.max(1),
+        present_mode: wgpu::PresentMode::AutoVsync,
+        alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+        view_formats: vec![],
+        desired_maximum_frame_latency: 2,
+    }
+}
+
+/// Why the renderer could not start. Messages are written for the person
+/// running the viewer, not just for logs.
+#[derive(Debug)]
+pub enum GpuError {
+    Surface(wgpu::CreateSurfaceError),
+    NoAdapter {
+        backends: wgpu::Backends,
+    },
+    Device {
+        adapter: String,
+        source: wgpu::RequestDeviceError,
+    },
+    UnsupportedSurface {
+        adapter: String,
+    },
+    WorldTooLarge {
+        needed: usize,
+        allowed: usize,
+    },
+    Shader(ShaderError),
+}
+
+impl fmt::Display for GpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GpuError::Surface(e) => write!(f, "Could not create a drawing surface for the window: {}", e),
+            GpuError::NoAdapter { backends } => {
+                write!(f, "No compatible graphics adapter was found (tried {:?}). ", backends)?;
+                if cfg!(target_arch = "wasm32") {
+                    write!(f, "Use a browser with WebGPU or WebGL2 enabled, and check that hardware acceleration is turned on.")
+                } else {
+                    write!(f, "Check that your GPU drivers are installed and up to date, or try another backend with --backend.")
+                }
+            }
+            GpuError::Device { adapter, source } => {
+                write!(f, "The graphics adapter {} could not create a device: {}", adapter, source)
+            }
+            GpuError::UnsupportedSurface { adapter } => {
+                write!(f, "The graphics adapter {} cannot present to this window", adapter)
+            }
+            GpuError::WorldTooLarge { needed, allowed } => write!(
+                f,
+                "The world needs {} bytes of uniform data but this adapter allows {}; reduce the cell count",
+                needed, allowed
+            ),
+            GpuError::Shader(e) => write!(f, "A built-in shader is malformed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for GpuError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GpuError::Surface(e) => Some(e),
+            GpuError::Device { source, .. } => Some(source),
+            GpuError::Shader(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for GpuError {
+    fn from(e: wgpu::CreateSurfaceError) -> Self {
+        GpuError::Surface(e)
+    }
+}
+
+impl From<ShaderError> for GpuError {
+    fn from(e: ShaderError) -> Self {
+        GpuError::Shader(e)
+    }
+}
+
+pub struct GpuState {
+    /// None for headless and embedded states, which render with
+    /// `render_image` and `render_to`
+    pub surface: Option<wgpu::Surface<'static>>,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub config: wgpu::SurfaceConfiguration,
+    pub size: winit::dpi::PhysicalSize<u32>,
+    // Present modes the surface supports; only native builds switch mode
+    #[cfg(not(target_arch = "wasm32"))]
+    present_modes: Vec<wgpu::PresentMode>,
+    pub adapter_info: wgpu::AdapterInfo,
+
+    // Buffers; one set of frame uniforms per view
+    frame_uniform_buffers: Vec<wgpu::Buffer>,
+    raymarch_params_buffer: wgpu::Buffer,
+
+    // Phase colors for the current palette, one texel per phase
+    palette_texture: wgpu::Texture,
+    palette: Palette,
+    custom_gradient: Option<Gradient>,
+    // Box the raymarch is confined to
+    bounds: Bounds,
+    // The world's seed folded to 32 bits, for the starfield
+    background_seed: u32,
+    // Phases the palette colors are generated from
+    phases: Vec<VendekPhase>,
+    // Density to tint and opacity, one row per phase
+    transfer_texture: wgpu::Texture,
+    // Transfer functions set for each phase; `None` keeps the phase's own
+    transfer_functions: Vec<Option<TransferFunction>>,
+    // Simulated per-cell state, one texel per cell
+    cell_state_texture: wgpu::Texture,
+    // Seeds each spatial grid cell lists, rewritten when a streamed
+    // world's seeds move
+    grid_texture: wgpu::Texture,
+
+    // Pipelines for the raymarch path this adapter supports
+    path: RenderPath,
+
+    // Pass order and the intermediate textures between passes
+    graph: RenderGraph<Stage>,
+    // Views drawn side by side in the last frame, in raymarch target pixels
+    viewports: Vec<Viewport>,
+    // Uniforms of the last frame's views, and how many samples per pixel
+    // the compute path has accumulated while they stayed the same.
+    // Cleared whenever anything else in the picture changes.
+    still_views: Vec<FrameUniforms>,
+    samples: u32,
+    sample_limit: u32,
+    // Raymarched volume, rasterized isosurface, or both
+    render_mode: RenderMode,
+    isosurface: Isosurface,
+    // Requested raymarch resolution relative to the surface, and the
+    // dynamic resolution factor applied on top of it
+    render_scale: f32,
+    dynamic_scale: f32,
+
+    // Spatial grid wireframe and seed markers drawn over the raymarched frame
+    grid_overlay: GridOverlay,
+    // Axes and bounding box drawn over the raymarched frame
+    gizmo: Gizmo,
+    // Stats overlay drawn on top of the presented frame
+    overlay: Overlay,
+    // Labels, readouts and captions drawn over everything else
+    text: TextRenderer,
+
+    // Per-pass GPU timings, when the adapter supports timestamp queries
+    profiler: Option<GpuProfiler>,
+
+    // Compiled pipelines saved for the next run, where the backend supports it
+    pipeline_cache: Option<DiskPipelineCache>,
+
+    // Uncaptured device errors not yet reported to the page
+    #[cfg(target_arch = "wasm32")]
+    errors: Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+/// How the volume is raymarched, chosen from the adapter's capabilities
+enum RenderPath {
+    /// Compute shader into a storage texture, then a display pass with bloom
+    Compute(Box<ComputePath>),
+    /// Fullscreen fragment shader straight into the swapchain, for adapters
+    /// without compute shaders or storage buffers (WebGL2)
+    Fragment(FragmentPath),
+}
+
+/// A view's rectangle in the raymarch target, in pixels
+#[derive(Clone, Copy, Debug)]
+struct Viewport {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Viewport {
+    /// View `index` of `count` equal columns across a `width` x `height` target
+    fn split(width: u32, height: u32, index: u32, count: u32) -> Self {
+        let x = width * index / count;
+        let right = width * (index + 1) / count;
+        Self {
+            x,
+            y: 0,
+            width: (right - x).max(1),
+            height,
+        }
+    }
+
+    /// x, y, width and height, as render passes take them
+    fn rect(&self) -> [f32; 4] {
+        [
+            self.x as f32,
+            self.y as f32,
+            self.width as f32,
+            self.height as f32,
+        ]
+    }
+}
+
+/// Color and depth of the rasterized isosurface, at the raymarch resolution,
+/// which the raymarch reads back to composite the surface with the volume
+#[derive(Clone, Copy, Debug)]
+struct GeometryTargets {
+    color: TextureId,
+    depth: TextureId,
+}
+
+impl GeometryTargets {
+    /// Add the targets to the graph, with the isosurface pass that draws them
+    fn declare(graph: &mut RenderGraphBuilder<Stage>) -> Self {
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        let color = graph.texture(TextureDesc {
+            label: "Geometry Color",
+            format: isosurface::COLOR_FORMAT,
+            usage,
+            scale: 1.0,
+        });
+        let depth = graph.texture(TextureDesc {
+            label: "Geometry Depth",
+            format: isosurface::DEPTH_FORMAT,
+            usage,
+            scale: 1.0,
+        });
+        let targets = Self { color, depth };
+        graph.pass(Stage::Isosurface, &[], &targets.slots());
+        targets
+    }
+
+    fn slots(&self) -> [Slot; 2] {
+        [Slot::Texture(self.color), Slot::Texture(self.depth)]
+    }
+
+    /// Layout entries for the color and depth at `binding` and the one after.
+    /// Depth is bound as an unfilterable float texture, so the raymarch can
+    /// `textureLoad` it on WebGL2 too.
+    fn layout_entries(
+        binding: u32,
+        visibility: wgpu::ShaderStages,
+    ) -> [wgpu::BindGroupLayoutEntry; 2] {
+        [binding, binding + 1].map(|binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        })
+    }
+
+    fn bind_group_entries<'a>(
+        &self,
+        graph: &'a RenderGraph<Stage>,
+        binding: u32,
+    ) -> [wgpu::BindGroupEntry<'a>; 2] {
+        [
+            wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::TextureView(
+                    graph.view(self.color, Access::Sampled),
+                ),
+            },
+            wgpu::BindGroupEntry {
+                binding: binding + 1,
+                resource: wgpu::BindingResource::TextureView(
+                    graph.view(self.depth, Access::Sampled),
+                ),
+            },
+        ]
+    }
+}
+
+/// Passes of a frame, as scheduled by the render graph
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    /// Raymarch into the HDR target on the compute path, or straight into
+    /// the surface on the fragment path
+    Raymarch,
+    /// Filter the noise out of the HDR target (compute path only)
+    Denoise,
+    /// Tonemap and bloom the HDR target into the surface (compute path only)
+    Display,
+    /// Rasterized membrane surface, which the raymarch shows on its own in
+    /// isosurface mode and composites with the volume in hybrid mode
+    Isosurface,
+    /// Spatial grid wireframe over the raymarched frame
+    GridOverlay,
+    /// Orientation axes and bounding box over the raymarched frame
+    Gizmo,
+    /// Stats overlay on top of the finished frame
+    Overlay,
+    /// Queued text over the stats overlay
+    Text,
+}
+
+impl GpuState {
+    /// Set up rendering for `window`, calling `progress` as each stage of
+    /// it begins
+    pub async fn new(
+        window: Arc<Window>,
+        world: &HoneycombWorld,
+        options: &AdapterOptions,
+        mut progress: impl FnMut(LoadStage),
+    ) -> Result<Self, GpuError> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let (width, height) = {
+            let size = window.inner_size();
+            (size.width.max(1), size.height.max(1))
+        };
+
+        // On WASM, window.inner_size() can lag behind the canvas. The app sizes the
+        // canvas backing store in device pixels before init, so use it directly
+        #[cfg(target_arch = "wasm32")]
+        let (width, height) = {
+            use winit::platform::web::WindowExtWebSys;
+            let canvas = window.canvas().unwrap();
+            (canvas.width().max(1), canvas.height().max(1))
+        };
+
+        tracing::info!("GPU init - size: {}x{}", width, height);
+        progress(LoadStage::Adapter);
+
+        // Create wgpu instance; WebGPU support can only be detected asynchronously
+        let instance = wgpu::util::new_instance_with_webgpu_detection(&wgpu::InstanceDescriptor {
+            backends: options.backends,
+            ..Default::default()
+        })
+        .await;
+
+        // Create surface
+        let surface = instance.create_surface(window)?;
+
+        // Request adapter, by name if one was asked for
+        let adapter = match Self::find_named_adapter(&instance, Some(&surface), options) {
+            Some(adapter) => adapter,
+            None => instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: options.power_preference,
+                    compatible_surface: Some(&surface),
+                    force_fallback_adapter: false,
+                })
+                .instrument(tracing::info_span!("request_adapter"))
+                .await
+                .map_err(|_| GpuError::NoAdapter {
+                    backends: options.backends,
+                })?,
+        };
+
+        let info = adapter.get_info();
+        #[cfg(target_arch = "wasm32")]
+        crate::report::note_adapter(describe_adapter(&info));
+        let use_compute = options
+            .raymarch
+            .use_compute(ComputePath::supported(&adapter));
+        tracing::info!(
+            "Using {} with the {} raymarch path",
+            describe_adapter(&info),
+            if use_compute { "compute" } else { "fragment" }
+        );
+
+        progress(LoadStage::Device);
+        let (device, queue) = Self::request_device(&adapter, &info, use_compute)
+            .instrument(tracing::info_span!("request_device"))
+            .await?;
+
+        // Configure surface
+        let surface_caps = surface.get_capabilities(&adapter);
+        let surface_format = surface_caps
+            .formats
+            .iter()
+            .find(|f| f.is_srgb())
+            .or(surface_caps.formats.first())
+            .copied()
+            .ok_or(GpuError::UnsupportedSurface {
+                adapter: info.name.clone(),
+            })?;
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            alpha_mode: surface_caps.alpha_modes[0],
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        surface.configure(&device, &config);
+
+        // Pipeline creation blocks, so let the page show the stage first
+        progress(LoadStage::Pipelines);
+        #[cfg(target_arch = "wasm32")]
+        crate::web::yield_to_browser().await;
+
+        let state = Self::build(
+            device,
+            queue,
+            info,
+            use_compute,
+            Some(surface),
+            config,
+            world,
+        )?;
+        #[cfg(not(target_arch = "wasm32"))]
+        let state = Self {
+            present_modes: surface_caps.present_modes,
+            ..state
+        };
+        state.watch_errors();
+        Ok(state)
+    }
+
+    /// A state that renders offscreen, for tests and tools without a window.
+    /// Frames are read back with `render_image`, in `HEADLESS_FORMAT`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new_headless(
+        world: &HoneycombWorld,
+        options: &AdapterOptions,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, GpuError> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: options.backends,
+            ..Default::default()
+        });
+        let adapter = match Self::find_named_adapter(&instance, None, options) {
+            Some(adapter) => adapter,
+            None => pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: options.power_preference,
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            }))
+            .map_err(|_| GpuError::NoAdapter {
+                backends: options.backends,
+            })?,
+        };
+        let info = adapter.get_info();
+        let use_compute = options
+            .raymarch
+            .use_compute(ComputePath::supported(&adapter));
+        tracing::info!(
+            "Rendering headless with {} on the {} raymarch path",
+            describe_adapter(&info),
+            if use_compute { "compute" } else { "fragment" }
+        );
+        let (device, queue) =
+            pollster::block_on(Self::request_device(&adapter, &info, use_compute))?;
+
+        let state = Self::build(
+            device,
+            queue,
+            info,
+            use_compute,
+            None,
+            offscreen_config(HEADLESS_FORMAT, width, height),
+            world,
+        )?;
+        state.watch_errors();
+        Ok(state)
+    }
+
+    /// A state drawing with a device the host application opened, into
+    /// texture views in `format` that the host passes to `render_to`. The
+    /// raymarch path is chosen from what both the adapter and the device's
+    /// limits allow.
+    pub fn from_device(
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        world: &HoneycombWorld,
+    ) -> Result<Self, GpuError> {
+        let info = adapter.get_info();
+        let use_compute = ComputePath::supported(adapter) && ComputePath::within(&device.limits());
+        tracing::info!(
+            "Embedded on {} with the {} raymarch path",
+            describe_adapter(&info),
+            if use_compute { "compute" } else { "fragment" }
+        );
+        Self::build(
+            device.clone(),
+            queue.clone(),
+            info,
+            use_compute,
+            None,
+            offscreen_config(format, width, height),
+            world,
+        )
+    }
+
+    /// The device and queue, with the limits the raymarch path needs
+    async fn request_device(
+        adapter: &wgpu::Adapter,
+        info: &wgpu::AdapterInfo,
+        use_compute: bool,
+    ) -> Result<(wgpu::Device, wgpu::Queue), GpuError> {
+        adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("Device"),
+                required_features: adapter.features()
+                    & (wgpu::Features::TIMESTAMP_QUERY
+                        | wgpu::Features::SHADER_F16
+                        | DiskPipelineCache::FEATURES),
+                required_limits: Self::required_limits(adapter, use_compute),
+                memory_hints: Default::default(),
+                trace: wgpu::Trace::Off,
+            })
+            .await
+            .map_err(|source| GpuError::Device {
+                adapter: info.name.clone(),
+                source,
+            })
+    }
+
+    /// The downlevel limits at the adapter's resolutions. The compute path
+    /// also takes the adapter's buffer sizes, so stills accumulate at print
+    /// resolutions.
+    fn required_limits(adapter: &wgpu::Adapter, use_compute: bool) -> wgpu::Limits {
+        if !use_compute {
+            return wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits());
+        }
+        let supported = adapter.limits();
+        wgpu::Limits {
+            max_storage_buffer_binding_size: supported.max_storage_buffer_binding_size,
+            max_buffer_size: supported.max_buffer_size,
+            ..wgpu::Limits::downlevel_defaults().using_resolution(supported.clone())
+        }
+    }
+
+    /// Everything past the device: buffers, textures and pipelines
+    fn build(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        info: wgpu::AdapterInfo,
+        use_compute: bool,
+        surface: Option<wgpu::Surface<'static>>,
+        config: wgpu::SurfaceConfiguration,
+        world: &HoneycombWorld,
+    ) -> Result<Self, GpuError> {
+        let _span = tracing::info_span!("create_pipelines").entered();
+        let (width, height) = (config.width, config.height);
+        let surface_format = config.format;
+        let pipeline_cache = DiskPipelineCache::load(&device, &info);
+        let cache = pipeline_cache.as_ref().map(|c| c.cache().clone());
+
+        // Create uniform buffers
+        let frame_uniforms = FrameUniforms {
+            view_proj: glam::Mat4::IDENTITY,
+            inv_view_proj: glam::Mat4::IDENTITY,
+            camera_position: Vec3::ZERO,
+            time: 0.0,
+            resolution: [width as f32, height as f32],
+            near: 0.1,
+            far: 100.0,
+            viewport_origin: [0.0; 2],
+            sample_index: 0,
+            sample_limit: MAX_SAMPLES,
+        };
+
+        let frame_uniform_buffers: Vec<wgpu::Buffer> = (0..MAX_VIEWS)
+            .map(|view| {
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("Frame Uniforms Buffer {}", view)),
+                    contents: bytemuck::cast_slice(&[frame_uniforms]),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                })
+            })
+            .collect();
+
+        let bounds = world.bounds;
+        let background_seed = background_seed(world.seed);
+        let raymarch_params = RaymarchParams {
+            volume_min: bounds.min,
+            _pad0: 0.0,
+            volume_max: bounds.max,
+            _pad1: 0.0,
+            max_steps: MAX_STEPS,
+            step_size: STEP_SIZE,
+            membrane_thickness: MEMBRANE_THICKNESS,
+            membrane_glow: MEMBRANE_GLOW,
+            density_multiplier: 1.0,
+            enable_coupling: 1.0,
+            palette: 0,
+            clip_plane_count: 0,
+            clip_planes: [[0.0; 4]; MAX_CLIP_PLANES],
+            render_mode: RenderMode::Volume.index(),
+            visible_phases: ALL_PHASES,
+            ripple_amplitude: RIPPLE_AMPLITUDE,
+            ripple_speed: RIPPLE_SPEED,
+            reaction_strength: 0.0,
+            heat_strength: 0.0,
+            min_transmittance: MIN_TRANSMITTANCE,
+            scattering: 0.0,
+            light_direction: Vec3::Y,
+            emission: 0.0,
+            background: Background::Solid.index(),
+            background_seed,
+            lod_falloff: 0.0,
+            refine_threshold: REFINE_THRESHOLD,
+            debug_view: DebugView::Off.index(),
+            _pad2: [0; 3],
+        };
+
+        let raymarch_params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Raymarch Params Buffer"),
+            contents: bytemuck::cast_slice(&[raymarch_params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let palette = Palette::Rainbow;
+        let transfer_functions = vec![None; world.phases.len()];
+        let textures = WorldTextures::new(
+            &device,
+            &queue,
+            &world.phases,
+            &palette.phase_colors(&world.phases, None),
+            &transfer_functions,
+            world.cells.len(),
+            &SpatialGrid::for_world(world),
+        );
+        let views = textures.views();
+
+        let mut builder = RenderGraphBuilder::new();
+        let geometry = GeometryTargets::declare(&mut builder);
+        let compute_targets = if use_compute {
+            Some(ComputePath::declare(&mut builder, geometry))
+        } else {
+            FragmentPath::declare(&mut builder, geometry);
+            None
+        };
+        builder.pass(Stage::GridOverlay, &[Slot::Surface], &[Slot::Surface]);
+        builder.pass(Stage::Gizmo, &[Slot::Surface], &[Slot::Surface]);
+        builder.pass(Stage::Overlay, &[Slot::Surface], &[Slot::Surface]);
+        builder.pass(Stage::Text, &[Slot::Surface], &[Slot::Surface]);
+        let graph = builder.build(&device, width, height);
+
+        let path = if let Some((hdr_target, denoise_targets)) = compute_targets {
+            RenderPath::Compute(Box::new(ComputePath::new(
+                &device,
+                surface_format,
+                &graph,
+                hdr_target,
+                denoise_targets,
+                geometry,
+                &frame_uniform_buffers,
+                &raymarch_params_buffer,
+                &views,
+                world,
+                cache.clone(),
+                WorkgroupSize::default(),
+            )?))
+        } else {
+            RenderPath::Fragment(FragmentPath::new(
+                &device,
+                surface_format,
+                &graph,
+                geometry,
+                &frame_uniform_buffers,
+                &raymarch_params_buffer,
+                &views,
+                world,
+                cache.clone(),
+            )?)
+        };
+
+        let isosurface = Isosurface::new(
+            &device,
+            geometry.color,
+            geometry.depth,
+            &frame_uniform_buffers,
+            &raymarch_params_buffer,
+            &views.palette,
+            world,
+            (bounds.min, bounds.max),
+            cache.as_ref(),
+        )?;
+        let grid_overlay = GridOverlay::new(&device, surface_format, cache.as_ref(), world);
+        let gizmo = Gizmo::new(
+            &device,
+            surface_format,
+            cache.as_ref(),
+            bounds.min,
+            bounds.max,
+        );
+        let overlay = Overlay::new(&device, surface_format, cache.as_ref());
+        let text = TextRenderer::new(&device, &queue, surface_format, cache.as_ref());
+        // Save right away so a crash later on still leaves a warm cache
+        if let Some(pipeline_cache) = &pipeline_cache {
+            pipeline_cache.save();
+        }
+        let profiler = GpuProfiler::new(&device, &queue);
+        if profiler.is_none() {
+            tracing::info!("Timestamp queries unavailable; GPU pass timings disabled");
+        }
+
+        Ok(Self {
+            surface,
+            device,
+            queue,
+            config,
+            size: winit::dpi::PhysicalSize::new(width, height),
+            #[cfg(not(target_arch = "wasm32"))]
+            present_modes: Vec::new(),
+            adapter_info: info,
+            frame_uniform_buffers,
+            raymarch_params_buffer,
+            palette_texture: textures.palette,
+            palette,
+            custom_gradient: None,
+            bounds,
+            background_seed,
+            phases: world.phases.clone(),
+            transfer_texture: textures.transfer,
+            transfer_functions,
+            cell_state_texture: textures.cell_state,
+            grid_texture: textures.grid,
+            path,
+            graph,
+            viewports: Vec::new(),
+            still_views: Vec::new(),
+            samples: 0,
+            sample_limit: MAX_SAMPLES,
+            render_mode: RenderMode::Volume,
+            isosurface,
+            render_scale: 1.0,
+            dynamic_scale: 1.0,
+            grid_overlay,
+            gizmo,
+            overlay,
+            text,
+            profiler,
+            pipeline_cache,
+            #[cfg(target_arch = "wasm32")]
+            errors: Arc::default(),
+        })
+    }
+
+    /// Log uncaptured device errors, and queue them so the app can forward
+    /// them to the viewer they belong to. Only for devices the state opened
+    /// itself; a host's device keeps the host's handler.
+    fn watch_errors(&self) {
+        #[cfg(target_arch = "wasm32")]
+        let error_queue = self.errors.clone();
+        self.device.on_uncaptured_error(Box::new(move |error| {
+            tracing::error!("GPU error: {}", error);
+            #[cfg(target_arch = "wasm32")]
+            error_queue.lock().unwrap().push(error.to_string());
+        }));
+    }
+
+    /// The first adapter matching `options.name` that can draw to `surface`,
+    /// if there is one
+    #[cfg(not(target_arch = "wasm32"))]
+    fn find_named_adapter(
+        instance: &wgpu::Instance,
+        surface: Option<&wgpu::Surface>,
+        options: &AdapterOptions,
+    ) -> Option<wgpu::Adapter> {
+        let wanted = options.name.as_ref()?.to_lowercase();
+        let adapters = instance.enumerate_adapters(options.backends);
+        let found = adapters.iter().position(|adapter| {
+            adapter.get_info().name.to_lowercase().contains(&wanted)
+                && surface.is_none_or(|surface| adapter.is_surface_supported(surface))
+        });
+
+        if found.is_none() {
+            tracing::warn!("No adapter matching `{}`; available adapters:", wanted);
+            for adapter in &adapters {
+                tracing::warn!("  {}", describe_adapter(&adapter.get_info()));
+            }
+        }
+        found.map(|index| adapters.into_iter().nth(index).unwrap())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn find_named_adapter(
+        _instance: &wgpu::Instance,
+        _surface: Option<&wgpu::Surface>,
+        _options: &AdapterOptions,
+    ) -> Option<wgpu::Adapter> {
+        None
+    }
+
+    /// Drain device errors raised since the last call
+    #[cfg(target_arch = "wasm32")]
+    pub fn take_errors(&self) -> Vec<String> {
+        std::mem::take(&mut *self.errors.lock().unwrap())
+    }
+
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        let width = new_size.width;
+        let height = new_size.height;
+
+        if width > 0 && height > 0 {
+            self.size = winit::dpi::PhysicalSize::new(width, height);
+            self.config.width = width;
+            self.config.height = height;
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.config);
+            }
+
+            self.resize_targets();
+        }
+    }
+
+    /// Resize the graph's textures for the surface size and render scale,
+    /// keeping the HDR target within the device's texture size limit
+    fn resize_targets(&mut self) {
+        self.still_views.clear();
+        let (width, height) = (self.size.width, self.size.height);
+        if let RenderPath::Compute(path) = &self.path {
+            let max_dimension = self.device.limits().max_texture_dimension_2d as f32;
+            let scale = (self.render_scale * self.dynamic_scale)
+                .min(max_dimension / width.max(height) as f32);
+            // Geometry is rasterized at the raymarch resolution, pixel for pixel
+            let denoise = path.denoiser.targets();
+            for target in [
+                path.hdr_target,
+                denoise.guide,
+                denoise.scratch,
+                path.geometry.color,
+                path.geometry.depth,
+            ] {
+                self.graph.set_scale(target, scale);
+            }
+        }
+        self.graph.resize(&self.device, width, height);
+        match &mut self.path {
+            RenderPath::Compute(path) => path.bind_targets(&self.device, &self.graph),
+            RenderPath::Fragment(path) => path.bind_targets(&self.device, &self.graph),
+        }
+    }
+
+    /// Upload changed runtime parameters: the raymarch uniforms, the shader
+    /// permutation and the palette. Called when they change, not every frame.
+    pub fn set_params(&mut self, runtime_params: &RuntimeParams) {
+        let runtime_params = &runtime_params.power_limited();
+        self.still_views.clear();
+        let clip_planes: Vec<[f32; 4]> = runtime_params
+            .clip_planes
+            .iter()
+            .flatten()
+            .map(ClipPlane::equation)
+            .collect();
+        let raymarch_params = RaymarchParams {
+            volume_min: self.bounds.min,
+            _pad0: 0.0,
+            volume_max: self.bounds.max,
+            _pad1: 0.0,
+            max_steps: runtime_params.max_steps,
+            step_size: runtime_params.step_size,
+            membrane_thickness: runtime_params.membrane_thickness,
+            membrane_glow: runtime_params.membrane_glow,
+            density_multiplier: runtime_params.density,
+            enable_coupling: if runtime_params.enable_coupling {
+                1.0
+            } else {
+                0.0
+            },
+            palette: runtime_params.palette,
+            clip_plane_count: clip_planes.len() as u32,
+            clip_planes: std::array::from_fn(|i| clip_planes.get(i).copied().unwrap_or_default()),
+            render_mode: runtime_params.render_mode.index(),
+            visible_phases: runtime_params.visible_phases,
+            ripple_amplitude: runtime_params.ripple_amplitude,
+            ripple_speed: runtime_params.ripple_speed,
+            reaction_strength: runtime_params.reaction_strength,
+            heat_strength: runtime_params.heat_strength,
+            min_transmittance: runtime_params.min_transmittance,
+            scattering: runtime_params.scattering,
+            light_direction: runtime_params.light_direction(),
+            emission: runtime_params.emission,
+            background: runtime_params.background.index(),
+            background_seed: self.background_seed,
+            lod_falloff: runtime_params.lod_falloff,
+            refine_threshold: runtime_params.refine_threshold,
+            debug_view: runtime_params.debug_view.index(),
+            _pad2: [0; 3],
+        };
+
+        self.queue.write_buffer(
+            &self.raymarch_params_buffer,
+            0,
+            bytemuck::cast_slice(&[raymarch_params]),
+        );
+
+        // Switch shader permutation when a feature is toggled
+        let features = ShaderFeatures::for_params(runtime_params);
+        let built = match &mut self.path {
+            RenderPath::Compute(path) => {
+                // Anything shaping the density or the light may have changed
+                path.shadow.invalidate();
+                path.shadow.set_in_use(runtime_params.scattering > 0.0);
+                // Debug views show each pixel's own value
+                path.denoiser.set_strength(match runtime_params.debug_view {
+                    DebugView::Off => runtime_params.denoise,
+                    _ => 0.0,
+                });
+                path.select_features(&self.device, features)
+            }
+            RenderPath::Fragment(path) => path.select_features(&self.device, features),
+        };
+        if let (true, Some(pipeline_cache)) = (built, &self.pipeline_cache) {
+            pipeline_cache.save();
+        }
+
+        self.render_mode = runtime_params.render_mode;
+        if self.render_mode.rasterizes() {
+            self.isosurface
+                .prepare(&self.device, runtime_params.membrane_thickness);
+        }
+
+        self.gizmo.show_axes = runtime_params.show_axes;
+        self.gizmo.show_bounds = runtime_params.show_bounds;
+        self.grid_overlay.show = runtime_params.show_grid;
+
+        let palette = Palette::from_index(runtime_params.palette);
+        if palette != self.palette {
+            self.set_palette(palette);
+        }
+
+        // Supersampling only applies to the compute path's HDR target
+        if runtime_params.supersample != self.render_scale {
+            self.render_scale = runtime_params.supersample;
+            self.resize_targets();
+        }
+    }
+
+    /// What kind of adapter this is, for the viewer's starting quality
+    pub fn device_class(&self) -> DeviceClass {
+        DeviceClass::of(
+            &self.adapter_info,
+            &self.device.limits(),
+            self.scales_resolution(),
+        )
+    }
+
+    /// Whether the raymarch resolution can differ from the surface's, which
+    /// needs the compute path's separate HDR target
+    pub fn scales_resolution(&self) -> bool {
+        matches!(self.path, RenderPath::Compute(_))
+    }
+
+    /// Set the dynamic resolution factor, multiplied with supersampling
+    pub fn set_dynamic_scale(&mut self, scale: f32) {
+        if scale != self.dynamic_scale {
+            self.dynamic_scale = scale;
+            self.resize_targets();
+        }
+    }
+
+    /// Size of the image the raymarch writes: the HDR target on the compute
+    /// path, the surface on the fragment path
+    fn raymarch_size(&self) -> (u32, u32) {
+        match &self.path {
+            RenderPath::Compute(path) => {
+                let size = self.graph.texture(path.hdr_target).size();
+                (size.width, size.height)
+            }
+            RenderPath::Fragment(_) => (self.size.width, self.size.height),
+        }
+    }
+
+    /// Render one frame with a view per camera, side by side from left to
+    /// right. Cameras beyond `MAX_VIEWS` are ignored.
+    pub fn render(&mut self, cameras: &[&Camera], time: f32) -> Result<(), wgpu::SurfaceError> {
+        self.prepare_frame(cameras, time);
+        // Headless and embedded states have nothing to present to
+        let Some(surface) = &self.surface else {
+            return Ok(());
+        };
+
+        // Get output texture
+        let output = tracing::trace_span!("acquire").in_scope(|| surface.get_current_texture())?;
+        let output_view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        self.draw(&output_view);
+        tracing::trace_span!("present").in_scope(|| output.present());
+        Ok(())
+    }
+
+    /// Render one frame, as `render`, into a texture view of the host's.
+    /// The view must be in the state's format and of its size.
+    pub fn render_to(&mut self, cameras: &[&Camera], time: f32, target: &wgpu::TextureView) {
+        self.prepare_frame(cameras, time);
+        self.draw(target);
+    }
+
+    /// Encode and submit every pass, the final ones drawing into `output_view`
+    fn draw(&mut self, output_view: &wgpu::TextureView) {
+        self.text.prepare(&self.device, &self.queue, self.size);
+        let encode = tracing::trace_span!("encode").entered();
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+
+        for stage in self.graph.passes() {
+            if !self.stage_active(stage) {
+                continue;
+            }
+            match stage {
+                Stage::Isosurface => {
+                    let timestamps = self
+                        .profiler
+                        .as_mut()
+                        .and_then(|p| p.render_pass(Pass::Isosurface));
+                    let viewports: Vec<[f32; 4]> =
+                        self.viewports.iter().map(Viewport::rect).collect();
+                    self.isosurface
+                        .encode(&mut encoder, &self.graph, &viewports, timestamps);
+                }
+                Stage::GridOverlay => self.grid_overlay.encode(&mut encoder, output_view),
+                Stage::Gizmo => self.gizmo.encode(&mut encoder, output_view),
+                Stage::Overlay => self.overlay.encode(&mut encoder, output_view),
+                Stage::Text => self.text.encode(&mut encoder, output_view),
+                _ => Self::encode_stage(
+                    stage,
+                    &self.path,
+                    &self.viewports,
+                    &mut encoder,
+                    output_view,
+                    self.profiler.as_mut(),
+                ),
+            }
+        }
+
+        if let Some(profiler) = &self.profiler {
+            profiler.resolve(&mut encoder);
+        }
+        drop(encode);
+
+        tracing::trace_span!("submit")
+            .in_scope(|| self.queue.submit(std::iter::once(encoder.finish())));
+
+        // Drive the timestamp readback; results show up a frame or two later
+        if let Some(profiler) = &mut self.profiler {
+            profiler.after_submit();
+            poll(&self.device, wgpu::PollType::Poll);
+            profiler.collect();
+        }
+    }
+
+    /// Upload the frame uniforms for each camera, and lay out their viewports.
+    /// Views the same as the last frame's take another sample per pixel on
+    /// the compute path; any change starts the accumulation over.
+    fn prepare_frame(&mut self, cameras: &[&Camera], time: f32) {
+        let cameras = &cameras[..cameras.len().min(MAX_VIEWS)];
+        let (width, height) = self.raymarch_size();
+        let count = cameras.len() as u32;
+        self.viewports = (0..count)
+            .map(|index| Viewport::split(width, height, index, count))
+            .collect();
+        let views: Vec<FrameUniforms> = cameras
+            .iter()
+            .zip(&self.viewports)
+            .map(|(camera, viewport)| {
+                let aspect = viewport.width as f32 / viewport.height as f32;
+                let view = camera.view_matrix();
+                let proj = camera.projection_matrix(aspect);
+                let view_proj = proj * view;
+                let inv_view_proj = view_proj.inverse();
+
+                FrameUniforms {
+                    view_proj,
+                    inv_view_proj,
+                    camera_position: camera.position(),
+                    time,
+                    resolution: [viewport.width as f32, viewport.height as f32],
+                    near: camera.near,
+                    far: camera.far,
+                    viewport_origin: [viewport.x as f32, viewport.y as f32],
+                    sample_index: 0,
+                    sample_limit: self.sample_limit,
+                }
+            })
+            .collect();
+
+        let still = views == self.still_views && matches!(self.path, RenderPath::Compute(_));
+        self.samples = if still {
+            (self.samples + 1).min(self.sample_limit)
+        } else {
+            0
+        };
+        for (index, view) in views.iter().enumerate() {
+            let frame_uniforms = FrameUniforms {
+                sample_index: self.samples,
+                ..*view
+            };
+            self.queue.write_buffer(
+                &self.frame_uniform_buffers[index],
+                0,
+                bytemuck::cast_slice(&[frame_uniforms]),
+            );
+        }
+        self.still_views = views;
+        if let RenderPath::Compute(path) = &self.path {
+            path.denoiser
+                .prepare(&self.queue, self.accumulated_samples());
+        }
+
+        // The gizmo and grid follow the main view, split the same way at
+        // the surface's resolution
+        if let Some(camera) = cameras.first() {
+            let main = Viewport::split(self.size.width, self.size.height, 0, count).rect();
+            self.grid_overlay.update(&self.queue, camera, main);
+            self.gizmo.update(&self.queue, camera, main);
+        }
+    }
+
+    /// Replace the gradient used by `Palette::Custom`
+    pub fn set_custom_gradient(&mut self, gradient: Gradient) {
+        self.custom_gradient = Some(gradient);
+        if self.palette == Palette::Custom {
+            self.set_palette(Palette::Custom);
+        }
+    }
+
+    /// Regenerate the phase colors for `palette` and upload them
+    fn set_palette(&mut self, palette: Palette) {
+        self.still_views.clear();
+        let colors = palette.phase_colors(&self.phases, self.custom_gradient.as_ref());
+        self.queue.write_texture(
+            self.palette_texture.as_image_copy(),
+            bytemuck::cast_slice(&colors),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(colors.len() as u32 * 4),
+                rows_per_image: Some(1),
+            },
+            self.palette_texture.size(),
+        );
+        self.palette = palette;
+    }
+
+    /// Replace the transfer function of `phase`, or of every phase when
+    /// `phase` is `None`. A `function` of `None` restores the fixed mapping.
+    /// Phases past the last are ignored.
+    pub fn set_transfer_function(
+        &mut self,
+        phase: Option<usize>,
+        function: Option<TransferFunction>,
+    ) {
+        self.still_views.clear();
+        for (index, slot) in self.transfer_functions.iter_mut().enumerate() {
+            if phase.is_none_or(|phase| phase == index) {
+                *slot = function.clone();
+            }
+        }
+        let texels = transfer_texels(&self.phases, &self.transfer_functions);
+        self.queue.write_texture(
+            self.transfer_texture.as_image_copy(),
+            bytemuck::cast_slice(&texels),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(transfer::SAMPLES * 16),
+                rows_per_image: Some(self.phases.len() as u32),
+            },
+            self.transfer_texture.size(),
+        );
+        if let RenderPath::Compute(path) = &mut self.path {
+            path.shadow.invalidate();
+        }
+    }
+
+    /// Replace the world with another, of any cell and phase count: rebuild
+    /// the world buffers, the textures sized by it and the pipelines and bind
+    /// groups using them, then apply `params`. The palette, custom gradient
+    /// and chosen transfer functions carry over. On error the old world is
+    /// left in place.
+    pub fn set_world(
+        &mut self,
+        world: &HoneycombWorld,
+        params: &RuntimeParams,
+    ) -> Result<(), GpuError> {
+        let _span = tracing::info_span!("set_world").entered();
+        let cache = self.pipeline_cache.as_ref().map(|c| c.cache().clone());
+        let mut transfer_functions = self.transfer_functions.clone();
+        transfer_functions.resize(world.phases.len(), None);
+        let textures = WorldTextures::new(
+            &self.device,
+            &self.queue,
+            &world.phases,
+            &self
+                .palette
+                .phase_colors(&world.phases, self.custom_gradient.as_ref()),
+            &transfer_functions,
+            world.cells.len(),
+            &SpatialGrid::for_world(world),
+        );
+        let views = textures.views();
+
+        let (path, geometry) = match &self.path {
+            RenderPath::Compute(path) => {
+                let new_path = ComputePath::new(
+                    &self.device,
+                    self.config.format,
+                    &self.graph,
+                    path.hdr_target,
+                    path.denoiser.targets(),
+                    path.geometry,
+                    &self.frame_uniform_buffers,
+                    &self.raymarch_params_buffer,
+                    &views,
+                    world,
+                    cache.clone(),
+                    path.workgroup,
+                )?;
+                (RenderPath::Compute(Box::new(new_path)), path.geometry)
+            }
+            RenderPath::Fragment(path) => {
+                let new_path = FragmentPath::new(
+                    &self.device,
+                    self.config.format,
+                    &self.graph,
+                    path.geometry,
+                    &self.frame_uniform_buffers,
+                    &self.raymarch_params_buffer,
+                    &views,
+                    world,
+                    cache.clone(),
+                )?;
+                (RenderPath::Fragment(new_path), path.geometry)
+            }
+        };
+        let isosurface = Isosurface::new(
+            &self.device,
+            geometry.color,
+            geometry.depth,
+            &self.frame_uniform_buffers,
+            &self.raymarch_params_buffer,
+            &views.palette,
+            world,
+            (world.bounds.min, world.bounds.max),
+            cache.as_ref(),
+        )?;
+
+        self.path = path;
+        self.isosurface = isosurface;
+        self.palette_texture = textures.palette;
+        self.transfer_texture = textures.transfer;
+        self.cell_state_texture = textures.cell_state;
+        self.grid_texture = textures.grid;
+        self.transfer_functions = transfer_functions;
+        self.phases = world.phases.clone();
+        self.background_seed = background_seed(world.seed);
+        self.grid_overlay
+            .set_world(&self.device, &self.queue, world);
+        if world.bounds != self.bounds {
+            self.bounds = world.bounds;
+            self.gizmo = Gizmo::new(
+                &self.device,
+                self.config.format,
+                cache.as_ref(),
+                world.bounds.min,
+                world.bounds.max,
+            );
+        }
+        self.resize_targets();
+        self.set_params(params);
+        Ok(())
+    }
+
+    /// Upload cells after their phases changed. Positions must stay as the
+    /// world was created with.
+    pub fn set_cells(&mut self, cells: &[HoneycombCell]) {
+        self.still_views.clear();
+        let (buffer, offset) = match &self.path {
+            RenderPath::Compute(path) => {
+                path.shadow.invalidate();
+                (&path.cells_buffer, 0)
+            }
+            RenderPath::Fragment(path) => (&path.world_buffer, path.cells_offset),
+        };
+        self.queue
+            .write_buffer(buffer, offset, bytemuck::cast_slice(cells));
+        self.isosurface.set_cells(&self.queue, cells);
+    }
+
+    /// Take the cells and bounds of a streamed world after chunks loaded or
+    /// unloaded, then apply `params` to move the raymarch volume with them.
+    /// The cell and phase counts must stay as they were.
+    pub fn stream_world(&mut self, world: &HoneycombWorld, params: &RuntimeParams) {
+        let _span = tracing::info_span!("stream_world").entered();
+        match &self.path {
+            RenderPath::Compute(path) => {
+                self.queue
+                    .write_buffer(&path.cells_buffer, 0, bytemuck::cast_slice(&world.cells));
+                self.queue.write_buffer(
+                    &path.bvh_buffer,
+                    0,
+                    bytemuck::cast_slice(&SeedBvh::build(&world.cells).nodes),
+                );
+            }
+            RenderPath::Fragment(path) => self.queue.write_buffer(
+                &path.world_buffer,
+                path.cells_offset,
+                bytemuck::cast_slice(&world.cells),
+            ),
+        }
+        let size = self.grid_texture.size();
+        self.queue.write_texture(
+            self.grid_texture.as_image_copy(),
+            &SpatialGrid::for_world(world).occupancy(),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(size.width),
+                rows_per_image: Some(size.height),
+            },
+            size,
+        );
+        let bounds = (world.bounds.min, world.bounds.max);
+        self.isosurface.set_world(&world.cells, bounds);
+        self.gizmo.set_bounds(&self.queue, bounds.0, bounds.1);
+        self.grid_overlay
+            .set_world(&self.device, &self.queue, world);
+        self.bounds = world.bounds;
+        self.set_params(params);
+    }
+
+    /// Upload simulated per-cell state, one value per cell:
+    /// reaction–diffusion substrate and catalyst in x and y, temperature in z
+    pub fn set_cell_states(&mut self, states: &[[f32; 4]]) {
+        self.still_views.clear();
+        let size = self.cell_state_texture.size();
+        self.queue.write_texture(
+            self.cell_state_texture.as_image_copy(),
+            bytemuck::cast_slice(&cell_state_texels(states, states.len())),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(size.width * 16),
+                rows_per_image: Some(size.height),
+            },
+            size,
+        );
+    }
+
+    /// Per-pass GPU time of a recent frame, if timestamp queries are available
+    pub fn timings(&self) -> Option<&GpuTimings> {
+        self.profiler.as_ref().and_then(|p| p.latest())
+    }
+
+    /// Switch presentation mode, e.g. to run uncapped for benchmarking.
+    /// Modes the surface doesn't support fall back to vsync.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) {
+        let auto = matches!(
+            mode,
+            wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync
+        );
+        self.config.present_mode = if auto || self.present_modes.contains(&mode) {
+            mode
+        } else {
+            tracing::warn!("Present mode {:?} is not supported here; using vsync", mode);
+            wgpu::PresentMode::AutoVsync
+        };
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+    }
+
+    /// Name of the raymarch path in use, "compute" or "fragment"
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn raymarch_path(&self) -> &'static str {
+        match self.path {
+            RenderPath::Compute(_) => "compute",
+            RenderPath::Fragment(_) => "fragment",
+        }
+    }
+
+    /// Raymarch workgroup size, on the compute path
+    pub fn workgroup_size(&self) -> Option<WorkgroupSize> {
+        match &self.path {
+            RenderPath::Compute(path) => Some(path.workgroup),
+            RenderPath::Fragment(_) => None,
+        }
+    }
+
+    /// Raymarch in `workgroup`-sized tiles on the compute path, where the
+    /// device allows workgroups that large
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_workgroup_size(&mut self, workgroup: WorkgroupSize) {
+        let RenderPath::Compute(path) = &mut self.path else {
+            return;
+        };
+        if !workgroup.fits(&self.device.limits()) {
+            tracing::warn!(
+                "The device doesn't allow {} workgroups; keeping {}",
+                workgroup,
+                path.workgroup
+            );
+            return;
+        }
+        if let Err(e) = path.set_workgroup(&self.device, workgroup) {
+            tracing::error!("Could not build the {} raymarch: {}", workgroup, e);
+            return;
+        }
+        self.still_views.clear();
+        if let Some(pipeline_cache) = &self.pipeline_cache {
+            pipeline_cache.save();
+        }
+    }
+
+    /// Time the raymarch from `camera` in each workgroup size the device
+    /// allows, and keep the fastest. Returns it, or None on the fragment
+    /// path or where frames are too slow to time quickly.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn autotune_workgroup(&mut self, camera: &Camera) -> Option<WorkgroupSize> {
+        let _span = tracing::info_span!("autotune_workgroup").entered();
+        self.workgroup_size()?;
+        let limits = self.device.limits();
+        let mut fastest: Option<(std::time::Duration, WorkgroupSize)> = None;
+        for workgroup in WorkgroupSize::ALL {
+            if !workgroup.fits(&limits) {
+                continue;
+            }
+            self.set_workgroup_size(workgroup);
+            let Some(elapsed) = self.time_raymarch(camera) else {
+                self.set_workgroup_size(WorkgroupSize::default());
+                tracing::info!(
+                    "Raymarch frames are too slow to autotune; using {} workgroups",
+                    WorkgroupSize::default()
+                );
+                return None;
+            };
+            tracing::debug!(
+                "{} workgroups: {:.2} ms per frame",
+                workgroup,
+                elapsed.as_secs_f64() * 1000.0
+            );
+            if fastest.is_none_or(|(time, _)| elapsed < time) {
+                fastest = Some((elapsed, workgroup));
+            }
+        }
+        let (elapsed, workgroup) = fastest?;
+        self.set_workgroup_size(workgroup);
+        tracing::info!(
+            "Raymarching in {} workgroups, {:.2} ms per frame",
+            workgroup,
+            elapsed.as_secs_f64() * 1000.0
+        );
+        Some(workgroup)
+    }
+
+    /// Average time the raymarch takes from `camera`, each frame a first
+    /// sample rather than refining the last. None if the first frame after
+    /// the warm-up runs past `AUTOTUNE_FRAME_LIMIT`.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn time_raymarch(&mut self, camera: &Camera) -> Option<std::time::Duration> {
+        let mut total = std::time::Duration::ZERO;
+        for frame in 0..=AUTOTUNE_FRAMES {
+            self.still_views.clear();
+            self.prepare_frame(&[camera], 0.0);
+            let RenderPath::Compute(path) = &self.path else {
+                break;
+            };
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Autotune Encoder"),
+                });
+            path.encode_raymarch(&mut encoder, &self.viewports, None);
+            let start = std::time::Instant::now();
+            self.queue.submit(std::iter::once(encoder.finish()));
+            poll(&self.device, wgpu::PollType::Wait);
+            let elapsed = start.elapsed();
+            if frame == 1 && elapsed > AUTOTUNE_FRAME_LIMIT {
+                return None;
+            }
+            if frame > 0 {
+                total += elapsed;
+            }
+        }
+        self.still_views.clear();
+        Some(total / AUTOTUNE_FRAMES)
+    }
+
+    /// Show the stats overlay on the next frame with the given contents
+    pub fn update_overlay(&mut self, lines: &[String], history: &FrameHistory) {
+        self.overlay.update(&self.queue, self.size, lines, history);
+    }
+
+    /// Draw `label` over the next frame presented or rendered into a host's
+    /// view; captures leave it out, as they do the overlay
+    pub fn draw_text(&mut self, label: Label) {
+        self.text.queue(label);
+    }
 
-#[cfg(target_arch = "wasm32")]
-use wasm_bindgen::prelude::*;
+    /// Width and height in pixels `label` would take up
+    pub fn measure_text(&self, label: &Label) -> [f32; 2] {
+        self.text.measure(label)
+    }
 
-/// Parameters that can be adjusted at runtime
-#[derive(Clone, Copy)]
-pub struct RuntimeParams {
-    pub membrane_thickness: f32,
-    pub membrane_glow: f32,
-    pub step_size: f32,
-    pub density: f32,
-    pub max_steps: u32,
-    pub enable_coupling: bool,
-    pub palette: u32,
-}
+    /// Compute workgroups covering the frame, the most dispatched once tile
+    /// culling has run, or `None` on the fragment path
+    pub fn dispatch_size(&self) -> Option<(u32, u32)> {
+        match &self.path {
+            RenderPath::Compute(path) => Some(path.dispatch_size(&self.graph)),
+            RenderPath::Fragment(_) => None,
+        }
+    }
 
-impl Default for RuntimeParams {
-    fn default() -> Self {
-        Self {
-            membrane_thickness: MEMBRANE_THICKNESS,
-            membrane_glow: MEMBRANE_GLOW,
-            step_size: STEP_SIZE,
-            density: 1.0,
-            max_steps: MAX_STEPS,
-            enable_coupling: true,
-            palette: 0,
+    /// Samples per pixel the last frame had accumulated, counting from one;
+    /// more than one only while the view holds still on the compute path
+    pub fn accumulated_samples(&self) -> u32 {
+        (self.samples + 1).min(self.sample_limit)
+    }
+
+    /// Whether drawing the last frame's view again would change nothing: it
+    /// was drawn after the last change, and on the compute path has taken
+    /// every sample it will
+    pub fn converged(&self) -> bool {
+        !self.still_views.is_empty()
+            && (!self.accumulates() || self.accumulated_samples() >= self.sample_limit)
+    }
+
+    /// Samples per pixel a still view accumulates before it stops, at least
+    /// one
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_sample_limit(&mut self, limit: u32) {
+        self.sample_limit = limit.max(1);
+    }
+
+    /// Whether still views accumulate samples at the current size: only on
+    /// the compute path, and only while the accumulation buffer fits in a
+    /// binding
+    pub fn accumulates(&self) -> bool {
+        let RenderPath::Compute(path) = &self.path else {
+            return false;
+        };
+        let size = self.graph.texture(path.hdr_target).size();
+        u64::from(size.width * size.height) * ACCUMULATION_TEXEL
+            <= u64::from(self.device.limits().max_storage_buffer_binding_size)
+    }
+
+    /// Triangles drawn in isosurface and hybrid modes; `None` in the
+    /// raymarched modes
+    pub fn isosurface_triangles(&self) -> Option<u32> {
+        if self.render_mode.rasterizes() {
+            self.isosurface.triangle_count()
+        } else {
+            None
         }
     }
-}
 
-#[cfg(target_arch = "wasm32")]
-pub fn read_js_params() -> RuntimeParams {
-    let window = web_sys::window().unwrap();
-    let params = js_sys::Reflect::get(&window, &"vendekParams".into()).ok();
+    /// Whether `stage` draws anything in the current render mode
+    fn stage_active(&self, stage: Stage) -> bool {
+        match stage {
+            Stage::Isosurface => self.render_mode.rasterizes(),
+            Stage::Denoise => match &self.path {
+                RenderPath::Compute(path) => path.denoiser.active(),
+                RenderPath::Fragment(_) => false,
+            },
+            Stage::Raymarch
+            | Stage::Display
+            | Stage::GridOverlay
+            | Stage::Gizmo
+            | Stage::Overlay
+            | Stage::Text => true,
+        }
+    }
 
-    if let Some(params) = params {
-        if params.is_object() {
-            let get_f32 = |key: &str, default: f32| -> f32 {
-                js_sys::Reflect::get(&params, &key.into())
-                    .ok()
-                    .and_then(|v| v.as_f64())
-                    .map(|v| v as f32)
-                    .unwrap_or(default)
-            };
+    /// Encode a raymarch or display stage. Stages that write the
+    /// surface draw into `target`.
+    fn encode_stage(
+        stage: Stage,
+        path: &RenderPath,
+        viewports: &[Viewport],
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        profiler: Option<&mut GpuProfiler>,
+    ) {
+        match (stage, path) {
+            (Stage::Raymarch, RenderPath::Compute(path)) => {
+                let timestamps = profiler.and_then(|p| p.compute_pass(Pass::Raymarch));
+                path.encode_raymarch(encoder, viewports, timestamps);
+            }
+            (Stage::Denoise, RenderPath::Compute(path)) => {
+                let timestamps = profiler.and_then(|p| p.compute_pass(Pass::Denoise));
+                path.denoiser.encode(encoder, timestamps);
+            }
+            (Stage::Display, RenderPath::Compute(path)) => {
+                let timestamps = profiler.and_then(|p| p.render_pass(Pass::Display));
+                let mut render_pass = Self::begin_surface_pass(encoder, target, timestamps);
+                render_pass.set_pipeline(&path.render_pipeline);
+                render_pass.set_bind_group(0, &path.render_bind_group, &[]);
+                render_pass.set_bind_group(1, &path.display_params_bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+            (Stage::Raymarch, RenderPath::Fragment(path)) => {
+                let timestamps = profiler.and_then(|p| p.render_pass(Pass::Raymarch));
+                let mut render_pass = Self::begin_surface_pass(encoder, target, timestamps);
+                render_pass.set_pipeline(path.pipelines.current());
+                render_pass.set_bind_group(1, &path.geometry_bind_group, &[]);
+                for (viewport, bind_group) in viewports.iter().zip(&path.bind_groups) {
+                    render_pass.set_viewport(
+                        viewport.x as f32,
+                        viewport.y as f32,
+                        viewport.width as f32,
+                        viewport.height as f32,
+                        0.0,
+                        1.0,
+                    );
+                    render_pass.set_bind_group(0, bind_group, &[]);
+                    render_pass.draw(0..3, 0..1);
+                }
+            }
+            (Stage::Denoise | Stage::Display, RenderPath::Fragment(_))
+            | (
+                Stage::Isosurface
+                | Stage::GridOverlay
+                | Stage::Gizmo
+                | Stage::Overlay
+                | Stage::Text,
+                _,
+            ) => {}
+        }
+    }
 
-            return RuntimeParams {
-                membrane_thickness: get_f32("membraneThickness", MEMBRANE_THICKNESS),
-                membrane_glow: get_f32("membraneGlow", MEMBRANE_GLOW),
-                step_size: get_f32("stepSize", STEP_SIZE),
-                density: get_f32("density", 1.0),
-                max_steps: get_f32("maxSteps", MAX_STEPS as f32) as u32,
-                enable_coupling: get_f32("enableCoupling", 1.0) > 0.5,
-                palette: get_f32("palette", 0.0) as u32,
-            };
+    /// Render pass that clears `target` to the background color
+    fn begin_surface_pass<'a>(
+        encoder: &'a mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) -> wgpu::RenderPass<'a> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.02,
+                        g: 0.02,
+                        b: 0.03,
+                        a: 1.0,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes,
+            occlusion_query_set: None,
+        })
+    }
+
+    /// Re-run the passes that draw the surface, minus the grid, gizmo, overlay
+    /// and text, into an offscreen texture and read it back as tightly packed
+    /// RGBA8 rows, matching what was last presented on screen
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn capture_frame(&self) -> Vec<u8> {
+        self.wait_for_capture(self.submit_capture(false))
+    }
+
+    /// Render a frame offscreen, without the grid, gizmo, overlay and text, and
+    /// read it back as tightly packed RGBA8 rows. Headless states render
+    /// this way.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn render_image(&mut self, cameras: &[&Camera], time: f32) -> Vec<u8> {
+        self.prepare_frame(cameras, time);
+        self.wait_for_capture(self.submit_capture(true))
+    }
+
+    /// Render one view `samples` times over offscreen, each time adding a
+    /// sample per pixel to the accumulation, then run the passes after the
+    /// raymarch once and read the image back as `render_image` does.
+    /// `progress` hears how many samples have been taken after each. Where
+    /// nothing accumulates a single sample is drawn.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn render_still(
+        &mut self,
+        camera: &Camera,
+        time: f32,
+        samples: u32,
+        mut progress: impl FnMut(u32),
+    ) -> Vec<u8> {
+        let samples = if self.accumulates() { samples } else { 1 };
+        self.set_sample_limit(samples);
+        self.still_views.clear();
+        for taken in 1..samples {
+            self.prepare_frame(&[camera], time);
+            if let RenderPath::Compute(path) = &self.path {
+                let mut encoder =
+                    self.device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("Still Encoder"),
+                        });
+                path.encode_raymarch(&mut encoder, &self.viewports, None);
+                self.queue.submit(std::iter::once(encoder.finish()));
+                poll(&self.device, wgpu::PollType::Wait);
+            }
+            progress(taken);
         }
+        let image = self.render_image(&[camera], time);
+        progress(self.accumulated_samples());
+        image
     }
 
-    RuntimeParams::default()
-}
+    /// Read back the compute path's HDR image as the last frame left it,
+    /// denoised but not yet tonemapped by the display pass: its size, and
+    /// its RGBA half floats as raw bits, top row first. It is at the
+    /// raymarch's resolution, which supersampling and render scale make
+    /// differ from the surface's. `None` on the fragment path, which draws
+    /// straight to the surface.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn capture_hdr(&self) -> Option<(wgpu::Extent3d, Vec<u16>)> {
+        let RenderPath::Compute(path) = &self.path else {
+            return None;
+        };
+        let texture = self.graph.texture(path.hdr_target);
+        let size = texture.size();
+
+        // Four half floats per texel, rows 256-byte aligned as for captures
+        let unpadded_row = size.width * 8;
+        let padded_row = unpadded_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HDR Readback Buffer"),
+            size: u64::from(padded_row * size.height),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("HDR Capture Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(size.height),
+                },
+            },
+            size,
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
 
-#[cfg(not(target_arch = "wasm32"))]
-pub fn read_js_params() -> RuntimeParams {
-    RuntimeParams::default()
-}
+        let bytes = self.wait_for_capture(PendingCapture {
+            buffer,
+            height: size.height,
+            unpadded_row,
+            padded_row,
+            bgra: false,
+        });
+        let pixels = bytes
+            .as_chunks::<2>()
+            .0
+            .iter()
+            .map(|&half| u16::from_le_bytes(half))
+            .collect();
+        Some((size, pixels))
+    }
 
-// Constants for initial visualization
-const VOLUME_MIN: Vec3 = Vec3::new(-12.0, -12.0, -12.0);
-const VOLUME_MAX: Vec3 = Vec3::new(12.0, 12.0, 12.0);
-const MAX_STEPS: u32 = 128;
-const STEP_SIZE: f32 = 0.15;
-const MEMBRANE_THICKNESS: f32 = 0.4;
-const MEMBRANE_GLOW: f32 = 0.5;
+    #[cfg(not(target_arch = "wasm32"))]
+    fn wait_for_capture(&self, capture: PendingCapture) -> Vec<u8> {
+        capture
+            .buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, |_| {});
+        poll(&self.device, wgpu::PollType::Wait);
+        capture.read()
+    }
 
-pub struct GpuState {
-    pub surface: wgpu::Surface<'static>,
-    pub device: wgpu::Device,
-    pub queue: wgpu::Queue,
-    pub config: wgpu::SurfaceConfiguration,
-    pub size: winit::dpi::PhysicalSize<u32>,
+    /// Read back the frame as `capture_frame` does, on either target: the
+    /// surface passes minus the grid, gizmo, overlay and text, as tightly
+    /// packed RGBA8 rows with the copy's row padding stripped. Natively the
+    /// device is polled until the copy lands, so the future is ready by its
+    /// first poll; in the browser it resolves once the buffer is mapped.
+    pub async fn read_frame(&self) -> Result<Vec<u8>, wgpu::BufferAsyncError> {
+        let capture = self.submit_capture(false);
+        let (sender, receiver) = futures_channel::oneshot::channel();
+        capture
+            .buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        #[cfg(not(target_arch = "wasm32"))]
+        poll(&self.device, wgpu::PollType::Wait);
+        // A dropped sender means the device was lost before mapping
+        receiver.await.unwrap_or(Err(wgpu::BufferAsyncError))?;
+        Ok(capture.read())
+    }
+
+    /// Browser version of `capture_frame`. The readback can't block on the web,
+    /// so `on_done` runs once the browser has mapped the buffer.
+    #[cfg(target_arch = "wasm32")]
+    pub fn capture_frame_async(
+        &self,
+        on_done: impl FnOnce(Result<Vec<u8>, wgpu::BufferAsyncError>) + 'static,
+    ) {
+        let capture = self.submit_capture(false);
+        let buffer = capture.buffer.clone();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                on_done(result.map(|()| capture.read()))
+            });
+    }
+
+    /// Draw into a texture and copy it to a buffer for reading back: every
+    /// pass with `all_passes`, otherwise those that draw the surface, reusing
+    /// the last frame's intermediate textures
+    fn submit_capture(&self, all_passes: bool) -> PendingCapture {
+        let width = self.size.width;
+        let height = self.size.height;
+
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Capture Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Rows in a texture-to-buffer copy must be 256-byte aligned
+        let unpadded_row = width * 4;
+        let padded_row = unpadded_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let readback_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Readback Buffer"),
+            size: (padded_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Capture Encoder"),
+            });
+        let stages: Vec<Stage> = if all_passes {
+            self.graph.passes().collect()
+        } else {
+            self.graph.surface_passes().collect()
+        };
+        for stage in stages {
+            if !self.stage_active(stage) {
+                continue;
+            }
+            match stage {
+                Stage::GridOverlay | Stage::Gizmo | Stage::Overlay | Stage::Text => {}
+                Stage::Isosurface => {
+                    let viewports: Vec<[f32; 4]> =
+                        self.viewports.iter().map(Viewport::rect).collect();
+                    self.isosurface
+                        .encode(&mut encoder, &self.graph, &viewports, None);
+                }
+                _ => Self::encode_stage(
+                    stage,
+                    &self.path,
+                    &self.viewports,
+                    &mut encoder,
+                    &view,
+                    None,
+                ),
+            }
+        }
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback_buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        PendingCapture {
+            buffer: readback_buffer,
+            height,
+            unpadded_row,
+            padded_row,
+            bgra: matches!(
+                self.config.format,
+                wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+            ),
+        }
+    }
+}
 
-    // Compute pipeline resources
-    compute_pipeline: wgpu::ComputePipeline,
-    compute_bind_group_0: wgpu::BindGroup,
+struct ComputePath {
+    // Compute pipeline resources, one pipeline per shader permutation
+    compute_pipelines: Permutations<wgpu::ComputePipeline>,
+    compute_pipeline_layout: wgpu::PipelineLayout,
+    pipeline_cache: Option<wgpu::PipelineCache>,
+    // Bind group 0 for each view, differing only in frame uniforms
+    compute_bind_groups_0: Vec<wgpu::BindGroup>,
     compute_bind_group_1: wgpu::BindGroup,
     compute_bind_group_layout_1: wgpu::BindGroupLayout,
+    // Prepass skipping tiles that can't see the volume
+    tiles: TileCulling,
+    // Raymarch workgroup size, which is also the tile size
+    workgroup: WorkgroupSize,
+    // Prepass computing how much light reaches the volume
+    shadow: ShadowMap,
+    // Rewritten when cells change phase
+    cells_buffer: wgpu::Buffer,
+    // Rewritten when a streamed world's seeds move
+    bvh_buffer: wgpu::Buffer,
+    // Whether the raymarch finds seeds through the hierarchy
+    use_bvh: bool,
+    // Whether the raymarch composites in half precision
+    use_f16: bool,
 
     // Render pipeline resources
     render_pipeline: wgpu::RenderPipeline,
     render_bind_group: wgpu::BindGroup,
     render_bind_group_layout: wgpu::BindGroupLayout,
+    display_params_bind_group: wgpu::BindGroup,
 
-    // Buffers
-    frame_uniform_buffer: wgpu::Buffer,
-    raymarch_params_buffer: wgpu::Buffer,
-
-    // Storage texture for compute output
-    storage_texture: wgpu::Texture,
-    storage_texture_view: wgpu::TextureView,
+    // Graph texture the raymarch writes and the display pass samples
+    hdr_target: TextureId,
+    // Filters the HDR target in place between the two
+    denoiser: Denoiser,
+    // Rasterized geometry the raymarch composites
+    geometry: GeometryTargets,
 
     // Sampler for display shader
     sampler: wgpu::Sampler,
 }
 
-impl GpuState {
-    pub async fn new(window: Arc<Window>, world: &HoneycombWorld) -> Self {
-        let size = window.inner_size();
-        let mut width = size.width.max(1);
-        let mut height = size.height.max(1);
-
-        // On WASM, window.inner_size() can return incorrect values
-        // Fall back to querying the window dimensions directly
-        #[cfg(target_arch = "wasm32")]
-        {
-            let web_window = web_sys::window().unwrap();
-            let fallback_width = web_window.inner_width().unwrap().as_f64().unwrap() as u32;
-            let fallback_height = web_window.inner_height().unwrap().as_f64().unwrap() as u32;
-
-            web_sys::console::log_1(&format!(
-                "GPU init - winit size: {}x{}, web_sys size: {}x{}",
-                width, height, fallback_width, fallback_height
-            ).into());
-
-            // Use web_sys dimensions if winit reports tiny values
-            if width < 100 || height < 100 {
-                width = fallback_width.max(100);
-                height = fallback_height.max(100);
-                web_sys::console::log_1(&format!(
-                    "Using fallback dimensions: {}x{}", width, height
-                ).into());
-            }
-        }
+impl ComputePath {
+    /// Compute shaders with indirect dispatch, six storage buffers and a
+    /// storage texture
+    fn supported(adapter: &wgpu::Adapter) -> bool {
+        adapter.get_downlevel_capabilities().flags.contains(
+            wgpu::DownlevelFlags::COMPUTE_SHADERS | wgpu::DownlevelFlags::INDIRECT_EXECUTION,
+        ) && Self::within(&adapter.limits())
+    }
 
-        #[cfg(not(target_arch = "wasm32"))]
-        log::info!("GPU init - size: {}x{}", width, height);
+    /// Whether `limits` allow the storage buffers and textures the path binds
+    fn within(limits: &wgpu::Limits) -> bool {
+        limits.max_storage_buffers_per_shader_stage >= 6
+            && limits.max_storage_textures_per_shader_stage >= 2
+    }
 
-        // Create wgpu instance
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            #[cfg(not(target_arch = "wasm32"))]
-            backends: wgpu::Backends::PRIMARY,
-            #[cfg(target_arch = "wasm32")]
-            backends: wgpu::Backends::BROWSER_WEBGPU,
-            ..Default::default()
+    /// Add the raymarch, denoise and display passes to the graph, returning
+    /// the HDR target between them and the denoiser's textures
+    fn declare(
+        graph: &mut RenderGraphBuilder<Stage>,
+        geometry: GeometryTargets,
+    ) -> (TextureId, DenoiseTargets) {
+        let hdr_target = graph.texture(TextureDesc {
+            label: "HDR Target",
+            format: wgpu::TextureFormat::Rgba16Float,
+            // Copied out for HDR stills
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            scale: 1.0,
         });
+        let denoise = DenoiseTargets::declare(graph);
+        graph.pass(
+            Stage::Raymarch,
+            &geometry.slots(),
+            &[Slot::Texture(hdr_target), Slot::Texture(denoise.guide)],
+        );
+        graph.pass(
+            Stage::Denoise,
+            &[Slot::Texture(hdr_target), Slot::Texture(denoise.guide)],
+            &[Slot::Texture(hdr_target), Slot::Texture(denoise.scratch)],
+        );
+        graph.pass(
+            Stage::Display,
+            &[Slot::Texture(hdr_target)],
+            &[Slot::Surface],
+        );
+        (hdr_target, denoise)
+    }
 
-        // Create surface
-        let surface = instance.create_surface(window).unwrap();
-
-        // Request adapter
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .expect("Failed to find an appropriate adapter");
-
-        // Request device and queue
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: Some("Device"),
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::downlevel_webgl2_defaults()
-                        .using_resolution(adapter.limits()),
-                    memory_hints: Default::default(),
-                },
-                None,
-            )
-            .await
-            .expect("Failed to create device");
-
-        // Configure surface
-        let surface_caps = surface.get_capabilities(&adapter);
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .find(|f| f.is_srgb())
-            .copied()
-            .unwrap_or(surface_caps.formats[0]);
-
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width,
-            height,
-            present_mode: wgpu::PresentMode::AutoVsync,
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
-        };
-        surface.configure(&device, &config);
-
-        // Create storage texture for compute output
-        let (storage_texture, storage_texture_view) =
-            Self::create_storage_texture(&device, width, height);
-
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        graph: &RenderGraph<Stage>,
+        hdr_target: TextureId,
+        denoise_targets: DenoiseTargets,
+        geometry: GeometryTargets,
+        frame_uniform_buffers: &[wgpu::Buffer],
+        raymarch_params_buffer: &wgpu::Buffer,
+        views: &WorldViews,
+        world: &HoneycombWorld,
+        pipeline_cache: Option<wgpu::PipelineCache>,
+        workgroup: WorkgroupSize,
+    ) -> Result<Self, ShaderError> {
         // Create sampler for display
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("Display Sampler"),
@@ -216,44 +2394,6 @@ impl GpuState {
             ..Default::default()
         });
 
-        // Create uniform buffers
-        let frame_uniforms = FrameUniforms {
-            view_proj: glam::Mat4::IDENTITY,
-            inv_view_proj: glam::Mat4::IDENTITY,
-            camera_position: Vec3::ZERO,
-            time: 0.0,
-            resolution: [width as f32, height as f32],
-            near: 0.1,
-            far: 100.0,
-        };
-
-        let frame_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Frame Uniforms Buffer"),
-            contents: bytemuck::cast_slice(&[frame_uniforms]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let raymarch_params = RaymarchParams {
-            volume_min: VOLUME_MIN,
-            _pad0: 0.0,
-            volume_max: VOLUME_MAX,
-            _pad1: 0.0,
-            max_steps: MAX_STEPS,
-            step_size: STEP_SIZE,
-            membrane_thickness: MEMBRANE_THICKNESS,
-            membrane_glow: MEMBRANE_GLOW,
-            density_multiplier: 1.0,
-            enable_coupling: 1.0,
-            palette: 0,
-            _pad2: 0,
-        };
-
-        let raymarch_params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Raymarch Params Buffer"),
-            contents: bytemuck::cast_slice(&[raymarch_params]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
         // Create storage buffers for world data
         let phases_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Phases Buffer"),
@@ -264,18 +2404,50 @@ impl GpuState {
         let cells_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Cells Buffer"),
             contents: bytemuck::cast_slice(&world.cells),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let membranes_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Membrane Pairs Buffer"),
+            contents: bytemuck::cast_slice(&membrane::pair_table(&world.phases)),
             usage: wgpu::BufferUsages::STORAGE,
         });
 
-        // Load shaders
-        let compute_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Honeycomb Compute Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/honeycomb.wgsl").into()),
+        let lights_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Emitter Lights Buffer"),
+            contents: bytemuck::cast_slice(&[EmitterLights::new(&world.emitters)]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        // Always bound, so the layout is the same either way, but only walked
+        // by worlds large enough to gain from it
+        let use_bvh = world.cells.len() >= bvh::MIN_CELLS;
+        let bvh_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("BVH Buffer"),
+            contents: bytemuck::cast_slice(&SeedBvh::build(&world.cells).nodes),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
         });
+        tracing::info!(
+            "Voronoi lookups {}",
+            if use_bvh {
+                "walk the seed hierarchy"
+            } else {
+                "test every seed"
+            }
+        );
+
+        // Half precision where the adapter has it, f32 otherwise
+        let use_f16 = device.features().contains(wgpu::Features::SHADER_F16);
+        tracing::info!(
+            "Raymarch composites in {}",
+            if use_f16 { "f16" } else { "f32" }
+        );
 
+        // Load shaders
+        let display_source = crate::shader::preprocess("display.wgsl", &[])?;
         let display_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Display Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/display.wgsl").into()),
+            source: wgpu::ShaderSource::Wgsl(display_source.into()),
         });
 
         // Create bind group layouts for compute pipeline
@@ -322,12 +2494,13 @@ impl GpuState {
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Storage { read_only: true },
                             has_dynamic_offset: false,
-                            min_binding_size: Some(
-                                std::num::NonZeroU64::new(
-                                    std::mem::size_of::<VendekPhase>() as u64
-                                )
-                                .unwrap(),
-                            ),
+                            min_binding_size:
+                                Some(
+                                    std::num::NonZeroU64::new(
+                                        std::mem::size_of::<VendekPhase>() as u64
+                                    )
+                                    .unwrap(),
+                                ),
                         },
                         count: None,
                     },
@@ -347,9 +2520,90 @@ impl GpuState {
                         },
                         count: None,
                     },
+                    // Palette colors
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    // Transfer functions
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    // Cell states
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    // Hierarchy over the seeds
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 7,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<BvhNode>() as u64
+                            ),
+                        },
+                        count: None,
+                    },
+                    // Membrane look for each pair of phases
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 8,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<MembranePair>() as u64,
+                            ),
+                        },
+                        count: None,
+                    },
+                    // Emitting cells
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 9,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<EmitterLights>() as u64,
+                            ),
+                        },
+                        count: None,
+                    },
+                    // Spatial grid occupancy
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 10,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: GRID_BINDING,
+                        count: None,
+                    },
                 ],
             });
 
+        let [geometry_color_entry, geometry_depth_entry] =
+            GeometryTargets::layout_entries(1, wgpu::ShaderStages::COMPUTE);
         let compute_bind_group_layout_1 =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("Compute Bind Group Layout 1"),
@@ -365,58 +2619,129 @@ impl GpuState {
                         },
                         count: None,
                     },
+                    // Rasterized geometry
+                    geometry_color_entry,
+                    geometry_depth_entry,
+                    // Samples accumulated per pixel
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // The denoiser's guide
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: wgpu::TextureFormat::Rgba16Float,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
                 ],
             });
 
-        // Create compute bind groups
-        let compute_bind_group_0 = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Compute Bind Group 0"),
-            layout: &compute_bind_group_layout_0,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: frame_uniform_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: raymarch_params_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: phases_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 3,
-                    resource: cells_buffer.as_entire_binding(),
-                },
-            ],
-        });
+        let mut tiles = TileCulling::new(
+            device,
+            &compute_bind_group_layout_0,
+            pipeline_cache.as_ref(),
+            workgroup,
+        )?;
+        let shadow = ShadowMap::new(
+            device,
+            &compute_bind_group_layout_0,
+            pipeline_cache.as_ref(),
+            use_bvh,
+        )?;
 
-        let compute_bind_group_1 = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Compute Bind Group 1"),
-            layout: &compute_bind_group_layout_1,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(&storage_texture_view),
-            }],
-        });
+        // Create compute bind groups
+        let compute_bind_groups_0 = frame_uniform_buffers
+            .iter()
+            .map(|frame_uniform_buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Compute Bind Group 0"),
+                    layout: &compute_bind_group_layout_0,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: frame_uniform_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: raymarch_params_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: phases_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: cells_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 4,
+                            resource: wgpu::BindingResource::TextureView(&views.palette),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 5,
+                            resource: wgpu::BindingResource::TextureView(&views.transfer),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 6,
+                            resource: wgpu::BindingResource::TextureView(&views.cell_state),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 7,
+                            resource: bvh_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 8,
+                            resource: membranes_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 9,
+                            resource: lights_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 10,
+                            resource: wgpu::BindingResource::TextureView(&views.grid),
+                        },
+                    ],
+                })
+            })
+            .collect();
 
         // Create compute pipeline
         let compute_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Compute Pipeline Layout"),
-                bind_group_layouts: &[&compute_bind_group_layout_0, &compute_bind_group_layout_1],
+                bind_group_layouts: &[
+                    &compute_bind_group_layout_0,
+                    &compute_bind_group_layout_1,
+                    tiles.list_layout(),
+                    shadow.read_layout(),
+                ],
                 push_constant_ranges: &[],
             });
 
-        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Compute Pipeline"),
-            layout: Some(&compute_pipeline_layout),
-            module: &compute_shader,
-            entry_point: Some("main"),
-            compilation_options: Default::default(),
-            cache: None,
-        });
+        let initial = ShaderFeatures::for_params(&RuntimeParams::default());
+        let compute_pipelines = Permutations::new(initial, |features| {
+            Self::create_compute_pipeline(
+                device,
+                &compute_pipeline_layout,
+                pipeline_cache.as_ref(),
+                features,
+                use_bvh,
+                use_f16,
+                workgroup,
+            )
+        })?;
 
         // Create render bind group layout
         let render_bind_group_layout =
@@ -442,29 +2767,57 @@ impl GpuState {
                 ],
             });
 
-        // Create render bind group - use a separate texture view for sampling
-        let sample_texture_view =
-            storage_texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Render Bind Group"),
-            layout: &render_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
+        // The parameters, for the display pass to tell debug views from the
+        // image
+        let display_params_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Display Params Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&sample_texture_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
-                },
-            ],
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let display_params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Display Params Bind Group"),
+            layout: &display_params_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: raymarch_params_buffer.as_entire_binding(),
+            }],
         });
 
+        // Bind groups for the graph's targets, rebuilt whenever it resizes them
+        let (compute_bind_group_1, render_bind_group) = Self::create_target_bind_groups(
+            device,
+            &compute_bind_group_layout_1,
+            &render_bind_group_layout,
+            &sampler,
+            graph,
+            hdr_target,
+            denoise_targets.guide,
+            geometry,
+        );
+        tiles.bind_targets(
+            device,
+            graph.view(hdr_target, Access::Storage),
+            graph.view(denoise_targets.guide, Access::Storage),
+            graph.texture(hdr_target).size(),
+            frame_uniform_buffers.len(),
+        );
+        let mut denoiser = Denoiser::new(device, denoise_targets, pipeline_cache.as_ref())?;
+        denoiser.bind_targets(device, graph, hdr_target);
+
         // Create render pipeline
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&render_bind_group_layout],
+                bind_group_layouts: &[&render_bind_group_layout, &display_params_layout],
                 push_constant_ranges: &[],
             });
 
@@ -503,219 +2856,566 @@ impl GpuState {
                 alpha_to_coverage_enabled: false,
             },
             multiview: None,
-            cache: None,
+            cache: pipeline_cache.as_ref(),
         });
 
-        Self {
-            surface,
-            device,
-            queue,
-            config,
-            size: winit::dpi::PhysicalSize::new(width, height),
-            compute_pipeline,
-            compute_bind_group_0,
+        Ok(Self {
+            compute_pipelines,
+            compute_pipeline_layout,
+            pipeline_cache,
+            compute_bind_groups_0,
             compute_bind_group_1,
             compute_bind_group_layout_1,
+            tiles,
+            workgroup,
+            shadow,
+            cells_buffer,
+            bvh_buffer,
+            use_bvh,
+            use_f16,
             render_pipeline,
             render_bind_group,
             render_bind_group_layout,
-            frame_uniform_buffer,
-            raymarch_params_buffer,
-            storage_texture,
-            storage_texture_view,
+            display_params_bind_group,
+            hdr_target,
+            denoiser,
+            geometry,
             sampler,
+        })
+    }
+
+    fn create_compute_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        cache: Option<&wgpu::PipelineCache>,
+        features: ShaderFeatures,
+        use_bvh: bool,
+        use_f16: bool,
+        workgroup: WorkgroupSize,
+    ) -> Result<wgpu::ComputePipeline, ShaderError> {
+        let mut defines = features.defines();
+        defines.push("SHADOWS");
+        if use_bvh {
+            defines.push("BVH");
+        }
+        if use_f16 {
+            defines.push("F16");
+        }
+        let source = crate::shader::preprocess("honeycomb.wgsl", &defines)?;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&format!("Honeycomb Compute Shader ({})", features.label())),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        Ok(
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(&format!(
+                    "Compute Pipeline ({}, {})",
+                    features.label(),
+                    workgroup
+                )),
+                layout: Some(layout),
+                module: &shader,
+                entry_point: Some("main"),
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &workgroup.constants(),
+                    ..Default::default()
+                },
+                cache,
+            }),
+        )
+    }
+
+    /// Returns true if a new pipeline had to be built
+    fn select_features(&mut self, device: &wgpu::Device, features: ShaderFeatures) -> bool {
+        let layout = &self.compute_pipeline_layout;
+        let cache = self.pipeline_cache.as_ref();
+        let use_bvh = self.use_bvh;
+        let use_f16 = self.use_f16;
+        let workgroup = self.workgroup;
+        self.compute_pipelines.select(features, |features| {
+            Self::create_compute_pipeline(
+                device, layout, cache, features, use_bvh, use_f16, workgroup,
+            )
+        })
+    }
+
+    /// Raymarch in `workgroup`-sized tiles, rebuilding the pipeline for the
+    /// current features. Permutations built for the old size are dropped.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn set_workgroup(
+        &mut self,
+        device: &wgpu::Device,
+        workgroup: WorkgroupSize,
+    ) -> Result<(), ShaderError> {
+        if workgroup == self.workgroup {
+            return Ok(());
         }
+        let cache = self.pipeline_cache.as_ref();
+        self.compute_pipelines =
+            Permutations::new(self.compute_pipelines.features(), |features| {
+                Self::create_compute_pipeline(
+                    device,
+                    &self.compute_pipeline_layout,
+                    cache,
+                    features,
+                    self.use_bvh,
+                    self.use_f16,
+                    workgroup,
+                )
+            })?;
+        self.tiles.set_workgroup(device, cache, workgroup);
+        self.workgroup = workgroup;
+        Ok(())
     }
 
-    fn create_storage_texture(
+    #[allow(clippy::too_many_arguments)]
+    fn create_target_bind_groups(
         device: &wgpu::Device,
-        width: u32,
-        height: u32,
-    ) -> (wgpu::Texture, wgpu::TextureView) {
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Storage Texture"),
-            size: wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
+        compute_layout: &wgpu::BindGroupLayout,
+        render_layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        graph: &RenderGraph<Stage>,
+        hdr_target: TextureId,
+        guide: TextureId,
+        geometry: GeometryTargets,
+    ) -> (wgpu::BindGroup, wgpu::BindGroup) {
+        // The raymarch writes the HDR target through its storage view and the
+        // display pass samples it through another
+        let [geometry_color, geometry_depth] = geometry.bind_group_entries(graph, 1);
+        // A mean and a variance per pixel of the target. One too large to
+        // bind is left at a single pixel, which the shader takes as having
+        // no accumulation.
+        let size = graph.texture(hdr_target).size();
+        let accumulation_size = u64::from(size.width * size.height) * ACCUMULATION_TEXEL;
+        let accumulation = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Accumulation Buffer"),
+            size: if accumulation_size <= u64::from(device.limits().max_storage_buffer_binding_size)
+            {
+                accumulation_size
+            } else {
+                ACCUMULATION_TEXEL
             },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba16Float,
-            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
         });
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        (texture, view)
+        let compute_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Compute Bind Group 1"),
+            layout: compute_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(
+                        graph.view(hdr_target, Access::Storage),
+                    ),
+                },
+                geometry_color,
+                geometry_depth,
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: accumulation.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(
+                        graph.view(guide, Access::Storage),
+                    ),
+                },
+            ],
+        });
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Render Bind Group"),
+            layout: render_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(
+                        graph.view(hdr_target, Access::Sampled),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        });
+        (compute_bind_group, render_bind_group)
     }
 
-    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        let mut width = new_size.width;
-        let mut height = new_size.height;
+    /// Point the bind groups at the graph's current targets
+    fn bind_targets(&mut self, device: &wgpu::Device, graph: &RenderGraph<Stage>) {
+        (self.compute_bind_group_1, self.render_bind_group) = Self::create_target_bind_groups(
+            device,
+            &self.compute_bind_group_layout_1,
+            &self.render_bind_group_layout,
+            &self.sampler,
+            graph,
+            self.hdr_target,
+            self.denoiser.targets().guide,
+            self.geometry,
+        );
+        self.tiles.bind_targets(
+            device,
+            graph.view(self.hdr_target, Access::Storage),
+            graph.view(self.denoiser.targets().guide, Access::Storage),
+            graph.texture(self.hdr_target).size(),
+            self.compute_bind_groups_0.len(),
+        );
+        self.denoiser.bind_targets(device, graph, self.hdr_target);
+    }
 
-        // On WASM, resize can be called with tiny values
-        #[cfg(target_arch = "wasm32")]
+    /// Workgroups needed to cover the HDR target, the most the raymarch
+    /// dispatches when no tile is culled
+    fn dispatch_size(&self, graph: &RenderGraph<Stage>) -> (u32, u32) {
+        let size = graph.texture(self.hdr_target).size();
+        let (width, height) = self.workgroup.extent();
+        (size.width.div_ceil(width), size.height.div_ceil(height))
+    }
+
+    /// Cull each view's tiles, then dispatch the raymarch into the HDR
+    /// target over the tiles left, one dispatch per view. The shadow map is
+    /// brought up to date first.
+    fn encode_raymarch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        viewports: &[Viewport],
+        timestamp_writes: Option<wgpu::ComputePassTimestampWrites>,
+    ) {
+        self.tiles.clear(encoder);
+        self.shadow.update(encoder, &self.compute_bind_groups_0[0]);
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Compute Pass"),
+            timestamp_writes,
+        });
+
+        for (index, (viewport, bind_group)) in viewports
+            .iter()
+            .zip(&self.compute_bind_groups_0)
+            .enumerate()
         {
-            if width < 100 || height < 100 {
-                let web_window = web_sys::window().unwrap();
-                width = web_window.inner_width().unwrap().as_f64().unwrap() as u32;
-                height = web_window.inner_height().unwrap().as_f64().unwrap() as u32;
-            }
-            web_sys::console::log_1(&format!(
-                "Resize called: input {}x{}, using {}x{}",
-                new_size.width, new_size.height, width, height
-            ).into());
+            self.tiles.cull(
+                &mut compute_pass,
+                index,
+                bind_group,
+                viewport.width,
+                viewport.height,
+            );
+            compute_pass.set_pipeline(self.compute_pipelines.current());
+            compute_pass.set_bind_group(0, bind_group, &[]);
+            compute_pass.set_bind_group(1, &self.compute_bind_group_1, &[]);
+            self.shadow.bind(&mut compute_pass, 3);
+            self.tiles.dispatch(&mut compute_pass, index, 2);
         }
+    }
+}
 
-        if width > 0 && height > 0 {
-            self.size = winit::dpi::PhysicalSize::new(width, height);
-            self.config.width = width;
-            self.config.height = height;
-            self.surface.configure(&self.device, &self.config);
-
-            // Recreate storage texture
-            let (storage_texture, storage_texture_view) =
-                Self::create_storage_texture(&self.device, width, height);
-            self.storage_texture = storage_texture;
-            self.storage_texture_view = storage_texture_view;
-
-            // Recreate compute bind group 1
-            self.compute_bind_group_1 = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Compute Bind Group 1"),
-                layout: &self.compute_bind_group_layout_1,
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&self.storage_texture_view),
-                }],
-            });
+struct FragmentPath {
+    pipelines: Permutations<wgpu::RenderPipeline>,
+    pipeline_layout: wgpu::PipelineLayout,
+    surface_format: wgpu::TextureFormat,
+    /// Constants prepended to the shader: the world's cell, phase and phase
+    /// pair counts
+    prelude: String,
+    pipeline_cache: Option<wgpu::PipelineCache>,
+    // One per view, differing only in frame uniforms
+    bind_groups: Vec<wgpu::BindGroup>,
+    // Phases, membrane pairs, then cells; the cells start at `cells_offset`
+    // bytes and are rewritten when they change phase
+    world_buffer: wgpu::Buffer,
+    cells_offset: wgpu::BufferAddress,
+    // Rasterized geometry the raymarch composites, rebound when the graph
+    // resizes it
+    geometry: GeometryTargets,
+    geometry_bind_group_layout: wgpu::BindGroupLayout,
+    geometry_bind_group: wgpu::BindGroup,
+}
 
-            // Recreate render bind group
-            let sample_texture_view = self
-                .storage_texture
-                .create_view(&wgpu::TextureViewDescriptor::default());
-            self.render_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Render Bind Group"),
-                layout: &self.render_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&sample_texture_view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&self.sampler),
-                    },
-                ],
-            });
-        }
+impl FragmentPath {
+    /// Add the raymarch pass, which draws straight into the surface
+    fn declare(graph: &mut RenderGraphBuilder<Stage>, geometry: GeometryTargets) {
+        graph.pass(Stage::Raymarch, &geometry.slots(), &[Slot::Surface]);
     }
 
-    pub fn render(&mut self, camera: &Camera, time: f32) -> Result<(), wgpu::SurfaceError> {
-        // Read runtime parameters from JavaScript
-        let runtime_params = read_js_params();
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        graph: &RenderGraph<Stage>,
+        geometry: GeometryTargets,
+        frame_uniform_buffers: &[wgpu::Buffer],
+        raymarch_params_buffer: &wgpu::Buffer,
+        views: &WorldViews,
+        world: &HoneycombWorld,
+        pipeline_cache: Option<wgpu::PipelineCache>,
+    ) -> Result<Self, GpuError> {
+        // Without storage buffers the whole world goes in one uniform buffer,
+        // phases and the membrane pairs first, sized exactly by the counts
+        // baked into the shader
+        let mut world_data = Vec::new();
+        world_data.extend_from_slice(bytemuck::cast_slice(&world.phases));
+        world_data.extend_from_slice(bytemuck::cast_slice(&membrane::pair_table(&world.phases)));
+        let cells_offset = world_data.len() as wgpu::BufferAddress;
+        world_data.extend_from_slice(bytemuck::cast_slice(&world.cells));
+
+        let allowed = device.limits().max_uniform_buffer_binding_size as usize;
+        if world_data.len() > allowed {
+            return Err(GpuError::WorldTooLarge {
+                needed: world_data.len(),
+                allowed,
+            });
+        }
 
-        // Update frame uniforms
-        let aspect = self.size.width as f32 / self.size.height as f32;
-        let view = camera.view_matrix();
-        let proj = camera.projection_matrix(aspect);
-        let view_proj = proj * view;
-        let inv_view_proj = view_proj.inverse();
+        let world_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("World Uniforms Buffer"),
+            contents: &world_data,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
 
-        let frame_uniforms = FrameUniforms {
-            view_proj,
-            inv_view_proj,
-            camera_position: camera.position(),
-            time,
-            resolution: [self.size.width as f32, self.size.height as f32],
-            near: camera.near,
-            far: camera.far,
-        };
+        let lights_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Emitter Lights Buffer"),
+            contents: bytemuck::cast_slice(&[EmitterLights::new(&world.emitters)]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
 
-        self.queue.write_buffer(
-            &self.frame_uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[frame_uniforms]),
+        let prelude = format!(
+            "const CELL_COUNT: u32 = {}u;\nconst PHASE_COUNT: u32 = {}u;\nconst PAIR_COUNT: u32 = {}u;\n",
+            world.cells.len(),
+            world.phases.len(),
+            world.phases.len() * world.phases.len(),
         );
 
-        // Update raymarch params with runtime values
-        let raymarch_params = RaymarchParams {
-            volume_min: VOLUME_MIN,
-            _pad0: 0.0,
-            volume_max: VOLUME_MAX,
-            _pad1: 0.0,
-            max_steps: runtime_params.max_steps,
-            step_size: runtime_params.step_size,
-            membrane_thickness: runtime_params.membrane_thickness,
-            membrane_glow: runtime_params.membrane_glow,
-            density_multiplier: runtime_params.density,
-            enable_coupling: if runtime_params.enable_coupling { 1.0 } else { 0.0 },
-            palette: runtime_params.palette,
-            _pad2: 0,
+        let uniform_entry = |binding: u32, size: usize| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: Some(std::num::NonZeroU64::new(size as u64).unwrap()),
+            },
+            count: None,
+        };
+        // Palette colors, transfer functions and cell states
+        let texture_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
         };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Fragment Raymarch Bind Group Layout"),
+            entries: &[
+                uniform_entry(0, std::mem::size_of::<FrameUniforms>()),
+                uniform_entry(1, std::mem::size_of::<RaymarchParams>()),
+                uniform_entry(2, world_data.len()),
+                texture_entry(3),
+                texture_entry(4),
+                texture_entry(5),
+                uniform_entry(6, std::mem::size_of::<EmitterLights>()),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: GRID_BINDING,
+                    count: None,
+                },
+            ],
+        });
 
-        self.queue.write_buffer(
-            &self.raymarch_params_buffer,
-            0,
-            bytemuck::cast_slice(&[raymarch_params]),
+        let bind_groups = frame_uniform_buffers
+            .iter()
+            .map(|frame_uniform_buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Fragment Raymarch Bind Group"),
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: frame_uniform_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: raymarch_params_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: world_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: wgpu::BindingResource::TextureView(&views.palette),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 4,
+                            resource: wgpu::BindingResource::TextureView(&views.transfer),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 5,
+                            resource: wgpu::BindingResource::TextureView(&views.cell_state),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 6,
+                            resource: lights_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 7,
+                            resource: wgpu::BindingResource::TextureView(&views.grid),
+                        },
+                    ],
+                })
+            })
+            .collect();
+
+        let geometry_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Fragment Geometry Bind Group Layout"),
+                entries: &GeometryTargets::layout_entries(0, wgpu::ShaderStages::FRAGMENT),
+            });
+        let geometry_bind_group =
+            Self::create_geometry_bind_group(device, &geometry_bind_group_layout, graph, geometry);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Fragment Raymarch Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, &geometry_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let initial = ShaderFeatures::for_params(&RuntimeParams::default());
+        let pipelines = Permutations::new(initial, |features| {
+            Self::create_pipeline(
+                device,
+                &pipeline_layout,
+                surface_format,
+                &prelude,
+                pipeline_cache.as_ref(),
+                features,
+            )
+        })?;
+
+        Ok(Self {
+            pipelines,
+            pipeline_layout,
+            surface_format,
+            prelude,
+            pipeline_cache,
+            bind_groups,
+            world_buffer,
+            cells_offset,
+            geometry,
+            geometry_bind_group_layout,
+            geometry_bind_group,
+        })
+    }
+
+    fn create_geometry_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        graph: &RenderGraph<Stage>,
+        geometry: GeometryTargets,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Fragment Geometry Bind Group"),
+            layout,
+            entries: &geometry.bind_group_entries(graph, 0),
+        })
+    }
+
+    /// Point the geometry bind group at the graph's current targets
+    fn bind_targets(&mut self, device: &wgpu::Device, graph: &RenderGraph<Stage>) {
+        self.geometry_bind_group = Self::create_geometry_bind_group(
+            device,
+            &self.geometry_bind_group_layout,
+            graph,
+            self.geometry,
         );
+    }
 
-        // Get output texture
-        let output = self.surface.get_current_texture()?;
-        let output_view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        surface_format: wgpu::TextureFormat,
+        prelude: &str,
+        cache: Option<&wgpu::PipelineCache>,
+        features: ShaderFeatures,
+    ) -> Result<wgpu::RenderPipeline, ShaderError> {
+        let source = prelude.to_string()
+            + crate::shader::preprocess("honeycomb_fragment.wgsl", &features.defines())?.as_str();
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&format!("Honeycomb Fragment Shader ({})", features.label())),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
 
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
+        Ok(
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(&format!(
+                    "Fragment Raymarch Pipeline ({})",
+                    features.label()
+                )),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: surface_format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache,
+            }),
+        )
+    }
 
-        // Compute pass
-        {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Compute Pass"),
-                timestamp_writes: None,
-            });
-            compute_pass.set_pipeline(&self.compute_pipeline);
-            compute_pass.set_bind_group(0, &self.compute_bind_group_0, &[]);
-            compute_pass.set_bind_group(1, &self.compute_bind_group_1, &[]);
+    /// Returns true if a new pipeline had to be built
+    fn select_features(&mut self, device: &wgpu::Device, features: ShaderFeatures) -> bool {
+        let (layout, format, prelude) = (&self.pipeline_layout, self.surface_format, &self.prelude);
+        let cache = self.pipeline_cache.as_ref();
+        self.pipelines.select(features, |features| {
+            Self::create_pipeline(device, layout, format, prelude, cache, features)
+        })
+    }
+}
 
-            let workgroups_x = (self.size.width + 7) / 8;
-            let workgroups_y = (self.size.height + 7) / 8;
-            compute_pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
-        }
+/// A captured frame copied into a readback buffer, waiting to be mapped
+struct PendingCapture {
+    buffer: wgpu::Buffer,
+    height: u32,
+    unpadded_row: u32,
+    padded_row: u32,
+    /// Swapchain format stores blue first; swizzle to RGBA on read
+    bgra: bool,
+}
 
-        // Render pass
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &output_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.02,
-                            g: 0.02,
-                            b: 0.03,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.render_bind_group, &[]);
-            render_pass.draw(0..3, 0..1);
+impl PendingCapture {
+    /// Strip row padding and swizzle to RGBA. The buffer must already be mapped.
+    fn read(&self) -> Vec<u8> {
+        let mapped = self.buffer.slice(..).get_mapped_range();
+        let mut pixels = Vec::with_capacity((self.unpadded_row * self.height) as usize);
+        for row in mapped.chunks(self.padded_row as usize) {
+            pixels.extend_from_slice(&row[..self.unpadded_row as usize]);
         }
+        drop(mapped);
+        self.buffer.unmap();
 
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        if self.bgra {
+            for pixel in pixels.as_chunks_mut::<4>().0 {
+                pixel.swap(0, 2);
+            }
+        }
 
-        Ok(())
+        pixels
     }
 }
@@ -0,0 +1,286 @@
+//! A small frame graph. Passes declare the textures they read and write; the
+//! graph creates those textures, recreates them when the surface is resized,
+//! and orders the passes so every texture is written before it is read.
+//!
+//! Encoding stays with whoever owns the pass: the graph only hands back pass
+//! ids in execution order, along with the textures they use.
+//!
+//! Each texture has a view per way it is accessed, created with only that
+//! usage, so a bind group or attachment states which access it means and
+//! wgpu can validate it and place the barriers between passes. Textures
+//! are checked against the passes that use them when the graph is built.
+
+/// A texture owned by the graph
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureId(usize);
+
+/// Something a pass reads or writes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Slot {
+    Texture(TextureId),
+    /// The frame being presented, or the capture target when re-rendering
+    /// for a screenshot
+    Surface,
+}
+
+/// How a pass accesses a texture, each through its own view
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// Bound as a texture to sample or load from
+    Sampled,
+    /// Bound as a storage texture a compute shader writes
+    Storage,
+    /// Drawn into as a color or depth attachment
+    Attachment,
+}
+
+impl Access {
+    const ALL: [Access; 3] = [Access::Sampled, Access::Storage, Access::Attachment];
+
+    fn usage(self) -> wgpu::TextureUsages {
+        match self {
+            Access::Sampled => wgpu::TextureUsages::TEXTURE_BINDING,
+            Access::Storage => wgpu::TextureUsages::STORAGE_BINDING,
+            Access::Attachment => wgpu::TextureUsages::RENDER_ATTACHMENT,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TextureDesc {
+    pub label: &'static str,
+    pub format: wgpu::TextureFormat,
+    pub usage: wgpu::TextureUsages,
+    /// Size relative to the surface
+    pub scale: f32,
+}
+
+struct Node<P> {
+    pass: P,
+    reads: Vec<Slot>,
+    writes: Vec<Slot>,
+}
+
+/// Collects textures and passes for [`RenderGraph`]
+pub struct RenderGraphBuilder<P> {
+    textures: Vec<TextureDesc>,
+    nodes: Vec<Node<P>>,
+}
+
+impl<P: Copy + PartialEq + std::fmt::Debug> RenderGraphBuilder<P> {
+    pub fn new() -> Self {
+        Self {
+            textures: Vec::new(),
+            nodes: Vec::new(),
+        }
+    }
+
+    pub fn texture(&mut self, desc: TextureDesc) -> TextureId {
+        self.textures.push(desc);
+        TextureId(self.textures.len() - 1)
+    }
+
+    /// Declare a pass. Passes that read and write the same slot, like an
+    /// overlay drawn over the surface, run in the order they were declared.
+    pub fn pass(&mut self, pass: P, reads: &[Slot], writes: &[Slot]) {
+        self.nodes.push(Node {
+            pass,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+        });
+    }
+
+    /// Order the passes and create their textures at `width` x `height`.
+    /// Panics if the passes depend on each other in a cycle, or use a
+    /// texture in a way its usage doesn't allow.
+    pub fn build(self, device: &wgpu::Device, width: u32, height: u32) -> RenderGraph<P> {
+        self.check_usage();
+        let count = self.nodes.len();
+        // `a` must run before `b` when it writes something `b` reads, unless
+        // both write it and `b` came first
+        let before = |a: usize, b: usize| {
+            a != b
+                && self.nodes[a].writes.iter().any(|slot| {
+                    self.nodes[b].reads.contains(slot)
+                        && (a < b || !self.nodes[b].writes.contains(slot))
+                })
+        };
+
+        // Kahn's algorithm, taking the earliest declared ready pass each step
+        let mut pending: Vec<usize> = (0..count)
+            .map(|b| (0..count).filter(|&a| before(a, b)).count())
+            .collect();
+        let mut done = vec![false; count];
+        let mut order = Vec::with_capacity(count);
+        while order.len() < count {
+            let Some(next) = (0..count).find(|&i| !done[i] && pending[i] == 0) else {
+                let stuck: Vec<P> = (0..count)
+                    .filter(|&i| !done[i])
+                    .map(|i| self.nodes[i].pass)
+                    .collect();
+                panic!("render graph has a cycle between {:?}", stuck);
+            };
+            done[next] = true;
+            order.push(next);
+            for (b, remaining) in pending.iter_mut().enumerate() {
+                if before(next, b) {
+                    *remaining -= 1;
+                }
+            }
+        }
+
+        let mut nodes: Vec<Option<Node<P>>> = self.nodes.into_iter().map(Some).collect();
+        RenderGraph {
+            textures: self
+                .textures
+                .into_iter()
+                .map(|desc| GraphTexture::new(device, desc, width, height))
+                .collect(),
+            nodes: order.into_iter().filter_map(|i| nodes[i].take()).collect(),
+        }
+    }
+}
+
+impl<P: std::fmt::Debug> RenderGraphBuilder<P> {
+    /// Panic unless every texture a pass reads can be sampled and every
+    /// texture it writes can be a storage texture or an attachment
+    fn check_usage(&self) {
+        let writable =
+            wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT;
+        for node in &self.nodes {
+            let textures = |slots: &[Slot]| -> Vec<TextureId> {
+                slots
+                    .iter()
+                    .filter_map(|slot| match slot {
+                        Slot::Texture(id) => Some(*id),
+                        Slot::Surface => None,
+                    })
+                    .collect()
+            };
+            for id in textures(&node.reads) {
+                let desc = &self.textures[id.0];
+                assert!(
+                    desc.usage.contains(wgpu::TextureUsages::TEXTURE_BINDING),
+                    "{:?} reads {:?}, which can't be sampled",
+                    node.pass,
+                    desc.label
+                );
+            }
+            for id in textures(&node.writes) {
+                let desc = &self.textures[id.0];
+                assert!(
+                    desc.usage.intersects(writable),
+                    "{:?} writes {:?}, which is neither a storage texture nor an attachment",
+                    node.pass,
+                    desc.label
+                );
+            }
+        }
+    }
+}
+
+impl<P: Copy + PartialEq + std::fmt::Debug> Default for RenderGraphBuilder<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct GraphTexture {
+    desc: TextureDesc,
+    texture: wgpu::Texture,
+    /// A view for each `Access` the usage allows, in `Access::ALL` order
+    views: [Option<wgpu::TextureView>; 3],
+}
+
+impl GraphTexture {
+    fn new(device: &wgpu::Device, desc: TextureDesc, width: u32, height: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(desc.label),
+            size: Self::extent(&desc, width, height),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: desc.format,
+            usage: desc.usage,
+            view_formats: &[],
+        });
+        let views = Access::ALL.map(|access| {
+            desc.usage.contains(access.usage()).then(|| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some(&format!("{} ({:?})", desc.label, access)),
+                    usage: Some(access.usage()),
+                    ..Default::default()
+                })
+            })
+        });
+        Self {
+            desc,
+            texture,
+            views,
+        }
+    }
+
+    /// Size of the texture for a `width` x `height` surface
+    fn extent(desc: &TextureDesc, width: u32, height: u32) -> wgpu::Extent3d {
+        let scaled = |extent: u32| ((extent as f32 * desc.scale).round() as u32).max(1);
+        wgpu::Extent3d {
+            width: scaled(width),
+            height: scaled(height),
+            depth_or_array_layers: 1,
+        }
+    }
+}
+
+/// Passes in execution order and the textures between them
+pub struct RenderGraph<P> {
+    textures: Vec<GraphTexture>,
+    nodes: Vec<Node<P>>,
+}
+
+impl<P: Copy + PartialEq> RenderGraph<P> {
+    /// Every pass, in execution order
+    pub fn passes(&self) -> impl Iterator<Item = P> + '_ {
+        self.nodes.iter().map(|node| node.pass)
+    }
+
+    /// Passes that draw into the surface, in execution order
+    pub fn surface_passes(&self) -> impl Iterator<Item = P> + '_ {
+        self.nodes
+            .iter()
+            .filter(|node| node.writes.contains(&Slot::Surface))
+            .map(|node| node.pass)
+    }
+
+    pub fn texture(&self, id: TextureId) -> &wgpu::Texture {
+        &self.textures[id.0].texture
+    }
+
+    /// The view of a texture for one kind of access. Panics if the texture
+    /// wasn't declared with the usage it needs.
+    pub fn view(&self, id: TextureId, access: Access) -> &wgpu::TextureView {
+        let slot = &self.textures[id.0];
+        let index = Access::ALL.iter().position(|&a| a == access).unwrap();
+        slot.views[index].as_ref().unwrap_or_else(|| {
+            panic!(
+                "{:?} has no {:?} view; its usage is {:?}",
+                slot.desc.label, access, slot.desc.usage
+            )
+        })
+    }
+
+    /// Change a texture's size relative to the surface. Takes effect on the
+    /// next `resize`.
+    pub fn set_scale(&mut self, id: TextureId, scale: f32) {
+        self.textures[id.0].desc.scale = scale;
+    }
+
+    /// Recreate textures whose size no longer matches the surface. Bind
+    /// groups that reference them have to be rebuilt afterwards.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        for slot in &mut self.textures {
+            if GraphTexture::extent(&slot.desc, width, height) != slot.texture.size() {
+                *slot = GraphTexture::new(device, slot.desc.clone(), width, height);
+            }
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! Spatial grid overlay: the boundaries of the grid cells that list nearby
+//! seeds for the raymarch, drawn as a faint wireframe over the frame, with
+//! a small cross at every seed, so it's easy to see how the acceleration
+//! structure divides the volume and which grid cell each seed lands in.
+
+use glam::{Mat4, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::camera::Camera;
+use crate::world::{HoneycombWorld, SpatialGrid};
+
+const LINE_COLOR: [f32; 4] = [0.6, 0.8, 1.0, 0.08];
+const SEED_COLOR: [f32; 4] = [1.0, 0.85, 0.3, 0.8];
+/// Half-length of each seed's cross, in grid cells
+const SEED_SIZE: f32 = 0.15;
+
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct GridVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+pub struct GridOverlay {
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    /// Main view's rectangle in the surface: x, y, width, height in pixels
+    viewport: [f32; 4],
+    pub show: bool,
+}
+
+impl GridOverlay {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        cache: Option<&wgpu::PipelineCache>,
+        world: &HoneycombWorld,
+    ) -> Self {
+        let vertices = vertices(world);
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Grid Overlay Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Grid Overlay Uniforms Buffer"),
+            contents: bytemuck::cast_slice(&[Mat4::IDENTITY]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Grid Overlay Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Grid Overlay Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Grid Overlay Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/grid_overlay.wgsl").into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Grid Overlay Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Grid Overlay Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<GridVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4],
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache,
+        });
+
+        Self {
+            vertex_buffer,
+            vertex_count: vertices.len() as u32,
+            uniform_buffer,
+            bind_group,
+            pipeline,
+            viewport: [0.0, 0.0, 1.0, 1.0],
+            show: false,
+        }
+    }
+
+    /// Outline the grid of a different or streamed world. The buffer grows
+    /// if the world has more seeds or a finer grid than it holds.
+    pub fn set_world(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        world: &HoneycombWorld,
+    ) {
+        let vertices = vertices(world);
+        let bytes: &[u8] = bytemuck::cast_slice(&vertices);
+        if bytes.len() as u64 > self.vertex_buffer.size() {
+            self.vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Grid Overlay Vertex Buffer"),
+                contents: bytes,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            });
+        } else {
+            queue.write_buffer(&self.vertex_buffer, 0, bytes);
+        }
+        self.vertex_count = vertices.len() as u32;
+    }
+
+    /// Follow `camera`, drawn in the `viewport` rectangle of the surface
+    /// (x, y, width, height in pixels)
+    pub fn update(&mut self, queue: &wgpu::Queue, camera: &Camera, viewport: [f32; 4]) {
+        self.viewport = viewport;
+        let aspect = viewport[2] / viewport[3].max(1.0);
+        let view_projection = camera.projection_matrix(aspect) * camera.view_matrix();
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[view_projection]),
+        );
+    }
+
+    /// Draw into `target` over what is already there
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        if !self.show {
+            return;
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Grid Overlay Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        let [x, y, width, height] = self.viewport;
+        render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+/// Lines along every grid cell edge, running the length of the volume on
+/// each axis, then a cross at each seed
+fn vertices(world: &HoneycombWorld) -> Vec<GridVertex> {
+    let grid_size = SpatialGrid::size_for(world.cells.len());
+    let min = world.bounds.min;
+    let max = world.bounds.max;
+    let cell_size = (max - min) / grid_size as f32;
+    let lines = grid_size as usize + 1;
+    let mut vertices = Vec::with_capacity(6 * lines * lines + 6 * world.cells.len());
+
+    let vertex = |position: Vec3, color| GridVertex {
+        position: position.to_array(),
+        color,
+    };
+    for axis in 0..3 {
+        // The other two axes, whose grid planes the lines run along
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for i in 0..lines {
+            for j in 0..lines {
+                let mut start = min;
+                start[u] += cell_size[u] * i as f32;
+                start[v] += cell_size[v] * j as f32;
+                let mut end = start;
+                end[axis] = max[axis];
+                vertices.push(vertex(start, LINE_COLOR));
+                vertices.push(vertex(end, LINE_COLOR));
+            }
+        }
+    }
+
+    for cell in &world.cells {
+        for axis in Vec3::AXES {
+            let arm = axis * cell_size * SEED_SIZE;
+            vertices.push(vertex(cell.position - arm, SEED_COLOR));
+            vertices.push(vertex(cell.position + arm, SEED_COLOR));
+        }
+    }
+    vertices
+}
@@ -0,0 +1,83 @@
+//! Rendering without a window, for regression tests and tools: a world
+//! drawn from a chosen camera with chosen parameters, read back as pixels.
+//! Frames are the raymarched image only, without the gizmo or overlay.
+
+use glam::Vec3;
+
+use crate::builder::VendekBuilder;
+use crate::camera::{Camera, CameraPose};
+use crate::gpu::GpuState;
+use crate::params::RuntimeParams;
+
+pub use crate::gpu::GpuError;
+pub use crate::params::ParamError;
+
+/// One world on an offscreen target
+pub struct HeadlessRenderer {
+    gpu: GpuState,
+    camera: Camera,
+    params: RuntimeParams,
+    /// What `reset_params` goes back to
+    initial_params: RuntimeParams,
+}
+
+impl HeadlessRenderer {
+    /// Generate the viewer's world and prepare to draw it at
+    /// `width`×`height`, with the camera and parameters the viewer starts
+    /// with. Any backend will do, including OpenGL, so software renderers
+    /// such as llvmpipe on build machines without a GPU can run it.
+    /// `VendekBuilder` starts from other settings.
+    pub fn new(width: u32, height: u32) -> Result<Self, GpuError> {
+        VendekBuilder::new().build_headless(width, height)
+    }
+
+    pub(crate) fn from_parts(mut gpu: GpuState, params: RuntimeParams, pose: CameraPose) -> Self {
+        gpu.set_params(&params);
+        let mut camera = Camera::new();
+        camera.jump_to(pose.focus, pose.yaw, pose.pitch, pose.distance);
+        Self {
+            gpu,
+            camera,
+            params,
+            initial_params: params,
+        }
+    }
+
+    /// Name and backend of the adapter drawing the frames
+    pub fn adapter(&self) -> String {
+        let info = &self.gpu.adapter_info;
+        format!("{} ({:?})", info.name, info.backend)
+    }
+
+    /// Width and height of the frames in pixels
+    pub fn size(&self) -> (u32, u32) {
+        (self.gpu.size.width, self.gpu.size.height)
+    }
+
+    /// Place the orbit camera: the point it looks at, angles in radians and
+    /// the distance from the point
+    pub fn set_camera(&mut self, focus: [f32; 3], yaw: f32, pitch: f32, distance: f32) {
+        self.camera
+            .jump_to(Vec3::from_array(focus), yaw, pitch, distance);
+    }
+
+    /// Set a runtime parameter by its JavaScript name, as the viewer's
+    /// `set_param`
+    pub fn set_param(&mut self, name: &str, value: f64) -> Result<(), ParamError> {
+        self.params.set(name, value)?;
+        self.gpu.set_params(&self.params);
+        Ok(())
+    }
+
+    /// Put every parameter back to how the renderer started
+    pub fn reset_params(&mut self) {
+        self.params = self.initial_params;
+        self.gpu.set_params(&self.params);
+    }
+
+    /// Draw the world as it is `time` seconds into its animation, returning
+    /// tightly packed sRGB RGBA8 rows, top row first
+    pub fn render(&mut self, time: f32) -> Vec<u8> {
+        self.gpu.render_image(&[&self.camera], time)
+    }
+}
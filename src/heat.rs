@@ -0,0 +1,97 @@
+//! Heat diffusion between coupled cells. Every cell has a temperature that
+//! starts at its phase's own, set by how fast the phase's membranes
+//! oscillate, and flows to the cells it shares a membrane with in
+//! proportion to the coupling strength of the two phases. Each cell is
+//! also held loosely at its phase's temperature, so membranes between
+//! strongly coupled phases blur into gradients while weakly coupled ones
+//! keep a sharp step. The raymarcher shows the temperature as a glow.
+
+use crate::world::{HoneycombCell, HoneycombWorld, VendekPhase};
+
+/// Simulation steps per second of viewer time
+const STEPS_PER_SECOND: f32 = 20.0;
+/// Steps run in one frame at most, so a stalled frame doesn't snowball
+const MAX_STEPS_PER_FRAME: u32 = 8;
+/// Fraction of the gap to its phase's temperature a cell closes per step
+const RELAXATION: f32 = 0.005;
+
+/// Default rate at which temperature evens out between neighbours
+pub const DIFFUSION: f32 = 0.5;
+
+pub struct HeatDiffusion {
+    neighbours: Vec<Vec<usize>>,
+    /// Largest neighbour count, which bounds the stable step
+    max_degree: usize,
+    temperature: Vec<f32>,
+    /// Time not yet simulated, in seconds
+    pending: f32,
+}
+
+impl HeatDiffusion {
+    /// Every cell at its phase's temperature, with the cells' neighbours
+    /// as `HoneycombWorld::neighbours` finds them
+    pub fn new(world: &HoneycombWorld, neighbours: Vec<Vec<usize>>) -> Self {
+        Self {
+            max_degree: neighbours.iter().map(Vec::len).max().unwrap_or(0),
+            neighbours,
+            temperature: world
+                .cells
+                .iter()
+                .map(|cell| phase_temperature(&world.phases[cell.phase_index as usize]))
+                .collect(),
+            pending: 0.0,
+        }
+    }
+
+    /// Run the steps `dt` seconds are worth. `cells` give the current phases,
+    /// `diffusion` scales the flow from 0 (none) to 1 (as fast as is
+    /// stable). Returns whether anything changed.
+    pub fn advance(
+        &mut self,
+        cells: &[HoneycombCell],
+        phases: &[VendekPhase],
+        diffusion: f32,
+        dt: f32,
+    ) -> bool {
+        self.pending += dt;
+        let steps = ((self.pending * STEPS_PER_SECOND) as u32).min(MAX_STEPS_PER_FRAME);
+        self.pending = (self.pending - steps as f32 / STEPS_PER_SECOND).min(1.0 / STEPS_PER_SECOND);
+        for _ in 0..steps {
+            self.step(cells, phases, diffusion);
+        }
+        steps > 0
+    }
+
+    /// Temperature of each cell, 0 to 1
+    pub fn temperatures(&self) -> impl Iterator<Item = f32> + '_ {
+        self.temperature.iter().copied()
+    }
+
+    fn step(&mut self, cells: &[HoneycombCell], phases: &[VendekPhase], diffusion: f32) {
+        let phase = |i: usize| &phases[cells[i].phase_index as usize];
+        let coupling = |i: usize| phase(i).membrane_params.w;
+        // Dividing by the largest degree keeps the explicit step stable and
+        // the flow between two cells symmetric, so heat is conserved
+        let rate = diffusion.clamp(0.0, 1.0) / self.max_degree.max(1) as f32;
+        self.temperature = self
+            .neighbours
+            .iter()
+            .enumerate()
+            .map(|(i, neighbours)| {
+                let t = self.temperature[i];
+                let flow: f32 = neighbours
+                    .iter()
+                    .map(|&j| (coupling(i) + coupling(j)) * 0.5 * (self.temperature[j] - t))
+                    .sum();
+                let relax = (phase_temperature(phase(i)) - t) * RELAXATION;
+                (t + flow * rate + relax).clamp(0.0, 1.0)
+            })
+            .collect();
+    }
+}
+
+/// A phase's own temperature, 0 to 1: its membrane oscillation frequency
+/// mapped from the generated range
+fn phase_temperature(phase: &VendekPhase) -> f32 {
+    ((phase.membrane_params.x - 0.5) / 4.5).clamp(0.0, 1.0)
+}
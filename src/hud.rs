@@ -0,0 +1,109 @@
+//! What the viewer writes over the frame through the text pass: a caption
+//! along the bottom, from `vendek.toml` or the page, and a short readout at
+//! the top whenever a key changes a setting, fading out after a moment.
+
+use serde::Deserialize;
+
+use crate::text::Label;
+
+/// Seconds a readout stays, the last of them fading out
+const READOUT_SECONDS: f32 = 2.0;
+const READOUT_FADE: f32 = 0.5;
+/// Distance of the text from the window's edge, in text sizes
+const MARGIN: f32 = 1.5;
+const TEXT_COLOR: [f32; 3] = [0.92, 0.92, 0.92];
+const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.55];
+
+/// `[hud]` section
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct HudConfig {
+    /// Line shown along the bottom of the window
+    pub caption: Option<String>,
+    /// Show what a key changed
+    pub readouts: bool,
+    /// Text height in logical pixels
+    pub size: f32,
+}
+
+impl Default for HudConfig {
+    fn default() -> Self {
+        Self {
+            caption: None,
+            readouts: true,
+            size: 18.0,
+        }
+    }
+}
+
+pub struct Hud {
+    config: HudConfig,
+    /// Readout shown, and the seconds it has left
+    readout: Option<(String, f32)>,
+}
+
+impl Hud {
+    pub fn new(config: HudConfig) -> Self {
+        Self {
+            config,
+            readout: None,
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn set_caption(&mut self, caption: Option<String>) {
+        self.config.caption = caption.filter(|caption| !caption.is_empty());
+    }
+
+    /// Show `text` at the top for a moment, replacing any readout shown
+    pub fn show_readout(&mut self, text: impl Into<String>) {
+        if self.config.readouts {
+            self.readout = Some((text.into(), READOUT_SECONDS));
+        }
+    }
+
+    /// Whether a readout is still showing, and so still fading
+    pub fn has_readout(&self) -> bool {
+        self.readout.is_some()
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        if let Some((_, left)) = &mut self.readout {
+            *left -= dt;
+            if *left <= 0.0 {
+                self.readout = None;
+            }
+        }
+    }
+
+    /// What to draw over a `width`×`height` frame, with `scale` physical
+    /// pixels to a logical one
+    pub fn labels(&self, width: u32, height: u32, scale: f32) -> Vec<Label> {
+        let size = self.config.size * scale;
+        let margin = size * MARGIN;
+        let center = width as f32 / 2.0;
+        let mut labels = Vec::new();
+        if let Some((text, left)) = &self.readout {
+            let opacity = (left / READOUT_FADE).min(1.0);
+            let [r, g, b] = TEXT_COLOR;
+            labels.push(
+                Label::new(text.clone(), [center, margin])
+                    .anchor(0.5, 0.0)
+                    .size(size)
+                    .color([r, g, b, opacity])
+                    .background([0.0, 0.0, 0.0, BACKGROUND[3] * opacity]),
+            );
+        }
+        if let Some(caption) = &self.config.caption {
+            let [r, g, b] = TEXT_COLOR;
+            labels.push(
+                Label::new(caption.clone(), [center, height as f32 - margin])
+                    .anchor(0.5, 1.0)
+                    .size(size)
+                    .color([r, g, b, 1.0])
+                    .background(BACKGROUND),
+            );
+        }
+        labels
+    }
+}
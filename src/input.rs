@@ -1,5 +1,6 @@
 use glam::Vec2;
-use std::collections::HashSet;
+use serde::{Deserialize, Deserializer};
+use std::collections::{HashMap, HashSet};
 use winit::event::{ElementState, MouseButton};
 use winit::keyboard::KeyCode;
 
@@ -45,10 +46,14 @@ impl InputState {
     }
 
     pub fn handle_mouse_move(&mut self, position: Vec2) {
-        self.mouse_delta = position - self.mouse_position;
         self.mouse_position = position;
     }
 
+    /// Accumulate raw device motion, which keeps reporting while the pointer is locked
+    pub fn handle_mouse_motion(&mut self, delta: Vec2) {
+        self.mouse_delta += delta;
+    }
+
     pub fn handle_scroll(&mut self, delta: f32) {
         self.scroll_delta = delta;
     }
@@ -58,7 +63,11 @@ impl InputState {
         self.scroll_delta = 0.0;
     }
 
-    #[allow(dead_code)]
+    /// Whether any key or mouse button is down
+    pub fn any_held(&self) -> bool {
+        !self.keys_held.is_empty() || !self.mouse_buttons.is_empty()
+    }
+
     pub fn is_key_held(&self, code: KeyCode) -> bool {
         self.keys_held.contains(&code)
     }
@@ -66,6 +75,13 @@ impl InputState {
     pub fn is_mouse_held(&self, button: MouseButton) -> bool {
         self.mouse_buttons.contains(&button)
     }
+
+    pub fn is_binding_held(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(code) => self.keys_held.contains(&code),
+            Binding::Mouse(button) => self.is_mouse_held(button),
+        }
+    }
 }
 
 impl Default for InputState {
@@ -73,3 +89,231 @@ impl Default for InputState {
         Self::new()
     }
 }
+
+/// Phase shown or hidden by a digit key: 1 to 9 for the first nine phases
+/// and 0 for the tenth, on the number row or the keypad
+pub fn phase_key(code: KeyCode) -> Option<u32> {
+    let digit = match code {
+        KeyCode::Digit0 | KeyCode::Numpad0 => 0,
+        KeyCode::Digit1 | KeyCode::Numpad1 => 1,
+        KeyCode::Digit2 | KeyCode::Numpad2 => 2,
+        KeyCode::Digit3 | KeyCode::Numpad3 => 3,
+        KeyCode::Digit4 | KeyCode::Numpad4 => 4,
+        KeyCode::Digit5 | KeyCode::Numpad5 => 5,
+        KeyCode::Digit6 | KeyCode::Numpad6 => 6,
+        KeyCode::Digit7 | KeyCode::Numpad7 => 7,
+        KeyCode::Digit8 | KeyCode::Numpad8 => 8,
+        KeyCode::Digit9 | KeyCode::Numpad9 => 9,
+        _ => return None,
+    };
+    Some((digit + 9) % 10)
+}
+
+/// High-level actions that physical inputs can be bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Drag to orbit the camera around its focus point
+    Orbit,
+    /// Drag to pan the focus point
+    Pan,
+    /// Drag vertically to zoom
+    Zoom,
+    /// Switch between orbit and fly (pointer-locked mouse look) cameras
+    ToggleFly,
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    /// Keyboard orbit and zoom (orbit camera)
+    OrbitLeft,
+    OrbitRight,
+    OrbitUp,
+    OrbitDown,
+    ZoomIn,
+    ZoomOut,
+    Screenshot,
+    /// Start recording a video of the window, or finish it
+    ToggleRecording,
+    /// Start logging each frame's clock, camera and parameters for
+    /// rendering again offline, or finish the log
+    ToggleSession,
+    TogglePause,
+    /// Show or hide the FPS and frame-time overlay
+    ToggleStats,
+    /// Switch between windowed and fullscreen; Alt+Enter also works
+    ToggleFullscreen,
+    /// Show an overview of the whole world beside the main view
+    ToggleSplitView,
+    /// Show or hide the orientation axes in the corner
+    ToggleAxes,
+    /// Show or hide the volume's bounding box
+    ToggleBounds,
+    /// Show or hide the spatial grid's wireframe and the seeds in it
+    ToggleGrid,
+    /// Switch low-power mode, which trades resolution, steps and frame
+    /// rate for battery life
+    ToggleLowPower,
+    /// Sweep a clipping plane through the volume, facing the camera; again
+    /// to remove it
+    SweepClipPlane,
+    /// Cycle the raymarched volume, the rasterized isosurface, both, and the
+    /// membranes alone
+    CycleRenderMode,
+    /// Switch between the membranes alone and the full volume
+    ToggleMembranes,
+    /// Cycle the image and the debug views: steps, phases, normals, depth
+    /// and grid occupancy
+    CycleDebugView,
+    /// Remember the camera's pose
+    AddBookmark,
+    /// Glide to the next remembered pose
+    NextBookmark,
+    /// Put the parameters and camera back as they were at startup
+    ResetView,
+    /// Generate a new world from a random seed
+    NewWorld,
+    /// Regenerate the world with a quarter more cells, or a fifth fewer
+    MoreCells,
+    FewerCells,
+    /// Regenerate the world with one more phase, or one fewer
+    MorePhases,
+    FewerPhases,
+    Quit,
+}
+
+/// A single physical input: a keyboard key or a mouse button
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl Binding {
+    /// Parse a binding name. Mouse buttons are written `MouseLeft`, `MouseRight`,
+    /// `MouseMiddle`, `MouseBack` or `MouseForward`; anything else is treated as
+    /// a winit `KeyCode` name such as `KeyW`, `Space` or `F12`.
+    pub fn parse(name: &str) -> Option<Self> {
+        use serde::de::value::{Error, StrDeserializer};
+
+        if let Some(button) = name.strip_prefix("Mouse") {
+            let button = match button {
+                "Left" => MouseButton::Left,
+                "Right" => MouseButton::Right,
+                "Middle" => MouseButton::Middle,
+                "Back" => MouseButton::Back,
+                "Forward" => MouseButton::Forward,
+                _ => return None,
+            };
+            return Some(Binding::Mouse(button));
+        }
+
+        KeyCode::deserialize(StrDeserializer::<Error>::new(name))
+            .ok()
+            .map(Binding::Key)
+    }
+}
+
+impl<'de> Deserialize<'de> for Binding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Binding::parse(&name)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown input binding `{}`", name)))
+    }
+}
+
+/// Maps actions to the physical inputs that trigger them
+#[derive(Clone, Debug)]
+pub struct InputMap {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl InputMap {
+    /// Whether any input bound to `action` is currently held
+    pub fn is_held(&self, input: &InputState, action: Action) -> bool {
+        self.bindings
+            .get(&action)
+            .is_some_and(|bindings| bindings.iter().any(|&b| input.is_binding_held(b)))
+    }
+
+    /// All actions triggered by the given physical input
+    pub fn actions_for(&self, binding: Binding) -> impl Iterator<Item = Action> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, bindings)| bindings.contains(&binding))
+            .map(|(&action, _)| action)
+    }
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let bindings = HashMap::from([
+            (Action::Orbit, vec![Binding::Mouse(MouseButton::Left)]),
+            (Action::Pan, vec![Binding::Mouse(MouseButton::Right)]),
+            (Action::Zoom, vec![Binding::Mouse(MouseButton::Middle)]),
+            (Action::ToggleFly, vec![Binding::Key(KeyCode::KeyF)]),
+            (Action::MoveForward, vec![Binding::Key(KeyCode::KeyW)]),
+            (Action::MoveBack, vec![Binding::Key(KeyCode::KeyS)]),
+            (Action::MoveLeft, vec![Binding::Key(KeyCode::KeyA)]),
+            (Action::MoveRight, vec![Binding::Key(KeyCode::KeyD)]),
+            (Action::MoveUp, vec![Binding::Key(KeyCode::KeyE)]),
+            (Action::MoveDown, vec![Binding::Key(KeyCode::KeyQ)]),
+            (Action::OrbitLeft, vec![Binding::Key(KeyCode::ArrowLeft)]),
+            (Action::OrbitRight, vec![Binding::Key(KeyCode::ArrowRight)]),
+            (Action::OrbitUp, vec![Binding::Key(KeyCode::ArrowUp)]),
+            (Action::OrbitDown, vec![Binding::Key(KeyCode::ArrowDown)]),
+            (
+                Action::ZoomIn,
+                vec![
+                    Binding::Key(KeyCode::Equal),
+                    Binding::Key(KeyCode::NumpadAdd),
+                ],
+            ),
+            (
+                Action::ZoomOut,
+                vec![
+                    Binding::Key(KeyCode::Minus),
+                    Binding::Key(KeyCode::NumpadSubtract),
+                ],
+            ),
+            (Action::Screenshot, vec![Binding::Key(KeyCode::F12)]),
+            (Action::ToggleRecording, vec![Binding::Key(KeyCode::F9)]),
+            (Action::ToggleSession, vec![Binding::Key(KeyCode::F8)]),
+            (Action::TogglePause, vec![Binding::Key(KeyCode::Space)]),
+            (Action::ToggleStats, vec![Binding::Key(KeyCode::F3)]),
+            (Action::ToggleFullscreen, vec![Binding::Key(KeyCode::F11)]),
+            (Action::ToggleSplitView, vec![Binding::Key(KeyCode::KeyV)]),
+            (Action::ToggleAxes, vec![Binding::Key(KeyCode::KeyG)]),
+            (Action::ToggleBounds, vec![Binding::Key(KeyCode::KeyB)]),
+            (Action::SweepClipPlane, vec![Binding::Key(KeyCode::KeyC)]),
+            (Action::CycleRenderMode, vec![Binding::Key(KeyCode::KeyM)]),
+            (Action::ToggleMembranes, vec![Binding::Key(KeyCode::KeyT)]),
+            (Action::CycleDebugView, vec![Binding::Key(KeyCode::F4)]),
+            (Action::ToggleGrid, vec![Binding::Key(KeyCode::F5)]),
+            (Action::ToggleLowPower, vec![Binding::Key(KeyCode::F6)]),
+            (Action::AddBookmark, vec![Binding::Key(KeyCode::KeyK)]),
+            (Action::NextBookmark, vec![Binding::Key(KeyCode::KeyN)]),
+            (Action::ResetView, vec![Binding::Key(KeyCode::KeyR)]),
+            (Action::NewWorld, vec![Binding::Key(KeyCode::KeyX)]),
+            (Action::MoreCells, vec![Binding::Key(KeyCode::BracketRight)]),
+            (Action::FewerCells, vec![Binding::Key(KeyCode::BracketLeft)]),
+            (Action::MorePhases, vec![Binding::Key(KeyCode::Period)]),
+            (Action::FewerPhases, vec![Binding::Key(KeyCode::Comma)]),
+            (Action::Quit, vec![Binding::Key(KeyCode::Escape)]),
+        ]);
+        Self { bindings }
+    }
+}
+
+impl<'de> Deserialize<'de> for InputMap {
+    /// Actions listed in the config replace the default bindings for that
+    /// action; unlisted actions keep their defaults.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let overrides = HashMap::<Action, Vec<Binding>>::deserialize(deserializer)?;
+        let mut map = Self::default();
+        map.bindings.extend(overrides);
+        Ok(map)
+    }
+}
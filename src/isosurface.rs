@@ -0,0 +1,775 @@
+//! Isosurface mode: the membranes extracted as triangles and rasterized with
+//! simple lighting, for crisp views of the cell structure.
+//!
+//! The membrane field is the same one the raymarcher shades: half the gap
+//! between the distances to the nearest and second-nearest cell centers. It
+//! is sampled on a regular grid and polygonized with marching cubes, using
+//! 256-case edge and triangle tables. A cube face with two diagonally
+//! opposite corners inside the membrane is always split to keep those
+//! corners apart, so neighbouring cubes agree on the faces they share and
+//! the surface has no cracks. The mesh is built on the CPU the first time
+//! the mode is shown, and again when the membrane thickness changes. When cells change phase only the vertex
+//! colors are rewritten.
+//!
+//! The surface is drawn into offscreen color and depth targets the size of
+//! the raymarch image. The raymarch then either passes the color through or,
+//! in hybrid mode, uses it as the background and stops rays at the depth.
+
+use std::collections::HashMap;
+
+use glam::Vec3;
+use wgpu::util::DeviceExt;
+
+use crate::graph::{Access, RenderGraph, TextureId};
+use crate::world::{HoneycombCell, HoneycombWorld};
+
+/// Grid samples per axis across the volume
+const RESOLUTION: usize = 48;
+/// Formats of the targets the surface is drawn into
+pub const COLOR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Cube corners as offsets along x, y and z
+const CORNERS: [[usize; 3]; 8] = [
+    [0, 0, 0],
+    [1, 0, 0],
+    [0, 1, 0],
+    [1, 1, 0],
+    [0, 0, 1],
+    [1, 0, 1],
+    [0, 1, 1],
+    [1, 1, 1],
+];
+
+/// Cube edges as pairs of corners: the four along x, then y, then z
+const EDGES: [[usize; 2]; 12] = [
+    [0, 1],
+    [2, 3],
+    [4, 5],
+    [6, 7],
+    [0, 2],
+    [1, 3],
+    [4, 6],
+    [5, 7],
+    [0, 4],
+    [1, 5],
+    [2, 6],
+    [3, 7],
+];
+
+/// Grid edges crossed by the surface in each of the 256 inside/outside
+/// cases, one bit per entry of `EDGES`, indexed by a bit per inside corner
+const EDGE_TABLE: [u16; 256] = [
+    0x000, 0x111, 0x221, 0x330, 0x412, 0x503, 0x633, 0x722, 0x822, 0x933, 0xa03, 0xb12, 0xc30,
+    0xd21, 0xe11, 0xf00, 0x144, 0x055, 0x365, 0x274, 0x556, 0x447, 0x777, 0x666, 0x966, 0x877,
+    0xb47, 0xa56, 0xd74, 0xc65, 0xf55, 0xe44, 0x284, 0x395, 0x0a5, 0x1b4, 0x696, 0x787, 0x4b7,
+    0x5a6, 0xaa6, 0xbb7, 0x887, 0x996, 0xeb4, 0xfa5, 0xc95, 0xd84, 0x3c0, 0x2d1, 0x1e1, 0x0f0,
+    0x7d2, 0x6c3, 0x5f3, 0x4e2, 0xbe2, 0xaf3, 0x9c3, 0x8d2, 0xff0, 0xee1, 0xdd1, 0xcc0, 0x448,
+    0x559, 0x669, 0x778, 0x05a, 0x14b, 0x27b, 0x36a, 0xc6a, 0xd7b, 0xe4b, 0xf5a, 0x878, 0x969,
+    0xa59, 0xb48, 0x50c, 0x41d, 0x72d, 0x63c, 0x11e, 0x00f, 0x33f, 0x22e, 0xd2e, 0xc3f, 0xf0f,
+    0xe1e, 0x93c, 0x82d, 0xb1d, 0xa0c, 0x6cc, 0x7dd, 0x4ed, 0x5fc, 0x2de, 0x3cf, 0x0ff, 0x1ee,
+    0xeee, 0xfff, 0xccf, 0xdde, 0xafc, 0xbed, 0x8dd, 0x9cc, 0x788, 0x699, 0x5a9, 0x4b8, 0x39a,
+    0x28b, 0x1bb, 0x0aa, 0xfaa, 0xebb, 0xd8b, 0xc9a, 0xbb8, 0xaa9, 0x999, 0x888, 0x888, 0x999,
+    0xaa9, 0xbb8, 0xc9a, 0xd8b, 0xebb, 0xfaa, 0x0aa, 0x1bb, 0x28b, 0x39a, 0x4b8, 0x5a9, 0x699,
+    0x788, 0x9cc, 0x8dd, 0xbed, 0xafc, 0xdde, 0xccf, 0xfff, 0xeee, 0x1ee, 0x0ff, 0x3cf, 0x2de,
+    0x5fc, 0x4ed, 0x7dd, 0x6cc, 0xa0c, 0xb1d, 0x82d, 0x93c, 0xe1e, 0xf0f, 0xc3f, 0xd2e, 0x22e,
+    0x33f, 0x00f, 0x11e, 0x63c, 0x72d, 0x41d, 0x50c, 0xb48, 0xa59, 0x969, 0x878, 0xf5a, 0xe4b,
+    0xd7b, 0xc6a, 0x36a, 0x27b, 0x14b, 0x05a, 0x778, 0x669, 0x559, 0x448, 0xcc0, 0xdd1, 0xee1,
+    0xff0, 0x8d2, 0x9c3, 0xaf3, 0xbe2, 0x4e2, 0x5f3, 0x6c3, 0x7d2, 0x0f0, 0x1e1, 0x2d1, 0x3c0,
+    0xd84, 0xc95, 0xfa5, 0xeb4, 0x996, 0x887, 0xbb7, 0xaa6, 0x5a6, 0x4b7, 0x787, 0x696, 0x1b4,
+    0x0a5, 0x395, 0x284, 0xe44, 0xf55, 0xc65, 0xd74, 0xa56, 0xb47, 0x877, 0x966, 0x666, 0x777,
+    0x447, 0x556, 0x274, 0x365, 0x055, 0x144, 0xf00, 0xe11, 0xd21, 0xc30, 0xb12, 0xa03, 0x933,
+    0x822, 0x722, 0x633, 0x503, 0x412, 0x330, 0x221, 0x111, 0x000,
+];
+
+/// Triangles for each case, as triples of `EDGES` indices ended by -1
+const TRIANGLE_TABLE: [[i8; 16]; 256] = [
+    [
+        -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+    ],
+    [0, 8, 4, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [4, 8, 9, 4, 9, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [1, 10, 4, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 10, 0, 10, 1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 5, 1, 10, 4, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [1, 10, 8, 1, 8, 9, 1, 9, 5, -1, -1, -1, -1, -1, -1, -1],
+    [1, 11, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 1, 11, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 11, 0, 11, 1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [1, 11, 9, 1, 9, 8, 1, 8, 4, -1, -1, -1, -1, -1, -1, -1],
+    [4, 10, 11, 4, 11, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 10, 0, 10, 11, 0, 11, 5, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 11, 0, 11, 10, 0, 10, 4, -1, -1, -1, -1, -1, -1, -1],
+    [8, 10, 11, 8, 11, 9, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [2, 8, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 6, 0, 6, 4, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 5, 2, 8, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [2, 9, 5, 2, 5, 4, 2, 4, 6, -1, -1, -1, -1, -1, -1, -1],
+    [1, 10, 4, 2, 8, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 6, 0, 6, 10, 0, 10, 1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 5, 1, 10, 4, 2, 8, 6, -1, -1, -1, -1, -1, -1, -1],
+    [1, 10, 6, 1, 6, 2, 1, 2, 9, 1, 9, 5, -1, -1, -1, -1],
+    [1, 11, 5, 2, 8, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 6, 0, 6, 4, 1, 11, 5, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 11, 0, 11, 1, 2, 8, 6, -1, -1, -1, -1, -1, -1, -1],
+    [1, 11, 9, 1, 9, 2, 1, 2, 6, 1, 6, 4, -1, -1, -1, -1],
+    [2, 8, 6, 4, 10, 11, 4, 11, 5, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 6, 0, 6, 10, 0, 10, 11, 0, 11, 5, -1, -1, -1, -1],
+    [0, 9, 11, 0, 11, 10, 0, 10, 4, 2, 8, 6, -1, -1, -1, -1],
+    [2, 9, 11, 2, 11, 10, 2, 10, 6, -1, -1, -1, -1, -1, -1, -1],
+    [2, 9, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 2, 9, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 7, 0, 7, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [2, 8, 4, 2, 4, 5, 2, 5, 7, -1, -1, -1, -1, -1, -1, -1],
+    [1, 10, 4, 2, 9, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 10, 0, 10, 1, 2, 9, 7, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 7, 0, 7, 5, 1, 10, 4, -1, -1, -1, -1, -1, -1, -1],
+    [1, 10, 8, 1, 8, 2, 1, 2, 7, 1, 7, 5, -1, -1, -1, -1],
+    [1, 11, 5, 2, 9, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 1, 11, 5, 2, 9, 7, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 7, 0, 7, 11, 0, 11, 1, -1, -1, -1, -1, -1, -1, -1],
+    [1, 11, 7, 1, 7, 2, 1, 2, 8, 1, 8, 4, -1, -1, -1, -1],
+    [2, 9, 7, 4, 10, 11, 4, 11, 5, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 10, 0, 10, 11, 0, 11, 5, 2, 9, 7, -1, -1, -1, -1],
+    [0, 2, 7, 0, 7, 11, 0, 11, 10, 0, 10, 4, -1, -1, -1, -1],
+    [2, 8, 10, 2, 10, 11, 2, 11, 7, -1, -1, -1, -1, -1, -1, -1],
+    [6, 8, 9, 6, 9, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 7, 0, 7, 6, 0, 6, 4, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 6, 0, 6, 7, 0, 7, 5, -1, -1, -1, -1, -1, -1, -1],
+    [4, 6, 7, 4, 7, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [1, 10, 4, 6, 8, 9, 6, 9, 7, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 7, 0, 7, 6, 0, 6, 10, 0, 10, 1, -1, -1, -1, -1],
+    [0, 8, 6, 0, 6, 7, 0, 7, 5, 1, 10, 4, -1, -1, -1, -1],
+    [1, 10, 6, 1, 6, 7, 1, 7, 5, -1, -1, -1, -1, -1, -1, -1],
+    [1, 11, 5, 6, 8, 9, 6, 9, 7, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 7, 0, 7, 6, 0, 6, 4, 1, 11, 5, -1, -1, -1, -1],
+    [0, 8, 6, 0, 6, 7, 0, 7, 11, 0, 11, 1, -1, -1, -1, -1],
+    [1, 11, 7, 1, 7, 6, 1, 6, 4, -1, -1, -1, -1, -1, -1, -1],
+    [4, 10, 11, 4, 11, 5, 6, 8, 9, 6, 9, 7, -1, -1, -1, -1],
+    [0, 9, 7, 0, 7, 6, 0, 6, 10, 0, 10, 11, 0, 11, 5, -1],
+    [0, 8, 6, 0, 6, 7, 0, 7, 11, 0, 11, 10, 0, 10, 4, -1],
+    [6, 10, 11, 6, 11, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [3, 10, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 3, 10, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 5, 3, 10, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [3, 10, 6, 4, 8, 9, 4, 9, 5, -1, -1, -1, -1, -1, -1, -1],
+    [1, 3, 6, 1, 6, 4, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 6, 0, 6, 3, 0, 3, 1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 5, 1, 3, 6, 1, 6, 4, -1, -1, -1, -1, -1, -1, -1],
+    [1, 3, 6, 1, 6, 8, 1, 8, 9, 1, 9, 5, -1, -1, -1, -1],
+    [1, 11, 5, 3, 10, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 1, 11, 5, 3, 10, 6, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 11, 0, 11, 1, 3, 10, 6, -1, -1, -1, -1, -1, -1, -1],
+    [1, 11, 9, 1, 9, 8, 1, 8, 4, 3, 10, 6, -1, -1, -1, -1],
+    [3, 11, 5, 3, 5, 4, 3, 4, 6, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 6, 0, 6, 3, 0, 3, 11, 0, 11, 5, -1, -1, -1, -1],
+    [0, 9, 11, 0, 11, 3, 0, 3, 6, 0, 6, 4, -1, -1, -1, -1],
+    [3, 11, 9, 3, 9, 8, 3, 8, 6, -1, -1, -1, -1, -1, -1, -1],
+    [2, 8, 10, 2, 10, 3, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 3, 0, 3, 10, 0, 10, 4, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 5, 2, 8, 10, 2, 10, 3, -1, -1, -1, -1, -1, -1, -1],
+    [2, 9, 5, 2, 5, 4, 2, 4, 10, 2, 10, 3, -1, -1, -1, -1],
+    [1, 3, 2, 1, 2, 8, 1, 8, 4, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 3, 0, 3, 1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 5, 1, 3, 2, 1, 2, 8, 1, 8, 4, -1, -1, -1, -1],
+    [1, 3, 2, 1, 2, 9, 1, 9, 5, -1, -1, -1, -1, -1, -1, -1],
+    [1, 11, 5, 2, 8, 10, 2, 10, 3, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 3, 0, 3, 10, 0, 10, 4, 1, 11, 5, -1, -1, -1, -1],
+    [0, 9, 11, 0, 11, 1, 2, 8, 10, 2, 10, 3, -1, -1, -1, -1],
+    [1, 11, 9, 1, 9, 2, 1, 2, 3, 1, 3, 10, 1, 10, 4, -1],
+    [2, 8, 4, 2, 4, 5, 2, 5, 11, 2, 11, 3, -1, -1, -1, -1],
+    [0, 2, 3, 0, 3, 11, 0, 11, 5, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 11, 0, 11, 3, 0, 3, 2, 0, 2, 8, 0, 8, 4, -1],
+    [2, 9, 11, 2, 11, 3, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [2, 9, 7, 3, 10, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 2, 9, 7, 3, 10, 6, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 7, 0, 7, 5, 3, 10, 6, -1, -1, -1, -1, -1, -1, -1],
+    [2, 8, 4, 2, 4, 5, 2, 5, 7, 3, 10, 6, -1, -1, -1, -1],
+    [1, 3, 6, 1, 6, 4, 2, 9, 7, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 6, 0, 6, 3, 0, 3, 1, 2, 9, 7, -1, -1, -1, -1],
+    [0, 2, 7, 0, 7, 5, 1, 3, 6, 1, 6, 4, -1, -1, -1, -1],
+    [1, 3, 6, 1, 6, 8, 1, 8, 2, 1, 2, 7, 1, 7, 5, -1],
+    [1, 11, 5, 2, 9, 7, 3, 10, 6, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 1, 11, 5, 2, 9, 7, 3, 10, 6, -1, -1, -1, -1],
+    [0, 2, 7, 0, 7, 11, 0, 11, 1, 3, 10, 6, -1, -1, -1, -1],
+    [1, 11, 7, 1, 7, 2, 1, 2, 8, 1, 8, 4, 3, 10, 6, -1],
+    [2, 9, 7, 3, 11, 5, 3, 5, 4, 3, 4, 6, -1, -1, -1, -1],
+    [0, 8, 6, 0, 6, 3, 0, 3, 11, 0, 11, 5, 2, 9, 7, -1],
+    [0, 2, 7, 0, 7, 11, 0, 11, 3, 0, 3, 6, 0, 6, 4, -1],
+    [2, 8, 6, 2, 6, 3, 2, 3, 11, 2, 11, 7, -1, -1, -1, -1],
+    [3, 10, 8, 3, 8, 9, 3, 9, 7, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 7, 0, 7, 3, 0, 3, 10, 0, 10, 4, -1, -1, -1, -1],
+    [0, 8, 10, 0, 10, 3, 0, 3, 7, 0, 7, 5, -1, -1, -1, -1],
+    [3, 10, 4, 3, 4, 5, 3, 5, 7, -1, -1, -1, -1, -1, -1, -1],
+    [1, 3, 7, 1, 7, 9, 1, 9, 8, 1, 8, 4, -1, -1, -1, -1],
+    [0, 9, 7, 0, 7, 3, 0, 3, 1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 0, 4, 1, 0, 1, 3, 0, 3, 7, 0, 7, 5, -1],
+    [1, 3, 7, 1, 7, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [1, 11, 5, 3, 10, 8, 3, 8, 9, 3, 9, 7, -1, -1, -1, -1],
+    [0, 9, 7, 0, 7, 3, 0, 3, 10, 0, 10, 4, 1, 11, 5, -1],
+    [0, 8, 10, 0, 10, 3, 0, 3, 7, 0, 7, 11, 0, 11, 1, -1],
+    [1, 11, 7, 1, 7, 3, 1, 3, 10, 1, 10, 4, -1, -1, -1, -1],
+    [3, 11, 5, 3, 5, 4, 3, 4, 8, 3, 8, 9, 3, 9, 7, -1],
+    [0, 9, 7, 0, 7, 3, 0, 3, 11, 0, 11, 5, -1, -1, -1, -1],
+    [0, 8, 4, 3, 11, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [3, 11, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [3, 11, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 3, 11, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 5, 3, 11, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [3, 11, 7, 4, 8, 9, 4, 9, 5, -1, -1, -1, -1, -1, -1, -1],
+    [1, 10, 4, 3, 11, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 10, 0, 10, 1, 3, 11, 7, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 5, 1, 10, 4, 3, 11, 7, -1, -1, -1, -1, -1, -1, -1],
+    [1, 10, 8, 1, 8, 9, 1, 9, 5, 3, 11, 7, -1, -1, -1, -1],
+    [1, 3, 7, 1, 7, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 1, 3, 7, 1, 7, 5, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 7, 0, 7, 3, 0, 3, 1, -1, -1, -1, -1, -1, -1, -1],
+    [1, 3, 7, 1, 7, 9, 1, 9, 8, 1, 8, 4, -1, -1, -1, -1],
+    [3, 10, 4, 3, 4, 5, 3, 5, 7, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 10, 0, 10, 3, 0, 3, 7, 0, 7, 5, -1, -1, -1, -1],
+    [0, 9, 7, 0, 7, 3, 0, 3, 10, 0, 10, 4, -1, -1, -1, -1],
+    [3, 10, 8, 3, 8, 9, 3, 9, 7, -1, -1, -1, -1, -1, -1, -1],
+    [2, 8, 6, 3, 11, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 6, 0, 6, 4, 3, 11, 7, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 5, 2, 8, 6, 3, 11, 7, -1, -1, -1, -1, -1, -1, -1],
+    [2, 9, 5, 2, 5, 4, 2, 4, 6, 3, 11, 7, -1, -1, -1, -1],
+    [1, 10, 4, 2, 8, 6, 3, 11, 7, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 6, 0, 6, 10, 0, 10, 1, 3, 11, 7, -1, -1, -1, -1],
+    [0, 9, 5, 1, 10, 4, 2, 8, 6, 3, 11, 7, -1, -1, -1, -1],
+    [1, 10, 6, 1, 6, 2, 1, 2, 9, 1, 9, 5, 3, 11, 7, -1],
+    [1, 3, 7, 1, 7, 5, 2, 8, 6, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 6, 0, 6, 4, 1, 3, 7, 1, 7, 5, -1, -1, -1, -1],
+    [0, 9, 7, 0, 7, 3, 0, 3, 1, 2, 8, 6, -1, -1, -1, -1],
+    [1, 3, 7, 1, 7, 9, 1, 9, 2, 1, 2, 6, 1, 6, 4, -1],
+    [2, 8, 6, 3, 10, 4, 3, 4, 5, 3, 5, 7, -1, -1, -1, -1],
+    [0, 2, 6, 0, 6, 10, 0, 10, 3, 0, 3, 7, 0, 7, 5, -1],
+    [0, 9, 7, 0, 7, 3, 0, 3, 10, 0, 10, 4, 2, 8, 6, -1],
+    [2, 9, 7, 2, 7, 3, 2, 3, 10, 2, 10, 6, -1, -1, -1, -1],
+    [2, 9, 11, 2, 11, 3, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 2, 9, 11, 2, 11, 3, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 3, 0, 3, 11, 0, 11, 5, -1, -1, -1, -1, -1, -1, -1],
+    [2, 8, 4, 2, 4, 5, 2, 5, 11, 2, 11, 3, -1, -1, -1, -1],
+    [1, 10, 4, 2, 9, 11, 2, 11, 3, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 10, 0, 10, 1, 2, 9, 11, 2, 11, 3, -1, -1, -1, -1],
+    [0, 2, 3, 0, 3, 11, 0, 11, 5, 1, 10, 4, -1, -1, -1, -1],
+    [1, 10, 8, 1, 8, 2, 1, 2, 3, 1, 3, 11, 1, 11, 5, -1],
+    [1, 3, 2, 1, 2, 9, 1, 9, 5, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 1, 3, 2, 1, 2, 9, 1, 9, 5, -1, -1, -1, -1],
+    [0, 2, 3, 0, 3, 1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [1, 3, 2, 1, 2, 8, 1, 8, 4, -1, -1, -1, -1, -1, -1, -1],
+    [2, 9, 5, 2, 5, 4, 2, 4, 10, 2, 10, 3, -1, -1, -1, -1],
+    [0, 8, 10, 0, 10, 3, 0, 3, 2, 0, 2, 9, 0, 9, 5, -1],
+    [0, 2, 3, 0, 3, 10, 0, 10, 4, -1, -1, -1, -1, -1, -1, -1],
+    [2, 8, 10, 2, 10, 3, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [3, 11, 9, 3, 9, 8, 3, 8, 6, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 11, 0, 11, 3, 0, 3, 6, 0, 6, 4, -1, -1, -1, -1],
+    [0, 8, 6, 0, 6, 3, 0, 3, 11, 0, 11, 5, -1, -1, -1, -1],
+    [3, 11, 5, 3, 5, 4, 3, 4, 6, -1, -1, -1, -1, -1, -1, -1],
+    [1, 10, 4, 3, 11, 9, 3, 9, 8, 3, 8, 6, -1, -1, -1, -1],
+    [0, 9, 11, 0, 11, 3, 0, 3, 6, 0, 6, 10, 0, 10, 1, -1],
+    [0, 8, 6, 0, 6, 3, 0, 3, 11, 0, 11, 5, 1, 10, 4, -1],
+    [1, 10, 6, 1, 6, 3, 1, 3, 11, 1, 11, 5, -1, -1, -1, -1],
+    [1, 3, 6, 1, 6, 8, 1, 8, 9, 1, 9, 5, -1, -1, -1, -1],
+    [0, 9, 5, 0, 5, 1, 0, 1, 3, 0, 3, 6, 0, 6, 4, -1],
+    [0, 8, 6, 0, 6, 3, 0, 3, 1, -1, -1, -1, -1, -1, -1, -1],
+    [1, 3, 6, 1, 6, 4, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [3, 10, 4, 3, 4, 5, 3, 5, 9, 3, 9, 8, 3, 8, 6, -1],
+    [0, 9, 5, 3, 10, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 6, 0, 6, 3, 0, 3, 10, 0, 10, 4, -1, -1, -1, -1],
+    [3, 10, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [6, 10, 11, 6, 11, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 6, 10, 11, 6, 11, 7, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 5, 6, 10, 11, 6, 11, 7, -1, -1, -1, -1, -1, -1, -1],
+    [4, 8, 9, 4, 9, 5, 6, 10, 11, 6, 11, 7, -1, -1, -1, -1],
+    [1, 11, 7, 1, 7, 6, 1, 6, 4, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 6, 0, 6, 7, 0, 7, 11, 0, 11, 1, -1, -1, -1, -1],
+    [0, 9, 5, 1, 11, 7, 1, 7, 6, 1, 6, 4, -1, -1, -1, -1],
+    [1, 11, 7, 1, 7, 6, 1, 6, 8, 1, 8, 9, 1, 9, 5, -1],
+    [1, 10, 6, 1, 6, 7, 1, 7, 5, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 1, 10, 6, 1, 6, 7, 1, 7, 5, -1, -1, -1, -1],
+    [0, 9, 7, 0, 7, 6, 0, 6, 10, 0, 10, 1, -1, -1, -1, -1],
+    [1, 10, 6, 1, 6, 7, 1, 7, 9, 1, 9, 8, 1, 8, 4, -1],
+    [4, 6, 7, 4, 7, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 6, 0, 6, 7, 0, 7, 5, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 7, 0, 7, 6, 0, 6, 4, -1, -1, -1, -1, -1, -1, -1],
+    [6, 8, 9, 6, 9, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [2, 8, 10, 2, 10, 11, 2, 11, 7, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 7, 0, 7, 11, 0, 11, 10, 0, 10, 4, -1, -1, -1, -1],
+    [0, 9, 5, 2, 8, 10, 2, 10, 11, 2, 11, 7, -1, -1, -1, -1],
+    [2, 9, 5, 2, 5, 4, 2, 4, 10, 2, 10, 11, 2, 11, 7, -1],
+    [1, 11, 7, 1, 7, 2, 1, 2, 8, 1, 8, 4, -1, -1, -1, -1],
+    [0, 2, 7, 0, 7, 11, 0, 11, 1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 5, 1, 11, 7, 1, 7, 2, 1, 2, 8, 1, 8, 4, -1],
+    [1, 11, 7, 1, 7, 2, 1, 2, 9, 1, 9, 5, -1, -1, -1, -1],
+    [1, 10, 8, 1, 8, 2, 1, 2, 7, 1, 7, 5, -1, -1, -1, -1],
+    [0, 2, 7, 0, 7, 5, 0, 5, 1, 0, 1, 10, 0, 10, 4, -1],
+    [0, 9, 7, 0, 7, 2, 0, 2, 8, 0, 8, 10, 0, 10, 1, -1],
+    [1, 10, 4, 2, 9, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [2, 8, 4, 2, 4, 5, 2, 5, 7, -1, -1, -1, -1, -1, -1, -1],
+    [0, 2, 7, 0, 7, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 7, 0, 7, 2, 0, 2, 8, 0, 8, 4, -1, -1, -1, -1],
+    [2, 9, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [2, 9, 11, 2, 11, 10, 2, 10, 6, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 2, 9, 11, 2, 11, 10, 2, 10, 6, -1, -1, -1, -1],
+    [0, 2, 6, 0, 6, 10, 0, 10, 11, 0, 11, 5, -1, -1, -1, -1],
+    [2, 8, 4, 2, 4, 5, 2, 5, 11, 2, 11, 10, 2, 10, 6, -1],
+    [1, 11, 9, 1, 9, 2, 1, 2, 6, 1, 6, 4, -1, -1, -1, -1],
+    [0, 8, 6, 0, 6, 2, 0, 2, 9, 0, 9, 11, 0, 11, 1, -1],
+    [0, 2, 6, 0, 6, 4, 0, 4, 1, 0, 1, 11, 0, 11, 5, -1],
+    [1, 11, 5, 2, 8, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [1, 10, 6, 1, 6, 2, 1, 2, 9, 1, 9, 5, -1, -1, -1, -1],
+    [0, 8, 4, 1, 10, 6, 1, 6, 2, 1, 2, 9, 1, 9, 5, -1],
+    [0, 2, 6, 0, 6, 10, 0, 10, 1, -1, -1, -1, -1, -1, -1, -1],
+    [1, 10, 6, 1, 6, 2, 1, 2, 8, 1, 8, 4, -1, -1, -1, -1],
+    [2, 9, 5, 2, 5, 4, 2, 4, 6, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 6, 0, 6, 2, 0, 2, 9, 0, 9, 5, -1, -1, -1, -1],
+    [0, 2, 6, 0, 6, 4, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [2, 8, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [8, 10, 11, 8, 11, 9, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 11, 0, 11, 10, 0, 10, 4, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 10, 0, 10, 11, 0, 11, 5, -1, -1, -1, -1, -1, -1, -1],
+    [4, 10, 11, 4, 11, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [1, 11, 9, 1, 9, 8, 1, 8, 4, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 11, 0, 11, 1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, 0, 4, 1, 0, 1, 11, 0, 11, 5, -1, -1, -1, -1],
+    [1, 11, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [1, 10, 8, 1, 8, 9, 1, 9, 5, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 5, 0, 5, 1, 0, 1, 10, 0, 10, 4, -1, -1, -1, -1],
+    [0, 8, 10, 0, 10, 1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [1, 10, 4, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [4, 8, 9, 4, 9, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 9, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [0, 8, 4, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
+    [
+        -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+    ],
+];
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct SurfaceVertex {
+    pub position: [f32; 3],
+    /// Phase of the nearest cell, for the palette color
+    pub phase: u32,
+    pub normal: [f32; 3],
+    pub _pad: f32,
+}
+
+/// Membrane field value at a grid point: negative inside a membrane
+struct Sample {
+    value: f32,
+    /// Nearest cell
+    cell: u32,
+}
+
+/// An indexed triangle list
+#[derive(Default)]
+pub struct MeshData {
+    pub vertices: Vec<SurfaceVertex>,
+    pub indices: Vec<u32>,
+    /// Cell each vertex takes its phase from
+    pub vertex_cells: Vec<u32>,
+}
+
+/// Triangles of the membrane surface inside the box from `min` to `max`.
+/// `thickness` is the membrane thickness parameter; the surface sits halfway
+/// into the membrane, where its glow fades to half strength.
+pub fn extract(cells: &[HoneycombCell], min: Vec3, max: Vec3, thickness: f32) -> MeshData {
+    let points = RESOLUTION + 1;
+    let spacing = (max - min) / RESOLUTION as f32;
+    let iso = thickness * 0.5;
+    let index = |x: usize, y: usize, z: usize| (z * points + y) * points + x;
+    let coords = |i: usize| (i % points, i / points % points, i / (points * points));
+    let position =
+        |x: usize, y: usize, z: usize| min + spacing * Vec3::new(x as f32, y as f32, z as f32);
+
+    let mut samples = Vec::with_capacity(points * points * points);
+    for z in 0..points {
+        for y in 0..points {
+            for x in 0..points {
+                let (nearest, closest, second) = nearest_two(cells, position(x, y, z));
+                samples.push(Sample {
+                    value: (second - closest) * 0.5 - iso,
+                    cell: nearest as u32,
+                });
+            }
+        }
+    }
+
+    // Field gradient by central differences, one-sided at the faces
+    let gradient = |x: usize, y: usize, z: usize| {
+        let axis = |at: usize, step: &dyn Fn(usize) -> usize| {
+            let low = at.saturating_sub(1);
+            let high = (at + 1).min(RESOLUTION);
+            (samples[step(high)].value - samples[step(low)].value) / (high - low) as f32
+        };
+        Vec3::new(
+            axis(x, &|i| index(i, y, z)),
+            axis(y, &|i| index(x, i, z)),
+            axis(z, &|i| index(x, y, i)),
+        ) / spacing
+    };
+
+    // Surface vertices, one per crossed grid edge, shared by the triangles
+    // around it
+    let mut mesh = MeshData::default();
+    let mut edges: HashMap<(usize, usize), u32> = HashMap::new();
+    let mut crossing = |a: usize, b: usize| {
+        let key = (a.min(b), a.max(b));
+        *edges.entry(key).or_insert_with(|| {
+            let (sa, sb) = (&samples[a], &samples[b]);
+            let t = sa.value / (sa.value - sb.value);
+            let (pa, pb) = (coords(a), coords(b));
+            let normal = gradient(pa.0, pa.1, pa.2)
+                .lerp(gradient(pb.0, pb.1, pb.2), t)
+                .normalize_or_zero();
+            let cell = if t < 0.5 { sa.cell } else { sb.cell };
+            mesh.vertices.push(SurfaceVertex {
+                position: position(pa.0, pa.1, pa.2)
+                    .lerp(position(pb.0, pb.1, pb.2), t)
+                    .to_array(),
+                phase: cells[cell as usize].phase_index,
+                normal: normal.to_array(),
+                _pad: 0.0,
+            });
+            mesh.vertex_cells.push(cell);
+            mesh.vertices.len() as u32 - 1
+        })
+    };
+
+    let mut indices = Vec::new();
+    for z in 0..RESOLUTION {
+        for y in 0..RESOLUTION {
+            for x in 0..RESOLUTION {
+                let corners = CORNERS.map(|[dx, dy, dz]| index(x + dx, y + dy, z + dz));
+                let case = corners
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &c)| samples[c].value < 0.0)
+                    .fold(0, |case, (i, _)| case | 1 << i);
+                let crossed = EDGE_TABLE[case];
+                // Skip cubes the surface doesn't cross
+                if crossed == 0 {
+                    continue;
+                }
+
+                let mut vertices = [0; 12];
+                for (edge, &[a, b]) in EDGES.iter().enumerate() {
+                    if crossed & 1 << edge != 0 {
+                        vertices[edge] = crossing(corners[a], corners[b]);
+                    }
+                }
+                indices.extend(
+                    TRIANGLE_TABLE[case]
+                        .iter()
+                        .take_while(|&&edge| edge >= 0)
+                        .map(|&edge| vertices[edge as usize]),
+                );
+            }
+        }
+    }
+    mesh.indices = indices;
+    mesh
+}
+
+/// Nearest cell index and the distances to the nearest and second-nearest
+/// cell centers
+fn nearest_two(cells: &[HoneycombCell], point: Vec3) -> (usize, f32, f32) {
+    let mut nearest = 0;
+    let mut closest = f32::MAX;
+    let mut second = f32::MAX;
+    for (i, cell) in cells.iter().enumerate() {
+        let distance = cell.position.distance(point);
+        if distance < closest {
+            second = closest;
+            closest = distance;
+            nearest = i;
+        } else if distance < second {
+            second = distance;
+        }
+    }
+    (nearest, closest, second)
+}
+
+/// A built mesh and the thickness it was built for
+struct Mesh {
+    vertex_buffer: wgpu::Buffer,
+    /// Kept to rewrite the phases when cells change
+    vertices: Vec<SurfaceVertex>,
+    vertex_cells: Vec<u32>,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+    thickness: f32,
+}
+
+/// Rasterizes the membrane surface
+pub struct Isosurface {
+    pipeline: wgpu::RenderPipeline,
+    /// One per view, differing only in frame uniforms
+    bind_groups: Vec<wgpu::BindGroup>,
+    color_target: TextureId,
+    depth_target: TextureId,
+    cells: Vec<HoneycombCell>,
+    bounds: (Vec3, Vec3),
+    mesh: Option<Mesh>,
+}
+
+impl Isosurface {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        color_target: TextureId,
+        depth_target: TextureId,
+        frame_uniform_buffers: &[wgpu::Buffer],
+        raymarch_params_buffer: &wgpu::Buffer,
+        palette_view: &wgpu::TextureView,
+        world: &HoneycombWorld,
+        bounds: (Vec3, Vec3),
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Result<Self, crate::shader::ShaderError> {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Isosurface Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_groups = frame_uniform_buffers
+            .iter()
+            .map(|frame_uniform_buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Isosurface Bind Group"),
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: frame_uniform_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: raymarch_params_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(palette_view),
+                        },
+                    ],
+                })
+            })
+            .collect();
+
+        let source = crate::shader::preprocess("isosurface.wgsl", &[])?;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Isosurface Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Isosurface Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Isosurface Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<SurfaceVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x3,
+                        1 => Uint32,
+                        2 => Float32x3,
+                    ],
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: COLOR_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            // Membranes are seen from both sides
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache,
+        });
+
+        Ok(Self {
+            pipeline,
+            bind_groups,
+            color_target,
+            depth_target,
+            cells: world.cells.clone(),
+            bounds,
+            mesh: None,
+        })
+    }
+
+    /// Build the mesh if there is none yet or `thickness` changed
+    pub fn prepare(&mut self, device: &wgpu::Device, thickness: f32) {
+        if self
+            .mesh
+            .as_ref()
+            .is_some_and(|mesh| mesh.thickness == thickness)
+        {
+            return;
+        }
+        let start = web_time::Instant::now();
+        let data = extract(&self.cells, self.bounds.0, self.bounds.1, thickness);
+        tracing::info!(
+            "Extracted {} isosurface triangles in {:.0} ms",
+            data.indices.len() / 3,
+            start.elapsed().as_secs_f32() * 1000.0
+        );
+        // Padded so a mesh with no triangles still gets valid buffers
+        let buffer = |label: &str, contents: &[u8], usage: wgpu::BufferUsages| {
+            let mut contents = contents.to_vec();
+            contents.resize(contents.len().max(wgpu::COPY_BUFFER_ALIGNMENT as usize), 0);
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: &contents,
+                usage,
+            })
+        };
+        self.mesh = Some(Mesh {
+            vertex_buffer: buffer(
+                "Isosurface Vertex Buffer",
+                bytemuck::cast_slice(&data.vertices),
+                wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            ),
+            vertices: data.vertices,
+            vertex_cells: data.vertex_cells,
+            index_buffer: buffer(
+                "Isosurface Index Buffer",
+                bytemuck::cast_slice(&data.indices),
+                wgpu::BufferUsages::INDEX,
+            ),
+            index_count: data.indices.len() as u32,
+            thickness,
+        });
+    }
+
+    /// Take the phases of `cells`, which must sit where the current ones do,
+    /// recoloring the mesh in place
+    pub fn set_cells(&mut self, queue: &wgpu::Queue, cells: &[HoneycombCell]) {
+        self.cells = cells.to_vec();
+        let Some(mesh) = &mut self.mesh else {
+            return;
+        };
+        for (vertex, &cell) in mesh.vertices.iter_mut().zip(&mesh.vertex_cells) {
+            vertex.phase = cells[cell as usize].phase_index;
+        }
+        if !mesh.vertices.is_empty() {
+            queue.write_buffer(&mesh.vertex_buffer, 0, bytemuck::cast_slice(&mesh.vertices));
+        }
+    }
+
+    /// Take cells that moved, and the bounds around them, dropping the mesh
+    /// until the next `prepare`
+    pub fn set_world(&mut self, cells: &[HoneycombCell], bounds: (Vec3, Vec3)) {
+        self.cells = cells.to_vec();
+        self.bounds = bounds;
+        self.mesh = None;
+    }
+
+    /// Triangles in the current mesh, if one has been built
+    pub fn triangle_count(&self) -> Option<u32> {
+        self.mesh.as_ref().map(|mesh| mesh.index_count / 3)
+    }
+
+    /// Clear the targets and draw the surface into each view. `viewports` are
+    /// x, y, width and height in pixels of the targets.
+    pub fn encode<G: Copy + PartialEq>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        graph: &RenderGraph<G>,
+        viewports: &[[f32; 4]],
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Isosurface Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: graph.view(self.color_target, Access::Attachment),
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.02,
+                        g: 0.02,
+                        b: 0.03,
+                        a: 1.0,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: graph.view(self.depth_target, Access::Attachment),
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes,
+            occlusion_query_set: None,
+        });
+        let Some(mesh) = &self.mesh else {
+            return;
+        };
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        for (&[x, y, width, height], bind_group) in viewports.iter().zip(&self.bind_groups) {
+            render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! KD-tree over the Voronoi seeds, for nearest-seed queries on the CPU. The
+//! shaders find the cell a point lies in by its nearest seed; the tree
+//! answers the same question, and the k nearest seeds, in logarithmic time
+//! instead of testing every seed.
+
+use glam::Vec3;
+
+use crate::world::HoneycombCell;
+
+/// Seeds in a balanced implicit tree: the seed in the middle of any range of
+/// `order` splits the rest of the range along an axis, the lower half
+/// before it and the upper half after
+#[derive(Clone, Debug, Default)]
+pub struct KdTree {
+    /// Indices into the cells, in tree order
+    order: Vec<u32>,
+    /// Seed positions, in tree order
+    positions: Vec<Vec3>,
+    /// Axis each middle seed splits on, in tree order
+    axes: Vec<u8>,
+}
+
+impl KdTree {
+    pub fn build(cells: &[HoneycombCell]) -> Self {
+        let mut order: Vec<u32> = (0..cells.len() as u32).collect();
+        let mut axes = vec![0; cells.len()];
+        Self::build_range(cells, &mut order, &mut axes);
+        Self {
+            positions: order.iter().map(|&i| cells[i as usize].position).collect(),
+            order,
+            axes,
+        }
+    }
+
+    /// Arrange `order` as a subtree, splitting along the widest axis
+    fn build_range(cells: &[HoneycombCell], order: &mut [u32], axes: &mut [u8]) {
+        if order.len() <= 1 {
+            return;
+        }
+        let (min, max) = order.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), &i| {
+                let position = cells[i as usize].position;
+                (min.min(position), max.max(position))
+            },
+        );
+        let extent = max - min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        let middle = order.len() / 2;
+        order.select_nth_unstable_by(middle, |&a, &b| {
+            cells[a as usize].position[axis].total_cmp(&cells[b as usize].position[axis])
+        });
+        axes[middle] = axis as u8;
+        let (lower, rest) = order.split_at_mut(middle);
+        let (lower_axes, rest_axes) = axes.split_at_mut(middle);
+        Self::build_range(cells, lower, lower_axes);
+        Self::build_range(cells, &mut rest[1..], &mut rest_axes[1..]);
+    }
+
+    /// Indices of the `k` seeds nearest `point`, nearest first; fewer if
+    /// there aren't that many
+    pub fn nearest(&self, point: Vec3, k: usize) -> Vec<usize> {
+        let mut found = Vec::with_capacity(k + 1);
+        if k > 0 {
+            self.search(point, k, 0, self.order.len(), &mut found);
+        }
+        found.into_iter().map(|(_, i)| i).collect()
+    }
+
+    /// Add seeds in `start..end` nearer than those in `found`, which holds
+    /// up to `k` (squared distance, cell index) pairs sorted by distance
+    fn search(
+        &self,
+        point: Vec3,
+        k: usize,
+        start: usize,
+        end: usize,
+        found: &mut Vec<(f32, usize)>,
+    ) {
+        if start >= end {
+            return;
+        }
+        let middle = start + (end - start) / 2;
+        let position = self.positions[middle];
+
+        let distance = point.distance_squared(position);
+        if found.len() < k || distance < found[found.len() - 1].0 {
+            let at = found.partition_point(|&(d, _)| d <= distance);
+            found.insert(at, (distance, self.order[middle] as usize));
+            found.truncate(k);
+        }
+
+        let axis = self.axes[middle] as usize;
+        let offset = point[axis] - position[axis];
+        let (near, far) = if offset < 0.0 {
+            ((start, middle), (middle + 1, end))
+        } else {
+            ((middle + 1, end), (start, middle))
+        };
+        self.search(point, k, near.0, near.1, found);
+        // The far side can only hold a nearer seed if the splitting plane is
+        // nearer than the furthest seed found
+        if found.len() < k || offset * offset < found[found.len() - 1].0 {
+            self.search(point, k, far.0, far.1, found);
+        }
+    }
+}
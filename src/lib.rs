@@ -1,22 +1,241 @@
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(feature = "viewer", target_arch = "wasm32"))]
 use wasm_bindgen::prelude::*;
 
+// The shared viewer code calls into the platform's own modules, which need
+// that platform's feature
+#[cfg(all(
+    feature = "viewer",
+    target_arch = "wasm32",
+    not(feature = "wasm-viewer")
+))]
+compile_error!("building the viewer for wasm32 needs the `wasm-viewer` feature");
+#[cfg(all(
+    feature = "viewer",
+    not(target_arch = "wasm32"),
+    not(feature = "native-viewer")
+))]
+compile_error!("building the viewer natively needs the `native-viewer` feature");
+
+#[cfg(feature = "viewer")]
 mod app;
+#[cfg(feature = "viewer")]
+mod attract;
+#[cfg(feature = "viewer")]
+mod automaton;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+mod bench;
+#[cfg(feature = "viewer")]
+mod builder;
+#[cfg(any(feature = "viewer", feature = "world-only"))]
+pub mod bvh;
+#[cfg(feature = "viewer")]
 mod camera;
+#[cfg(feature = "viewer")]
+mod capture;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+mod cli;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+mod config;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+mod control;
+#[cfg(feature = "viewer")]
+mod denoise;
+#[cfg(feature = "viewer")]
+mod engine;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+mod gif;
+#[cfg(feature = "viewer")]
+mod gizmo;
+#[cfg(feature = "viewer")]
 mod gpu;
+#[cfg(feature = "viewer")]
+mod graph;
+#[cfg(feature = "viewer")]
+mod grid_overlay;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+pub mod headless;
+#[cfg(feature = "viewer")]
+mod heat;
+#[cfg(feature = "viewer")]
+mod hud;
+#[cfg(feature = "viewer")]
 mod input;
-mod world;
-
-#[cfg(target_arch = "wasm32")]
-#[wasm_bindgen(start)]
-pub async fn wasm_main() {
-    console_error_panic_hook::set_once();
-    console_log::init_with_level(log::Level::Info).expect("Failed to init logger");
-    app::run().await;
+#[cfg(feature = "viewer")]
+mod isosurface;
+#[cfg(any(feature = "viewer", feature = "world-only"))]
+pub mod kdtree;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+mod loop_export;
+#[cfg(feature = "viewer")]
+pub mod membrane;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+pub mod osc;
+#[cfg(feature = "viewer")]
+mod overlay;
+#[cfg(feature = "viewer")]
+mod pacer;
+#[cfg(feature = "viewer")]
+mod palette;
+#[cfg(feature = "viewer")]
+mod params;
+#[cfg(feature = "viewer")]
+mod pipeline_cache;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+mod power;
+#[cfg(feature = "viewer")]
+mod preset;
+#[cfg(feature = "viewer")]
+mod profiler;
+#[cfg(feature = "viewer")]
+mod reaction;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+mod reload;
+#[cfg(all(feature = "viewer", target_arch = "wasm32"))]
+mod report;
+#[cfg(feature = "viewer")]
+mod resolution;
+#[cfg(feature = "viewer")]
+mod script;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+mod session;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+mod settings;
+#[cfg(feature = "viewer")]
+mod shader;
+#[cfg(feature = "viewer")]
+mod shadow;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+mod still;
+#[cfg(all(feature = "viewer", target_arch = "wasm32"))]
+mod storage;
+#[cfg(feature = "viewer")]
+mod stream;
+#[cfg(feature = "viewer")]
+mod text;
+#[cfg(feature = "viewer")]
+mod tiles;
+#[cfg(feature = "viewer")]
+mod timeline;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+mod trace;
+#[cfg(feature = "viewer")]
+mod transfer;
+#[cfg(feature = "viewer")]
+pub mod uniforms;
+#[cfg(all(feature = "viewer", target_arch = "wasm32"))]
+mod url;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+mod video;
+#[cfg(any(feature = "viewer", feature = "world-only"))]
+mod voronoi;
+#[cfg(all(feature = "viewer", target_arch = "wasm32"))]
+mod web;
+#[cfg(any(feature = "viewer", feature = "world-only"))]
+pub mod world;
+
+#[cfg(feature = "viewer")]
+pub use builder::{Features, Quality, VendekBuilder};
+#[cfg(feature = "viewer")]
+pub use engine::{EngineConfig, VendekEngine};
+#[cfg(feature = "viewer")]
+pub use gpu::GpuError;
+#[cfg(feature = "viewer")]
+pub use params::ParamError;
+#[cfg(feature = "viewer")]
+pub use preset::Preset;
+#[cfg(feature = "viewer")]
+pub use text::Label;
+
+/// Create a viewer and return its handle. May be called several times to put
+/// independent viewers on one page. `options` is optional:
+/// `{ container: "id" | Element, width, height, autoResize, seed, cells,
+/// phases, preset, attract, syncHash, persist }`. Settings in the page URL override these,
+/// and those stored with `persist`; see `url` and `storage`.
+#[cfg(all(feature = "viewer", target_arch = "wasm32"))]
+#[wasm_bindgen]
+pub fn wasm_main(options: JsValue) -> Result<web::VendekViewer, JsError> {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| {
+        report::install_panic_hook();
+        console_log::init_with_level(log::Level::Info).expect("Failed to init logger");
+    });
+
+    let mut options = web::ViewerOptions::from_js(&options)?;
+    let viewer = web::VendekViewer::new();
+    if options.persist {
+        for (name, value) in storage::load_params() {
+            let _ = viewer.set_param(name, value);
+        }
+    }
+    url::UrlView::parse(&url::read_query()).apply(&mut options, &viewer);
+    if options.sync_hash {
+        url::UrlView::parse(&url::read_hash()).apply(&mut options, &viewer);
+    }
+    if let Some(seconds) = options.attract {
+        viewer.set_attract(seconds);
+    }
+    app::open_viewer(app::ViewerRequest {
+        options,
+        link: viewer.link(),
+    })
+    .map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(viewer)
 }
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
 pub fn native_main() {
     env_logger::init();
-    pollster::block_on(app::run());
+
+    let args = match cli::Args::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if args.list_adapters {
+        gpu::list_adapters(&args.adapter);
+        return;
+    }
+
+    let trace = match args.trace.as_deref().map(trace::record).transpose() {
+        Ok(trace) => trace,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let result: Result<(), Box<dyn std::error::Error>> =
+        match (args.still, args.loop_export, args.session) {
+            (Some(options), _, _) => {
+                still::run(args.adapter, args.preset, args.stream, options).map_err(Into::into)
+            }
+            (_, Some(options), _) => {
+                loop_export::run(args.adapter, args.preset, args.stream, options)
+                    .map_err(Into::into)
+            }
+            (_, _, Some(options)) => {
+                session::run(args.adapter, args.preset, args.stream, options).map_err(Into::into)
+            }
+            (None, None, None) => pollster::block_on(app::run(
+                args.adapter,
+                args.preset,
+                args.stream,
+                args.bench,
+                app::RemoteControl {
+                    osc: args.osc,
+                    stdin: args.stdin,
+                },
+                args.script,
+                args.timeline,
+            ))
+            .map_err(Into::into),
+        };
+    // Finish the trace before exiting, which would skip the guard's drop
+    drop(trace);
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,283 @@
+//! `vendek export-loop`: render one period of the membranes' oscillation
+//! offscreen and write it as an animated GIF or WebP that loops without a
+//! seam, for embedding where a video won't play. Each phase's membranes
+//! pulse at their own frequency, so no length brings them all back exactly;
+//! unless a length is given, the one from two to twelve seconds after which
+//! they are closest to where they started is used. The ripples, wisps and
+//! phase drift never repeat at all, so over the last quarter of the loop
+//! each frame fades into the one a loop earlier, arriving back at the
+//! first frame. The world, parameters and camera are the viewer's, as for
+//! `render-still`.
+//!
+//! GIF frames last whole hundredths of a second, so the frame rate is
+//! rounded to one that fits (25 and 50 fit exactly). GIFs are encoded in
+//! process by the `gif` module; WebP goes through `ffmpeg`, found as for
+//! recordings.
+
+use std::f32::consts::TAU;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::gif;
+use crate::gpu::{AdapterOptions, GpuError};
+use crate::preset::Preset;
+use crate::still::ViewerScene;
+use crate::video::{RecordingConfig, VideoFormat, VideoRecorder};
+use crate::world::HoneycombWorld;
+
+/// Shortest and longest loop the search considers, in seconds
+const MIN_LENGTH: f32 = 2.0;
+const MAX_LENGTH: f32 = 12.0;
+/// Share of the loop spent fading into the frames a loop earlier
+const FADE: f32 = 0.25;
+
+#[derive(Clone, Debug)]
+pub struct LoopOptions {
+    /// Seconds the loop lasts, or `None` to find the length the membranes
+    /// repeat after
+    pub length: Option<f32>,
+    /// Frames per second. GIF frames last whole hundredths of a second, so
+    /// GIFs round this to the nearest rate that fits.
+    pub fps: u32,
+    /// Samples per pixel accumulated for each frame
+    pub samples: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Seconds into the world's animation the loop starts at
+    pub time: f32,
+    /// Where the animation is written; the extension, `.gif` or `.webp`,
+    /// picks the format
+    pub output: PathBuf,
+}
+
+impl Default for LoopOptions {
+    fn default() -> Self {
+        Self {
+            length: None,
+            fps: 20,
+            samples: 1,
+            width: 480,
+            height: 270,
+            time: 0.0,
+            output: PathBuf::from("vendek-loop.gif"),
+        }
+    }
+}
+
+/// Why a loop could not be exported
+#[derive(Debug)]
+pub enum LoopError {
+    Gpu(GpuError),
+    Io(io::Error),
+    /// `ffmpeg`, which encodes WebP, could not be started
+    Ffmpeg(io::Error),
+    /// The output's extension is neither `.gif` nor `.webp`
+    Format(PathBuf),
+}
+
+impl fmt::Display for LoopError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoopError::Gpu(e) => e.fmt(f),
+            LoopError::Io(e) => write!(f, "Could not write the animation: {}", e),
+            LoopError::Ffmpeg(e) => write!(f, "Could not start ffmpeg: {}", e),
+            LoopError::Format(path) => write!(
+                f,
+                "Can't tell the format of {}; use a .gif or .webp extension",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoopError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoopError::Gpu(e) => Some(e),
+            LoopError::Io(e) | LoopError::Ffmpeg(e) => Some(e),
+            LoopError::Format(_) => None,
+        }
+    }
+}
+
+impl From<GpuError> for LoopError {
+    fn from(e: GpuError) -> Self {
+        LoopError::Gpu(e)
+    }
+}
+
+impl From<io::Error> for LoopError {
+    fn from(e: io::Error) -> Self {
+        LoopError::Io(e)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LoopFormat {
+    /// Encoded here, with frames lasting this many hundredths of a second
+    Gif { delay: u16 },
+    /// Encoded by `ffmpeg`
+    Webp,
+}
+
+impl LoopFormat {
+    fn for_output(options: &LoopOptions) -> Result<Self, LoopError> {
+        let extension = options
+            .output
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("gif") => {
+                let delay = (100.0 / options.fps.max(1) as f32).round() as u16;
+                // Browsers slow frames shorter than two hundredths down
+                Ok(LoopFormat::Gif {
+                    delay: delay.max(2),
+                })
+            }
+            Some("webp") => Ok(LoopFormat::Webp),
+            _ => Err(LoopError::Format(options.output.clone())),
+        }
+    }
+
+    /// Seconds of animation between frames
+    fn frame_time(self, fps: u32) -> f32 {
+        match self {
+            LoopFormat::Gif { delay } => delay as f32 / 100.0,
+            LoopFormat::Webp => 1.0 / fps.max(1) as f32,
+        }
+    }
+}
+
+/// The whole number of frames, each `frame_time` long, after which the
+/// phases' membrane oscillations come closest to repeating, and how far
+/// they are off then as the root mean square of their phase errors in
+/// radians
+fn detect_period(world: &HoneycombWorld, frame_time: f32) -> (u32, f32) {
+    let shortest = (MIN_LENGTH / frame_time).ceil().max(1.0) as u32;
+    let longest = (MAX_LENGTH / frame_time).floor().max(shortest as f32) as u32;
+    let error = |frames: u32| {
+        let length = frames as f32 * frame_time;
+        let sum: f32 = world
+            .phases
+            .iter()
+            .map(|phase| {
+                let turns = phase.membrane_params.x * length / TAU;
+                let off = (turns - turns.round()) * TAU;
+                off * off
+            })
+            .sum();
+        (sum / world.phases.len().max(1) as f32).sqrt()
+    };
+    (shortest..=longest)
+        .map(|frames| (frames, error(frames)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or((shortest, 0.0))
+}
+
+/// Frames the animation is written to
+enum Sink {
+    Gif { frames: Vec<Vec<u8>>, delay: u16 },
+    Webp(VideoRecorder),
+}
+
+pub fn run(
+    adapter: AdapterOptions,
+    preset: Option<Preset>,
+    stream: Option<u32>,
+    options: LoopOptions,
+) -> Result<(), LoopError> {
+    let format = LoopFormat::for_output(&options)?;
+    let ViewerScene {
+        config,
+        mut gpu,
+        camera,
+        world,
+        ..
+    } = ViewerScene::open(&adapter, preset, stream, options.width, options.height)?;
+
+    let frame_time = format.frame_time(options.fps);
+    let frames = match options.length {
+        Some(length) => (length / frame_time).round().max(1.0) as u32,
+        None => {
+            let (frames, error) = detect_period(&world, frame_time);
+            tracing::info!(
+                "Membranes repeat after {:.2}s to within {:.2} radians",
+                frames as f32 * frame_time,
+                error
+            );
+            frames
+        }
+    };
+    let fade = ((frames as f32 * FADE).round() as u32).min(frames - 1);
+    let length = frames as f32 * frame_time;
+
+    let mut sink = match format {
+        LoopFormat::Gif { delay } => Sink::Gif {
+            frames: Vec::with_capacity(frames as usize),
+            delay,
+        },
+        LoopFormat::Webp => {
+            let recording = RecordingConfig {
+                fps: options.fps,
+                format: VideoFormat::Webp,
+                ffmpeg: config.recording.ffmpeg,
+            };
+            Sink::Webp(
+                VideoRecorder::start(&recording, &options.output, options.width, options.height)
+                    .map_err(LoopError::Ffmpeg)?,
+            )
+        }
+    };
+
+    let start = Instant::now();
+    for frame in 0..frames {
+        eprint!("\rFrame {}/{}", frame + 1, frames);
+        let _ = io::stderr().flush();
+        let time = options.time + frame as f32 * frame_time;
+        let mut rgba = gpu.render_still(&camera, time, options.samples, |_| {});
+        // Fade toward the frame a loop earlier, which the first frame
+        // follows on from
+        if frame + fade >= frames {
+            let faded = frame + fade - frames + 1;
+            let weight = faded as f32 / (fade + 1) as f32;
+            let earlier = gpu.render_still(&camera, time - length, options.samples, |_| {});
+            for (pixel, earlier) in rgba.iter_mut().zip(earlier) {
+                let mixed = *pixel as f32 + (earlier as f32 - *pixel as f32) * weight;
+                *pixel = mixed.round() as u8;
+            }
+        }
+        match &mut sink {
+            Sink::Gif { frames, .. } => frames.push(rgba),
+            Sink::Webp(recorder) => recorder.push(&rgba)?,
+        }
+    }
+    eprintln!();
+
+    match sink {
+        Sink::Gif { frames, delay } => {
+            gif::save_gif(
+                &options.output,
+                options.width,
+                options.height,
+                delay,
+                &frames,
+            )?;
+        }
+        Sink::Webp(recorder) => {
+            recorder.finish()?;
+        }
+    }
+    println!(
+        "Wrote {} ({}x{}, {} frames looping every {:.2}s, {:.1}s)",
+        options.output.display(),
+        options.width,
+        options.height,
+        frames,
+        length,
+        start.elapsed().as_secs_f32()
+    );
+    Ok(())
+}
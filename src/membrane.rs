@@ -0,0 +1,55 @@
+//! How the membrane between each pair of phases looks. Every ordered pair
+//! of phases gets its own thickness, glow and tint, uploaded as a table of
+//! `phases × phases` entries that the raymarch indexes by the phases of the
+//! two cells meeting at a wall, so boundaries between different phases
+//! look distinct. The layout must match `MembranePair` in
+//! `shaders/types.wgsl`; `tests/layout.rs` checks it.
+
+use glam::Vec3;
+
+use crate::world::VendekPhase;
+
+/// Spread of the phases' membrane oscillation frequencies
+const FREQUENCY_RANGE: f32 = 4.5;
+
+/// The membrane between two phases, as the shaders read it
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct MembranePair {
+    /// Tint multiplying the membrane glow's color
+    pub color: Vec3,
+    /// Thickness relative to the `membrane_thickness` parameter
+    pub thickness: f32,
+    /// Brightness relative to the `membrane_glow` parameter
+    pub glow: f32,
+    pub _pad: [f32; 3],
+}
+
+impl MembranePair {
+    /// Walls between phases whose membranes oscillate at similar rates are
+    /// thin and faint, and grow thicker and brighter the further apart the
+    /// rates are; strong coupling softens them again. The tint comes from
+    /// the two phases' scattering, so each pair has its own.
+    pub fn between(a: &VendekPhase, b: &VendekPhase) -> Self {
+        let contrast =
+            ((a.membrane_params.x - b.membrane_params.x).abs() / FREQUENCY_RANGE).min(1.0);
+        let coupling = (a.membrane_params.w * b.membrane_params.w).sqrt();
+        let scattering = (a.scattering.truncate() + b.scattering.truncate()) * 0.5;
+        Self {
+            color: Vec3::ONE.lerp(scattering / scattering.max_element().max(1e-4), 0.5),
+            thickness: 0.6 + 0.9 * contrast,
+            glow: (0.6 + 0.8 * contrast) * (1.2 - 0.4 * coupling),
+            _pad: [0.0; 3],
+        }
+    }
+}
+
+/// The pair table for `phases`, the entry for phases `a` and `b` at
+/// `a * phases.len() + b`. Symmetric, since a wall looks the same from
+/// either side.
+pub fn pair_table(phases: &[VendekPhase]) -> Vec<MembranePair> {
+    let n = phases.len();
+    (0..n * n)
+        .map(|i| MembranePair::between(&phases[i / n], &phases[i % n]))
+        .collect()
+}
@@ -0,0 +1,255 @@
+//! OSC control: a UDP listener for Open Sound Control messages, so the viewer
+//! can be driven from TouchOSC, Max/MSP or show-control software. Messages
+//! are decoded on a background thread and queued for the render loop.
+//!
+//! Addresses live under `/vendek/`:
+//!
+//! - `/vendek/<param> <value>` sets a runtime parameter by its JavaScript
+//!   name, e.g. `/vendek/membraneGlow 0.8`
+//! - `/vendek/camera/orbit <dx> <dy>`, `/vendek/camera/pan <dx> <dy>` and
+//!   `/vendek/camera/zoom <amount>` move the camera as the mouse would, with
+//!   deltas in pixels and zoom in wheel lines
+//!
+//! Arguments may be floats, integers, doubles, 64-bit integers or the
+//! true/false tags, and must be finite. Bundles are unpacked and their
+//! messages applied at once, ignoring time tags; a message that can't be
+//! decoded or applied is logged and skipped without its neighbours.
+
+use std::fmt;
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex};
+
+use glam::Vec2;
+
+/// Prefix of every address the viewer answers to
+const PREFIX: &str = "/vendek/";
+/// Largest datagram read; OSC over UDP stays well below this
+const MAX_PACKET: usize = 65536;
+
+/// A decoded OSC message with its numeric arguments
+#[derive(Clone, Debug, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<f64>,
+}
+
+/// What a message asks the viewer to do
+#[derive(Clone, Debug, PartialEq)]
+pub enum OscCommand {
+    SetParam(String, f64),
+    Orbit(Vec2),
+    Pan(Vec2),
+    Zoom(f32),
+}
+
+/// Why a packet or message was rejected
+#[derive(Debug)]
+pub struct InvalidOsc(pub String);
+
+impl fmt::Display for InvalidOsc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid OSC: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidOsc {}
+
+/// Listens on a UDP port and queues the commands it receives
+pub struct OscListener {
+    commands: Arc<Mutex<Vec<OscCommand>>>,
+}
+
+impl OscListener {
+    /// Bind to `port` on every interface and start receiving, calling
+    /// `wake` after queueing each packet's commands
+    pub fn bind(port: u16, wake: impl Fn() + Send + 'static) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let queue = commands.clone();
+        std::thread::Builder::new()
+            .name("osc".to_string())
+            .spawn(move || receive(socket, queue, wake))?;
+        Ok(Self { commands })
+    }
+
+    /// Commands received since the last call, oldest first
+    pub fn take_commands(&self) -> Vec<OscCommand> {
+        std::mem::take(&mut *self.commands.lock().unwrap())
+    }
+}
+
+fn receive(socket: UdpSocket, queue: Arc<Mutex<Vec<OscCommand>>>, wake: impl Fn()) {
+    let mut buffer = vec![0; MAX_PACKET];
+    loop {
+        let (length, from) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(e) => {
+                tracing::warn!("OSC receive failed: {}", e);
+                continue;
+            }
+        };
+        let mut received = false;
+        for message in decode_packet(&buffer[..length]) {
+            match message.and_then(|message| OscCommand::from_message(&message)) {
+                Ok(command) => {
+                    queue.lock().unwrap().push(command);
+                    received = true;
+                }
+                Err(e) => tracing::warn!("Ignoring OSC from {}: {}", from, e),
+            }
+        }
+        if received {
+            wake();
+        }
+    }
+}
+
+impl OscCommand {
+    pub fn from_message(message: &OscMessage) -> Result<Self, InvalidOsc> {
+        let Some(path) = message.address.strip_prefix(PREFIX) else {
+            return Err(InvalidOsc(format!(
+                "address `{}` is not under {}",
+                message.address, PREFIX
+            )));
+        };
+        let arg = |i: usize| {
+            let value = message.args.get(i).copied().ok_or_else(|| {
+                InvalidOsc(format!("`{}` needs {} arguments", message.address, i + 1))
+            })?;
+            if !value.is_finite() {
+                return Err(InvalidOsc(format!(
+                    "`{}` argument {} is not finite",
+                    message.address,
+                    i + 1
+                )));
+            }
+            Ok(value)
+        };
+        Ok(match path {
+            "camera/orbit" => OscCommand::Orbit(Vec2::new(arg(0)? as f32, arg(1)? as f32)),
+            "camera/pan" => OscCommand::Pan(Vec2::new(arg(0)? as f32, arg(1)? as f32)),
+            "camera/zoom" => OscCommand::Zoom(arg(0)? as f32),
+            name => OscCommand::SetParam(name.to_string(), arg(0)?),
+        })
+    }
+}
+
+/// The messages in a packet: one message, or every message in a bundle and
+/// the bundles nested in it. Each element that can't be decoded is an error
+/// in its place, so one bad message doesn't cost the rest of the bundle;
+/// only an element whose size is wrong ends the bundle, as nothing after it
+/// can be found.
+pub fn decode_packet(packet: &[u8]) -> Vec<Result<OscMessage, InvalidOsc>> {
+    let mut messages = Vec::new();
+    decode_into(packet, &mut messages);
+    messages
+}
+
+fn decode_into(packet: &[u8], messages: &mut Vec<Result<OscMessage, InvalidOsc>>) {
+    if !packet.starts_with(b"#bundle\0") {
+        messages.push(decode_message(packet));
+        return;
+    }
+    let mut reader = Reader {
+        data: packet,
+        at: 0,
+    };
+    // Past the tag and the time tag
+    if let Err(e) = reader.take(16) {
+        messages.push(Err(e));
+        return;
+    }
+    while reader.at < packet.len() {
+        let element = reader.i32().and_then(|size| {
+            let size = usize::try_from(size)
+                .map_err(|_| InvalidOsc(format!("negative bundle element size {}", size)))?;
+            reader.take(size)
+        });
+        match element {
+            Ok(element) => decode_into(element, messages),
+            Err(e) => {
+                messages.push(Err(e));
+                return;
+            }
+        }
+    }
+}
+
+fn decode_message(packet: &[u8]) -> Result<OscMessage, InvalidOsc> {
+    let mut reader = Reader {
+        data: packet,
+        at: 0,
+    };
+    let address = reader.string()?;
+    if !address.starts_with('/') {
+        return Err(InvalidOsc(format!(
+            "address `{}` doesn't start with /",
+            address
+        )));
+    }
+    // Very old senders omit the type tags; treat that as no arguments
+    let tags = if reader.at < packet.len() {
+        reader.string()?
+    } else {
+        ",".to_string()
+    };
+    let Some(tags) = tags.strip_prefix(',') else {
+        return Err(InvalidOsc(format!(
+            "type tags `{}` don't start with a comma",
+            tags
+        )));
+    };
+    let mut args = Vec::with_capacity(tags.len());
+    for tag in tags.chars() {
+        args.push(match tag {
+            'f' => f32::from_bits(reader.i32()? as u32) as f64,
+            'i' => reader.i32()? as f64,
+            'd' => f64::from_bits(reader.i64()? as u64),
+            'h' => reader.i64()? as f64,
+            'T' => 1.0,
+            'F' => 0.0,
+            _ => return Err(InvalidOsc(format!("unsupported argument type `{}`", tag))),
+        });
+    }
+    Ok(OscMessage { address, args })
+}
+
+/// Big-endian fields of an OSC packet, strings padded to four bytes
+struct Reader<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], InvalidOsc> {
+        let bytes = self
+            .data
+            .get(self.at..)
+            .and_then(|rest| rest.get(..length))
+            .ok_or_else(|| InvalidOsc("packet ends early".to_string()))?;
+        self.at += length;
+        Ok(bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, InvalidOsc> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i64(&mut self) -> Result<i64, InvalidOsc> {
+        Ok(i64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, InvalidOsc> {
+        let rest = &self.data[self.at.min(self.data.len())..];
+        let length = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| InvalidOsc("unterminated string".to_string()))?;
+        let text = std::str::from_utf8(&rest[..length])
+            .map_err(|_| InvalidOsc("string is not UTF-8".to_string()))?
+            .to_string();
+        // The terminator, then padding to a multiple of four
+        self.take((length + 4) & !3)?;
+        Ok(text)
+    }
+}
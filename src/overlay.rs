@@ -0,0 +1,410 @@
+//! Stats overlay toggled with F3: text lines and a frame-time graph, rasterized
+//! on the CPU into a small texture and drawn over the top-left of the frame.
+
+use std::collections::VecDeque;
+
+use wgpu::util::DeviceExt;
+
+/// Frames kept for the frame-time graph
+pub const HISTORY_LEN: usize = 120;
+
+// Texture layout, in texels
+const WIDTH: u32 = 216;
+const HEIGHT: u32 = 96;
+const PADDING: u32 = 4;
+const LINE_HEIGHT: u32 = 9;
+const GLYPH_ADVANCE: u32 = 6;
+const GRAPH_HEIGHT: u32 = 40;
+/// Frame time at the top of the graph
+const GRAPH_MAX_MS: f32 = 50.0;
+/// On-screen pixels per texel
+const SCALE: f32 = 2.0;
+/// Distance from the window corner, in pixels
+const MARGIN: f32 = 8.0;
+
+const BACKGROUND: [u8; 4] = [0, 0, 0, 170];
+const TEXT: [u8; 4] = [235, 235, 235, 255];
+const GUIDE: [u8; 4] = [90, 90, 100, 255];
+
+/// Recent frame times in seconds, oldest first
+pub struct FrameHistory {
+    times: VecDeque<f32>,
+}
+
+impl FrameHistory {
+    pub fn new() -> Self {
+        Self {
+            times: VecDeque::with_capacity(HISTORY_LEN),
+        }
+    }
+
+    pub fn push(&mut self, dt: f32) {
+        if self.times.len() == HISTORY_LEN {
+            self.times.pop_front();
+        }
+        self.times.push_back(dt);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = f32> + '_ {
+        self.times.iter().copied()
+    }
+
+    pub fn average(&self) -> f32 {
+        if self.times.is_empty() {
+            0.0
+        } else {
+            self.times.iter().sum::<f32>() / self.times.len() as f32
+        }
+    }
+
+    pub fn max(&self) -> f32 {
+        self.times.iter().copied().fold(0.0, f32::max)
+    }
+}
+
+impl Default for FrameHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct OverlayUniforms {
+    /// Left, top, right, bottom in clip space
+    rect: [f32; 4],
+}
+
+pub struct Overlay {
+    texture: wgpu::Texture,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    pixels: Vec<u8>,
+    /// Set by `update`, cleared when drawn, so the overlay only appears on
+    /// frames the app asked for it
+    pending: bool,
+}
+
+impl Overlay {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Overlay Texture"),
+            size: wgpu::Extent3d {
+                width: WIDTH,
+                height: HEIGHT,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Nearest filtering keeps the glyphs crisp at integer scales
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Overlay Sampler"),
+            ..Default::default()
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Overlay Uniforms Buffer"),
+            contents: bytemuck::cast_slice(&[OverlayUniforms { rect: [0.0; 4] }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Overlay Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Overlay Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Overlay Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/overlay.wgsl").into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Overlay Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Overlay Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache,
+        });
+
+        Self {
+            texture,
+            uniform_buffer,
+            bind_group,
+            pipeline,
+            pixels: vec![0; (WIDTH * HEIGHT * 4) as usize],
+            pending: false,
+        }
+    }
+
+    /// Redraw the overlay contents and show it on the next frame
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        screen: winit::dpi::PhysicalSize<u32>,
+        lines: &[String],
+        history: &FrameHistory,
+    ) {
+        self.rasterize(lines, history);
+
+        queue.write_texture(
+            self.texture.as_image_copy(),
+            &self.pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(WIDTH * 4),
+                rows_per_image: Some(HEIGHT),
+            },
+            wgpu::Extent3d {
+                width: WIDTH,
+                height: HEIGHT,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        // Pixel rectangle in the top-left corner, converted to clip space
+        let to_clip_x = |x: f32| x / screen.width.max(1) as f32 * 2.0 - 1.0;
+        let to_clip_y = |y: f32| 1.0 - y / screen.height.max(1) as f32 * 2.0;
+        let rect = [
+            to_clip_x(MARGIN),
+            to_clip_y(MARGIN),
+            to_clip_x(MARGIN + WIDTH as f32 * SCALE),
+            to_clip_y(MARGIN + HEIGHT as f32 * SCALE),
+        ];
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[OverlayUniforms { rect }]),
+        );
+
+        self.pending = true;
+    }
+
+    /// Draw into `target` over what is already there, if `update` was called
+    /// since the last draw
+    pub fn encode(&mut self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        if !std::mem::take(&mut self.pending) {
+            return;
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overlay Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+
+    fn rasterize(&mut self, lines: &[String], history: &FrameHistory) {
+        for pixel in self.pixels.as_chunks_mut::<4>().0 {
+            *pixel = BACKGROUND;
+        }
+
+        for (row, line) in lines.iter().enumerate() {
+            let y = PADDING + row as u32 * LINE_HEIGHT;
+            for (column, c) in line.chars().enumerate() {
+                let x = PADDING + column as u32 * GLYPH_ADVANCE;
+                if x + GLYPH_ADVANCE > WIDTH - PADDING {
+                    break;
+                }
+                self.draw_glyph(x, y, c);
+            }
+        }
+
+        // Frame-time graph along the bottom, newest frame on the right
+        let bottom = HEIGHT - PADDING;
+        let top = bottom - GRAPH_HEIGHT;
+        let bar_height = |ms: f32| ((ms / GRAPH_MAX_MS).min(1.0) * GRAPH_HEIGHT as f32) as u32;
+
+        // Guides at 60 and 30 frames per second
+        for ms in [1000.0 / 60.0, 1000.0 / 30.0] {
+            let y = bottom - bar_height(ms);
+            for x in PADDING..WIDTH - PADDING {
+                self.set_pixel(x, y, GUIDE);
+            }
+        }
+
+        let right = WIDTH - PADDING;
+        let count = history.iter().count() as u32;
+        for (i, dt) in history.iter().enumerate() {
+            let ms = dt * 1000.0;
+            let color = if ms <= 1000.0 / 55.0 {
+                [90, 220, 120, 255]
+            } else if ms <= 1000.0 / 28.0 {
+                [235, 200, 70, 255]
+            } else {
+                [235, 80, 70, 255]
+            };
+            let x = right - count + i as u32;
+            for y in bottom - bar_height(ms)..bottom {
+                self.set_pixel(x, y.max(top), color);
+            }
+        }
+    }
+
+    fn draw_glyph(&mut self, x: u32, y: u32, c: char) {
+        let rows = glyph(c);
+        for (dy, bits) in rows.iter().enumerate() {
+            for dx in 0..5 {
+                if bits & (0x10 >> dx) != 0 {
+                    self.set_pixel(x + dx, y + dy as u32, TEXT);
+                }
+            }
+        }
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        if x < WIDTH && y < HEIGHT {
+            let i = ((y * WIDTH + x) * 4) as usize;
+            self.pixels[i..i + 4].copy_from_slice(&color);
+        }
+    }
+}
+
+/// 5x7 bitmap rows, most significant of the low five bits on the left.
+/// Lowercase letters other than `x` are drawn as capitals.
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'X' if c == 'x' => [0x00, 0x00, 0x11, 0x0A, 0x04, 0x0A, 0x11],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        _ => [0x00; 7],
+    }
+}
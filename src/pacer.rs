@@ -0,0 +1,50 @@
+//! Frame pacing under a frame cap. Frames are due on a fixed grid of
+//! intervals, so a late wake-up doesn't push every later frame back, and
+//! the event loop sleeps until the next one rather than spinning. A frame
+//! that starts on schedule steps the simulation by exactly one interval,
+//! which keeps motion even when the OS wakes the loop a little early or
+//! late.
+
+use std::time::Duration;
+
+use web_time::Instant;
+
+#[derive(Default)]
+pub struct FramePacer {
+    /// When the next frame is due; unset when uncapped or before the first
+    deadline: Option<Instant>,
+}
+
+impl FramePacer {
+    /// When the next frame is due at `fps`, or `None` if uncapped
+    pub fn next_frame_at(&self, fps: f32) -> Option<Instant> {
+        if fps > 0.0 {
+            self.deadline
+        } else {
+            None
+        }
+    }
+
+    /// Start a frame at `now`, `elapsed` seconds after the last, and
+    /// schedule the next at `fps` (0 for no cap). Returns the seconds the
+    /// simulation should step: one interval for a frame within half an
+    /// interval of its deadline, otherwise the time that really passed, and
+    /// the schedule starts over from `now`.
+    pub fn start_frame(&mut self, now: Instant, elapsed: f32, fps: f32) -> f32 {
+        if fps <= 0.0 {
+            self.deadline = None;
+            return elapsed;
+        }
+        let interval = Duration::from_secs_f32(1.0 / fps);
+        match self.deadline {
+            Some(deadline) if now.max(deadline) - now.min(deadline) <= interval / 2 => {
+                self.deadline = Some(deadline + interval);
+                interval.as_secs_f32()
+            }
+            _ => {
+                self.deadline = Some(now + interval);
+                elapsed
+            }
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Phase color palettes. Colors are generated here, one per phase, and
+//! uploaded to a small texture (a single row, since WebGL2 has no 1D
+//! textures) the raymarch shader looks phase colors up in. Besides the
+//! built-in palettes, users can supply their own gradient at runtime.
+
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::world::VendekPhase;
+
+/// Selectable palettes, in `palette` parameter order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Palette {
+    /// Each phase's own generated color
+    Rainbow,
+    Ocean,
+    Fire,
+    Forest,
+    Neon,
+    Pastel,
+    Monochrome,
+    /// Perceptually uniform blue-green-yellow
+    Viridis,
+    /// Viridis variant readable with red-green and blue-yellow color blindness
+    Cividis,
+    /// Okabe & Ito's categorical set, distinguishable under all common color
+    /// vision deficiencies. Black is swapped for grey so it shows in the volume.
+    OkabeIto,
+    /// The user's gradient, or the phases' own colors until one is set
+    Custom,
+}
+
+const VIRIDIS: &[u32] = &[
+    0x440154, 0x482878, 0x3e4989, 0x31688e, 0x26828e, 0x1f9e89, 0x35b779, 0x6dcd59, 0xfde725,
+];
+
+const CIVIDIS: &[u32] = &[
+    0x00224e, 0x123570, 0x3b496c, 0x575d6d, 0x707173, 0x8a8779, 0xa69d75, 0xc4b56c, 0xe4cf5b,
+    0xfee838,
+];
+
+const OKABE_ITO: &[u32] = &[
+    0xe69f00, 0x56b4e9, 0x009e73, 0xf0e442, 0x0072b2, 0xd55e00, 0xcc79a7, 0x999999,
+];
+
+impl Palette {
+    pub const ALL: [Palette; 11] = [
+        Palette::Rainbow,
+        Palette::Ocean,
+        Palette::Fire,
+        Palette::Forest,
+        Palette::Neon,
+        Palette::Pastel,
+        Palette::Monochrome,
+        Palette::Viridis,
+        Palette::Cividis,
+        Palette::OkabeIto,
+        Palette::Custom,
+    ];
+
+    /// The palette for a `palette` parameter value; out-of-range values fall
+    /// back to the phases' own colors
+    pub fn from_index(index: u32) -> Self {
+        Self::ALL
+            .get(index as usize)
+            .copied()
+            .unwrap_or(Palette::Rainbow)
+    }
+
+    /// The palette named in lowercase with hyphens, e.g. `viridis` or
+    /// `okabe-ito`
+    #[cfg(target_arch = "wasm32")]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "rainbow" => Palette::Rainbow,
+            "ocean" => Palette::Ocean,
+            "fire" => Palette::Fire,
+            "forest" => Palette::Forest,
+            "neon" => Palette::Neon,
+            "pastel" => Palette::Pastel,
+            "monochrome" => Palette::Monochrome,
+            "viridis" => Palette::Viridis,
+            "cividis" => Palette::Cividis,
+            "okabe-ito" => Palette::OkabeIto,
+            "custom" => Palette::Custom,
+            _ => return None,
+        })
+    }
+
+    /// Value of the `palette` parameter that selects this palette
+    pub fn index(self) -> u32 {
+        self as u32
+    }
+
+    /// One RGBA8 color per phase, in phase order. `custom` is the gradient
+    /// used by `Palette::Custom`.
+    pub fn phase_colors(self, phases: &[VendekPhase], custom: Option<&Gradient>) -> Vec<[u8; 4]> {
+        let gradient = match self {
+            Palette::Viridis => Some(Gradient::evenly_spaced(VIRIDIS)),
+            Palette::Cividis => Some(Gradient::evenly_spaced(CIVIDIS)),
+            Palette::Custom => custom.cloned(),
+            _ => None,
+        };
+        let count = phases.len().max(1) as f32;
+        phases
+            .iter()
+            .enumerate()
+            .map(|(i, phase)| {
+                // Position along the palette; sequential maps span it end to end
+                let hue = i as f32 / count;
+                let t = i as f32 / (count - 1.0).max(1.0);
+                let own_color = phase.color_density.truncate().to_array();
+                let [r, g, b] = match self {
+                    Palette::Rainbow => own_color,
+                    Palette::Ocean => hsv_to_rgb(0.5 + hue * 0.15, 0.6, 0.8 + hue * 0.2),
+                    Palette::Fire => hsv_to_rgb(hue * 0.12, 0.9, 0.9),
+                    Palette::Forest => {
+                        hsv_to_rgb(0.25 + hue * 0.15, 0.5 + hue * 0.3, 0.4 + hue * 0.4)
+                    }
+                    Palette::Neon => hsv_to_rgb(hue, 1.0, 1.0),
+                    Palette::Pastel => hsv_to_rgb(hue, 0.3, 0.95),
+                    Palette::Monochrome => [0.3 + hue * 0.5; 3],
+                    Palette::OkabeIto => rgb(OKABE_ITO[i % OKABE_ITO.len()]),
+                    Palette::Viridis | Palette::Cividis | Palette::Custom => match &gradient {
+                        Some(gradient) => gradient.sample(t),
+                        None => own_color,
+                    },
+                };
+                let unorm = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+                [unorm(r), unorm(g), unorm(b), 255]
+            })
+            .collect()
+    }
+}
+
+/// A color gradient defined by stops at positions in 0..=1, sampled once per
+/// phase. Deserializes from a list of `{ at, color }` tables, where `color` is
+/// a `"#rrggbb"` string or an `[r, g, b]` array in 0..=1 and `at` may be left
+/// out to space stops evenly.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "Vec<StopConfig>")]
+pub struct Gradient {
+    /// Sorted by position
+    stops: Vec<(f32, [f32; 3])>,
+}
+
+#[derive(Deserialize)]
+struct StopConfig {
+    at: Option<f32>,
+    color: ColorConfig,
+}
+
+/// A color in a config file: a `"#rrggbb"` string or an `[r, g, b]` array
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum ColorConfig {
+    Hex(String),
+    Rgb([f32; 3]),
+}
+
+impl ColorConfig {
+    pub fn into_rgb(self) -> Result<[f32; 3], InvalidGradient> {
+        match self {
+            ColorConfig::Hex(hex) => parse_hex_color(&hex),
+            ColorConfig::Rgb(rgb) => Ok(rgb),
+        }
+    }
+}
+
+/// Why a gradient definition was rejected
+#[derive(Debug)]
+pub struct InvalidGradient(pub String);
+
+impl fmt::Display for InvalidGradient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid palette gradient: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidGradient {}
+
+impl Gradient {
+    /// Build a gradient from stops in order. Stops without a position are
+    /// spaced evenly between their neighbours; the first and last default to
+    /// 0 and 1.
+    pub fn new(stops: Vec<(Option<f32>, [f32; 3])>) -> Result<Self, InvalidGradient> {
+        if stops.is_empty() {
+            return Err(InvalidGradient("needs at least one stop".to_string()));
+        }
+        let last = stops.len() - 1;
+        let mut positions: Vec<Option<f32>> = stops.iter().map(|(at, _)| *at).collect();
+        positions[0] = positions[0].or(Some(0.0));
+        positions[last] = positions[last].or(Some(if last == 0 { 0.0 } else { 1.0 }));
+
+        // Fill each run of missing positions between its known neighbours
+        let mut known = 0;
+        for i in 1..=last {
+            if let Some(end) = positions[i] {
+                let start = positions[known].unwrap();
+                for (j, position) in positions.iter_mut().enumerate().take(i).skip(known + 1) {
+                    let f = (j - known) as f32 / (i - known) as f32;
+                    *position = Some(start + (end - start) * f);
+                }
+                known = i;
+            }
+        }
+
+        let mut resolved = Vec::with_capacity(stops.len());
+        for (position, (_, color)) in positions.into_iter().zip(stops) {
+            let at = position.unwrap();
+            if !(0.0..=1.0).contains(&at) {
+                return Err(InvalidGradient(format!(
+                    "stop position {} is outside 0..1",
+                    at
+                )));
+            }
+            resolved.push((at, color.map(|c| c.clamp(0.0, 1.0))));
+        }
+        resolved.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Self { stops: resolved })
+    }
+
+    /// Evenly spaced stops from `0xrrggbb` colors
+    fn evenly_spaced(colors: &[u32]) -> Self {
+        let step = 1.0 / (colors.len() - 1).max(1) as f32;
+        Self {
+            stops: colors
+                .iter()
+                .enumerate()
+                .map(|(i, &hex)| (i as f32 * step, rgb(hex)))
+                .collect(),
+        }
+    }
+
+    /// Color at `t`, clamped to the first and last stops
+    pub fn sample(&self, t: f32) -> [f32; 3] {
+        let next = self.stops.partition_point(|(at, _)| *at <= t);
+        if next == 0 {
+            return self.stops[0].1;
+        }
+        if next == self.stops.len() {
+            return self.stops[next - 1].1;
+        }
+        let (a_at, a) = self.stops[next - 1];
+        let (b_at, b) = self.stops[next];
+        let f = (t - a_at) / (b_at - a_at);
+        [0, 1, 2].map(|c| a[c] + (b[c] - a[c]) * f)
+    }
+}
+
+impl TryFrom<Vec<StopConfig>> for Gradient {
+    type Error = InvalidGradient;
+
+    fn try_from(stops: Vec<StopConfig>) -> Result<Self, Self::Error> {
+        let stops = stops
+            .into_iter()
+            .map(|stop| Ok((stop.at, stop.color.into_rgb()?)))
+            .collect::<Result<_, InvalidGradient>>()?;
+        Self::new(stops)
+    }
+}
+
+/// Parse `#rrggbb` or `#rgb`, with or without the `#`
+pub fn parse_hex_color(text: &str) -> Result<[f32; 3], InvalidGradient> {
+    let digits = text.trim().trim_start_matches('#');
+    let expanded: String = match digits.len() {
+        3 => digits.chars().flat_map(|c| [c, c]).collect(),
+        6 => digits.to_string(),
+        _ => {
+            return Err(InvalidGradient(format!(
+                "`{}` is not a #rrggbb color",
+                text
+            )))
+        }
+    };
+    u32::from_str_radix(&expanded, 16)
+        .map(rgb)
+        .map_err(|_| InvalidGradient(format!("`{}` is not a #rrggbb color", text)))
+}
+
+fn rgb(hex: u32) -> [f32; 3] {
+    [16, 8, 0].map(|shift| ((hex >> shift) & 0xff) as f32 / 255.0)
+}
+
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> [f32; 3] {
+    let h6 = h.rem_euclid(1.0) * 6.0;
+    let c = v * s;
+    let x = c * (1.0 - ((h6 % 2.0) - 1.0).abs());
+    let m = v - c;
+    let [r, g, b] = match h6 as u32 {
+        0 => [c, x, 0.0],
+        1 => [x, c, 0.0],
+        2 => [0.0, c, x],
+        3 => [0.0, x, c],
+        4 => [x, 0.0, c],
+        _ => [c, 0.0, x],
+    };
+    [r + m, g + m, b + m]
+}
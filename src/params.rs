@@ -0,0 +1,709 @@
+use std::f64::consts::{FRAC_PI_2, TAU};
+use std::fmt;
+
+use glam::Vec3;
+use serde::Deserialize;
+
+use crate::palette::Palette;
+use crate::world::Bounds;
+use crate::{automaton, heat, reaction};
+
+// Defaults for the adjustable raymarch parameters
+pub const MAX_STEPS: u32 = 128;
+pub const STEP_SIZE: f32 = 0.15;
+pub const MEMBRANE_THICKNESS: f32 = 0.4;
+pub const MEMBRANE_GLOW: f32 = 0.5;
+pub const RIPPLE_AMPLITUDE: f32 = 0.3;
+pub const RIPPLE_SPEED: f32 = 0.2;
+/// Rays stop once less than this much of the light behind them gets through
+pub const MIN_TRANSMITTANCE: f32 = 0.02;
+/// How brightly the phases scatter the light toward the eye
+pub const SCATTERING: f32 = 0.5;
+/// Where the scattered light comes from, as camera-style yaw and pitch:
+/// behind and above the default view, so forward-scattering phases glow
+pub const LIGHT_YAW: f32 = 2.4;
+pub const LIGHT_PITCH: f32 = 0.7;
+/// How brightly emitting cells glow
+pub const EMISSION: f32 = 1.0;
+/// Standard error of a pixel's luminance at which accumulation stops
+/// refining it
+pub const REFINE_THRESHOLD: f32 = 0.01;
+/// Frame rate dynamic resolution aims for
+pub const TARGET_FPS: f32 = 60.0;
+/// Frame rate low-power mode caps rendering at
+pub const LOW_POWER_FPS: f32 = 30.0;
+/// Clipping planes the raymarcher supports at once
+pub const MAX_CLIP_PLANES: usize = 3;
+/// `visible_phases` with every phase shown
+pub const ALL_PHASES: u32 = u32::MAX;
+
+/// How the world is drawn
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RenderMode {
+    /// Raymarched volume
+    #[default]
+    Volume,
+    /// Membrane surfaces extracted as triangles and rasterized
+    Isosurface,
+    /// The rasterized surfaces inside the raymarched volume, each hiding
+    /// what lies behind the other
+    Hybrid,
+    /// Only the raymarched membranes, with the cell interiors left empty and
+    /// the walls shaded brightest edge-on
+    Membranes,
+}
+
+impl RenderMode {
+    pub const ALL: [RenderMode; 4] = [
+        RenderMode::Volume,
+        RenderMode::Isosurface,
+        RenderMode::Hybrid,
+        RenderMode::Membranes,
+    ];
+
+    /// The mode for a `renderMode` parameter value; out-of-range values fall
+    /// back to the volume
+    pub fn from_index(index: u32) -> Self {
+        Self::ALL.get(index as usize).copied().unwrap_or_default()
+    }
+
+    pub fn index(self) -> u32 {
+        self as u32
+    }
+
+    /// The mode after this one, wrapping around
+    pub fn next(self) -> Self {
+        Self::from_index((self.index() + 1) % Self::ALL.len() as u32)
+    }
+
+    /// Whether the mode draws the rasterized isosurface
+    pub fn rasterizes(self) -> bool {
+        matches!(self, RenderMode::Isosurface | RenderMode::Hybrid)
+    }
+}
+
+/// What the raymarch shows in place of the rendered image, to check its
+/// workings
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DebugView {
+    /// The image itself
+    #[default]
+    Off,
+    /// Steps each ray took, from dark blue for none through green to dark
+    /// red for `max_steps`, showing where early exits and skipped space
+    /// save work
+    Steps,
+    /// The phase contributing most to each pixel, in a color of its own
+    Phases,
+    /// Normals of the membranes where the volume shows, estimated from
+    /// the distance to the nearest membrane, as RGB of XYZ
+    Normals,
+    /// Distance to the volume each pixel shows, white at the volume's
+    /// nearest corner fading to black at its farthest
+    Depth,
+    /// Seeds the spatial grid lists for the grid cell where the volume
+    /// shows, from dark blue for none to dark red for a full grid cell
+    Grid,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 6] = [
+        DebugView::Off,
+        DebugView::Steps,
+        DebugView::Phases,
+        DebugView::Normals,
+        DebugView::Depth,
+        DebugView::Grid,
+    ];
+
+    /// The view for a `debugView` parameter value; out-of-range values turn
+    /// it off
+    pub fn from_index(index: u32) -> Self {
+        Self::ALL.get(index as usize).copied().unwrap_or_default()
+    }
+
+    pub fn index(self) -> u32 {
+        self as u32
+    }
+
+    /// The view after this one, wrapping around to the image
+    pub fn next(self) -> Self {
+        Self::from_index((self.index() + 1) % Self::ALL.len() as u32)
+    }
+}
+
+/// What rays that miss the volume show
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Background {
+    /// A flat near-black
+    #[default]
+    Solid,
+    /// Stars over faint nebulae, different for every world seed
+    Starfield,
+}
+
+impl Background {
+    pub const ALL: [Background; 2] = [Background::Solid, Background::Starfield];
+
+    /// The background for a `background` parameter value; out-of-range
+    /// values fall back to the solid color
+    pub fn from_index(index: u32) -> Self {
+        Self::ALL.get(index as usize).copied().unwrap_or_default()
+    }
+
+    pub fn index(self) -> u32 {
+        self as u32
+    }
+}
+
+/// How cells change phase over time
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AutomatonRule {
+    /// Phases stay as generated
+    #[default]
+    Off,
+    /// A cell takes the phase most common among itself and its neighbours,
+    /// growing domains of one phase, with the odd random change
+    Majority,
+    /// A cell advances to the next phase once a neighbour has, like
+    /// rock-paper-scissors, sending waves through the honeycomb
+    Cyclic,
+}
+
+impl AutomatonRule {
+    pub const ALL: [AutomatonRule; 3] = [
+        AutomatonRule::Off,
+        AutomatonRule::Majority,
+        AutomatonRule::Cyclic,
+    ];
+
+    /// The rule for an `automatonRule` parameter value; out-of-range values
+    /// switch the automaton off
+    pub fn from_index(index: u32) -> Self {
+        Self::ALL.get(index as usize).copied().unwrap_or_default()
+    }
+
+    pub fn index(self) -> u32 {
+        self as u32
+    }
+}
+
+/// A plane that cuts the volume open. Everything on the side the normal
+/// points to is removed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClipPlane {
+    /// A point on the plane
+    pub position: Vec3,
+    pub normal: Vec3,
+    /// Movement along the normal in world units per second, bouncing
+    /// between the volume bounds; 0 keeps the plane still
+    pub speed: f32,
+}
+
+impl ClipPlane {
+    /// Normal and distance from the origin, as the shader takes them. A
+    /// zero normal gives a plane that clips nothing.
+    pub fn equation(&self) -> [f32; 4] {
+        let normal = self.normal.normalize_or_zero();
+        let distance = if normal == Vec3::ZERO {
+            f32::MAX
+        } else {
+            normal.dot(self.position)
+        };
+        [normal.x, normal.y, normal.z, distance]
+    }
+
+    /// Move the plane by `dt` seconds of its speed, turning around where it
+    /// would leave the box between `min` and `max`
+    pub fn advance(&mut self, dt: f32, min: Vec3, max: Vec3) {
+        let normal = self.normal.normalize_or_zero();
+        if self.speed == 0.0 || normal == Vec3::ZERO {
+            return;
+        }
+        // Range of distances along the normal that still cut the box
+        let center = (min + max) * 0.5;
+        let reach = normal.abs().dot((max - min) * 0.5);
+        let low = normal.dot(center) - reach;
+        let high = normal.dot(center) + reach;
+
+        let distance = normal.dot(self.position) + self.speed * dt;
+        let clamped = distance.clamp(low, high);
+        if clamped != distance {
+            self.speed = -self.speed;
+        }
+        self.position += normal * (clamped - normal.dot(self.position));
+    }
+}
+
+/// Parameters that can be adjusted at runtime
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RuntimeParams {
+    pub membrane_thickness: f32,
+    pub membrane_glow: f32,
+    pub step_size: f32,
+    pub density: f32,
+    pub max_steps: u32,
+    /// Transmittance below which a ray stops early, 0 to 1; 0 marches every
+    /// ray to the far side of the volume
+    pub min_transmittance: f32,
+    pub enable_coupling: bool,
+    pub palette: u32,
+    /// Raymarch resolution relative to the window, 1 to 2. Above 1 the frame
+    /// is supersampled and filtered down in the display pass.
+    pub supersample: f32,
+    /// Frame rate to hold by lowering the raymarch resolution when frames run
+    /// long; 0 keeps the resolution fixed
+    pub target_fps: f32,
+    /// Frames per second to cap rendering at; 0 for no cap
+    pub max_fps: f32,
+    /// Spare the battery: raymarch at half the resolution, with half as
+    /// many steps twice as long, at no more than 30 fps
+    pub low_power: bool,
+    /// Draw only when something on screen would change: the camera moves,
+    /// a parameter changes, the clock runs or samples still accumulate.
+    /// A paused, still view then costs next to nothing.
+    pub on_demand: bool,
+    /// Show an overview of the whole world beside the main view
+    pub split_view: bool,
+    /// Orientation axes in the corner of the main view
+    pub show_axes: bool,
+    /// Wireframe of the volume's bounding box
+    pub show_bounds: bool,
+    /// Wireframe of the spatial grid's cells, with a cross at each seed
+    pub show_grid: bool,
+    pub clip_planes: [Option<ClipPlane>; MAX_CLIP_PLANES],
+    pub render_mode: RenderMode,
+    /// Bit `i` set shows phase `i`'s cells; cleared hides them
+    pub visible_phases: u32,
+    /// How far membranes are displaced by the ripple noise, in world units;
+    /// 0 holds them still
+    pub ripple_amplitude: f32,
+    /// How fast the ripple noise evolves
+    pub ripple_speed: f32,
+    /// How strongly the reaction–diffusion catalyst tints and thickens cells,
+    /// 0 to 1; 0 also stops the simulation
+    pub reaction_strength: f32,
+    /// Gray–Scott feed and kill rates
+    pub reaction_feed: f32,
+    pub reaction_kill: f32,
+    /// How strongly cells glow with their temperature, 0 to 1; 0 also stops
+    /// the heat simulation
+    pub heat_strength: f32,
+    /// How fast temperature evens out between coupled cells, 0 to 1
+    pub heat_diffusion: f32,
+    /// Rule cells change phase by
+    pub automaton_rule: AutomatonRule,
+    /// Automaton ticks per second
+    pub automaton_rate: f32,
+    /// Strength of single scattering of the light, 0 for none
+    pub scattering: f32,
+    /// Direction the light comes from, in radians as the camera's yaw and
+    /// pitch are
+    pub light_yaw: f32,
+    pub light_pitch: f32,
+    /// Brightness of the world's emitting cells, 0 for none
+    pub emission: f32,
+    pub background: Background,
+    /// How fast the raymarch coarsens with distance from the camera: steps
+    /// grow by this many times their length every 32 world units, and
+    /// samples past twice the length leave out the ripples, wisps and
+    /// coupling. 0 keeps every sample at full detail.
+    pub lod_falloff: f32,
+    /// While the view holds still the compute path accumulates jittered
+    /// samples per pixel; a pixel stops taking them once the standard
+    /// error of its luminance falls below this, so the samples go to the
+    /// noisy ones, mostly membrane silhouettes. 0 refines every pixel
+    /// alike, up to the sample limit.
+    pub refine_threshold: f32,
+    /// Strength of the compute path's edge-aware denoiser, 0 to 1. Blurs
+    /// each pixel with neighbours of the same phase at a similar depth and
+    /// brightness, less as a still view accumulates samples; 0 turns it off.
+    pub denoise: f32,
+    /// Diagnostic view the raymarch draws instead of the image
+    pub debug_view: DebugView,
+}
+
+impl Default for RuntimeParams {
+    fn default() -> Self {
+        Self {
+            membrane_thickness: MEMBRANE_THICKNESS,
+            membrane_glow: MEMBRANE_GLOW,
+            step_size: STEP_SIZE,
+            density: 1.0,
+            max_steps: MAX_STEPS,
+            min_transmittance: MIN_TRANSMITTANCE,
+            enable_coupling: true,
+            palette: 0,
+            supersample: 1.0,
+            target_fps: TARGET_FPS,
+            max_fps: 0.0,
+            low_power: false,
+            on_demand: false,
+            split_view: false,
+            show_axes: true,
+            show_bounds: false,
+            show_grid: false,
+            clip_planes: [None; MAX_CLIP_PLANES],
+            render_mode: RenderMode::Volume,
+            visible_phases: ALL_PHASES,
+            ripple_amplitude: RIPPLE_AMPLITUDE,
+            ripple_speed: RIPPLE_SPEED,
+            reaction_strength: 0.0,
+            reaction_feed: reaction::FEED,
+            reaction_kill: reaction::KILL,
+            heat_strength: 0.0,
+            heat_diffusion: heat::DIFFUSION,
+            automaton_rule: AutomatonRule::Off,
+            automaton_rate: automaton::RATE,
+            scattering: SCATTERING,
+            light_yaw: LIGHT_YAW,
+            light_pitch: LIGHT_PITCH,
+            emission: EMISSION,
+            background: Background::Solid,
+            lod_falloff: 0.0,
+            refine_threshold: REFINE_THRESHOLD,
+            denoise: 0.0,
+            debug_view: DebugView::Off,
+        }
+    }
+}
+
+/// The current parameters and whether they changed since they were last
+/// taken, so GPU buffers are only rewritten when a value actually differs
+#[derive(Clone, Debug)]
+pub struct ParamStore {
+    params: RuntimeParams,
+    changed: bool,
+}
+
+impl ParamStore {
+    /// Starts out changed, so the first `take_changed` hands the values over
+    pub fn new(params: RuntimeParams) -> Self {
+        Self {
+            params,
+            changed: true,
+        }
+    }
+
+    pub fn get(&self) -> RuntimeParams {
+        self.params
+    }
+
+    /// Replace every parameter. Only counts as a change if something differs.
+    pub fn replace(&mut self, params: RuntimeParams) {
+        if params != self.params {
+            self.params = params;
+            self.changed = true;
+        }
+    }
+
+    pub fn update(&mut self, change: impl FnOnce(&mut RuntimeParams)) {
+        let mut params = self.params;
+        change(&mut params);
+        self.replace(params);
+    }
+
+    /// Set one parameter by name, as `RuntimeParams::set`
+    pub fn set(&mut self, name: &str, value: f64) -> Result<(), ParamError> {
+        let mut params = self.params;
+        params.set(name, value)?;
+        self.replace(params);
+        Ok(())
+    }
+
+    /// Sweep the moving clip planes `dt` seconds further through `bounds`;
+    /// only a change if some plane moves
+    pub fn advance_clip_planes(&mut self, dt: f32, bounds: &Bounds) {
+        if self
+            .params
+            .clip_planes
+            .iter()
+            .flatten()
+            .all(|plane| plane.speed == 0.0)
+        {
+            return;
+        }
+        self.update(|params| {
+            for plane in params.clip_planes.iter_mut().flatten() {
+                plane.advance(dt, bounds.min, bounds.max);
+            }
+        });
+    }
+
+    /// The parameters, if they changed since the last call
+    pub fn take_changed(&mut self) -> Option<RuntimeParams> {
+        std::mem::take(&mut self.changed).then_some(self.params)
+    }
+}
+
+impl Default for ParamStore {
+    fn default() -> Self {
+        Self::new(RuntimeParams::default())
+    }
+}
+
+/// Why `RuntimeParams::set` refused a value
+#[derive(Debug)]
+pub enum ParamError {
+    /// No parameter has the name
+    Unknown(String),
+    /// The value for the named parameter is NaN or infinite
+    NotFinite(String),
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamError::Unknown(name) => write!(f, "unknown parameter `{}`", name),
+            ParamError::NotFinite(name) => write!(f, "parameter `{}` must be finite", name),
+        }
+    }
+}
+
+impl std::error::Error for ParamError {}
+
+impl RuntimeParams {
+    /// Parameter names accepted by `set` and `get`, as exposed to JavaScript
+    pub const NAMES: &'static [&'static str] = &[
+        "membraneThickness",
+        "membraneGlow",
+        "stepSize",
+        "density",
+        "maxSteps",
+        "minTransmittance",
+        "enableCoupling",
+        "palette",
+        "supersample",
+        "targetFps",
+        "maxFps",
+        "lowPower",
+        "onDemand",
+        "splitView",
+        "showAxes",
+        "showBounds",
+        "showGrid",
+        "renderMode",
+        "visiblePhases",
+        "rippleAmplitude",
+        "rippleSpeed",
+        "reactionStrength",
+        "reactionFeed",
+        "reactionKill",
+        "heatStrength",
+        "heatDiffusion",
+        "automatonRule",
+        "automatonRate",
+        "scattering",
+        "lightYaw",
+        "lightPitch",
+        "emission",
+        "background",
+        "lodFalloff",
+        "refineThreshold",
+        "denoise",
+        "debugView",
+    ];
+
+    /// Lowest and highest value `set` takes for a parameter; values outside
+    /// are clamped into it. Booleans run from 0 to 1 and choices over their
+    /// indices. The ranges keep what reaches the shaders sane: `maxSteps`
+    /// bounds the raymarch loop, and `stepSize` and `density` stay positive.
+    pub fn range(name: &str) -> Option<(f64, f64)> {
+        let range = match name {
+            "membraneThickness" => (0.1, 2.0),
+            "membraneGlow" => (0.0, 2.0),
+            // The quality tiers run from Ultra's 0.05 to Low's 0.3
+            "stepSize" => (0.05, 0.5),
+            "density" => (0.1, 3.0),
+            // Ultra takes 512 steps
+            "maxSteps" => (16.0, 512.0),
+            "minTransmittance" => (0.0, 1.0),
+            "palette" => (0.0, (Palette::ALL.len() - 1) as f64),
+            "supersample" => (1.0, 2.0),
+            "targetFps" => (0.0, 240.0),
+            "maxFps" => (0.0, 1000.0),
+            "enableCoupling" | "lowPower" | "onDemand" | "splitView" | "showAxes"
+            | "showBounds" | "showGrid" => (0.0, 1.0),
+            "renderMode" => (0.0, (RenderMode::ALL.len() - 1) as f64),
+            "visiblePhases" => (0.0, ALL_PHASES as f64),
+            "rippleAmplitude" => (0.0, 2.0),
+            "rippleSpeed" => (-2.0, 2.0),
+            "reactionStrength" | "heatStrength" | "heatDiffusion" => (0.0, 1.0),
+            "reactionFeed" | "reactionKill" => (0.0, 0.1),
+            "automatonRule" => (0.0, (AutomatonRule::ALL.len() - 1) as f64),
+            "automatonRate" => (0.0, 60.0),
+            "scattering" | "emission" => (0.0, 4.0),
+            "lightYaw" => (0.0, TAU),
+            "lightPitch" => (-FRAC_PI_2, FRAC_PI_2),
+            "background" => (0.0, (Background::ALL.len() - 1) as f64),
+            "lodFalloff" => (0.0, 3.0),
+            "refineThreshold" | "denoise" => (0.0, 1.0),
+            "debugView" => (0.0, (DebugView::ALL.len() - 1) as f64),
+            _ => return None,
+        };
+        Some(range)
+    }
+
+    /// Set a parameter by name, clamped into its `range`. `lightYaw` wraps
+    /// around instead, booleans are true for values above 0.5 and integer
+    /// parameters are truncated.
+    pub fn set(&mut self, name: &str, value: f64) -> Result<(), ParamError> {
+        let (low, high) = Self::range(name).ok_or_else(|| ParamError::Unknown(name.to_string()))?;
+        if !value.is_finite() {
+            return Err(ParamError::NotFinite(name.to_string()));
+        }
+        let value = match name {
+            "lightYaw" => value.rem_euclid(TAU),
+            _ => value.clamp(low, high),
+        };
+        match name {
+            "membraneThickness" => self.membrane_thickness = value as f32,
+            "membraneGlow" => self.membrane_glow = value as f32,
+            "stepSize" => self.step_size = value as f32,
+            "density" => self.density = value as f32,
+            "maxSteps" => self.max_steps = value as u32,
+            "minTransmittance" => self.min_transmittance = value as f32,
+            "enableCoupling" => self.enable_coupling = value > 0.5,
+            "palette" => self.palette = value as u32,
+            "supersample" => self.supersample = value as f32,
+            "targetFps" => self.target_fps = value as f32,
+            "maxFps" => self.max_fps = value as f32,
+            "lowPower" => self.low_power = value > 0.5,
+            "onDemand" => self.on_demand = value > 0.5,
+            "splitView" => self.split_view = value > 0.5,
+            "showAxes" => self.show_axes = value > 0.5,
+            "showBounds" => self.show_bounds = value > 0.5,
+            "showGrid" => self.show_grid = value > 0.5,
+            "renderMode" => self.render_mode = RenderMode::from_index(value as u32),
+            "visiblePhases" => self.visible_phases = value as u32,
+            "rippleAmplitude" => self.ripple_amplitude = value as f32,
+            "rippleSpeed" => self.ripple_speed = value as f32,
+            "reactionStrength" => self.reaction_strength = value as f32,
+            "reactionFeed" => self.reaction_feed = value as f32,
+            "reactionKill" => self.reaction_kill = value as f32,
+            "heatStrength" => self.heat_strength = value as f32,
+            "heatDiffusion" => self.heat_diffusion = value as f32,
+            "automatonRule" => self.automaton_rule = AutomatonRule::from_index(value as u32),
+            "automatonRate" => self.automaton_rate = value as f32,
+            "scattering" => self.scattering = value as f32,
+            "lightYaw" => self.light_yaw = value as f32,
+            "lightPitch" => self.light_pitch = value as f32,
+            "emission" => self.emission = value as f32,
+            "background" => self.background = Background::from_index(value as u32),
+            "lodFalloff" => self.lod_falloff = value as f32,
+            "refineThreshold" => self.refine_threshold = value as f32,
+            "denoise" => self.denoise = value as f32,
+            "debugView" => self.debug_view = DebugView::from_index(value as u32),
+            _ => unreachable!("`range` knows `{}`", name),
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<f64> {
+        let value = match name {
+            "membraneThickness" => self.membrane_thickness as f64,
+            "membraneGlow" => self.membrane_glow as f64,
+            "stepSize" => self.step_size as f64,
+            "density" => self.density as f64,
+            "maxSteps" => self.max_steps as f64,
+            "minTransmittance" => self.min_transmittance as f64,
+            "enableCoupling" => flag(self.enable_coupling),
+            "palette" => self.palette as f64,
+            "supersample" => self.supersample as f64,
+            "targetFps" => self.target_fps as f64,
+            "maxFps" => self.max_fps as f64,
+            "lowPower" => flag(self.low_power),
+            "onDemand" => flag(self.on_demand),
+            "splitView" => flag(self.split_view),
+            "showAxes" => flag(self.show_axes),
+            "showBounds" => flag(self.show_bounds),
+            "showGrid" => flag(self.show_grid),
+            "renderMode" => self.render_mode.index() as f64,
+            "visiblePhases" => self.visible_phases as f64,
+            "rippleAmplitude" => self.ripple_amplitude as f64,
+            "rippleSpeed" => self.ripple_speed as f64,
+            "reactionStrength" => self.reaction_strength as f64,
+            "reactionFeed" => self.reaction_feed as f64,
+            "reactionKill" => self.reaction_kill as f64,
+            "heatStrength" => self.heat_strength as f64,
+            "heatDiffusion" => self.heat_diffusion as f64,
+            "automatonRule" => self.automaton_rule.index() as f64,
+            "automatonRate" => self.automaton_rate as f64,
+            "scattering" => self.scattering as f64,
+            "lightYaw" => self.light_yaw as f64,
+            "lightPitch" => self.light_pitch as f64,
+            "emission" => self.emission as f64,
+            "background" => self.background.index() as f64,
+            "lodFalloff" => self.lod_falloff as f64,
+            "refineThreshold" => self.refine_threshold as f64,
+            "denoise" => self.denoise as f64,
+            "debugView" => self.debug_view.index() as f64,
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// The parameters as rendered, with low-power mode's limits applied
+    pub fn power_limited(&self) -> RuntimeParams {
+        let mut params = *self;
+        if self.low_power {
+            params.supersample *= 0.5;
+            params.max_steps = (self.max_steps / 2).max(1);
+            params.step_size *= 2.0;
+            params.max_fps = if self.max_fps > 0.0 {
+                self.max_fps.min(LOW_POWER_FPS)
+            } else {
+                LOW_POWER_FPS
+            };
+            params.target_fps = self.target_fps.min(LOW_POWER_FPS);
+        }
+        params
+    }
+
+    /// Unit vector pointing toward the light
+    pub fn light_direction(&self) -> Vec3 {
+        let (yaw, pitch) = (self.light_yaw, self.light_pitch);
+        Vec3::new(
+            pitch.cos() * yaw.sin(),
+            pitch.sin(),
+            pitch.cos() * yaw.cos(),
+        )
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn set_phase_visible(&mut self, phase: u32, visible: bool) {
+        if visible {
+            self.visible_phases |= phase_bit(phase);
+        } else {
+            self.visible_phases &= !phase_bit(phase);
+        }
+    }
+
+    /// Show phase `phase` if it is hidden, hide it otherwise
+    pub fn toggle_phase(&mut self, phase: u32) {
+        self.visible_phases ^= phase_bit(phase);
+    }
+
+    /// Show only phase `phase`, or every phase if it is already shown alone
+    pub fn solo_phase(&mut self, phase: u32) {
+        let bit = phase_bit(phase);
+        self.visible_phases = if self.visible_phases == bit {
+            ALL_PHASES
+        } else {
+            bit
+        };
+    }
+}
+
+/// A boolean parameter's value, 1 or 0
+fn flag(value: bool) -> f64 {
+    f64::from(u8::from(value))
+}
+
+/// `visible_phases` bit for `phase`; phases past 31 can't be hidden
+fn phase_bit(phase: u32) -> u32 {
+    1u32.checked_shl(phase).unwrap_or(0)
+}
@@ -0,0 +1,88 @@
+//! Driver pipeline cache kept on disk between runs, so shader permutations
+//! compiled once don't have to be compiled again on the next startup. Only
+//! backends with `Features::PIPELINE_CACHE` (currently Vulkan) support it.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+/// A `wgpu::PipelineCache` and the file it is saved to
+pub struct DiskPipelineCache {
+    cache: wgpu::PipelineCache,
+    #[cfg(not(target_arch = "wasm32"))]
+    path: PathBuf,
+}
+
+impl DiskPipelineCache {
+    /// Features to request on the device so a cache can be created
+    pub const FEATURES: wgpu::Features = wgpu::Features::PIPELINE_CACHE;
+
+    /// Open the cache for this adapter, seeded from disk when a previous run
+    /// saved one. `None` if the device or platform can't cache pipelines.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(device: &wgpu::Device, adapter: &wgpu::AdapterInfo) -> Option<Self> {
+        if !device.features().contains(Self::FEATURES) {
+            return None;
+        }
+        let key = wgpu::util::pipeline_cache_key(adapter)?;
+        let path = dirs::cache_dir()?.join("vendek").join(key);
+        let data = std::fs::read(&path).ok();
+
+        // SAFETY: the data was written by `save` from a cache for the same
+        // adapter; `fallback` makes wgpu start empty if the driver rejects it
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("Pipeline Cache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+        tracing::info!(
+            "Pipeline cache {} ({})",
+            path.display(),
+            if data.is_some() { "loaded" } else { "new" }
+        );
+        Some(Self { cache, path })
+    }
+
+    /// Browsers cache pipelines themselves
+    #[cfg(target_arch = "wasm32")]
+    pub fn load(_device: &wgpu::Device, _adapter: &wgpu::AdapterInfo) -> Option<Self> {
+        None
+    }
+
+    pub fn cache(&self) -> &wgpu::PipelineCache {
+        &self.cache
+    }
+
+    /// Write the cache to disk. Written to a temporary file first so a crash
+    /// mid-write can't leave a truncated cache behind.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self) {
+        let Some(data) = self.cache.get_data() else {
+            return;
+        };
+        let temp = self.path.with_extension("tmp");
+        let result = self
+            .path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::write(&temp, &data))
+            .and_then(|()| std::fs::rename(&temp, &self.path));
+        if let Err(e) = result {
+            tracing::warn!(
+                "Could not save pipeline cache {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&self) {}
+}
+
+impl Drop for DiskPipelineCache {
+    fn drop(&mut self) {
+        self.save();
+    }
+}
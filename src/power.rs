@@ -0,0 +1,31 @@
+//! Whether the machine is running on its battery, so the viewer can start
+//! in low-power mode on an unplugged laptop. Only Linux reports it, through
+//! the power supplies in sysfs; elsewhere the viewer assumes mains power
+//! and `low_power` under `[render]` decides.
+
+/// Where Linux lists the power supplies, one directory each
+#[cfg(target_os = "linux")]
+const POWER_SUPPLIES: &str = "/sys/class/power_supply";
+
+/// Whether a battery is discharging: the machine has one, and no charger
+/// or mains adapter is plugged in
+#[cfg(target_os = "linux")]
+pub fn on_battery() -> bool {
+    let Ok(supplies) = std::fs::read_dir(POWER_SUPPLIES) else {
+        return false;
+    };
+    let read = |path: std::path::PathBuf| {
+        std::fs::read_to_string(path)
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    };
+    supplies.flatten().any(|supply| {
+        let path = supply.path();
+        read(path.join("type")) == "Battery" && read(path.join("status")) == "Discharging"
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn on_battery() -> bool {
+    false
+}
@@ -0,0 +1,87 @@
+//! Named starting points for world generation. Each preset bundles how the
+//! seeds are laid out, the cell and phase counts, how dense the phases are
+//! and the palette, and can be picked by name on the command line, in the
+//! config and from JavaScript. A preset only sets where the builder starts;
+//! the seed, and anything set after it, still applies.
+
+use serde::Deserialize;
+
+use crate::palette::Palette;
+use crate::world::{SeedLayout, WorldStyle};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preset {
+    /// Many even, bubble-like cells, thin and pale
+    Foam,
+    /// A jittered lattice of near-identical cells in a few dense phases
+    Crystal,
+    /// A few knots of small cells in wide, faint space
+    #[serde(alias = "sparse nebula")]
+    SparseNebula,
+    /// Many small, thick cells of every phase
+    #[serde(alias = "dense hive")]
+    DenseHive,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [
+        Preset::Foam,
+        Preset::Crystal,
+        Preset::SparseNebula,
+        Preset::DenseHive,
+    ];
+
+    /// The preset named in lowercase, words separated by hyphens, spaces or
+    /// underscores, e.g. `dense-hive` or `sparse nebula`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase().replace([' ', '_'], "-");
+        Self::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Foam => "foam",
+            Preset::Crystal => "crystal",
+            Preset::SparseNebula => "sparse-nebula",
+            Preset::DenseHive => "dense-hive",
+        }
+    }
+
+    pub(crate) fn cells(self) -> usize {
+        match self {
+            Preset::Foam => 400,
+            Preset::Crystal => 216,
+            Preset::SparseNebula => 72,
+            Preset::DenseHive => 1000,
+        }
+    }
+
+    pub(crate) fn phases(self) -> usize {
+        match self {
+            Preset::Foam => 6,
+            Preset::Crystal => 4,
+            Preset::SparseNebula => 8,
+            Preset::DenseHive => 12,
+        }
+    }
+
+    pub(crate) fn style(self) -> WorldStyle {
+        let (layout, density) = match self {
+            Preset::Foam => (SeedLayout::Relaxed, (0.01, 0.03)),
+            Preset::Crystal => (SeedLayout::Lattice, (0.05, 0.1)),
+            Preset::SparseNebula => (SeedLayout::Clustered, (0.005, 0.03)),
+            Preset::DenseHive => (SeedLayout::Uniform, (0.06, 0.14)),
+        };
+        WorldStyle { layout, density }
+    }
+
+    pub(crate) fn palette(self) -> Palette {
+        match self {
+            Preset::Foam => Palette::Pastel,
+            Preset::Crystal => Palette::Ocean,
+            Preset::SparseNebula => Palette::Neon,
+            Preset::DenseHive => Palette::Fire,
+        }
+    }
+}
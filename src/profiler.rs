@@ -0,0 +1,213 @@
+//! GPU pass timings from timestamp queries, when the adapter supports them.
+//!
+//! Each profiled pass writes a begin and end timestamp. The queries are
+//! resolved into a buffer that is mapped asynchronously, so results arrive a
+//! frame or two late; frames submitted while a readback is in flight are not
+//! profiled.
+
+use std::sync::{Arc, Mutex};
+
+/// Passes that can be timed, in query order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pass {
+    Raymarch,
+    Denoise,
+    Display,
+    Isosurface,
+}
+
+impl Pass {
+    const ALL: [Pass; 4] = [
+        Pass::Raymarch,
+        Pass::Denoise,
+        Pass::Display,
+        Pass::Isosurface,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pass::Raymarch => "raymarch",
+            Pass::Denoise => "denoise",
+            Pass::Display => "display",
+            Pass::Isosurface => "isosurface",
+        }
+    }
+
+    fn first_query(self) -> u32 {
+        self as u32 * 2
+    }
+}
+
+const QUERY_COUNT: u32 = Pass::ALL.len() as u32 * 2;
+const BUFFER_SIZE: u64 = QUERY_COUNT as u64 * wgpu::QUERY_SIZE as u64;
+
+/// GPU milliseconds per pass for one frame; passes that didn't run are absent
+#[derive(Clone, Debug, Default)]
+pub struct GpuTimings {
+    /// Index of the profiled frame, counting every submitted frame
+    pub frame: u64,
+    pub passes: Vec<(Pass, f32)>,
+}
+
+pub struct GpuProfiler {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    /// Passes recorded into the current frame's encoder
+    recorded: Vec<Pass>,
+    /// Frames submitted so far
+    frame: u64,
+    /// Frame and passes whose timestamps are being read back
+    in_flight: Option<(u64, Vec<Pass>)>,
+    /// Set by the map callback
+    mapped: Arc<Mutex<Option<Result<(), wgpu::BufferAsyncError>>>>,
+    latest: Option<GpuTimings>,
+}
+
+impl GpuProfiler {
+    /// `None` if the device was created without `TIMESTAMP_QUERY`
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Pass Timestamps"),
+            ty: wgpu::QueryType::Timestamp,
+            count: QUERY_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Resolve Buffer"),
+            size: BUFFER_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Readback Buffer"),
+            size: BUFFER_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+            recorded: Vec::new(),
+            frame: 0,
+            in_flight: None,
+            mapped: Arc::new(Mutex::new(None)),
+            latest: None,
+        })
+    }
+
+    /// Most recent completed timings
+    pub fn latest(&self) -> Option<&GpuTimings> {
+        self.latest.as_ref()
+    }
+
+    /// Timestamp writes for a compute pass, or `None` while a readback is pending
+    pub fn compute_pass(&mut self, pass: Pass) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        self.begin(pass)?;
+        Some(wgpu::ComputePassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(pass.first_query()),
+            end_of_pass_write_index: Some(pass.first_query() + 1),
+        })
+    }
+
+    /// Timestamp writes for a render pass, or `None` while a readback is pending
+    pub fn render_pass(&mut self, pass: Pass) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.begin(pass)?;
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(pass.first_query()),
+            end_of_pass_write_index: Some(pass.first_query() + 1),
+        })
+    }
+
+    fn begin(&mut self, pass: Pass) -> Option<()> {
+        if self.in_flight.is_some() {
+            return None;
+        }
+        self.recorded.push(pass);
+        Some(())
+    }
+
+    /// Copy this frame's timestamps out; call after the last profiled pass
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        if self.recorded.is_empty() {
+            return;
+        }
+        encoder.resolve_query_set(&self.query_set, 0..QUERY_COUNT, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            BUFFER_SIZE,
+        );
+    }
+
+    /// Start reading back this frame's timestamps; call after submitting
+    pub fn after_submit(&mut self) {
+        self.frame += 1;
+        if self.recorded.is_empty() {
+            return;
+        }
+        let mapped = self.mapped.clone();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                *mapped.lock().unwrap() = Some(result);
+            });
+        self.in_flight = Some((self.frame - 1, std::mem::take(&mut self.recorded)));
+    }
+
+    /// Pick up a finished readback. On native the device must have been
+    /// polled since `after_submit` for the mapping to complete.
+    pub fn collect(&mut self) {
+        let Some(result) = self.mapped.lock().unwrap().take() else {
+            return;
+        };
+        let Some((frame, passes)) = self.in_flight.take() else {
+            return;
+        };
+        if let Err(e) = result {
+            tracing::warn!("Timestamp readback failed: {}", e);
+            return;
+        }
+
+        let timestamps: Vec<u64> = {
+            let mapped = self.readback_buffer.slice(..).get_mapped_range();
+            bytemuck::cast_slice(&mapped).to_vec()
+        };
+        self.readback_buffer.unmap();
+
+        let timings = GpuTimings {
+            frame,
+            passes: passes
+                .into_iter()
+                .map(|pass| {
+                    let i = pass.first_query() as usize;
+                    let ticks = timestamps[i + 1].saturating_sub(timestamps[i]);
+                    (pass, ticks as f32 * self.period / 1_000_000.0)
+                })
+                .collect(),
+        };
+        tracing::debug!(
+            "GPU frame {}: {}",
+            timings.frame,
+            timings
+                .passes
+                .iter()
+                .map(|(pass, ms)| format!("{} {:.2} ms", pass.name(), ms))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        self.latest = Some(timings);
+    }
+}
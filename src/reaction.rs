@@ -0,0 +1,116 @@
+//! Gray–Scott reaction–diffusion over the cell adjacency graph. Each cell
+//! holds two chemicals: a substrate `u` fed in from outside and a catalyst
+//! `v` that consumes it to replicate and slowly decays. Both spread to
+//! neighbouring cells, the substrate faster, so spots of catalyst grow, split
+//! and travel across the honeycomb. The raymarcher tints and thickens cells by
+//! their catalyst.
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::world::HoneycombWorld;
+
+/// Simulation steps per second of viewer time
+const STEPS_PER_SECOND: f32 = 20.0;
+/// Steps run in one frame at most, so a stalled frame doesn't snowball
+const MAX_STEPS_PER_FRAME: u32 = 8;
+/// Rates at which each chemical evens out with the neighbours' average per
+/// step. Cells are coarse, so these are far below the usual grid values.
+const DIFFUSION_U: f32 = 0.1;
+const DIFFUSION_V: f32 = 0.05;
+/// Cells the catalyst starts in
+const INITIAL_SPOTS: usize = 4;
+/// Steps between drops of fresh catalyst, which keep a settled pattern moving
+const STEPS_PER_DROP: u32 = 200;
+
+/// Feed and kill rates for the default pattern: spots that keep dividing
+pub const FEED: f32 = 0.04;
+pub const KILL: f32 = 0.06;
+
+pub struct ReactionDiffusion {
+    neighbours: Vec<Vec<usize>>,
+    u: Vec<f32>,
+    v: Vec<f32>,
+    /// Time not yet simulated, in seconds
+    pending: f32,
+    steps: u32,
+    rng: ChaCha8Rng,
+}
+
+impl ReactionDiffusion {
+    /// All substrate and no catalyst but for a few cells picked by `seed`,
+    /// with the cells' neighbours as `HoneycombWorld::neighbours` finds them
+    pub fn new(world: &HoneycombWorld, neighbours: Vec<Vec<usize>>, seed: u64) -> Self {
+        let count = world.cells.len();
+        let mut simulation = Self {
+            neighbours,
+            u: vec![1.0; count],
+            v: vec![0.0; count],
+            pending: 0.0,
+            steps: 0,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        };
+        for _ in 0..INITIAL_SPOTS.min(count) {
+            simulation.drop_catalyst();
+        }
+        simulation
+    }
+
+    /// Run the steps `dt` seconds are worth with the given feed and kill
+    /// rates. Returns whether anything changed.
+    pub fn advance(&mut self, dt: f32, feed: f32, kill: f32) -> bool {
+        self.pending += dt;
+        let steps = ((self.pending * STEPS_PER_SECOND) as u32).min(MAX_STEPS_PER_FRAME);
+        self.pending = (self.pending - steps as f32 / STEPS_PER_SECOND).min(1.0 / STEPS_PER_SECOND);
+        for _ in 0..steps {
+            self.step(feed, kill);
+        }
+        steps > 0
+    }
+
+    /// Substrate and catalyst of each cell, both 0 to 1
+    pub fn concentrations(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        self.u.iter().copied().zip(self.v.iter().copied())
+    }
+
+    fn step(&mut self, feed: f32, kill: f32) {
+        let laplacian = |values: &[f32], i: usize, neighbours: &[usize]| {
+            if neighbours.is_empty() {
+                return 0.0;
+            }
+            let sum: f32 = neighbours.iter().map(|&j| values[j]).sum();
+            sum / neighbours.len() as f32 - values[i]
+        };
+        let (u, v): (Vec<f32>, Vec<f32>) = self
+            .neighbours
+            .iter()
+            .enumerate()
+            .map(|(i, neighbours)| {
+                let (u, v) = (self.u[i], self.v[i]);
+                let reaction = u * v * v;
+                let du =
+                    DIFFUSION_U * laplacian(&self.u, i, neighbours) - reaction + feed * (1.0 - u);
+                let dv =
+                    DIFFUSION_V * laplacian(&self.v, i, neighbours) + reaction - (feed + kill) * v;
+                ((u + du).clamp(0.0, 1.0), (v + dv).clamp(0.0, 1.0))
+            })
+            .unzip();
+        self.u = u;
+        self.v = v;
+
+        self.steps += 1;
+        if self.steps.is_multiple_of(STEPS_PER_DROP) {
+            self.drop_catalyst();
+        }
+    }
+
+    /// Seed a random cell with catalyst
+    fn drop_catalyst(&mut self) {
+        if self.u.is_empty() {
+            return;
+        }
+        let cell = self.rng.gen_range(0..self.u.len());
+        self.u[cell] = 0.5;
+        self.v[cell] = 0.75;
+    }
+}
@@ -0,0 +1,79 @@
+//! Files the native viewer reloads when they change on disk, so frame
+//! scripts and timelines can be edited while the viewer runs.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// How often a file is checked for changes
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A file, parsed again whenever it changes
+pub struct WatchedFile {
+    path: PathBuf,
+    /// What the file holds, for log messages
+    kind: &'static str,
+    /// Modification time of the version last loaded
+    modified: Option<SystemTime>,
+    last_check: Option<web_time::Instant>,
+}
+
+impl WatchedFile {
+    pub fn new(path: PathBuf, kind: &'static str) -> Self {
+        Self {
+            path,
+            kind,
+            modified: None,
+            last_check: None,
+        }
+    }
+
+    /// The file parsed, the first time and whenever it has changed since.
+    /// Checks at most once a second; a file that can't be read or parsed is
+    /// logged and leaves the caller's current version in use.
+    pub fn poll<T, E: fmt::Display>(
+        &mut self,
+        parse: impl FnOnce(&str) -> Result<T, E>,
+    ) -> Option<T> {
+        let now = web_time::Instant::now();
+        let first = self.last_check.is_none();
+        if self
+            .last_check
+            .is_some_and(|last| now - last < CHECK_INTERVAL)
+        {
+            return None;
+        }
+        self.last_check = Some(now);
+
+        // A missing file is reported once, and loaded when it appears
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if !first && modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        let source = match std::fs::read_to_string(&self.path) {
+            Ok(source) => source,
+            Err(e) => {
+                tracing::error!(
+                    "Could not read {} {}: {}",
+                    self.kind,
+                    self.path.display(),
+                    e
+                );
+                return None;
+            }
+        };
+        match parse(&source) {
+            Ok(parsed) => {
+                tracing::info!("Loaded {} {}", self.kind, self.path.display());
+                Some(parsed)
+            }
+            Err(e) => {
+                tracing::error!("{}: {}", self.path.display(), e);
+                None
+            }
+        }
+    }
+}
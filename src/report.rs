@@ -0,0 +1,176 @@
+//! Error reports in the page. Panics and GPU errors (shader validation,
+//! device loss, running out of memory) would otherwise only reach the
+//! browser console, which most visitors never open. Instead a panel at the
+//! bottom of the page describes what went wrong, with a report to copy
+//! into a bug: the message, the JavaScript stack for panics, the adapter
+//! and the browser.
+//!
+//! An error that repeats every frame is listed once, and a dismissed panel
+//! stays closed until something new goes wrong. After a panic the page's
+//! viewers have stopped and it needs reloading.
+
+use std::cell::RefCell;
+
+use wasm_bindgen::JsValue;
+
+/// Kinds of error kept at most, so an error repeated every frame can't grow
+/// the report without end
+const MAX_ENTRIES: usize = 10;
+const PANEL_STYLE: &str = "position: fixed; left: 16px; right: 16px; bottom: 16px; \
+    z-index: 2147483647; max-height: 60vh; overflow: auto; padding: 16px; \
+    background: #1b1b1f; color: #eee; border: 1px solid #c44; border-radius: 6px; \
+    font: 14px sans-serif; box-shadow: 0 4px 24px rgba(0, 0, 0, 0.6);";
+const REPORT_STYLE: &str = "box-sizing: border-box; width: 100%; height: 12em; \
+    margin: 8px 0; background: #111; color: #ddd; border: 1px solid #444; \
+    font: 12px monospace; white-space: pre;";
+
+#[derive(Default)]
+struct Report {
+    /// Each distinct error with how often it happened
+    entries: Vec<(String, usize)>,
+    /// Descriptions of the adapters viewers on the page use
+    adapters: Vec<String>,
+    panicked: bool,
+    /// The panel, once shown
+    panel: Option<web_sys::Element>,
+}
+
+thread_local! {
+    static REPORT: RefCell<Report> = RefCell::new(Report::default());
+}
+
+/// Report panics in the page as well as the console
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        console_error_panic_hook::hook(info);
+        let mut text = info.to_string();
+        if let Some(stack) = js_stack() {
+            text.push_str("\n\n");
+            text.push_str(&stack);
+        }
+        add(text, true);
+    }));
+}
+
+/// Note the adapter a viewer runs on, for the report
+pub fn note_adapter(description: String) {
+    let _ = REPORT.try_with(|report| {
+        let Ok(mut report) = report.try_borrow_mut() else {
+            return;
+        };
+        if !report.adapters.contains(&description) {
+            report.adapters.push(description);
+        }
+    });
+}
+
+/// Report an error from the GPU
+pub fn show_gpu_error(message: &str) {
+    add(format!("GPU error: {}", message), false);
+}
+
+fn add(text: String, panic: bool) {
+    let _ = REPORT.try_with(|report| {
+        // A panic while the report itself is being updated goes to the
+        // console only
+        let Ok(mut report) = report.try_borrow_mut() else {
+            return;
+        };
+        report.panicked |= panic;
+        let repeat = match report.entries.iter_mut().find(|(entry, _)| *entry == text) {
+            Some((_, count)) => {
+                *count += 1;
+                true
+            }
+            None => {
+                if report.entries.len() < MAX_ENTRIES {
+                    report.entries.push((text, 1));
+                }
+                false
+            }
+        };
+        // Repeats are counted but leave the panel alone, so an error every
+        // frame doesn't keep undoing the selection in it, or bring it back
+        // once dismissed
+        if repeat {
+            return;
+        }
+        if let Err(e) = report.show() {
+            web_sys::console::error_2(&"Could not show error report:".into(), &e);
+        }
+    });
+}
+
+impl Report {
+    fn text(&self) -> String {
+        let mut text = String::new();
+        for (entry, count) in &self.entries {
+            text.push_str(entry);
+            if *count > 1 {
+                text.push_str(&format!(" (×{})", count));
+            }
+            text.push_str("\n\n");
+        }
+        for adapter in &self.adapters {
+            text.push_str(&format!("Adapter: {}\n", adapter));
+        }
+        if let Some(agent) =
+            web_sys::window().and_then(|window| window.navigator().user_agent().ok())
+        {
+            text.push_str(&format!("Browser: {}\n", agent));
+        }
+        text.push_str(&format!("Vendek {}", env!("CARGO_PKG_VERSION")));
+        text
+    }
+
+    /// Show the panel, or bring it up to date if it's showing
+    fn show(&mut self) -> Result<(), JsValue> {
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or_else(|| JsValue::from_str("no document"))?;
+        let body = document
+            .body()
+            .ok_or_else(|| JsValue::from_str("document has no body"))?;
+
+        let panel = document.create_element("div")?;
+        panel.set_attribute("role", "alert")?;
+        panel.set_attribute("style", PANEL_STYLE)?;
+        let heading = document.create_element("h2")?;
+        heading.set_attribute("style", "margin: 0 0 8px; font-size: 18px;")?;
+        heading.set_text_content(Some("Vendek ran into a problem"));
+        let explanation = document.create_element("p")?;
+        explanation.set_attribute("style", "margin: 0;")?;
+        explanation.set_text_content(Some(if self.panicked {
+            "The viewer has stopped; reloading the page starts it again. \
+             If you report this, please include the text below."
+        } else {
+            "The picture may be wrong or missing. \
+             If you report this, please include the text below."
+        }));
+        let report = document.create_element("textarea")?;
+        report.set_attribute("readonly", "")?;
+        report.set_attribute("style", REPORT_STYLE)?;
+        report.set_attribute("onfocus", "this.select()")?;
+        report.set_text_content(Some(&self.text()));
+        let dismiss = document.create_element("button")?;
+        dismiss.set_attribute("onclick", "this.parentElement.remove()")?;
+        dismiss.set_text_content(Some("Dismiss"));
+        for child in [&heading, &explanation, &report, &dismiss] {
+            panel.append_child(child)?;
+        }
+
+        match self.panel.replace(panel.clone()) {
+            Some(old) if old.is_connected() => old.replace_with_with_node_1(&panel),
+            _ => body.append_child(&panel).map(|_| ()),
+        }
+    }
+}
+
+/// Where the panic happened, from a JavaScript stack trace; the Rust frames
+/// in it carry their function names when the build keeps them
+fn js_stack() -> Option<String> {
+    let error = js_sys::Error::new("");
+    js_sys::Reflect::get(&error, &"stack".into())
+        .ok()?
+        .as_string()
+}
@@ -0,0 +1,107 @@
+//! Dynamic resolution: lowers the raymarch resolution when frames take longer
+//! than the target frame rate allows, and raises it again once there is
+//! headroom. Steps down react within half a second; steps up wait longer, and
+//! wait longer still each time one had to be undone, so the scale settles
+//! instead of oscillating.
+
+/// Lowest scale, relative to the requested resolution
+const MIN_SCALE: f32 = 0.5;
+/// Change per adjustment
+const STEP: f32 = 0.1;
+/// Frames to let the average settle after a change before judging it
+const SETTLE_FRAMES: u32 = 30;
+/// Frames under budget before trying a step up, and the cap it backs off to
+const UP_DELAY: u32 = 120;
+const MAX_UP_DELAY: u32 = 1920;
+/// Cost above the budget by this factor triggers a step down
+const OVER_BUDGET: f32 = 1.15;
+/// Cost must stay under the budget by this factor to allow a step up
+const UNDER_BUDGET: f32 = 1.05;
+
+pub struct DynamicResolution {
+    scale: f32,
+    /// Smoothed frame cost in milliseconds
+    average_ms: f32,
+    /// Frames since the last change
+    since_change: u32,
+    up_delay: u32,
+    /// The last change was a step up that hasn't yet proven sustainable
+    probing: bool,
+}
+
+impl DynamicResolution {
+    pub fn new() -> Self {
+        Self {
+            scale: 1.0,
+            average_ms: 0.0,
+            since_change: 0,
+            up_delay: UP_DELAY,
+            probing: false,
+        }
+    }
+
+    /// Start below full resolution, at `scale` clamped to 0.5 to 1, on
+    /// adapters unlikely to hold the target frame rate at full resolution
+    pub fn starting_at(scale: f32) -> Self {
+        Self {
+            scale: scale.clamp(MIN_SCALE, 1.0),
+            ..Self::new()
+        }
+    }
+
+    /// Current scale, from 0.5 to 1
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Back to full resolution, e.g. when scaling is switched off
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Feed one frame's cost in milliseconds: GPU time where timestamp
+    /// queries are available, otherwise wall time. Returns the new scale when
+    /// it changes.
+    pub fn update(&mut self, frame_ms: f32, target_fps: f32) -> Option<f32> {
+        let budget_ms = 1000.0 / target_fps;
+        self.average_ms = if self.since_change == 0 {
+            frame_ms
+        } else {
+            self.average_ms + (frame_ms - self.average_ms) * 0.1
+        };
+        self.since_change += 1;
+        if self.since_change < SETTLE_FRAMES {
+            return None;
+        }
+
+        if self.average_ms > budget_ms * OVER_BUDGET && self.scale > MIN_SCALE {
+            // A step up that immediately misses the budget makes the next one wait longer
+            if self.probing {
+                self.up_delay = (self.up_delay * 2).min(MAX_UP_DELAY);
+            }
+            self.probing = false;
+            return Some(self.change((self.scale - STEP).max(MIN_SCALE)));
+        }
+
+        if self.since_change >= self.up_delay {
+            self.probing = false;
+            if self.average_ms < budget_ms * UNDER_BUDGET && self.scale < 1.0 {
+                self.probing = true;
+                return Some(self.change((self.scale + STEP).min(1.0)));
+            }
+        }
+        None
+    }
+
+    fn change(&mut self, scale: f32) -> f32 {
+        self.scale = scale;
+        self.since_change = 0;
+        scale
+    }
+}
+
+impl Default for DynamicResolution {
+    fn default() -> Self {
+        Self::new()
+    }
+}
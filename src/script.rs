@@ -0,0 +1,326 @@
+//! Frame scripts: Rhai code run once per frame, for programmed animation
+//! without recompiling:
+//!
+//! ```text
+//! // pulse the density and slowly orbit
+//! density = 1.5 + 0.5 * sin(t * 2);
+//! camera.yaw += dt * 0.1;
+//! let swell = smoothstep(0, 1, fract(t / 10));
+//! membraneGlow = mix(0.2, 1.2, swell);
+//! ```
+//!
+//! Every runtime parameter is a variable named as in JavaScript, and
+//! `camera` has `yaw`, `pitch`, `distance` and focus `x`, `y` and `z`
+//! properties; whatever the script leaves in them is applied once it has
+//! run. `t` (viewer time in seconds, which stops while paused), `dt` (seconds
+//! since the last frame) and `pi` are constants. The functions registered in
+//! `engine` take numbers with or without a decimal point. A script is
+//! compiled with every variable declared, so a misspelt name is an error
+//! when it loads rather than an assignment that silently does nothing, and
+//! run once on default values, which catches most other mistakes then too.
+//!
+//! A run is cut off after `MAX_OPERATIONS`, so a runaway loop can't hang
+//! the viewer. A frame whose run fails changes nothing and is logged once
+//! until the script runs cleanly again, and values that aren't finite are
+//! skipped. Camera changes glide in as mouse input does.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use glam::Vec3;
+use rhai::packages::{CorePackage, Package};
+use rhai::{Dynamic, Engine, EvalAltResult, OptimizationLevel, ParseError, Scope, AST};
+
+use crate::camera::{CameraPose, PoseField};
+use crate::params::RuntimeParams;
+use crate::world::HoneycombWorld;
+
+/// Operations a run may take before it is stopped, so a runaway loop can't
+/// stall the viewer
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// What the functions registered with Rhai return
+type Fallible<T> = Result<T, Box<EvalAltResult>>;
+
+type Unary = fn(f64) -> f64;
+type Binary = fn(f64, f64) -> f64;
+type Ternary = fn(f64, f64, f64) -> f64;
+
+/// Functions of one number
+const UNARY: &[(&str, Unary)] = &[
+    ("sin", f64::sin),
+    ("cos", f64::cos),
+    ("tan", f64::tan),
+    ("asin", f64::asin),
+    ("acos", f64::acos),
+    ("atan", f64::atan),
+    ("sqrt", f64::sqrt),
+    ("exp", f64::exp),
+    ("ln", f64::ln),
+    ("abs", f64::abs),
+    ("sign", |x| if x == 0.0 { 0.0 } else { x.signum() }),
+    ("floor", f64::floor),
+    ("ceil", f64::ceil),
+    ("round", f64::round),
+    ("fract", |x| x - x.floor()),
+];
+
+/// Functions of two numbers
+const BINARY: &[(&str, Binary)] = &[
+    ("atan2", f64::atan2),
+    ("min", f64::min),
+    ("max", f64::max),
+    ("step", |edge, x| if x < edge { 0.0 } else { 1.0 }),
+];
+
+/// Functions of three numbers
+const TERNARY: &[(&str, Ternary)] = &[
+    ("clamp", |x, low, high| x.max(low).min(high)),
+    ("mix", |a, b, t| a + (b - a) * t),
+    ("smoothstep", |low, high, x| {
+        let t = ((x - low) / (high - low)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }),
+];
+
+/// The camera properties scripts read and write
+const CAMERA_FIELDS: &[(&str, PoseField)] = &[
+    ("yaw", PoseField::Yaw),
+    ("pitch", PoseField::Pitch),
+    ("distance", PoseField::Distance),
+    ("x", PoseField::X),
+    ("y", PoseField::Y),
+    ("z", PoseField::Z),
+];
+
+/// Why a script was rejected or stopped
+#[derive(Debug)]
+pub struct ScriptError {
+    /// 1-based, where Rhai knows it
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "script line {}: {}", line, self.message),
+            None => write!(f, "script: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<ParseError> for ScriptError {
+    fn from(e: ParseError) -> Self {
+        Self {
+            line: e.position().line(),
+            message: e.err_type().to_string(),
+        }
+    }
+}
+
+impl From<Box<EvalAltResult>> for ScriptError {
+    fn from(mut e: Box<EvalAltResult>) -> Self {
+        // Taken out so the message doesn't repeat it
+        let position = e.take_position();
+        Self {
+            line: position.line(),
+            message: e.to_string(),
+        }
+    }
+}
+
+/// What a script reads and writes each frame
+pub struct Frame<'a> {
+    pub time: f32,
+    pub dt: f32,
+    pub params: &'a mut RuntimeParams,
+    pub camera: &'a mut CameraPose,
+    /// What `cell` and `phase` look points up in, lent to the script while
+    /// it runs
+    pub world: &'a mut HoneycombWorld,
+}
+
+/// A compiled script, ready to run
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    /// The world while a run has it. Rhai's functions can't borrow, so the
+    /// frame's world is swapped in for the run and back out after.
+    world: Rc<RefCell<HoneycombWorld>>,
+    /// Whether the last run failed, so an error every frame is logged once
+    failing: bool,
+}
+
+impl Script {
+    pub fn parse(source: &str) -> Result<Self, ScriptError> {
+        let world = Rc::new(RefCell::new(HoneycombWorld::default()));
+        let engine = engine(&world);
+        let mut params = RuntimeParams::default();
+        let mut camera = CameraPose {
+            focus: Vec3::ZERO,
+            yaw: 0.0,
+            pitch: 0.0,
+            distance: 1.0,
+        };
+        let mut frame = Frame {
+            time: 0.0,
+            dt: 0.0,
+            params: &mut params,
+            camera: &mut camera,
+            world: &mut HoneycombWorld::default(),
+        };
+        let ast = engine.compile_with_scope(&scope(&frame), source)?;
+        let script = Self {
+            engine,
+            ast,
+            world,
+            failing: false,
+        };
+        script.evaluate(&mut frame)?;
+        Ok(script)
+    }
+
+    /// Run the script once and apply what it set. Values that aren't finite,
+    /// such as a division by zero, are skipped. A run that fails changes
+    /// nothing, and is logged unless the one before failed too.
+    pub fn run(&mut self, frame: &mut Frame) {
+        match self.evaluate(frame) {
+            Ok(()) => self.failing = false,
+            Err(e) => {
+                if !self.failing {
+                    tracing::warn!("{}", e);
+                }
+                self.failing = true;
+            }
+        }
+    }
+
+    fn evaluate(&self, frame: &mut Frame) -> Result<(), ScriptError> {
+        let mut scope = scope(frame);
+        std::mem::swap(&mut *self.world.borrow_mut(), frame.world);
+        let result = self.engine.run_ast_with_scope(&mut scope, &self.ast);
+        std::mem::swap(&mut *self.world.borrow_mut(), frame.world);
+        result?;
+
+        let mut params = *frame.params;
+        for &name in RuntimeParams::NAMES {
+            let Some(value) = scope.get(name) else {
+                continue;
+            };
+            let value = number(value).map_err(|_| {
+                error(format!(
+                    "`{}` must be a number, not {}",
+                    name,
+                    value.type_name()
+                ))
+            })?;
+            if value.is_finite() && Some(value) != frame.params.get(name) {
+                let _ = params.set(name, value);
+            }
+        }
+        let camera = scope
+            .get_value::<CameraPose>("camera")
+            .ok_or_else(|| error("`camera` was replaced".to_string()))?;
+        *frame.params = params;
+        *frame.camera = camera;
+        Ok(())
+    }
+}
+
+/// The variables a script sees, as `frame` has them
+fn scope(frame: &Frame) -> Scope<'static> {
+    let mut scope = Scope::new();
+    scope.push_constant("t", frame.time as f64);
+    scope.push_constant("dt", frame.dt as f64);
+    scope.push_constant("pi", std::f64::consts::PI);
+    for &name in RuntimeParams::NAMES {
+        scope.push(name, frame.params.get(name).unwrap_or_default());
+    }
+    scope.push("camera", *frame.camera);
+    scope
+}
+
+/// A Rhai engine with the viewer's functions and camera type, looking cells
+/// up in `world`
+fn engine(world: &Rc<RefCell<HoneycombWorld>>) -> Engine {
+    let mut engine = Engine::new_raw();
+    engine.register_global_module(CorePackage::new().as_shared_module());
+    engine.set_strict_variables(true);
+    // `t` and `dt` are constants whose values change every frame, so they
+    // mustn't be folded into the compiled script
+    engine.set_optimization_level(OptimizationLevel::None);
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.on_print(|text| tracing::info!("script: {}", text));
+
+    for &(name, function) in UNARY {
+        engine.register_fn(name, move |x: Dynamic| -> Fallible<f64> {
+            Ok(function(number(&x)?))
+        });
+    }
+    for &(name, function) in BINARY {
+        engine.register_fn(name, move |a: Dynamic, b: Dynamic| -> Fallible<f64> {
+            Ok(function(number(&a)?, number(&b)?))
+        });
+    }
+    for &(name, function) in TERNARY {
+        engine.register_fn(
+            name,
+            move |a: Dynamic, b: Dynamic, c: Dynamic| -> Fallible<f64> {
+                Ok(function(number(&a)?, number(&b)?, number(&c)?))
+            },
+        );
+    }
+
+    // cell(x, y, z): index of the cell the point lies in, -1 outside the
+    // volume; phase(x, y, z): phase of that cell, likewise
+    for (name, phase) in [("cell", false), ("phase", true)] {
+        let world = world.clone();
+        engine.register_fn(
+            name,
+            move |x: Dynamic, y: Dynamic, z: Dynamic| -> Fallible<f64> {
+                let point = Vec3::new(number(&x)? as f32, number(&y)? as f32, number(&z)? as f32);
+                let world = world.borrow();
+                Ok(match world.cell_at(point) {
+                    Some(cell) if phase => world.cells[cell].phase_index as f64,
+                    Some(cell) => cell as f64,
+                    None => -1.0,
+                })
+            },
+        );
+    }
+
+    engine.register_type_with_name::<CameraPose>("Camera");
+    for &(name, field) in CAMERA_FIELDS {
+        engine.register_get(name, move |pose: &mut CameraPose| pose.get(field) as f64);
+        engine.register_set(
+            name,
+            move |pose: &mut CameraPose, value: Dynamic| -> Fallible<()> {
+                let value = number(&value)?;
+                if value.is_finite() {
+                    pose.set(field, value as f32);
+                }
+                Ok(())
+            },
+        );
+    }
+    engine
+}
+
+/// A number, whether written with a decimal point or not
+fn number(value: &Dynamic) -> Fallible<f64> {
+    value
+        .as_float()
+        .or_else(|_| value.as_int().map(|value| value as f64))
+        .map_err(|kind| format!("expected a number, not {}", kind).into())
+}
+
+fn error(message: String) -> ScriptError {
+    ScriptError {
+        line: None,
+        message,
+    }
+}
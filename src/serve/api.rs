@@ -0,0 +1,119 @@
+//! REST control API, for tools and scripts that would rather speak HTTP than
+//! hold a WebSocket open. Parameters and camera are the hub's shared session,
+//! so a change made here reaches every open page; presets are named sets of
+//! parameters kept for as long as the server runs.
+//!
+//! - `GET /api/params`: every parameter set so far, by JavaScript name
+//! - `POST /api/params`: set the parameters in a JSON object, e.g.
+//!   `{"membraneGlow": 0.8}`, and answer with the resulting state
+//! - `GET /api/camera`: the last camera pose shared, or `null`
+//! - `POST /api/camera`: move every page's camera to a pose, e.g.
+//!   `{"focus": [0, 0, 0], "yaw": 0.3, "pitch": 0.4, "distance": 35}`
+//! - `GET /api/presets`: every preset, by name
+//! - `GET`/`PUT`/`DELETE /api/presets/{name}`: read, store or remove one
+//! - `POST /api/presets/{name}/apply`: set a preset's parameters
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+
+use crate::live::{CameraPose, Hub, Params, Session};
+
+#[derive(Clone)]
+struct Api {
+    hub: Hub,
+    presets: Arc<Mutex<BTreeMap<String, Params>>>,
+}
+
+/// The API's routes, sharing `hub` with the live-control channel
+pub fn router(hub: Hub) -> Router {
+    Router::new()
+        .route("/api/params", get(get_params).post(set_params))
+        .route("/api/camera", get(get_camera).post(set_camera))
+        .route("/api/presets", get(list_presets))
+        .route(
+            "/api/presets/{name}",
+            get(get_preset).put(put_preset).delete(delete_preset),
+        )
+        .route("/api/presets/{name}/apply", post(apply_preset))
+        .with_state(Api {
+            hub,
+            presets: Arc::default(),
+        })
+}
+
+async fn get_params(State(api): State<Api>) -> Json<Params> {
+    Json(api.hub.session().params)
+}
+
+async fn set_params(State(api): State<Api>, Json(params): Json<Params>) -> Json<Params> {
+    Json(set(&api, params))
+}
+
+async fn get_camera(State(api): State<Api>) -> Json<Option<CameraPose>> {
+    Json(api.hub.session().camera)
+}
+
+async fn set_camera(State(api): State<Api>, Json(camera): Json<CameraPose>) -> StatusCode {
+    api.hub.update(&Session {
+        camera: Some(camera),
+        ..Default::default()
+    });
+    StatusCode::NO_CONTENT
+}
+
+async fn list_presets(State(api): State<Api>) -> Json<BTreeMap<String, Params>> {
+    Json(api.presets.lock().unwrap().clone())
+}
+
+async fn get_preset(State(api): State<Api>, Path(name): Path<String>) -> Response {
+    match api.presets.lock().unwrap().get(&name) {
+        Some(params) => Json(params.clone()).into_response(),
+        None => no_preset(&name),
+    }
+}
+
+async fn put_preset(
+    State(api): State<Api>,
+    Path(name): Path<String>,
+    Json(params): Json<Params>,
+) -> StatusCode {
+    match api.presets.lock().unwrap().insert(name, params) {
+        Some(_) => StatusCode::NO_CONTENT,
+        None => StatusCode::CREATED,
+    }
+}
+
+async fn delete_preset(State(api): State<Api>, Path(name): Path<String>) -> Response {
+    match api.presets.lock().unwrap().remove(&name) {
+        Some(_) => StatusCode::NO_CONTENT.into_response(),
+        None => no_preset(&name),
+    }
+}
+
+async fn apply_preset(State(api): State<Api>, Path(name): Path<String>) -> Response {
+    let preset = api.presets.lock().unwrap().get(&name).cloned();
+    match preset {
+        Some(params) => Json(set(&api, params)).into_response(),
+        None => no_preset(&name),
+    }
+}
+
+/// Set `params` everywhere and return every parameter
+fn set(api: &Api, params: Params) -> Params {
+    api.hub
+        .update(&Session {
+            params,
+            ..Default::default()
+        })
+        .params
+}
+
+fn no_preset(name: &str) -> Response {
+    (StatusCode::NOT_FOUND, format!("no preset named `{}`", name)).into_response()
+}
@@ -0,0 +1,97 @@
+//! Caching for the static files. Every file gets an ETag from its
+//! modification time and length, so a browser revalidating
+//! `pkg/vendek_bg.wasm` on a repeat visit gets a bodiless 304 instead of
+//! megabytes, and a rebuild is picked up at once since the tag changes with
+//! it. Nothing is cached without revalidation: the package's file names
+//! don't change between builds, so a max-age would serve a stale viewer
+//! after `build-web.sh`. Compression is tower-http's `CompressionLayer`,
+//! which sits inside this middleware, so each encoding of a file is tagged
+//! separately and a 304 never compresses anything.
+
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{header, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use percent_encoding::percent_decode_str;
+
+/// Revalidate on every use; with the ETag that is a cheap 304
+const CACHE_CONTROL: &str = "no-cache";
+
+/// Middleware for the file service: tag and answer conditional requests
+pub async fn handle(request: Request, next: Next) -> Response {
+    if request.method() != Method::GET {
+        return next.run(request).await;
+    }
+    let Some(version) = file_version(request.uri().path()) else {
+        return next.run(request).await;
+    };
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+
+    let mut response = next.run(request).await;
+    // Errors and partial content (Range requests) go out untouched
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+    // A compressed variant is a different representation, so it gets its
+    // own tag
+    let etag = match response
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(encoding) => format!("\"{}-{}\"", version, encoding),
+        None => format!("\"{}\"", version),
+    };
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(CACHE_CONTROL),
+    );
+    headers.insert(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+
+    if if_none_match.is_some_and(|value| names_etag(&value, &etag)) {
+        // The body is dropped unread, so the file is neither read nor
+        // compressed
+        let (mut parts, _) = response.into_parts();
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_LENGTH);
+        return Response::from_parts(parts, Body::empty());
+    }
+    response
+}
+
+/// Modification time and length of the file a request path names under the
+/// working directory, which the file service serves, as `ServeDir` resolves
+/// it. They change whenever the file is rewritten.
+fn file_version(path: &str) -> Option<String> {
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+    let mut file = PathBuf::from(".");
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => return None,
+            _ => file.push(component),
+        }
+    }
+    let mut metadata = std::fs::metadata(&file).ok()?;
+    if metadata.is_dir() {
+        file.push("index.html");
+        metadata = std::fs::metadata(&file).ok()?;
+    }
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("{:x}-{:x}", modified.as_nanos(), metadata.len()))
+}
+
+/// Whether `If-None-Match` names `etag`
+fn names_etag(if_none_match: &HeaderValue, etag: &str) -> bool {
+    if_none_match.to_str().is_ok_and(|value| {
+        value.split(',').any(|candidate| {
+            let candidate = candidate.trim();
+            candidate == "*" || candidate.trim_start_matches("W/") == etag
+        })
+    })
+}
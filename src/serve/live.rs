@@ -0,0 +1,205 @@
+//! Live-control channel: viewer pages and control pages connect to `/ws`,
+//! and every text message one client sends is relayed to all the others.
+//! Messages are JSON such as `{"params": {"membraneGlow": 0.8}}` or
+//! `{"camera": {"focus": [0, 0, 0], "yaw": 0.3, "pitch": 0.4, "distance": 35}}`;
+//! the server relays them as they are, and the pages apply them through the
+//! viewer's JavaScript API. The hub also keeps the session they add up to,
+//! the latest value of every parameter and the latest camera, so whoever
+//! wrote last wins. It hands the session to each page as it connects and
+//! lets the REST API read and change it.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::Response;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// Messages a slow client may fall behind by before it misses some
+const BACKLOG: usize = 256;
+/// Sender of the messages the server makes itself; clients count from 1
+const SERVER: u64 = 0;
+
+/// Parameter values by their JavaScript name
+pub type Params = BTreeMap<String, f64>;
+
+/// The connected clients and the session they share
+#[derive(Clone)]
+pub struct Hub {
+    sender: broadcast::Sender<Relayed>,
+    next_client: Arc<AtomicU64>,
+    session: Arc<std::sync::Mutex<Session>>,
+}
+
+/// Shared viewing state, and the part of a message the hub reads; anything
+/// else in a message is only relayed
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default, skip_serializing_if = "Params::is_empty")]
+    pub params: Params,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera: Option<CameraPose>,
+}
+
+/// A camera pose as the viewer's `get_camera` gives it
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct CameraPose {
+    pub focus: [f64; 3],
+    pub yaw: f64,
+    pub pitch: f64,
+    pub distance: f64,
+}
+
+/// A message and the client it came from, which doesn't get it back
+#[derive(Clone, Debug)]
+struct Relayed {
+    from: u64,
+    text: Arc<str>,
+}
+
+impl Hub {
+    pub fn new() -> Self {
+        Self {
+            sender: broadcast::channel(BACKLOG).0,
+            next_client: Arc::new(AtomicU64::new(1)),
+            session: Arc::default(),
+        }
+    }
+
+    /// Every parameter set since the server started at its latest value,
+    /// and the last camera pose
+    pub fn session(&self) -> Session {
+        self.session.lock().unwrap().clone()
+    }
+
+    /// Record `changes` and send them to every client. Returns the session
+    /// after the change.
+    pub fn update(&self, changes: &Session) -> Session {
+        self.record(SERVER, changes, None)
+    }
+
+    /// Number of pages connected
+    pub fn client_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Send `text` to every client without recording anything
+    pub fn broadcast(&self, text: &str) {
+        let _ = self.sender.send(Relayed {
+            from: SERVER,
+            text: text.into(),
+        });
+    }
+
+    /// Merge `changes` into the session and relay them, as `text` if given.
+    /// Holding the lock while sending keeps every client's order the same
+    /// as the session's.
+    fn record(&self, from: u64, changes: &Session, text: Option<Arc<str>>) -> Session {
+        let mut session = self.session.lock().unwrap();
+        session.params.extend(
+            changes
+                .params
+                .iter()
+                .map(|(name, &value)| (name.clone(), value)),
+        );
+        if changes.camera.is_some() {
+            session.camera = changes.camera;
+        }
+        let text = text.unwrap_or_else(|| changes.to_message().into());
+        let _ = self.sender.send(Relayed { from, text });
+        session.clone()
+    }
+}
+
+impl Session {
+    fn is_empty(&self) -> bool {
+        self.params.is_empty() && self.camera.is_none()
+    }
+
+    fn to_message(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+/// Longest message accepted; control messages are a few hundred bytes
+const MAX_MESSAGE: usize = 1 << 20;
+
+/// `GET /ws`: upgrade to a WebSocket and join the hub
+pub async fn handler(State(hub): State<Hub>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade
+        .max_message_size(MAX_MESSAGE)
+        .on_failed_upgrade(|e| eprintln!("WebSocket upgrade failed: {}", e))
+        .on_upgrade(move |socket| connection(socket, hub))
+}
+
+/// Relay between one client and the rest until it disconnects
+async fn connection(mut socket: WebSocket, hub: Hub) {
+    let id = hub.next_client.fetch_add(1, Ordering::Relaxed);
+    let mut receiver = hub.sender.subscribe();
+    println!(
+        "Live client {} connected ({} total)",
+        id,
+        hub.sender.receiver_count()
+    );
+
+    // Bring the page up to date; messages sent meanwhile are already queued
+    // for it, and applying one twice does no harm
+    let session = hub.session();
+    if !session.is_empty()
+        && socket
+            .send(Message::Text(session.to_message().into()))
+            .await
+            .is_err()
+    {
+        return;
+    }
+
+    // Pings are answered and closes acknowledged by the socket itself
+    loop {
+        tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    let text: Arc<str> = text.as_str().into();
+                    match serde_json::from_str::<Session>(&text) {
+                        Ok(changes) if !changes.is_empty() => {
+                            hub.record(id, &changes, Some(text));
+                        }
+                        _ => {
+                            let _ = hub.sender.send(Relayed { from: id, text });
+                        }
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    eprintln!("Live client {}: {}", id, e);
+                    break;
+                }
+            },
+            // Forward the other clients' messages
+            relayed = receiver.recv() => match relayed {
+                Ok(message) if message.from != id => {
+                    if socket
+                        .send(Message::Text(message.text.as_ref().into()))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    eprintln!(
+                        "Live client {} fell behind and missed {} messages",
+                        id, missed
+                    );
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        }
+    }
+    println!("Live client {} disconnected", id);
+}
@@ -0,0 +1,142 @@
+use axum::{
+    http::{HeaderName, HeaderValue},
+    middleware,
+    routing::get,
+    Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use tower_http::{
+    compression::CompressionLayer, services::ServeDir, set_header::SetResponseHeaderLayer,
+};
+
+mod api;
+mod cache;
+mod live;
+mod watch;
+
+const USAGE: &str =
+    "Usage: serve [PORT] [--watch] [--build] [--tls-cert CERT.pem --tls-key KEY.pem]";
+
+#[tokio::main]
+async fn main() {
+    let mut port: u16 = 3000;
+    let mut watch = false;
+    let mut build = false;
+    let mut tls_cert = None;
+    let mut tls_key = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--watch" => watch = true,
+            // Rebuilding is only useful if the result is reloaded
+            "--build" => (watch, build) = (true, true),
+            "--tls-cert" => tls_cert = Some(path_value(&arg, args.next())),
+            "--tls-key" => tls_key = Some(path_value(&arg, args.next())),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => match arg.parse() {
+                Ok(value) => port = value,
+                Err(_) => {
+                    eprintln!("Unexpected argument `{}`\n{}", arg, USAGE);
+                    std::process::exit(2);
+                }
+            },
+        }
+    }
+    let tls = match (tls_cert, tls_key) {
+        (Some(cert), Some(key)) => Some((cert, key)),
+        (None, None) => None,
+        _ => {
+            eprintln!("--tls-cert and --tls-key must be given together\n{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    // Serve static files from the project root
+    // Required headers for SharedArrayBuffer (needed by some WASM features)
+    let serve_dir = ServeDir::new(".").append_index_html_on_directories(true);
+    // Compressed with brotli or gzip and tagged for revalidation; the API
+    // and WebSocket aren't
+    let files = Router::new()
+        .fallback_service(serve_dir)
+        .layer(CompressionLayer::new())
+        .layer(middleware::from_fn(cache::handle));
+
+    // Live control: pages relay parameter changes to each other, and the
+    // REST API reads and changes the same state
+    let hub = live::Hub::new();
+    if watch {
+        watch::spawn(hub.clone(), build).expect("Failed to start the file watcher");
+    }
+    let app = Router::new()
+        .route("/ws", get(live::handler))
+        .with_state(hub.clone())
+        .merge(api::router(hub))
+        .fallback_service(files)
+        .layer(SetResponseHeaderLayer::overriding(
+            HeaderName::from_static("cross-origin-opener-policy"),
+            HeaderValue::from_static("same-origin"),
+        ))
+        .layer(SetResponseHeaderLayer::overriding(
+            HeaderName::from_static("cross-origin-embedder-policy"),
+            HeaderValue::from_static("require-corp"),
+        ));
+
+    // Load the certificate before announcing anything
+    let tls = match tls {
+        Some((cert, key)) => match RustlsConfig::from_pem_file(&cert, &key).await {
+            Ok(config) => Some(config),
+            Err(e) => {
+                eprintln!(
+                    "Failed to load {} and {}: {}",
+                    cert.display(),
+                    key.display(),
+                    e
+                );
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let (http, ws) = if tls.is_some() {
+        ("https", "wss")
+    } else {
+        ("http", "ws")
+    };
+    println!("Serving at {}://localhost:{}", http, port);
+    println!("Live control at {}://localhost:{}/ws", ws, port);
+    println!("Control API at {}://localhost:{}/api/params", http, port);
+    if build {
+        println!("Rebuilding and reloading pages when sources change");
+    } else if watch {
+        println!("Reloading pages when index.html, web/ or pkg/ change");
+    }
+    println!("Press Ctrl+C to stop");
+
+    match tls {
+        Some(config) => axum_server::bind_rustls(addr, config)
+            .serve(app.into_make_service())
+            .await
+            .unwrap(),
+        None => {
+            let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+            axum::serve(listener, app).await.unwrap();
+        }
+    }
+}
+
+/// The file named after `flag`, or exit if there is none
+fn path_value(flag: &str, value: Option<String>) -> PathBuf {
+    match value {
+        Some(value) => PathBuf::from(value),
+        None => {
+            eprintln!("{} needs a file\n{}", flag, USAGE);
+            std::process::exit(2);
+        }
+    }
+}
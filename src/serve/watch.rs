@@ -0,0 +1,113 @@
+//! `--watch`: poll the files the page loads and tell every connected page to
+//! reload once they change, so a rebuild shows up without touching the
+//! browser. With `--build`, changes to the crate's sources also run
+//! `build-web.sh` first; the new package it writes then triggers the reload.
+//! Polling keeps this free of platform file-notification APIs, and the tree
+//! is small enough that a walk every half second costs nothing.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::live::Hub;
+
+/// How often the files are checked
+const POLL: Duration = Duration::from_millis(500);
+/// What the page loads
+const SERVED: &[&str] = &["index.html", "web", "pkg"];
+/// What the package is built from
+const SOURCES: &[&str] = &["Cargo.toml", "src"];
+/// Sources that belong to this server rather than the package
+const SERVER_SOURCES: &str = "src/serve";
+/// Sent to every page when the served files change
+const RELOAD: &str = r#"{"reload":true}"#;
+
+/// Modification time of every file under some paths
+type Snapshot = BTreeMap<PathBuf, SystemTime>;
+
+/// Start watching on a background thread
+pub fn spawn(hub: Hub, build: bool) -> io::Result<()> {
+    thread::Builder::new()
+        .name("watch".to_string())
+        .spawn(move || watch(hub, build))?;
+    Ok(())
+}
+
+fn watch(hub: Hub, build: bool) {
+    let mut served = snapshot(SERVED);
+    let mut sources = snapshot(SOURCES);
+    // Writers such as wasm-bindgen replace several files in turn; reload
+    // once a poll finds nothing new rather than after the first one
+    let mut served_changed = false;
+    loop {
+        thread::sleep(POLL);
+
+        if build {
+            let current = snapshot(SOURCES);
+            if current != sources {
+                println!("Sources changed, rebuilding...");
+                run_build();
+                // Edits made during the build are picked up next time round
+                sources = snapshot(SOURCES);
+                continue;
+            }
+        }
+
+        let current = snapshot(SERVED);
+        if current != served {
+            served = current;
+            served_changed = true;
+        } else if served_changed {
+            served_changed = false;
+            println!(
+                "Files changed, reloading pages ({} connected)",
+                hub.client_count()
+            );
+            hub.broadcast(RELOAD);
+        }
+    }
+}
+
+fn run_build() {
+    match Command::new("bash").arg("build-web.sh").output() {
+        Ok(output) if output.status.success() => println!("Build finished"),
+        Ok(output) => {
+            eprintln!("Build failed:");
+            eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+        }
+        Err(e) => eprintln!("Could not run build-web.sh: {}", e),
+    }
+}
+
+fn snapshot(roots: &[&str]) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for root in roots {
+        visit(Path::new(root), &mut snapshot);
+    }
+    snapshot
+}
+
+/// Record `path`, or everything under it; paths that vanish midway are
+/// skipped, since they show up as a change anyway
+fn visit(path: &Path, snapshot: &mut Snapshot) {
+    if path == Path::new(SERVER_SOURCES) {
+        return;
+    }
+    let Ok(metadata) = fs::metadata(path) else {
+        return;
+    };
+    if metadata.is_dir() {
+        let Ok(entries) = fs::read_dir(path) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            visit(&entry.path(), snapshot);
+        }
+    } else if let Ok(modified) = metadata.modified() {
+        snapshot.insert(path.to_path_buf(), modified);
+    }
+}
@@ -0,0 +1,428 @@
+//! Session recording and `vendek render-session`. While a session records,
+//! the viewer logs the state each frame was drawn with, its clock, camera
+//! pose and every runtime parameter, to a compact binary file. The log can
+//! then be drawn again offscreen at any resolution and sample count and
+//! encoded as a video, so a session explored interactively at a few
+//! samples per pixel comes out as a clean render. Unlike a video recording
+//! nothing is read back while exploring, so it costs the viewer almost
+//! nothing. The simulations are not logged and start over on playback.
+//!
+//! The file is little-endian: the magic bytes, a version, the world's
+//! seed, cell and phase counts and the parameter names, then per frame the
+//! seconds since recording began, the clock, the pose, and those
+//! parameters that changed since the frame before as index and value
+//! pairs. The first frame lists every parameter.
+//!
+//! Playback keeps the session's pace: frames are taken at the video's rate
+//! through the recording, with the clock and pose eased between the logged
+//! frames either side. The world comes from the seed and counts in the
+//! header, so regenerating the world ends a recording. Custom palettes,
+//! transfer functions and clip planes come from `vendek.toml`, as for
+//! `render-still`, and low-power mode is ignored.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use glam::Vec3;
+
+use crate::camera::{Camera, CameraPose};
+use crate::gpu::{AdapterOptions, GpuError};
+use crate::params::RuntimeParams;
+use crate::preset::Preset;
+use crate::still::ViewerScene;
+use crate::video::{RecordingConfig, VideoFormat, VideoRecorder};
+
+const MAGIC: &[u8; 4] = b"VKSS";
+const VERSION: u16 = 1;
+
+/// Timestamped output path for a session log in the working directory
+pub fn session_path() -> PathBuf {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    PathBuf::from(format!("vendek-{}.session", secs))
+}
+
+/// The world a session was recorded in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SessionWorld {
+    pub seed: u64,
+    /// Per chunk in a streamed world
+    pub cells: usize,
+    pub phases: usize,
+}
+
+/// Writes the state of each frame drawn to a session log
+pub struct SessionRecorder {
+    out: BufWriter<File>,
+    path: PathBuf,
+    start: Instant,
+    /// Parameter values as last written, in `RuntimeParams::NAMES` order
+    last: Option<Vec<f64>>,
+    frames: u32,
+}
+
+impl SessionRecorder {
+    /// Create `path` and write the header for a session in `world`
+    pub fn start(path: &Path, world: SessionWorld) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&world.seed.to_le_bytes())?;
+        out.write_all(&(world.cells as u32).to_le_bytes())?;
+        out.write_all(&(world.phases as u32).to_le_bytes())?;
+        out.write_all(&(RuntimeParams::NAMES.len() as u16).to_le_bytes())?;
+        for name in RuntimeParams::NAMES {
+            out.write_all(&[name.len() as u8])?;
+            out.write_all(name.as_bytes())?;
+        }
+        Ok(Self {
+            out,
+            path: path.to_path_buf(),
+            start: Instant::now(),
+            last: None,
+            frames: 0,
+        })
+    }
+
+    /// Log a frame drawn `time` seconds into the animation
+    pub fn record(&mut self, time: f32, camera: &Camera, params: &RuntimeParams) -> io::Result<()> {
+        let values: Vec<f64> = RuntimeParams::NAMES
+            .iter()
+            .map(|name| params.get(name).unwrap_or_default())
+            .collect();
+        let changed: Vec<(u16, f64)> = values
+            .iter()
+            .enumerate()
+            .filter(|&(i, value)| self.last.as_ref().is_none_or(|last| last[i] != *value))
+            .map(|(i, &value)| (i as u16, value))
+            .collect();
+
+        let out = &mut self.out;
+        let floats = [
+            self.start.elapsed().as_secs_f32(),
+            time,
+            camera.focus.x,
+            camera.focus.y,
+            camera.focus.z,
+            camera.yaw,
+            camera.pitch,
+            camera.distance,
+        ];
+        for float in floats {
+            out.write_all(&float.to_le_bytes())?;
+        }
+        out.write_all(&(changed.len() as u16).to_le_bytes())?;
+        for (index, value) in changed {
+            out.write_all(&index.to_le_bytes())?;
+            out.write_all(&value.to_le_bytes())?;
+        }
+        self.last = Some(values);
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Flush the log. Returns where it was written and how many frames it
+    /// holds.
+    pub fn finish(mut self) -> io::Result<(PathBuf, u32)> {
+        self.out.flush()?;
+        Ok((self.path, self.frames))
+    }
+}
+
+/// One logged frame
+#[derive(Clone, Debug)]
+struct SessionFrame {
+    /// Seconds since recording began
+    at: f32,
+    /// Seconds into the animation
+    time: f32,
+    pose: CameraPose,
+    /// Every parameter's value, in the session's name order
+    values: Vec<f64>,
+}
+
+/// A session log read back
+pub struct Session {
+    pub world: SessionWorld,
+    names: Vec<String>,
+    frames: Vec<SessionFrame>,
+}
+
+impl Session {
+    pub fn load(path: &Path) -> Result<Self, SessionError> {
+        let mut input = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(SessionError::Format("not a session log".to_string()));
+        }
+        let version = u16::from_le_bytes(read(&mut input)?);
+        if version != VERSION {
+            return Err(SessionError::Format(format!(
+                "unsupported version {}",
+                version
+            )));
+        }
+        let world = SessionWorld {
+            seed: u64::from_le_bytes(read(&mut input)?),
+            cells: u32::from_le_bytes(read(&mut input)?) as usize,
+            phases: u32::from_le_bytes(read(&mut input)?) as usize,
+        };
+        let count = u16::from_le_bytes(read(&mut input)?) as usize;
+        let mut names = Vec::with_capacity(count);
+        for _ in 0..count {
+            let [length] = read(&mut input)?;
+            let mut name = vec![0; length as usize];
+            input.read_exact(&mut name)?;
+            names.push(
+                String::from_utf8(name)
+                    .map_err(|_| SessionError::Format("parameter name isn't UTF-8".to_string()))?,
+            );
+        }
+
+        let mut frames = Vec::new();
+        let mut values = vec![0.0; count];
+        loop {
+            // The log may end mid-frame if the viewer didn't close it
+            let mut at = [0; 4];
+            match input.read_exact(&mut at) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+            let frame = (|| -> io::Result<SessionFrame> {
+                let mut float = || read(&mut input).map(f32::from_le_bytes);
+                let time = float()?;
+                let focus = Vec3::new(float()?, float()?, float()?);
+                let (yaw, pitch, distance) = (float()?, float()?, float()?);
+                let changed = u16::from_le_bytes(read(&mut input)?);
+                for _ in 0..changed {
+                    let index = u16::from_le_bytes(read(&mut input)?) as usize;
+                    let value = f64::from_le_bytes(read(&mut input)?);
+                    if let Some(slot) = values.get_mut(index) {
+                        *slot = value;
+                    }
+                }
+                Ok(SessionFrame {
+                    at: f32::from_le_bytes(at),
+                    time,
+                    pose: CameraPose {
+                        focus,
+                        yaw,
+                        pitch,
+                        distance,
+                    },
+                    values: values.clone(),
+                })
+            })();
+            match frame {
+                Ok(frame) => frames.push(frame),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if frames.is_empty() {
+            return Err(SessionError::Format("no frames recorded".to_string()));
+        }
+        Ok(Self {
+            world,
+            names,
+            frames,
+        })
+    }
+
+    /// Seconds the session lasted
+    pub fn duration(&self) -> f32 {
+        self.frames.last().map_or(0.0, |frame| frame.at)
+    }
+
+    /// The state `at` seconds into the session: the clock and camera pose
+    /// eased between the frames either side, and the parameters of the
+    /// frame before, set on top of `params`
+    pub fn sample(&self, at: f32, params: &mut RuntimeParams) -> (f32, CameraPose) {
+        let next = self.frames.partition_point(|frame| frame.at <= at);
+        let before = &self.frames[next.saturating_sub(1)];
+        let after = self.frames.get(next).unwrap_or(before);
+        let span = after.at - before.at;
+        let t = if span > 0.0 {
+            ((at - before.at) / span).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        for (name, &value) in self.names.iter().zip(&before.values) {
+            // Parameters this version no longer has are skipped
+            let _ = params.set(name, value);
+        }
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        let pose = CameraPose {
+            focus: before.pose.focus.lerp(after.pose.focus, t),
+            yaw: lerp(before.pose.yaw, after.pose.yaw),
+            pitch: lerp(before.pose.pitch, after.pose.pitch),
+            distance: lerp(before.pose.distance, after.pose.distance),
+        };
+        (lerp(before.time, after.time), pose)
+    }
+}
+
+fn read<const N: usize>(input: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[derive(Clone, Debug)]
+pub struct SessionOptions {
+    /// The session log to render
+    pub input: PathBuf,
+    /// Frames per second of the video
+    pub fps: u32,
+    /// Samples per pixel accumulated for each frame
+    pub samples: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Where the video is written; the extension, `.mp4`, `.webm` or
+    /// `.webp`, picks the format
+    pub output: PathBuf,
+}
+
+impl Default for SessionOptions {
+    fn default() -> Self {
+        Self {
+            input: PathBuf::new(),
+            fps: 30,
+            samples: 16,
+            width: 1920,
+            height: 1080,
+            output: PathBuf::from("vendek-session.mp4"),
+        }
+    }
+}
+
+/// Why a session could not be read or rendered
+#[derive(Debug)]
+pub enum SessionError {
+    Gpu(GpuError),
+    Io(io::Error),
+    /// The log is damaged or from an incompatible version
+    Format(String),
+    /// `ffmpeg`, which encodes the video, could not be started
+    Ffmpeg(io::Error),
+    /// The output's extension isn't a video format
+    Output(PathBuf),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::Gpu(e) => e.fmt(f),
+            SessionError::Io(e) => write!(f, "Could not read the session: {}", e),
+            SessionError::Format(reason) => write!(f, "Could not read the session: {}", reason),
+            SessionError::Ffmpeg(e) => write!(f, "Could not start ffmpeg: {}", e),
+            SessionError::Output(path) => write!(
+                f,
+                "Can't tell the format of {}; use a .mp4, .webm or .webp extension",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SessionError::Gpu(e) => Some(e),
+            SessionError::Io(e) | SessionError::Ffmpeg(e) => Some(e),
+            SessionError::Format(_) | SessionError::Output(_) => None,
+        }
+    }
+}
+
+impl From<GpuError> for SessionError {
+    fn from(e: GpuError) -> Self {
+        SessionError::Gpu(e)
+    }
+}
+
+impl From<io::Error> for SessionError {
+    fn from(e: io::Error) -> Self {
+        SessionError::Io(e)
+    }
+}
+
+pub fn run(
+    adapter: AdapterOptions,
+    preset: Option<Preset>,
+    stream: Option<u32>,
+    options: SessionOptions,
+) -> Result<(), SessionError> {
+    let format = VideoFormat::for_path(&options.output)
+        .ok_or_else(|| SessionError::Output(options.output.clone()))?;
+    let session = Session::load(&options.input)?;
+    let world = session.world;
+    let ViewerScene {
+        config,
+        mut gpu,
+        mut camera,
+        params: base,
+        ..
+    } = ViewerScene::open_with(
+        &adapter,
+        preset,
+        stream,
+        options.width,
+        options.height,
+        |builder| {
+            builder
+                .seed(world.seed)
+                .cells(world.cells)
+                .phases(world.phases)
+        },
+    )?;
+
+    let recording = RecordingConfig {
+        fps: options.fps,
+        format,
+        ffmpeg: config.recording.ffmpeg,
+    };
+    let mut recorder =
+        VideoRecorder::start(&recording, &options.output, options.width, options.height)
+            .map_err(SessionError::Ffmpeg)?;
+
+    let fps = options.fps.max(1) as f32;
+    let frames = (session.duration() * fps).floor() as u32 + 1;
+    let start = Instant::now();
+    let mut applied = None;
+    for frame in 0..frames {
+        eprint!("\rFrame {}/{}", frame + 1, frames);
+        let _ = io::stderr().flush();
+        let mut params = base;
+        let (time, pose) = session.sample(frame as f32 / fps, &mut params);
+        // Offline renders take as long as they need at full quality
+        params.low_power = false;
+        if applied != Some(params) {
+            gpu.set_params(&params);
+            applied = Some(params);
+        }
+        camera.jump_to(pose.focus, pose.yaw, pose.pitch, pose.distance);
+        let rgba = gpu.render_still(&camera, time, options.samples, |_| {});
+        recorder.push(&rgba)?;
+    }
+    eprintln!();
+
+    let (path, frames) = recorder.finish()?;
+    println!(
+        "Wrote {} ({}x{}, {} frames, {} samples per pixel, {:.1}s)",
+        path.display(),
+        options.width,
+        options.height,
+        frames,
+        gpu.accumulated_samples(),
+        start.elapsed().as_secs_f32()
+    );
+    Ok(())
+}
@@ -0,0 +1,143 @@
+//! Native viewer state kept between runs in the platform's config directory
+//! (`~/.config/vendek/settings.toml` on Linux): the window's size and
+//! position, the camera, camera bookmarks, and parameters changed from the
+//! ones `vendek.toml` starts with. Written on exit, read on launch.
+//!
+//! A window that was fullscreen or maximized keeps its earlier placement,
+//! and one last seen on a monitor that is gone opens at the default
+//! position. Benchmarks neither read nor write the file.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::CameraPose;
+use crate::params::RuntimeParams;
+
+/// What was saved on the last exit
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Parameters by their JavaScript names, only those changed from the
+    /// config's values, so later edits to the config still take effect
+    pub params: BTreeMap<String, f64>,
+    pub window: Option<WindowPlacement>,
+    pub camera: Option<Pose>,
+    #[serde(rename = "bookmark")]
+    pub bookmarks: Vec<Pose>,
+}
+
+/// Size and position of the window when it was last neither fullscreen nor
+/// maximized, in physical pixels
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct WindowPlacement {
+    pub width: u32,
+    pub height: u32,
+    /// Not every platform reports it, Wayland among them
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+}
+
+/// A `CameraPose` as written to the file
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Pose {
+    focus: [f32; 3],
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+}
+
+impl From<CameraPose> for Pose {
+    fn from(pose: CameraPose) -> Self {
+        Self {
+            focus: pose.focus.to_array(),
+            yaw: pose.yaw,
+            pitch: pose.pitch,
+            distance: pose.distance,
+        }
+    }
+}
+
+impl From<Pose> for CameraPose {
+    fn from(pose: Pose) -> Self {
+        Self {
+            focus: Vec3::from_array(pose.focus),
+            yaw: pose.yaw,
+            pitch: pose.pitch,
+            distance: pose.distance,
+        }
+    }
+}
+
+impl Settings {
+    fn path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("vendek").join("settings.toml"))
+    }
+
+    /// The settings saved last time; empty on the first run or if the file
+    /// can't be read
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        let Ok(source) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        match toml::from_str(&source) {
+            Ok(settings) => {
+                tracing::info!("Restored settings from {}", path.display());
+                settings
+            }
+            Err(e) => {
+                tracing::warn!("Ignoring settings in {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Write the settings, through a temporary file so a crash mid-write
+    /// can't leave a truncated one behind
+    pub fn save(&self) {
+        let Some(path) = Self::path() else {
+            return;
+        };
+        let source = match toml::to_string(self) {
+            Ok(source) => source,
+            Err(e) => {
+                tracing::warn!("Could not save settings: {}", e);
+                return;
+            }
+        };
+        let temp = path.with_extension("tmp");
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::write(&temp, source))
+            .and_then(|()| std::fs::rename(&temp, &path));
+        if let Err(e) = result {
+            tracing::warn!("Could not save settings {}: {}", path.display(), e);
+        }
+    }
+
+    /// Apply the saved parameters on top of `params`
+    pub fn restore_params(&self, params: &mut RuntimeParams) {
+        for (name, &value) in &self.params {
+            if let Err(e) = params.set(name, value) {
+                tracing::warn!("Ignoring {} in settings", e);
+            }
+        }
+    }
+
+    /// Record the parameters that differ from `startup`
+    pub fn store_params(&mut self, params: &RuntimeParams, startup: &RuntimeParams) {
+        self.params = RuntimeParams::NAMES
+            .iter()
+            .filter_map(|name| {
+                let value = params.get(name)?;
+                (startup.get(name) != Some(value)).then(|| (name.to_string(), value))
+            })
+            .collect();
+    }
+}
@@ -0,0 +1,239 @@
+//! A small WGSL preprocessor, so shaders can be split into modules and
+//! compiled in feature permutations instead of branching at runtime.
+//!
+//! Directives sit on their own line:
+//! - `#include "file.wgsl"` inserts another shader source. Each file is
+//!   included at most once, so shared modules can be included freely.
+//! - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif` keep or drop the
+//!   lines between them depending on the defines passed to [`preprocess`].
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::params::RuntimeParams;
+
+/// Shader sources by include name, embedded at build time
+const SOURCES: &[(&str, &str)] = &[
+    ("types.wgsl", include_str!("shaders/types.wgsl")),
+    ("volume.wgsl", include_str!("shaders/volume.wgsl")),
+    ("voronoi.wgsl", include_str!("shaders/voronoi.wgsl")),
+    ("noise.wgsl", include_str!("shaders/noise.wgsl")),
+    ("density.wgsl", include_str!("shaders/density.wgsl")),
+    ("background.wgsl", include_str!("shaders/background.wgsl")),
+    ("debug_view.wgsl", include_str!("shaders/debug_view.wgsl")),
+    ("raymarch.wgsl", include_str!("shaders/raymarch.wgsl")),
+    ("tiles.wgsl", include_str!("shaders/tiles.wgsl")),
+    ("tile_cull.wgsl", include_str!("shaders/tile_cull.wgsl")),
+    ("shadow.wgsl", include_str!("shaders/shadow.wgsl")),
+    ("denoise.wgsl", include_str!("shaders/denoise.wgsl")),
+    ("honeycomb.wgsl", include_str!("shaders/honeycomb.wgsl")),
+    (
+        "honeycomb_fragment.wgsl",
+        include_str!("shaders/honeycomb_fragment.wgsl"),
+    ),
+    ("isosurface.wgsl", include_str!("shaders/isosurface.wgsl")),
+    ("display.wgsl", include_str!("shaders/display.wgsl")),
+];
+
+/// Optional features of the raymarch shader. Each enabled feature is a
+/// define, and every combination is its own pipeline.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ShaderFeatures {
+    /// Membrane oscillation coupled to the neighbouring cell's phase
+    pub coupling: bool,
+}
+
+impl ShaderFeatures {
+    /// The permutation the given runtime parameters call for
+    pub fn for_params(params: &RuntimeParams) -> Self {
+        Self {
+            coupling: params.enable_coupling,
+        }
+    }
+
+    pub fn defines(self) -> Vec<&'static str> {
+        let mut defines = Vec::new();
+        if self.coupling {
+            defines.push("COUPLING");
+        }
+        defines
+    }
+
+    /// Short description for pipeline labels and logs
+    pub fn label(self) -> String {
+        let defines = self.defines();
+        if defines.is_empty() {
+            "base".to_string()
+        } else {
+            defines.join("+")
+        }
+    }
+}
+
+/// Pipelines for each feature permutation used so far. Permutations are built
+/// the first time they are selected; the initial one always exists, so a
+/// permutation that fails to build leaves the current one in place.
+pub struct Permutations<P> {
+    pipelines: HashMap<ShaderFeatures, P>,
+    current: ShaderFeatures,
+}
+
+impl<P> Permutations<P> {
+    pub fn new(
+        features: ShaderFeatures,
+        build: impl FnOnce(ShaderFeatures) -> Result<P, ShaderError>,
+    ) -> Result<Self, ShaderError> {
+        let pipeline = build(features)?;
+        Ok(Self {
+            pipelines: HashMap::from([(features, pipeline)]),
+            current: features,
+        })
+    }
+
+    /// Switch to the pipeline for `features`, building it if this is the
+    /// first time. Returns true if a pipeline was built.
+    pub fn select(
+        &mut self,
+        features: ShaderFeatures,
+        build: impl FnOnce(ShaderFeatures) -> Result<P, ShaderError>,
+    ) -> bool {
+        if features == self.current {
+            return false;
+        }
+        let mut built = false;
+        if let Entry::Vacant(entry) = self.pipelines.entry(features) {
+            tracing::info!("Building {} shader permutation", features.label());
+            match build(features) {
+                Ok(pipeline) => {
+                    entry.insert(pipeline);
+                    built = true;
+                }
+                Err(e) => {
+                    tracing::error!("Shader permutation {} failed: {}", features.label(), e);
+                    return false;
+                }
+            }
+        }
+        self.current = features;
+        built
+    }
+
+    pub fn current(&self) -> &P {
+        &self.pipelines[&self.current]
+    }
+
+    /// Features of the current pipeline
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn features(&self) -> ShaderFeatures {
+        self.current
+    }
+}
+
+/// A malformed directive or missing include
+#[derive(Debug)]
+pub struct ShaderError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+/// Expand `entry` and its includes with the given defines into one WGSL source
+pub fn preprocess(entry: &str, defines: &[&str]) -> Result<String, ShaderError> {
+    let mut output = String::new();
+    let mut included = HashSet::new();
+    expand(entry, defines, &mut included, &mut output, None)?;
+    Ok(output)
+}
+
+fn expand(
+    file: &str,
+    defines: &[&str],
+    included: &mut HashSet<String>,
+    output: &mut String,
+    from: Option<(&str, usize)>,
+) -> Result<(), ShaderError> {
+    if !included.insert(file.to_string()) {
+        return Ok(());
+    }
+    let Some((_, source)) = SOURCES.iter().find(|(name, _)| *name == file) else {
+        let (includer, line) = from.unwrap_or((file, 0));
+        return Err(ShaderError {
+            file: includer.to_string(),
+            line,
+            message: format!("unknown shader `{}`", file),
+        });
+    };
+
+    let error = |line: usize, message: String| ShaderError {
+        file: file.to_string(),
+        line,
+        message,
+    };
+
+    // One entry per open #ifdef: (this branch is active, the enclosing block is active)
+    let mut conditions: Vec<(bool, bool)> = Vec::new();
+    let active =
+        |conditions: &[(bool, bool)]| conditions.last().is_none_or(|&(on, outer)| on && outer);
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let trimmed = text.trim();
+        let Some(directive) = trimmed.strip_prefix('#') else {
+            if active(&conditions) {
+                output.push_str(text);
+                output.push('\n');
+            }
+            continue;
+        };
+
+        let (keyword, argument) = directive
+            .split_once(char::is_whitespace)
+            .map(|(k, a)| (k, a.trim()))
+            .unwrap_or((directive, ""));
+        match keyword {
+            "ifdef" | "ifndef" => {
+                if argument.is_empty() {
+                    return Err(error(line, format!("#{} needs a name", keyword)));
+                }
+                let defined = defines.contains(&argument);
+                let outer = active(&conditions);
+                conditions.push((defined == (keyword == "ifdef"), outer));
+            }
+            "else" => match conditions.last_mut() {
+                Some((on, _)) => *on = !*on,
+                None => return Err(error(line, "#else without #ifdef".to_string())),
+            },
+            "endif" => {
+                if conditions.pop().is_none() {
+                    return Err(error(line, "#endif without #ifdef".to_string()));
+                }
+            }
+            "include" => {
+                if active(&conditions) {
+                    let name = argument
+                        .strip_prefix('"')
+                        .and_then(|a| a.strip_suffix('"'))
+                        .ok_or_else(|| {
+                            error(line, "#include needs a quoted file name".to_string())
+                        })?;
+                    expand(name, defines, included, output, Some((file, line)))?;
+                }
+            }
+            _ => return Err(error(line, format!("unknown directive `#{}`", keyword))),
+        }
+    }
+
+    if !conditions.is_empty() {
+        return Err(error(source.lines().count(), "missing #endif".to_string()));
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+// What rays that miss the volume show, by `params.background`: the flat
+// BACKGROUND, or a starfield over faint nebulae. The sky depends only on the
+// ray's direction, so it stays put as the camera moves, and on the world's
+// seed, so each world has its own. Shared by the raymarch and the tile cull
+// prepass, which fills tiles that can't see the volume.
+
+#include "types.wgsl"
+#include "volume.wgsl"
+#include "noise.wgsl"
+
+// Values of `params.background`
+const BACKGROUND_SOLID: u32 = 0u;
+const BACKGROUND_STARFIELD: u32 = 1u;
+
+// The sky is cut into lattice cells this many to a unit of direction, each
+// holding at most one star
+const STAR_SCALE: f32 = 120.0;
+// Share of lattice cells with a star
+const STAR_DENSITY: f32 = 0.06;
+
+// Three hashes of a lattice point under the world's seed, each in 0..1
+fn sky_hash(cell: vec3<i32>) -> vec3<f32> {
+    let hash = pcg3d(bitcast<vec3<u32>>(cell) ^ vec3(params.background_seed));
+    return vec3<f32>(hash >> vec3(8u)) / 16777216.0;
+}
+
+// The star of the lattice cell `dir` points into, if it has one: a small
+// blur around a point in the cell, from dim red to bright blue-white
+fn stars(dir: vec3<f32>) -> vec3<f32> {
+    let p = dir * STAR_SCALE;
+    let cell = vec3<i32>(floor(p));
+    let hash = sky_hash(cell);
+    if hash.x >= STAR_DENSITY {
+        return vec3(0.0);
+    }
+    let center = vec3<f32>(cell) + 0.25 + 0.5 * sky_hash(cell + vec3(7919, 0, 0));
+    let d = distance(p, center);
+    let brightness = 0.2 + 2.5 * pow(hash.y, 6.0);
+    let color = mix(vec3(1.0, 0.7, 0.5), vec3(0.7, 0.8, 1.0), hash.z);
+    return color * brightness * exp(-d * d * 120.0);
+}
+
+// Nebulae: two fractal noise fields over the sky, one deciding where clouds
+// are and the other blending between two hues the seed picks
+fn nebula(dir: vec3<f32>) -> vec3<f32> {
+    let offset = sky_hash(vec3(0, 0, 0)) * 100.0;
+    let hues = sky_hash(vec3(1, 0, 0));
+    let cloud = smoothstep(0.45, 0.8, fbm(dir * 1.5 + offset, 4u));
+    let blend = fbm(dir * 3.0 - offset, 3u);
+    let a = 0.5 + 0.5 * cos(6.2832 * (hues.x + vec3(0.0, 0.33, 0.67)));
+    let b = 0.5 + 0.5 * cos(6.2832 * (hues.y + vec3(0.0, 0.33, 0.67)));
+    return mix(a, b, blend) * cloud * 0.12;
+}
+
+// The sky along the unit vector `dir`
+fn background_color(dir: vec3<f32>) -> vec3<f32> {
+    if params.background == BACKGROUND_STARFIELD {
+        return BACKGROUND + nebula(dir) + stars(dir);
+    }
+    return BACKGROUND;
+}
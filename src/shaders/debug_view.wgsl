@@ -0,0 +1,127 @@
+// Debug views, drawn in place of the image for `params.debug_view` from
+// what the raymarch saw of a pixel. Colors are returned as they should
+// look, in sRGB. Reads the Voronoi cells through the entry point's world
+// accessors, and the spatial grid from its `grid_occupancy` texture.
+
+#include "types.wgsl"
+#include "voronoi.wgsl"
+
+// Values of `params.debug_view`
+const DEBUG_VIEW_OFF: u32 = 0u;
+const DEBUG_VIEW_STEPS: u32 = 1u;
+const DEBUG_VIEW_PHASES: u32 = 2u;
+const DEBUG_VIEW_NORMALS: u32 = 3u;
+const DEBUG_VIEW_DEPTH: u32 = 4u;
+const DEBUG_VIEW_GRID: u32 = 5u;
+
+// Seeds a spatial grid cell lists at most
+const GRID_CAPACITY: f32 = 8.0;
+
+// Google's Turbo colormap, by its polynomial approximation: dark blue at 0
+// through cyan, green and yellow to dark red at 1
+fn turbo(x: f32) -> vec3<f32> {
+    let r4 = vec4(0.13572138, 4.61539260, -42.66032258, 132.13108234);
+    let g4 = vec4(0.09140261, 2.19418839, 4.84296658, -14.18503333);
+    let b4 = vec4(0.10667330, 12.64194608, -60.58204836, 110.36276771);
+    let r2 = vec2(-152.94239396, 59.28637943);
+    let g2 = vec2(4.27729857, 2.82956604);
+    let b2 = vec2(-89.90310912, 27.34824973);
+    let t = clamp(x, 0.0, 1.0);
+    let v4 = vec4(1.0, t, t * t, t * t * t);
+    let v2 = v4.zw * v4.z;
+    let color = vec3(
+        dot(v4, r4) + dot(v2, r2),
+        dot(v4, g4) + dot(v2, g2),
+        dot(v4, b4) + dot(v2, b2),
+    );
+    return clamp(color, vec3(0.0), vec3(1.0));
+}
+
+// A color of its own for each phase, hues a golden angle apart so
+// neighbouring indices differ most
+fn phase_false_color(phase: u32) -> vec3<f32> {
+    let hue = fract(f32(phase) * 0.618034);
+    let rgb = clamp(abs(fract(hue + vec3(0.0, 2.0, 1.0) / 3.0) * 6.0 - 3.0) - 1.0, vec3(0.0), vec3(1.0));
+    return mix(vec3(1.0), rgb, 0.7) * 0.95;
+}
+
+// Distance from `pos` to the membrane nearest it, halfway between the two
+// nearest seeds' distances
+fn membrane_distance(pos: vec3<f32>) -> f32 {
+    let hit = voronoi_cell(pos);
+    return (hit.dist_second - hit.dist_closest) * 0.5;
+}
+
+// Normal of the membrane nearest `pos`, by central differences `h` apart
+// of the distance to it, turned to face back along `ray_dir`
+fn membrane_normal(pos: vec3<f32>, ray_dir: vec3<f32>, h: f32) -> vec3<f32> {
+    let dx = vec3(h, 0.0, 0.0);
+    let dy = vec3(0.0, h, 0.0);
+    let dz = vec3(0.0, 0.0, h);
+    let gradient = vec3(
+        membrane_distance(pos + dx) - membrane_distance(pos - dx),
+        membrane_distance(pos + dy) - membrane_distance(pos - dy),
+        membrane_distance(pos + dz) - membrane_distance(pos - dz),
+    );
+    if dot(gradient, gradient) < 1e-12 {
+        return -ray_dir;
+    }
+    let normal = normalize(gradient);
+    return select(normal, -normal, dot(normal, ray_dir) > 0.0);
+}
+
+// Seeds listed by the spatial grid cell containing `pos`
+fn grid_count(pos: vec3<f32>) -> u32 {
+    let size = vec3<i32>(textureDimensions(grid_occupancy));
+    let uvw = (pos - params.volume_min) / (params.volume_max - params.volume_min);
+    let cell = clamp(vec3<i32>(floor(uvw * vec3<f32>(size))), vec3(0), size - 1);
+    return textureLoad(grid_occupancy, cell, 0).r;
+}
+
+// Distance to the volume each pixel shows, 1 at the corner of the volume
+// nearest the camera falling to 0 at the farthest
+fn linear_depth(depth: f32) -> f32 {
+    let eye = frame.camera_position;
+    let outside = max(max(params.volume_min - eye, eye - params.volume_max), vec3(0.0));
+    let nearest = length(outside);
+    let farthest = length(max(abs(params.volume_min - eye), abs(params.volume_max - eye)));
+    return 1.0 - clamp((depth - nearest) / max(farthest - nearest, 1e-4), 0.0, 1.0);
+}
+
+// The color debug view `view` gives a pixel whose ray from `origin` along
+// `ray_dir` took `steps` steps, and whose volume lies `depth` along it
+// and mostly in phase `phase`; `shows` is whether any volume does
+fn debug_color(
+    view: u32,
+    origin: vec3<f32>,
+    ray_dir: vec3<f32>,
+    steps: u32,
+    depth: f32,
+    phase: u32,
+    shows: bool,
+) -> vec3<f32> {
+    if view == DEBUG_VIEW_STEPS {
+        return turbo(f32(steps) / f32(max(params.max_steps, 1u)));
+    }
+    if !shows {
+        return vec3(0.0);
+    }
+    let pos = origin + ray_dir * depth;
+    switch view {
+        case DEBUG_VIEW_PHASES: {
+            return phase_false_color(phase);
+        }
+        case DEBUG_VIEW_NORMALS: {
+            return membrane_normal(pos, ray_dir, params.step_size * 0.5) * 0.5 + 0.5;
+        }
+        case DEBUG_VIEW_DEPTH: {
+            return vec3(linear_depth(depth));
+        }
+        case DEBUG_VIEW_GRID: {
+            return turbo(f32(grid_count(pos)) / GRID_CAPACITY);
+        }
+        default: {
+            return vec3(0.0);
+        }
+    }
+}
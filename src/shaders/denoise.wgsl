@@ -0,0 +1,78 @@
+// One pass of the compute path's à-trous denoiser: a 5×5 B3-spline kernel
+// with `settings.step` pixels between taps, weighted down across changes of
+// phase, depth and luminance in the guide the raymarch wrote.
+
+struct DenoisePass {
+    step: u32,
+    luminance_sigma: f32,
+    _pad0: u32,
+    _pad1: u32,
+}
+
+@group(0) @binding(0) var input: texture_2d<f32>;
+// Depth of the volume in x, phase in y, -1 where no volume shows
+@group(0) @binding(1) var guide: texture_2d<f32>;
+@group(0) @binding(2) var output: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(3) var<uniform> settings: DenoisePass;
+
+// Relative depth difference at which taps weigh a third as much
+const DEPTH_SIGMA: f32 = 0.05;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+// B3-spline weight of the tap `offset` steps from the center, -2 to 2
+fn kernel(offset: i32) -> f32 {
+    switch abs(offset) {
+        case 0: {
+            return 3.0 / 8.0;
+        }
+        case 1: {
+            return 1.0 / 4.0;
+        }
+        default: {
+            return 1.0 / 16.0;
+        }
+    }
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let size = vec2<i32>(textureDimensions(input));
+    let pixel = vec2<i32>(gid.xy);
+    if pixel.x >= size.x || pixel.y >= size.y {
+        return;
+    }
+
+    let center = textureLoad(input, pixel, 0);
+    let center_guide = textureLoad(guide, pixel, 0).xy;
+    // The background and rasterized geometry have no noise to remove
+    if center_guide.y < 0.0 {
+        textureStore(output, pixel, center);
+        return;
+    }
+    let center_luminance = luminance(center.rgb);
+
+    var sum = vec3(0.0);
+    var total = 0.0;
+    for (var y = -2; y <= 2; y++) {
+        for (var x = -2; x <= 2; x++) {
+            let tap = pixel + vec2(x, y) * i32(settings.step);
+            if any(tap < vec2(0)) || any(tap >= size) {
+                continue;
+            }
+            let tap_guide = textureLoad(guide, tap, 0).xy;
+            if tap_guide.y != center_guide.y {
+                continue;
+            }
+            let color = textureLoad(input, tap, 0).rgb;
+            let depth_weight = exp(-abs(tap_guide.x - center_guide.x) / (DEPTH_SIGMA * max(center_guide.x, 1e-3)));
+            let luminance_weight = exp(-abs(luminance(color) - center_luminance) / settings.luminance_sigma);
+            let weight = kernel(x) * kernel(y) * depth_weight * luminance_weight;
+            sum += color * weight;
+            total += weight;
+        }
+    }
+    textureStore(output, pixel, vec4(sum / total, center.a));
+}
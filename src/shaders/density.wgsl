@@ -0,0 +1,38 @@
+// How dense the volume is, shared by the raymarch and the shadow prepass:
+// which phases are shown, their transfer functions, and the opacity light
+// meets. The entry point declares the `params` uniform and the
+// `transfer_functions` texture, and provides the world accessors
+// voronoi.wgsl uses.
+
+#include "types.wgsl"
+#include "volume.wgsl"
+#include "voronoi.wgsl"
+
+// Whether phase `i` is shown. Phases past 31 have no bit and always are.
+fn phase_visible(i: u32) -> bool {
+    return i >= 32u || ((params.visible_phases >> i) & 1u) != 0u;
+}
+
+// Tint in rgb and opacity in a for `density` in phase `i`, interpolated
+// between the texels of its transfer function row
+fn transfer(i: u32, density: f32) -> vec4<f32> {
+    let last = textureDimensions(transfer_functions).x - 1u;
+    let x = clamp(density, 0.0, 1.0) * f32(last);
+    let left = min(u32(x), last);
+    let right = min(left + 1u, last);
+    let a = textureLoad(transfer_functions, vec2<i32>(i32(left), i32(i)), 0);
+    let b = textureLoad(transfer_functions, vec2<i32>(i32(right), i32(i)), 0);
+    return mix(a, b, x - f32(left));
+}
+
+// Opacity per world unit at `pos`, as the raymarch accumulates it but
+// without the phase drift, wisps and ripples that change every frame
+fn extinction(pos: vec3<f32>) -> f32 {
+    let hit = voronoi_cell(pos);
+    let phase = world_cell(hit.closest).phase_index;
+    if !phase_visible(phase) {
+        return 0.0;
+    }
+    let density = hit.dist_closest / max(hit.dist_second, 1e-4);
+    return transfer(phase, density).a * boundary_fade(pos) * params.density_multiplier;
+}
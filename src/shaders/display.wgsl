@@ -1,3 +1,8 @@
+// Display pass: filters the compute path's HDR image down to the surface,
+// with bloom and tone mapping. Debug views are shown as they are.
+
+#include "types.wgsl"
+
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) uv: vec2<f32>,
@@ -25,10 +30,30 @@ fn vs_main(@builtin(vertex_index) idx: u32) -> VertexOutput {
 
 @group(0) @binding(0) var render_texture: texture_2d<f32>;
 @group(0) @binding(1) var render_sampler: sampler;
+@group(1) @binding(0) var<uniform> params: RaymarchParams;
+
+// Average the texels under one output pixel with a tent filter, so a
+// supersampled frame shrinks to the window without aliasing. `pixel_size` is
+// one output pixel in uv units.
+fn downsample(uv: vec2<f32>, pixel_size: vec2<f32>) -> vec3<f32> {
+    var color = vec3(0.0);
+    var total = 0.0;
+    for (var y = 0; y < 4; y++) {
+        for (var x = 0; x < 4; x++) {
+            // Taps spread over +-0.75 output pixels, weighted by a tent one pixel wide
+            let offset = (vec2<f32>(f32(x), f32(y)) - 1.5) * 0.5;
+            let weight = (1.0 - abs(offset.x)) * (1.0 - abs(offset.y));
+            let sample_uv = uv + offset * pixel_size;
+            color += textureSampleLevel(render_texture, render_sampler, sample_uv, 0.0).rgb * weight;
+            total += weight;
+        }
+    }
+    return color / total;
+}
 
-// Simple bloom by sampling neighbors and adding bright contribution
-fn bloom_sample(uv: vec2<f32>, tex_size: vec2<f32>) -> vec3<f32> {
-    let pixel_size = 1.0 / tex_size;
+// Simple bloom by sampling neighbors and adding bright contribution.
+// Offsets are in output pixels, so the glow keeps its size when supersampling.
+fn bloom_sample(uv: vec2<f32>, pixel_size: vec2<f32>) -> vec3<f32> {
     var bloom = vec3(0.0);
 
     // Sample in a cross pattern at multiple distances
@@ -55,11 +80,25 @@ fn bloom_sample(uv: vec2<f32>, tex_size: vec2<f32>) -> vec3<f32> {
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let base_color = textureSample(render_texture, render_sampler, in.uv).rgb;
     let tex_size = vec2<f32>(textureDimensions(render_texture));
+    // One output pixel in uv units, from how fast uv changes across the screen
+    let pixel_size = abs(vec2(dpdx(in.uv.x), dpdy(in.uv.y)));
+
+    // Texels per output pixel; above 1 the frame was supersampled
+    var base_color: vec3<f32>;
+    if pixel_size.x * tex_size.x > 1.01 {
+        base_color = downsample(in.uv, pixel_size);
+    } else {
+        base_color = textureSampleLevel(render_texture, render_sampler, in.uv, 0.0).rgb;
+    }
+
+    // Debug views map values to exact colors, which bloom would smear
+    if params.debug_view != 0u {
+        return vec4(base_color, 1.0);
+    }
 
     // Add bloom
-    let bloom = bloom_sample(in.uv, tex_size);
+    let bloom = bloom_sample(in.uv, max(pixel_size, 1.0 / tex_size));
     let bloom_intensity = 0.4;
     var final_color = base_color + bloom * bloom_intensity;
 
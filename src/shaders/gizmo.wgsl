@@ -0,0 +1,36 @@
+struct GizmoUniforms {
+    // Camera rotation into the gizmo's corner, for the axes
+    axes: mat4x4<f32>,
+    // The main view's view-projection, for the bounding box
+    world: mat4x4<f32>,
+}
+
+struct VertexInput {
+    // w is 0 for axes vertices and 1 for bounding box vertices
+    @location(0) position: vec4<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> gizmo: GizmoUniforms;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    if in.position.w > 0.5 {
+        out.position = gizmo.world * vec4(in.position.xyz, 1.0);
+    } else {
+        out.position = gizmo.axes * vec4(in.position.xyz, 1.0);
+    }
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,78 @@
+// Fragment path for adapters without compute shaders or storage buffers
+// (WebGL2): the world lives in a uniform buffer and each fragment of a
+// fullscreen triangle is raymarched straight into the swapchain.
+// CELL_COUNT, PHASE_COUNT and PAIR_COUNT, its square, are defined by the
+// host.
+
+#include "raymarch.wgsl"
+
+struct WorldUniforms {
+    phases: array<VendekPhase, PHASE_COUNT>,
+    membranes: array<MembranePair, PAIR_COUNT>,
+    cells: array<HoneycombCell, CELL_COUNT>,
+}
+
+@group(0) @binding(0) var<uniform> frame: FrameUniforms;
+@group(0) @binding(1) var<uniform> params: RaymarchParams;
+@group(0) @binding(2) var<uniform> world: WorldUniforms;
+@group(0) @binding(3) var palette_colors: texture_2d<f32>;
+@group(0) @binding(4) var transfer_functions: texture_2d<f32>;
+@group(0) @binding(5) var cell_states: texture_2d<f32>;
+@group(0) @binding(6) var<uniform> lights: EmitterLights;
+@group(0) @binding(7) var grid_occupancy: texture_3d<u32>;
+
+@group(1) @binding(0) var geometry_color: texture_2d<f32>;
+@group(1) @binding(1) var geometry_depth: texture_2d<f32>;
+
+fn world_cell_count() -> u32 {
+    return CELL_COUNT;
+}
+
+fn world_cell(i: u32) -> HoneycombCell {
+    return world.cells[i];
+}
+
+fn world_phase_count() -> u32 {
+    return PHASE_COUNT;
+}
+
+fn world_phase(i: u32) -> VendekPhase {
+    return world.phases[i];
+}
+
+fn world_membrane(i: u32) -> MembranePair {
+    return world.membranes[i];
+}
+
+fn world_emitter_count() -> u32 {
+    return lights.count;
+}
+
+fn world_emitter(i: u32) -> Emitter {
+    return lights.emitters[i];
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) idx: u32) -> @builtin(position) vec4<f32> {
+    // Fullscreen triangle
+    var positions = array<vec2<f32>, 3>(
+        vec2(-1.0, -1.0),
+        vec2(3.0, -1.0),
+        vec2(-1.0, 3.0),
+    );
+    return vec4(positions[idx], 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let uv = (position.xy - frame.viewport_origin) / frame.resolution;
+    var color = shade_pixel(uv, vec2<i32>(position.xy), 0.0).rgb;
+
+    // Same tone mapping as the display pass, and likewise none for debug
+    // views; there is no bloom on this path
+    if params.debug_view == 0u {
+        color = color / (1.0 + color * 0.2);
+    }
+
+    return vec4(color, 1.0);
+}
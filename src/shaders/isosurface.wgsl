@@ -0,0 +1,61 @@
+// Rasterized membrane isosurface, lit from the camera and colored by the
+// nearest cell's phase. The volume's clipping planes cut it too, and the
+// walls of hidden phases' cells are left out.
+
+#include "types.wgsl"
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) phase: u32,
+    @location(2) normal: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) @interpolate(flat) phase: u32,
+}
+
+@group(0) @binding(0) var<uniform> frame: FrameUniforms;
+@group(0) @binding(1) var<uniform> params: RaymarchParams;
+@group(0) @binding(2) var palette_colors: texture_2d<f32>;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = frame.view_proj * vec4(in.position, 1.0);
+    out.world_position = in.position;
+    out.normal = in.normal;
+    out.phase = in.phase;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if in.phase < 32u && ((params.visible_phases >> in.phase) & 1u) == 0u {
+        discard;
+    }
+    for (var i = 0u; i < params.clip_plane_count; i++) {
+        let plane = params.clip_planes[i];
+        if dot(plane.xyz, in.world_position) > plane.w {
+            discard;
+        }
+    }
+
+    let base = textureLoad(palette_colors, vec2<i32>(i32(in.phase), 0), 0).rgb;
+    let to_camera = frame.camera_position - in.world_position;
+    let view_dir = normalize(to_camera);
+
+    // Headlight, lighting both sides of the membrane alike
+    let normal = normalize(in.normal);
+    let diffuse = abs(dot(normal, view_dir));
+    let rim = pow(1.0 - diffuse, 3.0) * 0.4;
+    var color = base * (0.2 + 0.8 * diffuse) + vec3(rim);
+
+    // Same depth fog as the volume
+    let fog_factor = 1.0 - exp(-0.015 * length(to_camera));
+    color = mix(color, vec3(0.05, 0.05, 0.08), fog_factor * 0.5);
+
+    return vec4(color, 1.0);
+}
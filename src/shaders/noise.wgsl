@@ -0,0 +1,154 @@
+// Gradient noise, hashed on the GPU so it needs no lookup textures.
+
+// Three well-mixed 32-bit hashes of an integer lattice point (Jarzynski &
+// Olano's pcg3d)
+fn pcg3d(seed: vec3<u32>) -> vec3<u32> {
+    var v = seed * 1664525u + 1013904223u;
+    v.x += v.y * v.z;
+    v.y += v.z * v.x;
+    v.z += v.x * v.y;
+    v ^= v >> vec3(16u);
+    v.x += v.y * v.z;
+    v.y += v.z * v.x;
+    v.z += v.x * v.y;
+    return v;
+}
+
+// Pseudo-random gradient at a lattice point, each component in -1..1
+fn lattice_gradient(cell: vec3<f32>) -> vec3<f32> {
+    let hash = pcg3d(bitcast<vec3<u32>>(vec3<i32>(cell)));
+    return vec3<f32>(hash & vec3(0xffffu)) / 32767.5 - 1.0;
+}
+
+// Perlin-style gradient noise, roughly in -1..1
+fn gradient_noise(p: vec3<f32>) -> f32 {
+    let cell = floor(p);
+    let f = p - cell;
+    // Quintic fade, so the noise is smooth across cell faces
+    let u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+
+    let c000 = dot(lattice_gradient(cell), f);
+    let c100 = dot(lattice_gradient(cell + vec3(1.0, 0.0, 0.0)), f - vec3(1.0, 0.0, 0.0));
+    let c010 = dot(lattice_gradient(cell + vec3(0.0, 1.0, 0.0)), f - vec3(0.0, 1.0, 0.0));
+    let c110 = dot(lattice_gradient(cell + vec3(1.0, 1.0, 0.0)), f - vec3(1.0, 1.0, 0.0));
+    let c001 = dot(lattice_gradient(cell + vec3(0.0, 0.0, 1.0)), f - vec3(0.0, 0.0, 1.0));
+    let c101 = dot(lattice_gradient(cell + vec3(1.0, 0.0, 1.0)), f - vec3(1.0, 0.0, 1.0));
+    let c011 = dot(lattice_gradient(cell + vec3(0.0, 1.0, 1.0)), f - vec3(0.0, 1.0, 1.0));
+    let c111 = dot(lattice_gradient(cell + vec3(1.0, 1.0, 1.0)), f - vec3(1.0, 1.0, 1.0));
+
+    let x0 = mix(mix(c000, c100, u.x), mix(c010, c110, u.x), u.y);
+    let x1 = mix(mix(c001, c101, u.x), mix(c011, c111, u.x), u.y);
+    return mix(x0, x1, u.z);
+}
+
+// `octaves` octaves of gradient noise, each at twice the frequency and half
+// the amplitude of the last, normalized to roughly 0..1
+fn fbm(p: vec3<f32>, octaves: u32) -> f32 {
+    var sum = 0.0;
+    var total = 0.0;
+    var amplitude = 1.0;
+    var q = p;
+    for (var i = 0u; i < octaves; i++) {
+        sum += gradient_noise(q) * amplitude;
+        total += amplitude;
+        amplitude *= 0.5;
+        // Not exactly 2, so the octaves' lattices don't line up
+        q *= 2.03;
+    }
+    return sum / max(total, 1e-4) * 0.5 + 0.5;
+}
+
+// Simplex noise over 4D, roughly in -1..1. A port of Ashima Arts' and Stefan
+// Gustavson's webgl-noise (MIT), which hashes with a permutation polynomial
+// in floating point.
+fn mod289_4(x: vec4<f32>) -> vec4<f32> {
+    return x - floor(x * (1.0 / 289.0)) * 289.0;
+}
+
+fn mod289_1(x: f32) -> f32 {
+    return x - floor(x * (1.0 / 289.0)) * 289.0;
+}
+
+fn permute_4(x: vec4<f32>) -> vec4<f32> {
+    return mod289_4((x * 34.0 + 10.0) * x);
+}
+
+fn permute_1(x: f32) -> f32 {
+    return mod289_1((x * 34.0 + 10.0) * x);
+}
+
+fn taylor_inv_sqrt_4(r: vec4<f32>) -> vec4<f32> {
+    return 1.79284291400159 - 0.85373472095314 * r;
+}
+
+fn grad4(j: f32, ip: vec4<f32>) -> vec4<f32> {
+    var p: vec4<f32>;
+    p = vec4(floor(fract(vec3(j) * ip.xyz) * 7.0) * ip.z - 1.0, 0.0);
+    p.w = 1.5 - dot(abs(p.xyz), vec3(1.0));
+    let s = select(vec4(0.0), vec4(1.0), p < vec4(0.0));
+    return vec4(p.xyz + (s.xyz * 2.0 - 1.0) * s.www, p.w);
+}
+
+fn simplex_noise_4d(v: vec4<f32>) -> f32 {
+    // (sqrt(5) - 1) / 4, and the matching unskew constants
+    let f4 = 0.309016994374947451;
+    let c = vec4(0.138196601125011, 0.276393202250021, 0.414589803375032, -0.447213595499958);
+
+    // First corner
+    var i = floor(v + dot(v, vec4(f4)));
+    let x0 = v - i + dot(i, c.xxxx);
+
+    // Rank the coordinates to find the other corners
+    var i0: vec4<f32>;
+    let is_x = step(x0.yzw, x0.xxx);
+    let is_yz = step(x0.zww, x0.yyz);
+    i0.x = is_x.x + is_x.y + is_x.z;
+    i0 = vec4(i0.x, 1.0 - is_x);
+    i0.y += is_yz.x + is_yz.y;
+    i0.z += 1.0 - is_yz.x;
+    i0.w += 1.0 - is_yz.y;
+    i0.z += is_yz.z;
+    i0.w += 1.0 - is_yz.z;
+    let i3 = clamp(i0, vec4(0.0), vec4(1.0));
+    let i2 = clamp(i0 - 1.0, vec4(0.0), vec4(1.0));
+    let i1 = clamp(i0 - 2.0, vec4(0.0), vec4(1.0));
+
+    let x1 = x0 - i1 + c.xxxx;
+    let x2 = x0 - i2 + c.yyyy;
+    let x3 = x0 - i3 + c.zzzz;
+    let x4 = x0 + c.wwww;
+
+    // Hash the corners
+    i = mod289_4(i);
+    let j0 = permute_1(permute_1(permute_1(permute_1(i.w) + i.z) + i.y) + i.x);
+    let j1 = permute_4(
+        permute_4(
+            permute_4(
+                permute_4(i.w + vec4(i1.w, i2.w, i3.w, 1.0)) + i.z + vec4(i1.z, i2.z, i3.z, 1.0)
+            ) + i.y + vec4(i1.y, i2.y, i3.y, 1.0)
+        ) + i.x + vec4(i1.x, i2.x, i3.x, 1.0)
+    );
+
+    // Gradients: 7x7x6 points over a cube, mapped onto the 4-cross polytope
+    let ip = vec4(1.0 / 294.0, 1.0 / 49.0, 1.0 / 7.0, 0.0);
+    let norm = taylor_inv_sqrt_4(vec4(
+        dot(grad4(j0, ip), grad4(j0, ip)),
+        dot(grad4(j1.x, ip), grad4(j1.x, ip)),
+        dot(grad4(j1.y, ip), grad4(j1.y, ip)),
+        dot(grad4(j1.z, ip), grad4(j1.z, ip)),
+    ));
+    let p0 = grad4(j0, ip) * norm.x;
+    let p1 = grad4(j1.x, ip) * norm.y;
+    let p2 = grad4(j1.y, ip) * norm.z;
+    let p3 = grad4(j1.z, ip) * norm.w;
+    var p4 = grad4(j1.w, ip);
+    p4 *= taylor_inv_sqrt_4(vec4(dot(p4, p4))).x;
+
+    // Mix the contributions of the five corners
+    var m0 = max(0.6 - vec3(dot(x0, x0), dot(x1, x1), dot(x2, x2)), vec3(0.0));
+    var m1 = max(0.6 - vec2(dot(x3, x3), dot(x4, x4)), vec2(0.0));
+    m0 = m0 * m0;
+    m1 = m1 * m1;
+    return 49.0 * (dot(m0 * m0, vec3(dot(p0, x0), dot(p1, x1), dot(p2, x2)))
+        + dot(m1 * m1, vec2(dot(p3, x3), dot(p4, x4))));
+}
@@ -0,0 +1,37 @@
+struct OverlayUniforms {
+    // Left, top, right, bottom in clip space
+    rect: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> overlay: OverlayUniforms;
+@group(0) @binding(1) var overlay_texture: texture_2d<f32>;
+@group(0) @binding(2) var overlay_sampler: sampler;
+
+@vertex
+fn vs_main(@builtin(vertex_index) idx: u32) -> VertexOutput {
+    // Two triangles covering the overlay rectangle
+    var corners = array<vec2<f32>, 6>(
+        vec2(0.0, 0.0),
+        vec2(1.0, 0.0),
+        vec2(0.0, 1.0),
+        vec2(0.0, 1.0),
+        vec2(1.0, 0.0),
+        vec2(1.0, 1.0),
+    );
+    let corner = corners[idx];
+
+    var out: VertexOutput;
+    out.position = vec4(mix(overlay.rect.xy, overlay.rect.zw, corner), 0.0, 1.0);
+    out.uv = corner;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(overlay_texture, overlay_sampler, in.uv);
+}
@@ -0,0 +1,429 @@
+// Raymarching shared by the compute and fragment paths. Each entry point file
+// includes this, declares the `frame` and `params` uniforms and the
+// `palette_colors`, `transfer_functions`, `cell_states`, `geometry_color`,
+// `geometry_depth` and `grid_occupancy` textures, and provides the world
+// accessors `world_cell_count()`, `world_cell(i)`, `world_phase_count()`,
+// `world_phase(i)`, `world_membrane(i)`, entry `i` of the phase-pair
+// membrane table, `world_emitter_count()` and `world_emitter(i)`.
+//
+// Features: COUPLING couples membrane oscillation to the neighbouring phase.
+// SHADOWS shadows the scattered light by the shadow prepass's map, which the
+// entry point then declares as `shadow_map` with `shadow_sampler`. F16
+// composites the samples in half precision; the entry point then enables
+// f16 first thing.
+
+#include "types.wgsl"
+#include "volume.wgsl"
+#include "voronoi.wgsl"
+#include "density.wgsl"
+#include "background.wgsl"
+#include "noise.wgsl"
+#include "debug_view.wgsl"
+
+// Precision of the front-to-back compositing
+#ifdef F16
+alias Composite = f16;
+#else
+alias Composite = f32;
+#endif
+
+// Phase color from the host-generated palette texture, one texel per phase
+fn phase_color(i: u32) -> vec3<f32> {
+    return textureLoad(palette_colors, vec2<i32>(i32(i), 0), 0).rgb;
+}
+
+// Simulated state of cell `i`, one texel per cell in rows as wide as the
+// texture: reaction-diffusion substrate in x, catalyst in y and temperature
+// in z
+fn cell_state(i: u32) -> vec4<f32> {
+    let width = textureDimensions(cell_states).x;
+    return textureLoad(cell_states, vec2<i32>(vec2(i % width, i / width)), 0);
+}
+
+// The membrane between cells of phases `a` and `b`
+fn membrane_pair(a: u32, b: u32) -> MembranePair {
+    return world_membrane(a * world_phase_count() + b);
+}
+
+// World units per cycle of the membrane ripple noise, inverted
+const RIPPLE_SCALE: f32 = 0.35;
+
+// Shift the membrane between the two cells of `hit` by animated 4D noise over
+// position and time. Each pair of phases reads its own region of the noise,
+// so different kinds of wall ripple differently. Samples the wall sweeps
+// over change hands to the neighbouring cell.
+fn ripple_membrane(hit: VoronoiHit, pos: vec3<f32>) -> VoronoiHit {
+    let phase_a = world_cell(hit.closest).phase_index;
+    let phase_b = world_cell(hit.second).phase_index;
+    let pair = min(phase_a, phase_b) * 64u + max(phase_a, phase_b);
+    let pair_offset = vec4<f32>(vec4(pcg3d(vec3(pair, pair ^ 0x5bu, 0x9eu)), pair) & vec4(0xffu));
+    let noise_pos = vec4(pos * RIPPLE_SCALE, frame.time * params.ripple_speed) + pair_offset;
+
+    // Positive noise pushes the wall away from the lower-indexed cell
+    var shift = simplex_noise_4d(noise_pos) * params.ripple_amplitude;
+    if hit.closest > hit.second {
+        shift = -shift;
+    }
+
+    var out = hit;
+    out.dist_closest = hit.dist_closest - shift;
+    out.dist_second = hit.dist_second + shift;
+    if out.dist_closest > out.dist_second {
+        out = VoronoiHit(hit.second, hit.closest, out.dist_second, out.dist_closest);
+    }
+    out.dist_closest = max(out.dist_closest, 0.0);
+    return out;
+}
+
+// Henyey–Greenstein phase function: how much light is scattered through the
+// angle with cosine `cos_theta` for anisotropy `g`, -1 all back to 1 all
+// forward. Scaled by 4π, so isotropic scattering gives 1 in every direction.
+fn henyey_greenstein(cos_theta: f32, g: f32) -> f32 {
+    let g2 = g * g;
+    let denom = max(1.0 + g2 - 2.0 * g * cos_theta, 1e-4);
+    return (1.0 - g2) / (denom * sqrt(denom));
+}
+
+#ifdef SHADOWS
+// Share of the light that reaches `pos` through the volume
+fn light_transmittance(pos: vec3<f32>) -> f32 {
+    let uvw = (pos - params.volume_min) / (params.volume_max - params.volume_min);
+    return textureSampleLevel(shadow_map, shadow_sampler, uvw, 0.0).r;
+}
+#else
+fn light_transmittance(pos: vec3<f32>) -> f32 {
+    return 1.0;
+}
+#endif
+
+// Light reaching `pos` from the emitting cells, each in the color of its
+// cell's current phase, at full brightness at its seed, a quarter at its
+// radius and falling off with the fourth power of the distance beyond
+fn emitter_light(pos: vec3<f32>) -> vec3<f32> {
+    var light = vec3(0.0);
+    for (var i = 0u; i < world_emitter_count(); i++) {
+        let emitter = world_emitter(i);
+        let d = distance(pos, emitter.position) / emitter.radius;
+        let color = phase_color(world_cell(emitter.cell).phase_index);
+        let falloff = 1.0 / (1.0 + d * d);
+        light += color * emitter.intensity * falloff * falloff;
+    }
+    return light;
+}
+
+// Anisotropy of a phase with mean free path `mean_free_path` (0.5 to 2):
+// phases light travels far through scatter it forward, dense ones back
+fn scattering_anisotropy(mean_free_path: f32) -> f32 {
+    return clamp((mean_free_path - 1.25) / 0.75, -1.0, 1.0) * 0.7;
+}
+
+// Ray distance over which steps grow by `params.lod_falloff` times their
+// length
+const LOD_DISTANCE: f32 = 32.0;
+// Step scale past which samples skip the noise and coupling terms
+const LOD_DETAIL_LIMIT: f32 = 2.0;
+
+// How many times `params.step_size` the step at ray distance `t` is: 1 at
+// the camera, growing linearly with distance by `params.lod_falloff`
+fn lod_scale(t: f32) -> f32 {
+    return 1.0 + params.lod_falloff * t / LOD_DISTANCE;
+}
+
+// What the last `shade_pixel` call saw, for the compute path's denoiser:
+// the distance along the ray to the volume the pixel shows, weighted by
+// opacity, and the phase contributing most to it, NO_PHASE where no volume
+// shows
+var<private> shaded_depth: f32;
+var<private> shaded_phase: u32;
+const NO_PHASE: u32 = 0xffffffffu;
+
+// Ray of the last `shade_pixel` call, and the steps it took through the
+// volume, for the debug views
+var<private> marched_origin: vec3<f32>;
+var<private> marched_direction: vec3<f32>;
+var<private> marched_steps: u32;
+
+// Values of `params.render_mode`
+const RENDER_MODE_VOLUME: u32 = 0u;
+const RENDER_MODE_ISOSURFACE: u32 = 1u;
+const RENDER_MODE_HYBRID: u32 = 2u;
+const RENDER_MODE_MEMBRANES: u32 = 3u;
+
+// Raymarch the volume for one pixel. `uv` runs from (0, 0) at the top-left
+// corner of the image to (1, 1) at the bottom-right; `pixel` is the same
+// pixel in the geometry textures. The first sample lies `start_offset`
+// steps into the volume, 0 to 1, so accumulated samples can stagger them.
+fn shade_pixel(uv: vec2<f32>, pixel: vec2<i32>, start_offset: f32) -> vec4<f32> {
+    let color = march_pixel(uv, pixel, start_offset);
+    if params.debug_view == DEBUG_VIEW_OFF {
+        return color;
+    }
+    let debug = debug_color(
+        params.debug_view,
+        marched_origin,
+        marched_direction,
+        marched_steps,
+        shaded_depth,
+        shaded_phase,
+        shaded_phase != NO_PHASE,
+    );
+    // The display pass shows debug views as they are, in linear color
+    return vec4(pow(debug, vec3(2.2)), 1.0);
+}
+
+// The image for one pixel, as `shade_pixel` takes it
+fn march_pixel(uv: vec2<f32>, pixel: vec2<i32>, start_offset: f32) -> vec4<f32> {
+    shaded_depth = frame.far;
+    shaded_phase = NO_PHASE;
+    marched_steps = 0u;
+
+    // Reconstruct ray from pixel coordinates
+    let ndc = uv * 2.0 - 1.0;
+
+    let clip_near = vec4(ndc.x, -ndc.y, 0.0, 1.0);
+    let clip_far = vec4(ndc.x, -ndc.y, 1.0, 1.0);
+    var world_near = frame.inv_view_proj * clip_near;
+    var world_far = frame.inv_view_proj * clip_far;
+    world_near /= world_near.w;
+    world_far /= world_far.w;
+
+    let ray_origin = world_near.xyz;
+    let ray_dir = normalize(world_far.xyz - world_near.xyz);
+    marched_origin = ray_origin;
+    marched_direction = ray_dir;
+
+    // Rasterized geometry stands in for the background and stops the ray
+    // where it was hit. Depth 1 is the far plane, where nothing was drawn.
+    var background = background_color(ray_dir);
+    var t_geometry = frame.far;
+    var depth = 1.0;
+    if params.render_mode == RENDER_MODE_ISOSURFACE || params.render_mode == RENDER_MODE_HYBRID {
+        depth = textureLoad(geometry_depth, pixel, 0).r;
+    }
+    let has_geometry = depth < 1.0;
+    if has_geometry {
+        var hit = frame.inv_view_proj * vec4(ndc.x, -ndc.y, depth, 1.0);
+        hit /= hit.w;
+        t_geometry = distance(ray_origin, hit.xyz);
+        background = textureLoad(geometry_color, pixel, 0).rgb;
+    }
+    if params.render_mode == RENDER_MODE_ISOSURFACE {
+        return vec4(background, 1.0);
+    }
+
+    // Find intersection with volume bounds, minus what the clipping planes remove
+    let t_range = clip_range(ray_origin, ray_dir, intersect_box(ray_origin, ray_dir));
+
+    if t_range.x >= min(t_range.y, t_geometry) {
+        // Outside volume, or behind the geometry
+        return vec4(background, 1.0);
+    }
+
+    // Raymarch through the volume
+    var accumulated_color = vec3<Composite>(0.0);
+    var accumulated_alpha = Composite(0.0);
+    var weighted_depth = 0.0;
+    var strongest = 0.0;
+
+    let t_start = t_range.x + start_offset * params.step_size;
+    let t_end = min(t_range.y, t_geometry);
+    var t = t_start;
+
+    for (var step = 0u; step < params.max_steps; step++) {
+        // Stop once what lies further along could barely show through
+        if t >= t_end || 1.0 - f32(accumulated_alpha) < params.min_transmittance {
+            break;
+        }
+        marched_steps += 1u;
+
+        let pos = ray_origin + ray_dir * t;
+
+        // Level of detail: distant samples take longer steps, and past a
+        // point leave out the ripples, wisps and coupling, whose detail is
+        // too fine to see there
+        let lod = lod_scale(t);
+        let step_size = params.step_size * lod;
+        let detailed = lod < LOD_DETAIL_LIMIT;
+
+        // Soft boundary fade
+        let edge_fade = boundary_fade(pos);
+        if edge_fade < 0.01 {
+            t += step_size;
+            continue;
+        }
+
+        var vor = voronoi_cell(pos);
+        if params.ripple_amplitude > 0.0 && detailed {
+            vor = ripple_membrane(vor, pos);
+        }
+        let cell_idx = vor.closest;
+        let dist_closest = vor.dist_closest;
+        let dist_second = vor.dist_second;
+
+        // Get phase for this cell with slow time-based transitions
+        let base_phase_idx = world_cell(cell_idx).phase_index;
+
+        // Cells of hidden phases are empty
+        if !phase_visible(base_phase_idx) {
+            t += step_size;
+            continue;
+        }
+
+        // Slow phase drift based on cell position and time
+        let cell_pos = world_cell(cell_idx).position;
+        let drift_speed = 0.05; // Very slow transition
+        let phase_drift = sin(frame.time * drift_speed + cell_pos.x * 0.3 + cell_pos.y * 0.2 + cell_pos.z * 0.1);
+
+        // Blend between current phase and next phase
+        let next_phase_idx = (base_phase_idx + 1u) % world_phase_count();
+        let blend_factor = phase_drift * 0.5 + 0.5; // 0 to 1
+
+        let phase_a = world_phase(base_phase_idx);
+        let phase_b = world_phase(next_phase_idx);
+
+        // Create interpolated phase
+        var phase: VendekPhase;
+        phase.membrane_params = mix(phase_a.membrane_params, phase_b.membrane_params, blend_factor * 0.2);
+        phase.scattering = mix(phase_a.scattering, phase_b.scattering, blend_factor * 0.2);
+        let phase_rgb = mix(phase_color(base_phase_idx), phase_color(next_phase_idx), blend_factor * 0.3);
+
+        // Membrane detection: how close are we to a cell boundary? Each pair
+        // of phases has its own thickness, glow and tint.
+        let second_phase_idx = world_cell(vor.second).phase_index;
+        let membrane = membrane_pair(base_phase_idx, second_phase_idx);
+        let membrane_dist = (dist_second - dist_closest) * 0.5;
+        let membrane_factor = smoothstep(0.0, params.membrane_thickness * membrane.thickness, membrane_dist);
+        let membrane_glow = params.membrane_glow * membrane.glow;
+
+        // Membrane-only mode skips the cell interiors
+        let membranes_only = params.render_mode == RENDER_MODE_MEMBRANES;
+        if membranes_only && membrane_factor >= 1.0 {
+            t += step_size;
+            continue;
+        }
+
+        // Base cell color and opacity from the transfer functions, by density:
+        // 0 at the cell center rising to 1 at its membranes
+        let density = dist_closest / max(dist_second, 1e-4);
+        let transfer_value = mix(
+            transfer(base_phase_idx, density),
+            transfer(next_phase_idx, density),
+            blend_factor * 0.3,
+        );
+        var sample_color = phase_rgb * transfer_value.rgb;
+        var sample_alpha = transfer_value.a * step_size * edge_fade * params.density_multiplier;
+
+        // Single scattering: light from the light direction turned toward the
+        // eye, tinted by the phase's scattering coefficients and dimmed by
+        // the volume it crossed on the way in
+        if params.scattering > 0.0 {
+            let lobe = henyey_greenstein(dot(params.light_direction, ray_dir), scattering_anisotropy(phase.scattering.w));
+            let light = lobe * light_transmittance(pos) * params.scattering;
+            sample_color += phase_rgb * phase.scattering.rgb * light;
+        }
+
+        // Wispy structure inside the cells from the phase's own noise, slowly
+        // rising. Averages out to the unmodulated density.
+        if detailed {
+            let noise_pos = pos * phase_a.noise_scale + vec3(0.0, -frame.time * 0.05, 0.0);
+            let wisps = smoothstep(0.3, 0.7, fbm(noise_pos, phase_a.noise_octaves)) * 2.0;
+            sample_alpha *= mix(1.0, wisps, phase_a.noise_strength);
+        }
+
+        // Reaction-diffusion: cells rich in catalyst shift toward the next
+        // hue around and thicken, cells without it thin out
+        if params.reaction_strength > 0.0 {
+            let catalyst = cell_state(cell_idx).y;
+            let shifted = mix(sample_color, sample_color.gbr, clamp(catalyst * 2.0, 0.0, 1.0));
+            sample_color = mix(sample_color, shifted, params.reaction_strength);
+            sample_alpha *= mix(1.0, 0.3 + catalyst * 3.0, params.reaction_strength);
+        }
+
+        // Heat: hot cells glow ember to white, cold ones not at all
+        if params.heat_strength > 0.0 {
+            let temperature = cell_state(cell_idx).z;
+            let glow = mix(vec3(1.0, 0.3, 0.05), vec3(1.0, 0.9, 0.7), temperature) * temperature * temperature;
+            sample_color += glow * params.heat_strength * 2.0;
+        }
+
+        // Emitting cells light the volume around them, and glow brightest
+        // from within
+        if params.emission > 0.0 && world_emitter_count() > 0u {
+            sample_color += emitter_light(pos) * params.emission * 8.0;
+        }
+
+        // Add membrane glow at boundaries
+        if membrane_factor < 1.0 {
+            let phase_freq = phase.membrane_params.x;
+
+            // Simple oscillation without coupling (faster), also for
+            // distant samples
+            let base_phase = phase_freq * frame.time + dist_closest * 2.0;
+            var oscillation = sin(base_phase) * 0.5 + 0.5;
+            var membrane_color = mix(phase_rgb, vec3(1.0), 0.7) * membrane.color * membrane_glow;
+
+#ifdef COUPLING
+            if detailed {
+                let phase_coupling = phase.membrane_params.w;
+                let second_freq = world_phase(second_phase_idx).membrane_params.x;
+
+                // Coupled oscillation - interference between two adjacent cell frequencies
+                let coupled_phase = second_freq * frame.time + dist_second * 2.0;
+                let interference = sin(base_phase) * 0.5 + sin(coupled_phase) * phase_coupling * 0.5;
+                oscillation = interference * 0.5 + 0.5;
+
+                // Membrane color blends the two adjacent phases
+                let blend_color = mix(phase_rgb, phase_color(second_phase_idx), 0.5);
+                membrane_color = mix(blend_color, vec3(1.0), 0.6) * membrane.color * membrane_glow;
+            }
+#endif
+
+            let membrane_intensity = (1.0 - membrane_factor) * (0.3 + 0.7 * oscillation);
+            if membranes_only {
+                // The wall alone, denser than in the volume and brightest
+                // where the ray grazes it, so the network reads in outline
+                let normal = normalize(world_cell(vor.second).position - cell_pos);
+                let edge = 1.0 - abs(dot(ray_dir, normal));
+                let shade = 0.4 + 1.6 * edge * edge;
+                sample_color = membrane_color * shade;
+                sample_alpha = min(membrane_intensity * 0.2 * shade, 1.0);
+            } else {
+                sample_color = mix(sample_color, membrane_color, membrane_intensity);
+                sample_alpha += membrane_intensity * 0.15;
+            }
+        }
+
+        // Front-to-back compositing
+        let weight = Composite(sample_alpha) * (1.0 - accumulated_alpha);
+        accumulated_color += vec3<Composite>(sample_color) * weight;
+        accumulated_alpha += weight;
+        weighted_depth += t * f32(weight);
+        if f32(weight) > strongest {
+            strongest = f32(weight);
+            shaded_phase = base_phase_idx;
+        }
+
+        t += step_size;
+    }
+
+    let opacity = f32(accumulated_alpha);
+    if opacity > 0.0 {
+        shaded_depth = weighted_depth / opacity;
+    }
+
+    // Blend with background
+    var final_color = vec3<f32>(accumulated_color) + background * (1.0 - opacity);
+
+    // Depth fog - fade distant parts toward background. Geometry is already
+    // fogged, so only the volume in front of it fades.
+    let avg_depth = (t_start + t) * 0.5; // Approximate average depth
+    let fog_density = 0.015;
+    var fog_factor = 1.0 - exp(-fog_density * avg_depth);
+    if has_geometry {
+        fog_factor *= opacity;
+    }
+    let fog_color = vec3(0.05, 0.05, 0.08); // Slightly blue-tinted fog
+    final_color = mix(final_color, fog_color, fog_factor * 0.5);
+
+    return vec4(final_color, 1.0);
+}
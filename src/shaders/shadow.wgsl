@@ -0,0 +1,75 @@
+// Compute path prepass: the share of the light that reaches each texel of a
+// 3D grid over the volume, marched toward the light through the density in
+// between. The raymarch samples it to shadow the scattered light instead of
+// marching toward the light from every sample. The texels are written to a
+// buffer, packed as rgba16float, which the host copies into the 3D texture.
+//
+// Features: BVH, as in voronoi.wgsl.
+
+#include "types.wgsl"
+#include "volume.wgsl"
+#include "voronoi.wgsl"
+#include "density.wgsl"
+
+@group(0) @binding(1) var<uniform> params: RaymarchParams;
+@group(0) @binding(3) var<storage, read> cells: array<HoneycombCell>;
+@group(0) @binding(5) var transfer_functions: texture_2d<f32>;
+#ifdef BVH
+@group(0) @binding(7) var<storage, read> bvh: array<BvhNode>;
+#endif
+
+@group(1) @binding(0) var<storage, read_write> shadow_map: array<vec2<u32>>;
+
+// Texels along each side of the grid, as in shadow.rs
+const SIZE: u32 = 32u;
+
+// Steps along one texel's path to the light at most; enough to cross the
+// grid diagonally
+const MAX_SHADOW_STEPS: u32 = 256u;
+// Light below this much is as good as none, and ends the march
+const MIN_LIGHT: f32 = 0.01;
+
+fn world_cell_count() -> u32 {
+    return arrayLength(&cells);
+}
+
+fn world_cell(i: u32) -> HoneycombCell {
+    return cells[i];
+}
+
+#ifdef BVH
+fn world_bvh_node(i: u32) -> BvhNode {
+    return bvh[i];
+}
+#endif
+
+@compute @workgroup_size(4, 4, 4)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let size = vec3(SIZE);
+    if any(gid >= size) {
+        return;
+    }
+
+    let extent = params.volume_max - params.volume_min;
+    let pos = params.volume_min + (vec3<f32>(gid) + 0.5) / vec3<f32>(size) * extent;
+    let light = params.light_direction;
+
+    // From the texel to where the light enters the volume, less what the
+    // clipping planes cut away, in steps of about a texel
+    let range = clip_range(pos, light, intersect_box(pos, light));
+    let texel = extent / vec3<f32>(size);
+    let step_size = min(min(texel.x, texel.y), texel.z);
+
+    var transmittance = 1.0;
+    var t = range.x + step_size * 0.5;
+    for (var step = 0u; step < MAX_SHADOW_STEPS; step++) {
+        if t >= range.y || transmittance < MIN_LIGHT {
+            break;
+        }
+        transmittance *= 1.0 - min(extinction(pos + light * t) * step_size, 1.0);
+        t += step_size;
+    }
+
+    let index = (gid.z * SIZE + gid.y) * SIZE + gid.x;
+    shadow_map[index] = vec2(pack2x16float(vec2(transmittance, 0.0)), pack2x16float(vec2(0.0, 1.0)));
+}
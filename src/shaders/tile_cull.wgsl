@@ -0,0 +1,107 @@
+// Compute path prepass: one invocation per tile of a view. Tiles whose
+// rays can reach the volume go on the tile list for the raymarch; the rest
+// only see the background and are filled with it here, so the raymarch
+// never launches a workgroup for them.
+
+#include "types.wgsl"
+#include "volume.wgsl"
+#include "tiles.wgsl"
+#include "background.wgsl"
+
+@group(0) @binding(0) var<uniform> frame: FrameUniforms;
+@group(0) @binding(1) var<uniform> params: RaymarchParams;
+
+@group(1) @binding(0) var output: texture_storage_2d<rgba16float, write>;
+@group(1) @binding(1) var<storage, read_write> tile_list: TileListWrite;
+// The denoiser's guide, as the raymarch writes it for pixels without volume
+@group(1) @binding(2) var guide: texture_storage_2d<rgba16float, write>;
+
+// World position of a point on the image, `uv` as in `shade_pixel`, at
+// `depth` between the near (0) and far (1) planes
+fn unproject(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc = uv * 2.0 - 1.0;
+    let p = frame.inv_view_proj * vec4(ndc.x, -ndc.y, depth, 1.0);
+    return p.xyz / p.w;
+}
+
+// Whether the box is at least partly on the inner side of the plane through
+// `origin` with normal `normal`
+fn box_inside(origin: vec3<f32>, normal: vec3<f32>) -> bool {
+    let furthest = select(params.volume_min, params.volume_max, normal > vec3(0.0));
+    return dot(normal, furthest - origin) >= 0.0;
+}
+
+// Whether any ray through the pixels from `first` up to `last` can hit the
+// volume: false only if the box lies wholly outside one of the planes
+// bounding the tile's rays. Clipping planes only shorten rays, so they are
+// left out.
+fn tile_sees_volume(first: vec2<f32>, last: vec2<f32>) -> bool {
+    let corners = array(
+        first,
+        vec2(last.x, first.y),
+        last,
+        vec2(first.x, last.y),
+    );
+    var near: array<vec3<f32>, 4>;
+    var far: array<vec3<f32>, 4>;
+    var inside = vec3(0.0);
+    for (var i = 0; i < 4; i++) {
+        let uv = corners[i] / frame.resolution;
+        near[i] = unproject(uv, 0.0);
+        far[i] = unproject(uv, 1.0);
+        inside += near[i] + far[i];
+    }
+    inside /= 8.0;
+
+    // Each side of the tile's frustum, normals turned toward its middle
+    for (var i = 0; i < 4; i++) {
+        let j = (i + 1) % 4;
+        var normal = cross(far[i] - near[i], near[j] - near[i]);
+        if dot(normal, inside - near[i]) < 0.0 {
+            normal = -normal;
+        }
+        if !box_inside(near[i], normal) {
+            return false;
+        }
+    }
+    // And the near plane, which keeps out boxes behind the camera
+    var normal = cross(near[1] - near[0], near[3] - near[0]);
+    if dot(normal, inside - near[0]) < 0.0 {
+        normal = -normal;
+    }
+    return box_inside(near[0], normal);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if all(gid == vec3(0u)) {
+        tile_list.workgroups_z = 1u;
+    }
+    let size = vec2<u32>(frame.resolution);
+    let tiles = (size + tile_size() - 1u) / tile_size();
+    if gid.x >= tiles.x || gid.y >= tiles.y {
+        return;
+    }
+
+    let first = gid.xy * tile_size();
+    let last = min(first + tile_size(), size);
+    if tile_sees_volume(vec2<f32>(first), vec2<f32>(last)) {
+        let i = atomicAdd(&tile_list.count, 1u);
+        tile_list.tiles[i] = pack_tile(gid.xy);
+        atomicMax(&tile_list.workgroups_x, min(i + 1u, TILE_ROW));
+        if i % TILE_ROW == 0u {
+            atomicAdd(&tile_list.workgroups_y, 1u);
+        }
+        return;
+    }
+
+    let origin = vec2<u32>(frame.viewport_origin);
+    for (var y = first.y; y < last.y; y++) {
+        for (var x = first.x; x < last.x; x++) {
+            let uv = vec2<f32>(vec2(x, y)) / frame.resolution;
+            let dir = normalize(unproject(uv, 1.0) - unproject(uv, 0.0));
+            textureStore(output, vec2<i32>(origin + vec2(x, y)), vec4(background_color(dir), 1.0));
+            textureStore(guide, vec2<i32>(origin + vec2(x, y)), vec4(frame.far, -1.0, 0.0, 0.0));
+        }
+    }
+}
@@ -0,0 +1,44 @@
+// The compute path's list of tiles that can see the volume, written by the
+// tile cull prepass and read by the raymarch, which dispatches one
+// workgroup per listed tile through the counts at the front.
+
+// Width and height of a tile in pixels, and of a raymarch workgroup; set
+// per pipeline to the workgroup size chosen for the adapter
+override TILE_WIDTH: u32 = 8u;
+override TILE_HEIGHT: u32 = 8u;
+// Workgroups in each row of the raymarch dispatch, well within every
+// adapter's per-dimension limit; tile `i` is workgroup (i % TILE_ROW,
+// i / TILE_ROW)
+const TILE_ROW: u32 = 4096u;
+
+// As the prepass writes it
+struct TileListWrite {
+    // Indirect dispatch size: x and y grow as tiles are added, z is 1
+    workgroups_x: atomic<u32>,
+    workgroups_y: atomic<u32>,
+    workgroups_z: u32,
+    count: atomic<u32>,
+    // x in the low 16 bits, y in the high 16, in tiles from the view's corner
+    tiles: array<u32>,
+}
+
+// As the raymarch reads it
+struct TileList {
+    workgroups_x: u32,
+    workgroups_y: u32,
+    workgroups_z: u32,
+    count: u32,
+    tiles: array<u32>,
+}
+
+fn tile_size() -> vec2<u32> {
+    return vec2(TILE_WIDTH, TILE_HEIGHT);
+}
+
+fn pack_tile(tile: vec2<u32>) -> u32 {
+    return tile.x | (tile.y << 16u);
+}
+
+fn unpack_tile(packed: u32) -> vec2<u32> {
+    return vec2(packed & 0xffffu, packed >> 16u);
+}
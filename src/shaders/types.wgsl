@@ -0,0 +1,146 @@
+// Uniform and world structs shared by every raymarch shader. Layouts match
+// the `#[repr(C)]` types in uniforms.rs, world.rs, bvh.rs and membrane.rs;
+// tests/layout.rs checks that they do.
+
+struct FrameUniforms {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    time: f32,
+    // Size of this view in pixels
+    resolution: vec2<f32>,
+    near: f32,
+    far: f32,
+    // Top-left pixel of this view in the render target
+    viewport_origin: vec2<f32>,
+    // Samples accumulated for this view so far; 0 starts over
+    sample_index: u32,
+    // Samples a pixel accumulates at most
+    sample_limit: u32,
+}
+
+struct RaymarchParams {
+    volume_min: vec3<f32>,
+    _pad0: f32,
+    volume_max: vec3<f32>,
+    _pad1: f32,
+    max_steps: u32,
+    step_size: f32,
+    membrane_thickness: f32,
+    membrane_glow: f32,
+    density_multiplier: f32,
+    // Selects the COUPLING permutation on the host; unused in the shader
+    enable_coupling: f32,
+    palette: u32,
+    clip_plane_count: u32,
+    // Normal in xyz and distance from the origin in w; samples with
+    // dot(normal, p) > w are cut away
+    clip_planes: array<vec4<f32>, 3>,
+    // 0 volume, 1 isosurface, 2 hybrid, 3 membranes only; in isosurface and
+    // hybrid the rasterized geometry replaces the background and ends rays
+    // where it is hit
+    render_mode: u32,
+    // Bit i set shows phase i; cells of hidden phases are left empty
+    visible_phases: u32,
+    // Membranes shift by up to this many world units of animated noise
+    ripple_amplitude: f32,
+    ripple_speed: f32,
+    // How far the reaction-diffusion catalyst in the cell states tints and
+    // thickens cells, 0 to 1
+    reaction_strength: f32,
+    // How far cells glow with the temperature in the cell states, 0 to 1
+    heat_strength: f32,
+    // Rays stop once less than this share of the light behind them gets
+    // through; 0 marches them to the far side of the volume
+    min_transmittance: f32,
+    // Strength of the light scattered toward the eye by each phase's
+    // scattering coefficients; 0 for none
+    scattering: f32,
+    // Unit vector toward the light
+    light_direction: vec3<f32>,
+    // Brightness of the emitting cells; 0 turns them off
+    emission: f32,
+    // 0 solid color, 1 starfield and nebulae, seeded by background_seed
+    background: u32,
+    background_seed: u32,
+    // Steps grow by this many times their length every LOD_DISTANCE along
+    // the ray, and distant samples drop the fine detail; 0 for none
+    lod_falloff: f32,
+    // Accumulated pixels stop taking samples once the standard error of
+    // their luminance falls below this; 0 never stops them early
+    refine_threshold: f32,
+    // 0 the image; 1 the steps each ray took, 2 phases, 3 membrane normals,
+    // 4 depth and 5 spatial grid occupancy, as debug_view.wgsl draws them.
+    // The display pass shows debug views without bloom or tone mapping.
+    debug_view: u32,
+    _pad2: u32,
+    _pad3: u32,
+    _pad4: u32,
+}
+
+struct VendekPhase {
+    color_density: vec4<f32>,
+    scattering: vec4<f32>,
+    membrane_params: vec4<f32>,
+    phase_id: u32,
+    // Density noise: frequency per world unit, octaves, and strength 0 to 1
+    noise_scale: f32,
+    noise_octaves: u32,
+    noise_strength: f32,
+}
+
+// The membrane between two phases, one entry per ordered pair of phases:
+// tint for the glow, and thickness and glow relative to the parameters
+struct MembranePair {
+    color: vec3<f32>,
+    thickness: f32,
+    glow: f32,
+    _pad0: f32,
+    _pad1: vec2<f32>,
+}
+
+struct HoneycombCell {
+    position: vec3<f32>,
+    phase_index: u32,
+}
+
+// A cell glowing from within: its seed, the distance within which its
+// light is at least half as bright, the cell, and its brightness relative
+// to params.emission
+struct Emitter {
+    position: vec3<f32>,
+    radius: f32,
+    cell: u32,
+    intensity: f32,
+    _pad: vec2<f32>,
+}
+
+// The world's emitters, `count` of them used; sized like MAX_EMITTERS on
+// the host
+struct EmitterLights {
+    count: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+    emitters: array<Emitter, 32>,
+}
+
+// Node of the hierarchy over the seeds, depth first: an inner node's first
+// child follows it and `right` is its second; leaves have `right` 0 and
+// list `count` seeds in `cells`
+struct BvhNode {
+    min: vec3<f32>,
+    right: u32,
+    max: vec3<f32>,
+    count: u32,
+    cells: array<u32, 4>,
+}
+
+// Cells of the spatial grid over the volume, each listing up to eight
+// Voronoi cells near it (-1 for none). Not bound yet; declared so the
+// layout stays checked until the grid is used again.
+struct GridCell {
+    cell_indices: array<i32, 8>,
+    count: u32,
+    _pad: array<u32, 3>,
+}
@@ -0,0 +1,53 @@
+// Bounds of the raymarched volume, from `params`.
+
+#include "types.wgsl"
+
+// What rays that miss the volume, and the geometry in it, show
+const BACKGROUND: vec3<f32> = vec3(0.02, 0.02, 0.03);
+
+// Ray-box intersection
+fn intersect_box(ray_origin: vec3<f32>, ray_dir: vec3<f32>) -> vec2<f32> {
+    let inv_dir = 1.0 / ray_dir;
+    let t1 = (params.volume_min - ray_origin) * inv_dir;
+    let t2 = (params.volume_max - ray_origin) * inv_dir;
+    let tmin = max(max(min(t1.x, t2.x), min(t1.y, t2.y)), min(t1.z, t2.z));
+    let tmax = min(min(max(t1.x, t2.x), max(t1.y, t2.y)), max(t1.z, t2.z));
+    return vec2(max(tmin, 0.0), tmax);
+}
+
+// Narrow a ray's [t_min, t_max] range to the part the clipping planes keep
+fn clip_range(ray_origin: vec3<f32>, ray_dir: vec3<f32>, range: vec2<f32>) -> vec2<f32> {
+    var clipped = range;
+    for (var i = 0u; i < params.clip_plane_count; i++) {
+        let plane = params.clip_planes[i];
+        let facing = dot(plane.xyz, ray_dir);
+        let height = plane.w - dot(plane.xyz, ray_origin);
+        if abs(facing) < 1e-6 {
+            // Parallel: kept entirely or not at all
+            if height < 0.0 {
+                clipped.y = clipped.x;
+            }
+        } else if facing > 0.0 {
+            clipped.y = min(clipped.y, height / facing);
+        } else {
+            clipped.x = max(clipped.x, height / facing);
+        }
+    }
+    return clipped;
+}
+
+// Calculate fade factor for soft volume boundaries
+fn boundary_fade(pos: vec3<f32>) -> f32 {
+    let fade_distance = 2.0; // Distance from edge to start fading
+    let normalized = (pos - params.volume_min) / (params.volume_max - params.volume_min);
+
+    // Distance from each face (0 at edge, 0.5 at center)
+    let dist_from_edge = min(normalized, 1.0 - normalized);
+
+    // Minimum distance to any face
+    let min_dist = min(min(dist_from_edge.x, dist_from_edge.y), dist_from_edge.z);
+
+    // Convert to world units and apply fade
+    let world_dist = min_dist * (params.volume_max.x - params.volume_min.x);
+    return smoothstep(0.0, fade_distance, world_dist);
+}
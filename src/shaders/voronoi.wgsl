@@ -0,0 +1,87 @@
+// Voronoi cell lookups over the world's cells, through the accessors the
+// entry point provides.
+//
+// Features: BVH walks the hierarchy over the seeds through
+// `world_bvh_node(i)`, which the entry point then provides too, instead of
+// testing every seed.
+
+#include "types.wgsl"
+
+// The two cells whose seeds are nearest a point: the one it lies in, and the
+// neighbour across the nearest membrane
+struct VoronoiHit {
+    closest: u32,
+    second: u32,
+    dist_closest: f32,
+    dist_second: f32,
+}
+
+// `hit` updated with cell `i`, at distance `d` from the point
+fn nearer(hit: VoronoiHit, i: u32, d: f32) -> VoronoiHit {
+    var out = hit;
+    if d < hit.dist_closest {
+        out.second = hit.closest;
+        out.dist_second = hit.dist_closest;
+        out.closest = i;
+        out.dist_closest = d;
+    } else if d < hit.dist_second {
+        out.second = i;
+        out.dist_second = d;
+    }
+    return out;
+}
+
+#ifdef BVH
+// Deeper than any hierarchy of median splits over 2^32 seeds
+const BVH_STACK: u32 = 32u;
+
+// Distance from `pos` to the nearest point of a node's box
+fn node_distance(node: BvhNode, pos: vec3<f32>) -> f32 {
+    return distance(pos, clamp(pos, node.min, node.max));
+}
+
+// Find closest Voronoi cell and distance to second-closest (for membrane
+// detection), skipping nodes whose box is further than the second-closest
+fn voronoi_cell(pos: vec3<f32>) -> VoronoiHit {
+    var hit = VoronoiHit(0u, 0u, 1e10, 1e10);
+
+    var stack: array<u32, BVH_STACK>;
+    var top = 1u;
+    while top > 0u {
+        top -= 1u;
+        let index = stack[top];
+        var node = world_bvh_node(index);
+        if node_distance(node, pos) >= hit.dist_second {
+            continue;
+        }
+        if node.right == 0u {
+            for (var k = 0u; k < node.count; k++) {
+                let i = node.cells[k];
+                hit = nearer(hit, i, distance(pos, world_cell(i).position));
+            }
+        } else {
+            // The nearer child goes on top, so the other is more often skipped
+            let left = index + 1u;
+            let left_first = node_distance(world_bvh_node(left), pos)
+                <= node_distance(world_bvh_node(node.right), pos);
+            stack[top] = select(left, node.right, left_first);
+            stack[top + 1u] = select(node.right, left, left_first);
+            top += 2u;
+        }
+    }
+
+    return hit;
+}
+#else
+// Find closest Voronoi cell and distance to second-closest (for membrane detection)
+fn voronoi_cell(pos: vec3<f32>) -> VoronoiHit {
+    var hit = VoronoiHit(0u, 0u, 1e10, 1e10);
+
+    let count = world_cell_count();
+    for (var i = 0u; i < count; i++) {
+        hit = nearer(hit, i, distance(pos, world_cell(i).position));
+    }
+
+    return hit;
+}
+#endif
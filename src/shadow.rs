@@ -0,0 +1,225 @@
+//! Deep shadow map for the compute path. A prepass marches from each texel
+//! of a coarse 3D grid over the volume toward the light and stores how much
+//! light gets through, and the raymarch samples the grid to shadow the
+//! scattered light, rather than marching toward the light from every sample.
+//! The map only depends on the light, the world and the parameters shaping
+//! the density, so it is recomputed when one of those changes, and not at
+//! all while nothing is scattered.
+//!
+//! The prepass writes a storage buffer, which is then copied into the 3D
+//! texture the raymarch samples: the GL backend binds only the first layer
+//! of a 3D storage texture, so writing the texture directly leaves the rest
+//! of the map dark there.
+
+use std::cell::Cell;
+
+use crate::shader::ShaderError;
+
+/// Texels along each side of the grid
+const SIZE: u32 = 32;
+/// Side of the prepass's cubic workgroups
+const WORKGROUP_SIZE: u32 = 4;
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Bytes per texel of `FORMAT`, as the prepass packs them
+const TEXEL_SIZE: u32 = 8;
+
+pub struct ShadowMap {
+    pipeline: wgpu::ComputePipeline,
+    /// The prepass's output, copied into `texture`
+    texels: wgpu::Buffer,
+    texture: wgpu::Texture,
+    /// For the prepass: the texels to write
+    write_bind_group: wgpu::BindGroup,
+    /// For the raymarch: the map to sample, and its sampler
+    read_layout: wgpu::BindGroupLayout,
+    read_bind_group: wgpu::BindGroup,
+    /// Whether the map is out of date. Cleared while encoding, which
+    /// borrows the map immutably.
+    stale: Cell<bool>,
+    /// Whether the raymarch uses the map; while it doesn't, it goes stale
+    in_use: bool,
+}
+
+impl ShadowMap {
+    /// The prepass shares the raymarch's bind group 0, with the world and
+    /// parameters, and looks seeds up the same way
+    pub fn new(
+        device: &wgpu::Device,
+        frame_layout: &wgpu::BindGroupLayout,
+        cache: Option<&wgpu::PipelineCache>,
+        use_bvh: bool,
+    ) -> Result<Self, ShaderError> {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Map"),
+            size: wgpu::Extent3d {
+                width: SIZE,
+                height: SIZE,
+                depth_or_array_layers: SIZE,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let texels = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Map Texels"),
+            size: u64::from(SIZE * SIZE * SIZE * TEXEL_SIZE),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Map Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let write_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shadow Write Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let write_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shadow Write Bind Group"),
+            layout: &write_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: texels.as_entire_binding(),
+            }],
+        });
+
+        let read_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shadow Read Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let read_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shadow Read Bind Group"),
+            layout: &read_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let defines: &[&str] = if use_bvh { &["BVH"] } else { &[] };
+        let source = crate::shader::preprocess("shadow.wgsl", defines)?;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[frame_layout, &write_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache,
+        });
+
+        Ok(Self {
+            pipeline,
+            texels,
+            texture,
+            write_bind_group,
+            read_layout,
+            read_bind_group,
+            stale: Cell::new(true),
+            in_use: false,
+        })
+    }
+
+    /// Layout of the bind group `bind` sets for the raymarch
+    pub fn read_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.read_layout
+    }
+
+    /// Recompute the map before it is next used
+    pub fn invalidate(&self) {
+        self.stale.set(true);
+    }
+
+    /// Whether the raymarch reads the map at all, i.e. anything scatters
+    pub fn set_in_use(&mut self, in_use: bool) {
+        self.in_use = in_use;
+    }
+
+    /// Recompute the map if it is stale and in use, in a pass of its own
+    /// before the raymarch's. `frame_bind_group` is any view's bind group
+    /// 0; the map is the same for all of them.
+    pub fn update(&self, encoder: &mut wgpu::CommandEncoder, frame_bind_group: &wgpu::BindGroup) {
+        if !(self.stale.get() && self.in_use) {
+            return;
+        }
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Shadow Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, frame_bind_group, &[]);
+            pass.set_bind_group(1, &self.write_bind_group, &[]);
+            let groups = SIZE.div_ceil(WORKGROUP_SIZE);
+            pass.dispatch_workgroups(groups, groups, groups);
+        }
+        encoder.copy_buffer_to_texture(
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.texels,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(SIZE * TEXEL_SIZE),
+                    rows_per_image: Some(SIZE),
+                },
+            },
+            self.texture.as_image_copy(),
+            self.texture.size(),
+        );
+        self.stale.set(false);
+    }
+
+    /// Set the map for the raymarch in bind group `group`
+    pub fn bind(&self, pass: &mut wgpu::ComputePass, group: u32) {
+        pass.set_bind_group(group, &self.read_bind_group, &[]);
+    }
+}
@@ -0,0 +1,234 @@
+//! `vendek render-still`: draw the viewer's world once, offscreen and at any
+//! resolution, for posters and figures. The view holds still while the
+//! raymarch takes sample after sample per pixel, as the viewer's does when
+//! the camera rests, until the requested count; then the denoise and
+//! display passes run once and the image is written as a PNG, or, for an
+//! `.exr` path, the HDR image before tonemapping as OpenEXR. The world,
+//! parameters and camera are the viewer's: `vendek.toml`, plus whatever the
+//! last run saved.
+//!
+//! The EXR image has the raymarch's resolution, which `supersample` raises
+//! above the requested one, and needs the compute path. The device is
+//! opened with the adapter's largest storage buffers so accumulation fits
+//! at print resolutions; where it still doesn't, or on the fragment path,
+//! one sample is drawn and a warning logged.
+
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::builder::VendekBuilder;
+use crate::camera::{Camera, CameraPose};
+use crate::capture;
+use crate::config::Config;
+use crate::gpu::{AdapterOptions, GpuError, GpuState};
+use crate::params::RuntimeParams;
+use crate::preset::Preset;
+use crate::settings::Settings;
+use crate::world::HoneycombWorld;
+
+#[derive(Clone, Debug)]
+pub struct StillOptions {
+    /// Samples per pixel to accumulate
+    pub samples: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Seconds into the world's animation
+    pub time: f32,
+    /// Where the image is written; an `.exr` extension writes the HDR
+    /// image rather than a PNG
+    pub output: PathBuf,
+}
+
+impl Default for StillOptions {
+    fn default() -> Self {
+        Self {
+            samples: 256,
+            width: 3840,
+            height: 2160,
+            time: 0.0,
+            output: PathBuf::from("vendek-still.png"),
+        }
+    }
+}
+
+/// Why a still could not be rendered
+#[derive(Debug)]
+pub enum StillError {
+    Gpu(GpuError),
+    Png(png::EncodingError),
+    Exr(std::io::Error),
+    /// EXR output needs the compute path's HDR image
+    NoHdr,
+}
+
+impl fmt::Display for StillError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StillError::Gpu(e) => e.fmt(f),
+            StillError::Png(e) => write!(f, "Could not write the image: {}", e),
+            StillError::Exr(e) => write!(f, "Could not write the image: {}", e),
+            StillError::NoHdr => write!(
+                f,
+                "EXR output needs the compute path, which this adapter doesn't support"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StillError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StillError::Gpu(e) => Some(e),
+            StillError::Png(e) => Some(e),
+            StillError::Exr(e) => Some(e),
+            StillError::NoHdr => None,
+        }
+    }
+}
+
+impl From<GpuError> for StillError {
+    fn from(e: GpuError) -> Self {
+        StillError::Gpu(e)
+    }
+}
+
+impl From<png::EncodingError> for StillError {
+    fn from(e: png::EncodingError) -> Self {
+        StillError::Png(e)
+    }
+}
+
+impl From<std::io::Error> for StillError {
+    fn from(e: std::io::Error) -> Self {
+        StillError::Exr(e)
+    }
+}
+
+/// The viewer's world, parameters and camera, set up offscreen
+pub struct ViewerScene {
+    pub config: Config,
+    pub gpu: GpuState,
+    pub camera: Camera,
+    pub world: HoneycombWorld,
+    /// What the GPU was given to start with
+    pub params: RuntimeParams,
+}
+
+impl ViewerScene {
+    /// Generate the world `vendek.toml` describes, with `preset` and
+    /// `stream` overriding it as on the viewer's command line, and set it
+    /// up to render `width`×`height` frames with the parameters and camera
+    /// the viewer last saved
+    pub fn open(
+        adapter: &AdapterOptions,
+        preset: Option<Preset>,
+        stream: Option<u32>,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, GpuError> {
+        Self::open_with(adapter, preset, stream, width, height, |builder| builder)
+    }
+
+    /// `open`, with `world` adjusting how the world is generated
+    pub fn open_with(
+        adapter: &AdapterOptions,
+        preset: Option<Preset>,
+        stream: Option<u32>,
+        width: u32,
+        height: u32,
+        world: impl FnOnce(VendekBuilder) -> VendekBuilder,
+    ) -> Result<Self, GpuError> {
+        let mut config = Config::load();
+        if preset.is_some() {
+            config.world.preset = preset;
+        }
+        if stream.is_some() {
+            config.world.stream = stream;
+        }
+        let settings = Settings::load();
+        let builder = world(config.world_builder());
+        let pose = settings
+            .camera
+            .map_or(builder.camera_pose(), CameraPose::from);
+        let (world, _) = builder.generate_world_around(pose.focus);
+
+        let mut gpu = GpuState::new_headless(&world, adapter, width, height)?;
+        if let Some(workgroup) = config.render.workgroup {
+            gpu.set_workgroup_size(workgroup);
+        }
+        if let Some(palette) = &config.palette {
+            gpu.set_custom_gradient(palette.stops.clone());
+        }
+        for transfer in &config.transfer_functions {
+            gpu.set_transfer_function(transfer.phase, Some(transfer.points.clone()));
+        }
+        let mut params = builder.initial_params();
+        config.apply_params(&mut params);
+        settings.restore_params(&mut params);
+        // Offscreen renders take as long as they need at full quality
+        params.low_power = false;
+        gpu.set_params(&params);
+
+        let mut camera = Camera::new();
+        camera.jump_to(pose.focus, pose.yaw, pose.pitch, pose.distance);
+        Ok(Self {
+            config,
+            gpu,
+            camera,
+            world,
+            params,
+        })
+    }
+}
+
+pub fn run(
+    adapter: AdapterOptions,
+    preset: Option<Preset>,
+    stream: Option<u32>,
+    options: StillOptions,
+) -> Result<(), StillError> {
+    let exr = options
+        .output
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("exr"));
+    let ViewerScene {
+        mut gpu, camera, ..
+    } = ViewerScene::open(&adapter, preset, stream, options.width, options.height)?;
+    // Only the compute path dispatches, and only it has an HDR image
+    if exr && gpu.dispatch_size().is_none() {
+        return Err(StillError::NoHdr);
+    }
+    if !gpu.accumulates() && options.samples > 1 {
+        tracing::warn!(
+            "Samples don't accumulate at {}x{} on this adapter; drawing one",
+            options.width,
+            options.height
+        );
+    }
+
+    let start = Instant::now();
+    let rgba = gpu.render_still(&camera, options.time, options.samples, |taken| {
+        eprint!("\rSample {}/{}", taken, options.samples);
+        let _ = std::io::stderr().flush();
+    });
+    eprintln!();
+    let (width, height) = if exr {
+        let (size, pixels) = gpu.capture_hdr().ok_or(StillError::NoHdr)?;
+        capture::save_exr(&options.output, size.width, size.height, &pixels)?;
+        (size.width, size.height)
+    } else {
+        capture::save_png(&options.output, options.width, options.height, &rgba)?;
+        (options.width, options.height)
+    };
+    println!(
+        "Wrote {} ({}x{}, {} samples per pixel, {:.1}s)",
+        options.output.display(),
+        width,
+        height,
+        gpu.accumulated_samples(),
+        start.elapsed().as_secs_f32()
+    );
+    Ok(())
+}
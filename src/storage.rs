@@ -0,0 +1,85 @@
+//! Settings kept in the browser's localStorage with the `persist` option, so
+//! returning visitors find the viewer as they left it: the parameters, and
+//! the camera bookmarks. Both are written in the same `name=value` form as
+//! URLs (see `url`), and only parameters that differ from their defaults are
+//! stored. Every viewer on a site shares them.
+
+use crate::camera::CameraPose;
+use crate::params::RuntimeParams;
+use crate::url::{self, UrlView};
+
+const PARAMS_KEY: &str = "vendek.params";
+const BOOKMARKS_KEY: &str = "vendek.bookmarks";
+/// Least time between writes while parameters keep changing, as they do
+/// while a slider is dragged
+const SAVE_INTERVAL: web_time::Duration = web_time::Duration::from_secs(1);
+
+/// None where storage is disabled, as in some private browsing modes
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+fn read(key: &str) -> Option<String> {
+    storage()?.get_item(key).ok()?
+}
+
+fn write(key: &str, value: &str) {
+    if let Some(storage) = storage() {
+        if storage.set_item(key, value).is_err() {
+            tracing::warn!("Could not save {} to localStorage", key);
+        }
+    }
+}
+
+/// Parameters stored on an earlier visit
+pub fn load_params() -> Vec<(&'static str, f64)> {
+    read(PARAMS_KEY)
+        .map(|text| UrlView::parse(&text).params)
+        .unwrap_or_default()
+}
+
+/// Bookmarks stored on an earlier visit, in the order they were made
+pub fn load_bookmarks() -> Vec<CameraPose> {
+    read(BOOKMARKS_KEY)
+        .map(|text| text.split(';').filter_map(url::parse_camera).collect())
+        .unwrap_or_default()
+}
+
+pub fn save_bookmarks(bookmarks: &[CameraPose]) {
+    let text: Vec<String> = bookmarks.iter().map(url::format_camera).collect();
+    write(BOOKMARKS_KEY, &text.join(";"));
+}
+
+/// Stores a viewer's parameters as they change
+pub struct ParamStorage {
+    /// What storage holds
+    written: String,
+    last_save: Option<web_time::Instant>,
+}
+
+impl ParamStorage {
+    pub fn new() -> Self {
+        Self {
+            written: read(PARAMS_KEY).unwrap_or_default(),
+            last_save: None,
+        }
+    }
+
+    /// Store `params` if they have changed, at most once a second
+    pub fn update(&mut self, params: &RuntimeParams) {
+        let now = web_time::Instant::now();
+        if self
+            .last_save
+            .is_some_and(|last| now - last < SAVE_INTERVAL)
+        {
+            return;
+        }
+        self.last_save = Some(now);
+
+        let text = url::format_params(params, true);
+        if text != self.written {
+            write(PARAMS_KEY, &text);
+            self.written = text;
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Worlds larger than one box, generated in chunks around the camera. The
+//! builder's volume becomes one chunk, tiling space, and the chunks within a
+//! radius of the one the camera's focus is in are loaded. There are always
+//! as many of them, so the cell count, and everything on the GPU sized by
+//! it, stays as it was. When the focus crosses into another chunk, the
+//! chunks left behind give their share of the cell array to those coming
+//! into range, and only those are generated.
+//!
+//! A chunk comes back exactly as it was generated, whatever the automaton
+//! did to it while loaded, and the simulations start over whenever the
+//! loaded chunks change. Streamed worlds have no emitters.
+
+use glam::{IVec3, Vec3};
+
+use crate::world::{Bounds, HoneycombWorld, WorldStyle};
+
+/// Chunks a streamed world keeps loaded on each side of the center one, at
+/// most; two loads 125 chunks
+pub const MAX_STREAM_RADIUS: u32 = 2;
+/// How far the focus goes past the center chunk's faces before the loaded
+/// chunks move, as a share of the chunk size, so a focus resting on a face
+/// doesn't load chunks back and forth
+const HYSTERESIS: f32 = 0.25;
+
+/// Which chunks of a streamed world are loaded, and where their cells sit
+#[derive(Clone, Debug)]
+pub struct WorldStream {
+    /// Box of chunk zero; the others are moved by whole chunk sizes
+    chunk: Bounds,
+    cells_per_chunk: usize,
+    style: WorldStyle,
+    radius: i32,
+    /// Chunk the loaded ones are centered on
+    center: IVec3,
+    /// Chunk whose cells each run of `cells_per_chunk` cells holds
+    slots: Vec<IVec3>,
+}
+
+impl WorldStream {
+    /// Chunks of `chunk`'s size with `cells_per_chunk` cells each, in
+    /// `style`, the ones within `radius` of `focus` loaded
+    pub fn new(
+        chunk: Bounds,
+        cells_per_chunk: usize,
+        style: WorldStyle,
+        radius: u32,
+        focus: Vec3,
+    ) -> Self {
+        let radius = radius.min(MAX_STREAM_RADIUS) as i32;
+        let center = chunk_at(&chunk, focus);
+        Self {
+            chunk,
+            cells_per_chunk,
+            style,
+            radius,
+            center,
+            slots: window(center, radius).collect(),
+        }
+    }
+
+    /// The world with the loaded chunks
+    pub fn generate(&self, seed: u64, phase_count: usize) -> HoneycombWorld {
+        HoneycombWorld::generate_chunked(
+            seed,
+            self.cells_per_chunk,
+            phase_count,
+            self.chunk,
+            &self.slots,
+            &self.style,
+        )
+    }
+
+    /// Load the chunks around `focus` into `world` once it is far enough
+    /// out of the center chunk, regenerating the cells of the chunks that
+    /// came into range and moving the bounds with them. Returns whether the
+    /// world changed.
+    pub fn follow(&mut self, focus: Vec3, world: &mut HoneycombWorld) -> bool {
+        let local = (focus - self.chunk.tiled(self.center).min) / self.chunk.size();
+        let near = local.cmpge(Vec3::splat(-HYSTERESIS)).all()
+            && local.cmple(Vec3::splat(1.0 + HYSTERESIS)).all();
+        if near {
+            return false;
+        }
+
+        let _span = tracing::info_span!("stream_chunks").entered();
+        self.center = chunk_at(&self.chunk, focus);
+        let wanted: Vec<IVec3> = window(self.center, self.radius).collect();
+        let mut entering = wanted
+            .iter()
+            .filter(|coord| !self.slots.contains(coord))
+            .copied()
+            .collect::<Vec<_>>()
+            .into_iter();
+        let count = self.cells_per_chunk;
+        let mut loaded = 0;
+        for slot in 0..self.slots.len() {
+            if wanted.contains(&self.slots[slot]) {
+                continue;
+            }
+            // As many chunks come into range as leave it
+            let Some(coord) = entering.next() else {
+                break;
+            };
+            let cells = world.chunk_cells(self.chunk, coord, count, self.style.layout);
+            world.cells[slot * count..(slot + 1) * count].copy_from_slice(&cells);
+            self.slots[slot] = coord;
+            loaded += 1;
+        }
+        world.bounds = self.loaded_bounds();
+        world.reindex();
+        tracing::info!("Loaded {} chunks around chunk {}", loaded, self.center);
+        true
+    }
+
+    /// The box the loaded chunks fill
+    fn loaded_bounds(&self) -> Bounds {
+        let reach = IVec3::splat(self.radius);
+        Bounds {
+            min: self.chunk.tiled(self.center - reach).min,
+            max: self.chunk.tiled(self.center + reach).max,
+        }
+    }
+}
+
+/// The chunk `point` lies in
+fn chunk_at(chunk: &Bounds, point: Vec3) -> IVec3 {
+    ((point - chunk.min) / chunk.size()).floor().as_ivec3()
+}
+
+/// The chunks within `radius` of `center` along each axis
+fn window(center: IVec3, radius: i32) -> impl Iterator<Item = IVec3> {
+    (-radius..=radius).flat_map(move |z| {
+        (-radius..=radius)
+            .flat_map(move |y| (-radius..=radius).map(move |x| center + IVec3::new(x, y, z)))
+    })
+}
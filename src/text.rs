@@ -0,0 +1,543 @@
+//! Text over the finished frame: labels, readouts and captions at any size,
+//! natively and in the browser. Glyphs of the bundled DejaVu Sans Mono are
+//! rasterized on the CPU with `ab_glyph` the first time a size needs them,
+//! packed into a coverage atlas on the GPU, and drawn as quads in a pass of
+//! their own after the stats overlay. Like the overlay, text is queued for
+//! the next frame and dropped once drawn, so whatever should stay on screen
+//! is queued every frame.
+
+use std::collections::HashMap;
+
+use ab_glyph::{Font, FontRef, GlyphId, PxScale, ScaleFont};
+use wgpu::util::DeviceExt;
+
+const FONT: &[u8] = include_bytes!("fonts/DejaVuSansMono.ttf");
+/// Side of the square glyph atlas, in texels
+const ATLAS_SIZE: u32 = 1024;
+/// Texels kept clear around each glyph so filtering doesn't pick up its
+/// neighbours
+const ATLAS_PADDING: u32 = 1;
+/// Fully covered texels in the atlas's corner that backgrounds sample
+const SOLID: u32 = 3;
+/// Quads the vertex buffer starts with room for
+const INITIAL_QUADS: usize = 256;
+/// Space around the text inside its background, as a share of the size
+const BACKGROUND_PADDING: f32 = 0.3;
+
+/// Text to draw over the next frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    /// May span lines, separated by `\n`
+    pub text: String,
+    /// Where the anchor goes, in pixels from the surface's top-left corner
+    pub position: [f32; 2],
+    /// Point of the text's box placed at `position`, as shares of its width
+    /// and height: `[0.0, 0.0]` is the top-left corner, `[0.5, 1.0]` the
+    /// middle of the bottom edge
+    pub anchor: [f32; 2],
+    /// Line height in pixels
+    pub size: f32,
+    /// sRGB color and opacity
+    pub color: [f32; 4],
+    /// Box drawn behind the text, transparent for none
+    pub background: [f32; 4],
+}
+
+impl Label {
+    /// White 16-pixel text with its top-left corner at `position`
+    pub fn new(text: impl Into<String>, position: [f32; 2]) -> Self {
+        Self {
+            text: text.into(),
+            position,
+            anchor: [0.0, 0.0],
+            size: 16.0,
+            color: [1.0; 4],
+            background: [0.0; 4],
+        }
+    }
+
+    pub fn anchor(mut self, x: f32, y: f32) -> Self {
+        self.anchor = [x, y];
+        self
+    }
+
+    pub fn size(mut self, size: f32) -> Self {
+        self.size = size.max(1.0);
+        self
+    }
+
+    pub fn color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn background(mut self, color: [f32; 4]) -> Self {
+        self.background = color;
+        self
+    }
+}
+
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct TextVertex {
+    /// Clip space
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+/// Where a rasterized glyph sits in the atlas
+#[derive(Clone, Copy, Debug)]
+struct AtlasGlyph {
+    /// Left and top in the atlas, in texels
+    origin: [u32; 2],
+    width: u32,
+    height: u32,
+    /// Offset of its top-left corner from the pen position on the baseline
+    offset: [f32; 2],
+}
+
+/// Glyphs packed into rows, left to right and then top to bottom
+struct Atlas {
+    texture: wgpu::Texture,
+    /// By glyph and whole-pixel size; `None` for glyphs with no outline
+    glyphs: HashMap<(GlyphId, u32), Option<AtlasGlyph>>,
+    /// Top-left of the free space in the current row, and the row's height
+    cursor: [u32; 2],
+    row_height: u32,
+    /// Set when a glyph didn't fit and the atlas was emptied for it
+    cleared: bool,
+}
+
+impl Atlas {
+    fn clear(&mut self) {
+        self.glyphs.clear();
+        self.cursor = [SOLID + ATLAS_PADDING, 0];
+        self.row_height = SOLID + ATLAS_PADDING;
+        self.cleared = true;
+    }
+
+    /// The glyph at `size`, rasterizing it into the atlas first if needed
+    fn glyph(
+        &mut self,
+        queue: &wgpu::Queue,
+        font: &FontRef<'static>,
+        id: GlyphId,
+        size: u32,
+    ) -> Option<AtlasGlyph> {
+        if let Some(&glyph) = self.glyphs.get(&(id, size)) {
+            return glyph;
+        }
+        let outline = font.outline_glyph(id.with_scale(PxScale::from(size as f32)));
+        let Some(outline) = outline else {
+            self.glyphs.insert((id, size), None);
+            return None;
+        };
+        let bounds = outline.px_bounds();
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+        // Glyphs this large would crowd everything else out; they aren't drawn
+        if width > ATLAS_SIZE / 2 || height > ATLAS_SIZE / 2 {
+            return None;
+        }
+        if self.cursor[0] + width + ATLAS_PADDING > ATLAS_SIZE {
+            self.cursor = [0, self.cursor[1] + self.row_height];
+            self.row_height = 0;
+        }
+        if self.cursor[1] + height + ATLAS_PADDING > ATLAS_SIZE {
+            self.clear();
+            return self.glyph(queue, font, id, size);
+        }
+
+        let mut coverage = vec![0u8; (width * height) as usize];
+        outline.draw(|x, y, c| {
+            if x < width && y < height {
+                coverage[(y * width + x) as usize] = (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        });
+        let origin = self.cursor;
+        if width > 0 && height > 0 {
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &self.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: origin[0],
+                        y: origin[1],
+                        z: 0,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &coverage,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        self.cursor[0] += width + ATLAS_PADDING;
+        self.row_height = self.row_height.max(height + ATLAS_PADDING);
+
+        let glyph = AtlasGlyph {
+            origin,
+            width,
+            height,
+            offset: [bounds.min.x, bounds.min.y],
+        };
+        self.glyphs.insert((id, size), Some(glyph));
+        Some(glyph)
+    }
+}
+
+pub struct TextRenderer {
+    font: FontRef<'static>,
+    atlas: Atlas,
+    vertex_buffer: wgpu::Buffer,
+    /// Quads the vertex buffer has room for
+    capacity: usize,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    /// Whether the surface stores sRGB, so colors are given to it linear
+    srgb: bool,
+    /// Queued since the last frame was drawn
+    labels: Vec<Label>,
+    /// Vertices for the queued labels, made before the frame is drawn
+    vertex_count: u32,
+}
+
+impl TextRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        surface_format: wgpu::TextureFormat,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let font = FontRef::try_from_slice(FONT).expect("the bundled font is valid");
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Text Atlas"),
+            size: wgpu::Extent3d {
+                width: ATLAS_SIZE,
+                height: ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            texture.as_image_copy(),
+            &[u8::MAX; (SOLID * SOLID) as usize],
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(SOLID),
+                rows_per_image: Some(SOLID),
+            },
+            wgpu::Extent3d {
+                width: SOLID,
+                height: SOLID,
+                depth_or_array_layers: 1,
+            },
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Text Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Text Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Text Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Text Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/text.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Text Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Text Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: size_of::<TextVertex>() as u64,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                        2 => Float32x4
+                    ],
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache,
+        });
+
+        let mut atlas = Atlas {
+            texture,
+            glyphs: HashMap::new(),
+            cursor: [0; 2],
+            row_height: 0,
+            cleared: false,
+        };
+        atlas.clear();
+        Self {
+            font,
+            atlas,
+            vertex_buffer: Self::create_vertex_buffer(device, INITIAL_QUADS),
+            capacity: INITIAL_QUADS,
+            bind_group,
+            pipeline,
+            srgb: surface_format.is_srgb(),
+            labels: Vec::new(),
+            vertex_count: 0,
+        }
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, quads: usize) -> wgpu::Buffer {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Text Vertex Buffer"),
+            contents: &vec![0; quads * 6 * size_of::<TextVertex>()],
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        })
+    }
+
+    /// Draw `label` over the next frame
+    pub fn queue(&mut self, label: Label) {
+        self.labels.push(label);
+    }
+
+    /// Width and height in pixels of the box `label` takes up, without its
+    /// background
+    pub fn measure(&self, label: &Label) -> [f32; 2] {
+        let font = self.font.as_scaled(PxScale::from(pixel_size(label) as f32));
+        let width = label
+            .text
+            .lines()
+            .map(|line| line.chars().map(|c| font.h_advance(font.glyph_id(c))).sum())
+            .fold(0.0, f32::max);
+        let lines = label.text.lines().count().max(1) as f32;
+        [width, lines * (font.height() + font.line_gap())]
+    }
+
+    /// Lay the queued labels out for a `screen`-sized surface, rasterizing
+    /// glyphs no earlier frame needed
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        screen: winit::dpi::PhysicalSize<u32>,
+    ) {
+        self.atlas.cleared = false;
+        let mut vertices = self.layout(queue, screen);
+        // Glyphs placed before the atlas was emptied have moved; they are
+        // in it again after a second pass
+        if self.atlas.cleared {
+            vertices = self.layout(queue, screen);
+        }
+
+        let quads = vertices.len() / 6;
+        if quads > self.capacity {
+            self.capacity = quads.next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.capacity);
+        }
+        if !vertices.is_empty() {
+            queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        }
+        self.vertex_count = vertices.len() as u32;
+    }
+
+    fn layout(
+        &mut self,
+        queue: &wgpu::Queue,
+        screen: winit::dpi::PhysicalSize<u32>,
+    ) -> Vec<TextVertex> {
+        let to_clip = |[x, y]: [f32; 2]| {
+            [
+                x / screen.width.max(1) as f32 * 2.0 - 1.0,
+                1.0 - y / screen.height.max(1) as f32 * 2.0,
+            ]
+        };
+        let texel = 1.0 / ATLAS_SIZE as f32;
+        let mut vertices = Vec::new();
+        let mut quad =
+            |min: [f32; 2], max: [f32; 2], uv_min: [f32; 2], uv_max: [f32; 2], color: [f32; 4]| {
+                let corners = [
+                    (min, uv_min),
+                    ([max[0], min[1]], [uv_max[0], uv_min[1]]),
+                    ([min[0], max[1]], [uv_min[0], uv_max[1]]),
+                    ([min[0], max[1]], [uv_min[0], uv_max[1]]),
+                    ([max[0], min[1]], [uv_max[0], uv_min[1]]),
+                    (max, uv_max),
+                ];
+                vertices.extend(corners.map(|(position, uv)| TextVertex {
+                    position: to_clip(position),
+                    uv,
+                    color,
+                }));
+            };
+
+        for label in &self.labels {
+            let size = pixel_size(label);
+            let font = self.font.as_scaled(PxScale::from(size as f32));
+            let [width, height] = self.measure(label);
+            let left = (label.position[0] - width * label.anchor[0]).round();
+            let top = (label.position[1] - height * label.anchor[1]).round();
+
+            if label.background[3] > 0.0 {
+                let padding = size as f32 * BACKGROUND_PADDING;
+                let solid = (SOLID as f32 / 2.0) * texel;
+                quad(
+                    [left - padding, top - padding],
+                    [left + width + padding, top + height + padding],
+                    [solid; 2],
+                    [solid; 2],
+                    self.linear(label.background),
+                );
+            }
+
+            let color = self.linear(label.color);
+            let line_height = font.height() + font.line_gap();
+            for (row, line) in label.text.lines().enumerate() {
+                let baseline = (top + row as f32 * line_height + font.ascent()).round();
+                let mut pen = left;
+                let mut previous = None;
+                for c in line.chars() {
+                    let id = font.glyph_id(c);
+                    if let Some(previous) = previous {
+                        pen += font.kern(previous, id);
+                    }
+                    previous = Some(id);
+                    let advance = font.h_advance(id);
+                    let Some(glyph) = self.atlas.glyph(queue, &self.font, id, size) else {
+                        pen += advance;
+                        continue;
+                    };
+                    let min = [pen.round() + glyph.offset[0], baseline + glyph.offset[1]];
+                    let max = [min[0] + glyph.width as f32, min[1] + glyph.height as f32];
+                    let uv_min = [
+                        glyph.origin[0] as f32 * texel,
+                        glyph.origin[1] as f32 * texel,
+                    ];
+                    let uv_max = [
+                        uv_min[0] + glyph.width as f32 * texel,
+                        uv_min[1] + glyph.height as f32 * texel,
+                    ];
+                    quad(min, max, uv_min, uv_max, color);
+                    pen += advance;
+                }
+            }
+        }
+        vertices
+    }
+
+    /// `color` as the surface wants it
+    fn linear(&self, [r, g, b, a]: [f32; 4]) -> [f32; 4] {
+        if !self.srgb {
+            return [r, g, b, a];
+        }
+        let decode = |c: f32| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        [decode(r), decode(g), decode(b), a]
+    }
+
+    /// Draw the prepared labels into `target` over what is already there,
+    /// and drop them
+    pub fn encode(&mut self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        self.labels.clear();
+        let vertex_count = std::mem::take(&mut self.vertex_count);
+        if vertex_count == 0 {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Text Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..vertex_count, 0..1);
+    }
+}
+
+/// Whole-pixel size glyphs of `label` are rasterized at
+fn pixel_size(label: &Label) -> u32 {
+    label.size.round().max(1.0) as u32
+}
@@ -0,0 +1,319 @@
+//! Tile culling for the compute path. Before the raymarch, a prepass looks
+//! at each tile of every view and tests whether the rays through it can
+//! reach the volume's bounding box. Tiles that can go on a list in a storage
+//! buffer, along with the indirect dispatch size for them; the rest are
+//! filled with the background by the prepass, and marked empty in the
+//! denoiser's guide. The raymarch then dispatches
+//! indirectly, one workgroup per listed tile, so zoomed-out and wide views
+//! don't launch workgroups for empty sky.
+//!
+//! A tile is the raymarch's workgroup, 8×8, 16×8 or 16×16 pixels. Which is
+//! fastest depends on the adapter, so the viewer times each at startup;
+//! the sizes reach the shaders as the `TILE_WIDTH` and `TILE_HEIGHT`
+//! overrides.
+
+use std::fmt;
+
+use serde::Deserialize;
+
+/// Dispatch size and tile count at the front of a tile list, as
+/// `TileList` in `tiles.wgsl`
+const HEADER_SIZE: u64 = 16;
+/// Side of the prepass's workgroups, in tiles
+const CULL_WORKGROUP: u32 = 8;
+
+/// Raymarch workgroup size, and so the tile size, in pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum WorkgroupSize {
+    #[default]
+    #[serde(rename = "8x8")]
+    Size8x8,
+    #[serde(rename = "16x8")]
+    Size16x8,
+    #[serde(rename = "16x16")]
+    Size16x16,
+}
+
+impl WorkgroupSize {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub const ALL: [WorkgroupSize; 3] = [
+        WorkgroupSize::Size8x8,
+        WorkgroupSize::Size16x8,
+        WorkgroupSize::Size16x16,
+    ];
+
+    /// Width and height in pixels
+    pub fn extent(self) -> (u32, u32) {
+        match self {
+            WorkgroupSize::Size8x8 => (8, 8),
+            WorkgroupSize::Size16x8 => (16, 8),
+            WorkgroupSize::Size16x16 => (16, 16),
+        }
+    }
+
+    /// Whether a device with `limits` can run workgroups this large
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn fits(self, limits: &wgpu::Limits) -> bool {
+        let (width, height) = self.extent();
+        width <= limits.max_compute_workgroup_size_x
+            && height <= limits.max_compute_workgroup_size_y
+            && width * height <= limits.max_compute_invocations_per_workgroup
+    }
+
+    /// Values of the shaders' `TILE_WIDTH` and `TILE_HEIGHT` overrides
+    pub fn constants(self) -> [(&'static str, f64); 2] {
+        let (width, height) = self.extent();
+        [("TILE_WIDTH", width as f64), ("TILE_HEIGHT", height as f64)]
+    }
+}
+
+impl fmt::Display for WorkgroupSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (width, height) = self.extent();
+        write!(f, "{}x{}", width, height)
+    }
+}
+
+pub struct TileCulling {
+    pipeline: wgpu::ComputePipeline,
+    #[cfg(not(target_arch = "wasm32"))]
+    pipeline_layout: wgpu::PipelineLayout,
+    /// Preprocessed prepass shader, compiled again for each workgroup size
+    #[cfg(not(target_arch = "wasm32"))]
+    source: String,
+    bind_group_layout: wgpu::BindGroupLayout,
+    /// Layout of the raymarch's bind group with the tile list
+    list_layout: wgpu::BindGroupLayout,
+    views: Vec<ViewTiles>,
+    workgroup: WorkgroupSize,
+}
+
+/// Tile list of one view
+struct ViewTiles {
+    buffer: wgpu::Buffer,
+    /// For the prepass: the output and the list to write
+    cull_bind_group: wgpu::BindGroup,
+    /// For the raymarch: the list to read
+    list_bind_group: wgpu::BindGroup,
+}
+
+impl TileCulling {
+    /// The prepass shares the raymarch's bind group 0, with the frame
+    /// uniforms and parameters of each view. Tiles are `workgroup` large.
+    pub fn new(
+        device: &wgpu::Device,
+        frame_layout: &wgpu::BindGroupLayout,
+        cache: Option<&wgpu::PipelineCache>,
+        workgroup: WorkgroupSize,
+    ) -> Result<Self, crate::shader::ShaderError> {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Tile Cull Bind Group Layout"),
+            entries: &[
+                Self::target_entry(0),
+                Self::list_entry(1, false),
+                Self::target_entry(2),
+            ],
+        });
+        let list_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Tile List Bind Group Layout"),
+            entries: &[Self::list_entry(0, true)],
+        });
+
+        let source = crate::shader::preprocess("tile_cull.wgsl", &[])?;
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Tile Cull Pipeline Layout"),
+            bind_group_layouts: &[frame_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = Self::create_pipeline(device, &pipeline_layout, &source, cache, workgroup);
+
+        Ok(Self {
+            pipeline,
+            #[cfg(not(target_arch = "wasm32"))]
+            pipeline_layout,
+            #[cfg(not(target_arch = "wasm32"))]
+            source,
+            bind_group_layout,
+            list_layout,
+            views: Vec::new(),
+            workgroup,
+        })
+    }
+
+    /// The prepass for `workgroup`-sized tiles. Each gets a shader module of
+    /// its own, as the GL backend reuses programs by module and entry point
+    /// whatever the override values.
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        source: &str,
+        cache: Option<&wgpu::PipelineCache>,
+        workgroup: WorkgroupSize,
+    ) -> wgpu::ComputePipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Tile Cull Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(&format!("Tile Cull Pipeline ({})", workgroup)),
+            layout: Some(layout),
+            module: &shader,
+            entry_point: Some("main"),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &workgroup.constants(),
+                ..Default::default()
+            },
+            cache,
+        })
+    }
+
+    /// Cut the views into `workgroup`-sized tiles from the next `cull` on.
+    /// The lists have room for the tiles of any size.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_workgroup(
+        &mut self,
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        workgroup: WorkgroupSize,
+    ) {
+        if workgroup != self.workgroup {
+            self.pipeline = Self::create_pipeline(
+                device,
+                &self.pipeline_layout,
+                &self.source,
+                cache,
+                workgroup,
+            );
+            self.workgroup = workgroup;
+        }
+    }
+
+    /// The output or the guide, both written for tiles that can't see the
+    /// volume
+    fn target_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: wgpu::TextureFormat::Rgba16Float,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        }
+    }
+
+    fn list_entry(binding: u32, read_only: bool) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(HEADER_SIZE + 4),
+            },
+            count: None,
+        }
+    }
+
+    /// Layout of the bind group `bind_targets` creates for the raymarch
+    pub fn list_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.list_layout
+    }
+
+    /// Create tile lists for `views` views of an output `size` pixels large,
+    /// each with room for every tile of the output at the smallest tile
+    /// size. `guide` is the denoiser's, the output's size.
+    pub fn bind_targets(
+        &mut self,
+        device: &wgpu::Device,
+        output: &wgpu::TextureView,
+        guide: &wgpu::TextureView,
+        size: wgpu::Extent3d,
+        views: usize,
+    ) {
+        let (tile_width, tile_height) = WorkgroupSize::Size8x8.extent();
+        let tiles =
+            size.width.div_ceil(tile_width) as u64 * size.height.div_ceil(tile_height) as u64;
+        self.views = (0..views)
+            .map(|_| {
+                let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Tile List"),
+                    size: HEADER_SIZE + tiles * 4,
+                    usage: wgpu::BufferUsages::STORAGE
+                        | wgpu::BufferUsages::INDIRECT
+                        | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let cull_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Tile Cull Bind Group"),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(output),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(guide),
+                        },
+                    ],
+                });
+                let list_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Tile List Bind Group"),
+                    layout: &self.list_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                });
+                ViewTiles {
+                    buffer,
+                    cull_bind_group,
+                    list_bind_group,
+                }
+            })
+            .collect();
+    }
+
+    /// Empty the tile lists; outside any pass, before `cull`
+    pub fn clear(&self, encoder: &mut wgpu::CommandEncoder) {
+        for view in &self.views {
+            encoder.clear_buffer(&view.buffer, 0, Some(HEADER_SIZE));
+        }
+    }
+
+    /// List the tiles of view `index`, `width`×`height` pixels, that can
+    /// see the volume, and fill the others with the background.
+    /// `frame_bind_group` is the view's bind group 0.
+    pub fn cull(
+        &self,
+        pass: &mut wgpu::ComputePass,
+        index: usize,
+        frame_bind_group: &wgpu::BindGroup,
+        width: u32,
+        height: u32,
+    ) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, frame_bind_group, &[]);
+        pass.set_bind_group(1, &self.views[index].cull_bind_group, &[]);
+        let (tile_width, tile_height) = self.workgroup.extent();
+        pass.dispatch_workgroups(
+            width.div_ceil(tile_width).div_ceil(CULL_WORKGROUP),
+            height.div_ceil(tile_height).div_ceil(CULL_WORKGROUP),
+            1,
+        );
+    }
+
+    /// Dispatch the pipeline set on `pass` once per tile `cull` listed for
+    /// view `index`, with the list in bind group `group`
+    pub fn dispatch(&self, pass: &mut wgpu::ComputePass, index: usize, group: u32) {
+        let view = &self.views[index];
+        pass.set_bind_group(group, &view.list_bind_group, &[]);
+        pass.dispatch_workgroups_indirect(&view.buffer, 0);
+    }
+}
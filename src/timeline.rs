@@ -0,0 +1,251 @@
+//! Keyframe timelines: recorded animation for repeatable camera moves and
+//! parameter changes, where frame scripts are for programmed motion. A
+//! timeline is a TOML file of tracks, each a list of keys that set one value
+//! at one moment:
+//!
+//! ```toml
+//! # a slow half orbit while the membranes brighten
+//! loop = true
+//!
+//! [[track]]
+//! name = "camera.yaw"
+//! keys = [
+//!     { time = 0, value = 0.3 },
+//!     { time = 12, value = 3.44, ease = "in-out" },
+//! ]
+//!
+//! [[track]]
+//! name = "membraneGlow"
+//! keys = [{ time = 0, value = 0.2 }, { time = 6, value = 1.2, ease = "out" }]
+//! ```
+//!
+//! Tracks are named as scripts name values: a runtime parameter by its
+//! JavaScript name, `camera.yaw`, `camera.pitch`, `camera.distance`, or the
+//! focus's `camera.x`, `camera.y` and `camera.z`. A key's `ease` shapes the
+//! way in to it from the key before: `linear` (the default), `in`, `out` and
+//! `in-out` for cubic easing, or `hold` to keep the earlier value until the
+//! key's time. Before its first key a track holds the first value, and after
+//! its last key the last. The timeline ends with its last key, or at
+//! `length` seconds if that is given, and starts over if `loop` is set.
+//!
+//! Camera tracks place the camera exactly rather than gliding, so the
+//! mouse can't move what a playing timeline tracks. Timelines stop with the
+//! clock while paused, and run before any frame script, which can build on
+//! the values they set.
+
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::camera::{CameraPose, PoseField};
+use crate::params::RuntimeParams;
+
+/// How a key is approached from the one before it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Ease {
+    #[default]
+    Linear,
+    In,
+    Out,
+    InOut,
+    Hold,
+}
+
+impl Ease {
+    /// Progress through a segment, `0..=1`, shaped by the easing
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            Ease::Linear => t,
+            Ease::In => t * t * t,
+            Ease::Out => 1.0 - (1.0 - t).powi(3),
+            Ease::InOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (2.0 - 2.0 * t).powi(3) / 2.0
+                }
+            }
+            Ease::Hold => 0.0,
+        }
+    }
+}
+
+/// Why a timeline was rejected
+#[derive(Debug)]
+pub enum TimelineError {
+    Toml(toml::de::Error),
+    /// A track named something that isn't a parameter or camera value
+    UnknownTarget(String),
+    /// A track without keys
+    NoKeys(String),
+    /// A key at a negative or non-finite time, or with a non-finite value
+    BadKey(String),
+}
+
+impl fmt::Display for TimelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimelineError::Toml(e) => write!(f, "invalid timeline: {}", e),
+            TimelineError::UnknownTarget(name) => write!(
+                f,
+                "timeline track `{}` is not a parameter or camera value",
+                name
+            ),
+            TimelineError::NoKeys(name) => write!(f, "timeline track `{}` has no keys", name),
+            TimelineError::BadKey(name) => write!(
+                f,
+                "timeline track `{}` has a key with a negative or non-finite time or value",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TimelineError {}
+
+impl From<toml::de::Error> for TimelineError {
+    fn from(e: toml::de::Error) -> Self {
+        TimelineError::Toml(e)
+    }
+}
+
+/// A parsed timeline, ready to play
+#[derive(Clone, Debug)]
+pub struct Timeline {
+    tracks: Vec<Track>,
+    length: f32,
+    looping: bool,
+}
+
+#[derive(Clone, Debug)]
+struct Track {
+    target: Target,
+    /// In time order
+    keys: Vec<Key>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Target {
+    Param(&'static str),
+    Camera(PoseField),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Key {
+    time: f32,
+    value: f64,
+    #[serde(default)]
+    ease: Ease,
+}
+
+/// The file as written
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TimelineFile {
+    length: Option<f32>,
+    #[serde(default, rename = "loop")]
+    looping: bool,
+    #[serde(default, rename = "track")]
+    tracks: Vec<TrackFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TrackFile {
+    name: String,
+    keys: Vec<Key>,
+}
+
+impl Timeline {
+    pub fn parse(source: &str) -> Result<Self, TimelineError> {
+        let file: TimelineFile = toml::from_str(source)?;
+        let mut tracks = Vec::with_capacity(file.tracks.len());
+        for TrackFile { name, mut keys } in file.tracks {
+            let target = PoseField::from_name(&name)
+                .map(Target::Camera)
+                .or_else(|| {
+                    RuntimeParams::NAMES
+                        .iter()
+                        .find(|param| **param == name)
+                        .map(|param| Target::Param(param))
+                })
+                .ok_or_else(|| TimelineError::UnknownTarget(name.clone()))?;
+            if keys.is_empty() {
+                return Err(TimelineError::NoKeys(name));
+            }
+            if keys
+                .iter()
+                .any(|key| key.time.is_sign_negative() || !key.time.is_finite())
+                || keys.iter().any(|key| !key.value.is_finite())
+            {
+                return Err(TimelineError::BadKey(name));
+            }
+            // Stable, so keys written at the same time keep their order and
+            // the value jumps there
+            keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+            tracks.push(Track { target, keys });
+        }
+
+        let last_key = tracks
+            .iter()
+            .filter_map(|track| track.keys.last())
+            .map(|key| key.time)
+            .fold(0.0, f32::max);
+        let length = file
+            .length
+            .filter(|length| length.is_finite() && *length > 0.0)
+            .unwrap_or(last_key);
+        Ok(Self {
+            tracks,
+            length,
+            looping: file.looping,
+        })
+    }
+
+    /// Seconds from start to end
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.looping
+    }
+
+    /// Whether any track drives the camera
+    pub fn moves_camera(&self) -> bool {
+        self.tracks
+            .iter()
+            .any(|track| matches!(track.target, Target::Camera(_)))
+    }
+
+    /// Set every value a track drives to where it is `time` seconds in
+    pub fn apply(&self, time: f32, params: &mut RuntimeParams, camera: &mut CameraPose) {
+        for track in &self.tracks {
+            let value = track.value_at(time);
+            match track.target {
+                Target::Param(name) => {
+                    let _ = params.set(name, value);
+                }
+                Target::Camera(field) => camera.set(field, value as f32),
+            }
+        }
+    }
+}
+
+impl Track {
+    fn value_at(&self, time: f32) -> f64 {
+        let next = self.keys.partition_point(|key| key.time <= time);
+        if next == 0 {
+            return self.keys[0].value;
+        }
+        let Some(to) = self.keys.get(next) else {
+            return self.keys[next - 1].value;
+        };
+        let from = self.keys[next - 1];
+        let progress = (time - from.time) / (to.time - from.time);
+        let eased = to.ease.apply(progress.clamp(0.0, 1.0)) as f64;
+        from.value + (to.value - from.value) * eased
+    }
+}
@@ -0,0 +1,89 @@
+//! Chrome trace export for performance work. With `--trace <FILE>` every
+//! span the viewer opens (world generation, GPU startup, and each frame's
+//! update, simulation and render phases) is written to FILE in the Trace
+//! Event Format, which `chrome://tracing`, Perfetto and Speedscope open.
+//! Log messages appear in the trace as instant events. Logging to the
+//! terminal carries on as before, since `tracing` also forwards everything
+//! to `log`. The file is written by `tracing-chrome`.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::Event;
+use tracing_chrome::{ChromeLayerBuilder, EventOrSpan, FlushGuard};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::prelude::*;
+
+/// Why tracing couldn't start
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+    /// Another subscriber was installed first
+    AlreadySet,
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::Io(e) => write!(f, "could not create trace file: {}", e),
+            TraceError::AlreadySet => write!(f, "a trace is already being recorded"),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl From<io::Error> for TraceError {
+    fn from(e: io::Error) -> Self {
+        TraceError::Io(e)
+    }
+}
+
+/// Finishes the trace file when dropped
+pub type TraceGuard = FlushGuard;
+
+/// Record a trace to `path` until the returned guard is dropped, which
+/// finishes the file
+pub fn record(path: &Path) -> Result<TraceGuard, TraceError> {
+    // Created here rather than by the builder, which panics on failure
+    let file = BufWriter::new(File::create(path)?);
+    let (layer, guard) = ChromeLayerBuilder::new()
+        .writer(file)
+        .include_args(true)
+        .name_fn(Box::new(|event_or_span| match event_or_span {
+            EventOrSpan::Event(event) => message(event),
+            EventOrSpan::Span(span) => span.name().to_string(),
+        }))
+        .build();
+    // The viewer's own spans and events only, leaving out its dependencies'
+    let filter = Targets::new().with_target("vendek", LevelFilter::TRACE);
+    // Not `try_init`, which would also route `log` into `tracing`, the
+    // opposite of the way the terminal logging goes
+    let subscriber = tracing_subscriber::registry().with(layer.with_filter(filter));
+    tracing::subscriber::set_global_default(subscriber).map_err(|_| TraceError::AlreadySet)?;
+    tracing::info!("Recording a trace to {}", path.display());
+    Ok(guard)
+}
+
+/// A log message's text, which names its instant event in the trace
+fn message(event: &Event<'_>) -> String {
+    struct Message(Option<String>);
+
+    impl Visit for Message {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if field.name() == "message" {
+                self.0 = Some(format!("{:?}", value));
+            }
+        }
+    }
+
+    let mut message = Message(None);
+    event.record(&mut message);
+    message
+        .0
+        .unwrap_or_else(|| event.metadata().name().to_string())
+}
@@ -0,0 +1,156 @@
+//! Transfer functions: for each phase, the tint and opacity of the volume as
+//! a function of a sample's density. Density rises from 0 at a cell's center
+//! to 1 at its membranes (the distance to the nearest cell center over the
+//! distance to the second nearest), so a function can hide cell interiors,
+//! thin out the walls, or anything in between.
+//!
+//! Functions are baked into a small texture, one row of `SAMPLES` texels per
+//! phase, which the raymarch shader interpolates. Until a phase is given its
+//! own function it keeps the fixed mapping: untinted, at the phase's density.
+
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::palette::ColorConfig;
+use crate::world::VendekPhase;
+
+/// Texels per phase in the baked texture
+pub const SAMPLES: u32 = 64;
+
+/// Format of the baked texture: tint in rgb, opacity in a. Opacities are
+/// small, which 8 bits can't resolve.
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+/// A control point of a transfer function
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransferPoint {
+    /// Density in 0..=1
+    pub at: f32,
+    /// Multiplied with the phase's palette color
+    pub color: [f32; 3],
+    /// Absorption per world unit, scaled by the `density` parameter
+    pub opacity: f32,
+}
+
+/// Piecewise linear map from density to tint and opacity. Deserializes from a
+/// list of `{ at, color, opacity }` tables, where `color` is optional and
+/// written like a palette stop's.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "Vec<PointConfig>")]
+pub struct TransferFunction {
+    /// Sorted by density
+    points: Vec<TransferPoint>,
+}
+
+#[derive(Deserialize)]
+struct PointConfig {
+    at: f32,
+    color: Option<ColorConfig>,
+    opacity: f32,
+}
+
+/// Why a transfer function definition was rejected
+#[derive(Debug)]
+pub struct InvalidTransferFunction(pub String);
+
+impl fmt::Display for InvalidTransferFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid transfer function: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidTransferFunction {}
+
+impl TransferFunction {
+    pub fn new(mut points: Vec<TransferPoint>) -> Result<Self, InvalidTransferFunction> {
+        if points.is_empty() {
+            return Err(InvalidTransferFunction(
+                "needs at least one point".to_string(),
+            ));
+        }
+        for point in &mut points {
+            if !(0.0..=1.0).contains(&point.at) {
+                return Err(InvalidTransferFunction(format!(
+                    "point position {} is outside 0..1",
+                    point.at
+                )));
+            }
+            if point.opacity.is_nan() || point.opacity < 0.0 {
+                return Err(InvalidTransferFunction(format!(
+                    "opacity {} is not 0 or more",
+                    point.opacity
+                )));
+            }
+            point.color = point.color.map(|c| c.clamp(0.0, 1.0));
+        }
+        points.sort_by(|a, b| a.at.total_cmp(&b.at));
+        Ok(Self { points })
+    }
+
+    /// The fixed mapping: untinted, at `opacity` for every density
+    pub fn constant(opacity: f32) -> Self {
+        Self {
+            points: vec![TransferPoint {
+                at: 0.0,
+                color: [1.0; 3],
+                opacity,
+            }],
+        }
+    }
+
+    /// The default for `phase`: constant at its own density
+    pub fn for_phase(phase: &VendekPhase) -> Self {
+        Self::constant(phase.color_density.w)
+    }
+
+    /// Tint and opacity at `density`, clamped to the first and last points
+    pub fn sample(&self, density: f32) -> [f32; 4] {
+        let value = |point: &TransferPoint| {
+            let [r, g, b] = point.color;
+            [r, g, b, point.opacity]
+        };
+        let next = self.points.partition_point(|point| point.at <= density);
+        if next == 0 {
+            return value(&self.points[0]);
+        }
+        if next == self.points.len() {
+            return value(&self.points[next - 1]);
+        }
+        let (a, b) = (&self.points[next - 1], &self.points[next]);
+        let f = (density - a.at) / (b.at - a.at);
+        let (a, b) = (value(a), value(b));
+        [0, 1, 2, 3].map(|c| a[c] + (b[c] - a[c]) * f)
+    }
+}
+
+impl TryFrom<Vec<PointConfig>> for TransferFunction {
+    type Error = InvalidTransferFunction;
+
+    fn try_from(points: Vec<PointConfig>) -> Result<Self, Self::Error> {
+        let points = points
+            .into_iter()
+            .map(|point| {
+                let color = match point.color {
+                    Some(color) => color.into_rgb().map_err(|e| InvalidTransferFunction(e.0))?,
+                    None => [1.0; 3],
+                };
+                Ok(TransferPoint {
+                    at: point.at,
+                    color,
+                    opacity: point.opacity,
+                })
+            })
+            .collect::<Result<_, InvalidTransferFunction>>()?;
+        Self::new(points)
+    }
+}
+
+/// Texels for the baked texture: `SAMPLES` per function, one row per phase
+pub fn bake(functions: &[TransferFunction]) -> Vec<[f32; 4]> {
+    let step = 1.0 / (SAMPLES - 1) as f32;
+    functions
+        .iter()
+        .flat_map(|function| (0..SAMPLES).map(move |i| function.sample(i as f32 * step)))
+        .collect()
+}
@@ -0,0 +1,103 @@
+//! Uniform structs shared with the shaders. Their layouts must match the
+//! declarations in `shaders/types.wgsl` field for field, padding included;
+//! `tests/layout.rs` checks them with naga.
+
+use glam::{Mat4, Vec3};
+
+use crate::params::MAX_CLIP_PLANES;
+use crate::world::{Emitter, MAX_EMITTERS};
+
+/// Camera and time for one view, in one uniform buffer per view
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct FrameUniforms {
+    pub view_proj: Mat4,
+    pub inv_view_proj: Mat4,
+    pub camera_position: Vec3,
+    pub time: f32,
+    /// Size of this view in pixels
+    pub resolution: [f32; 2],
+    pub near: f32,
+    pub far: f32,
+    /// Top-left pixel of this view in the render target
+    pub viewport_origin: [f32; 2],
+    /// Samples the compute path has accumulated for this view so far; 0
+    /// starts over
+    pub sample_index: u32,
+    /// Samples a pixel accumulates at most
+    pub sample_limit: u32,
+}
+
+/// The runtime parameters as the raymarch and isosurface shaders read them
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct RaymarchParams {
+    pub volume_min: Vec3,
+    pub _pad0: f32,
+    pub volume_max: Vec3,
+    pub _pad1: f32,
+    pub max_steps: u32,
+    pub step_size: f32,
+    pub membrane_thickness: f32,
+    pub membrane_glow: f32,
+    pub density_multiplier: f32,
+    pub enable_coupling: f32, // 1.0 = enabled, 0.0 = disabled
+    pub palette: u32,
+    pub clip_plane_count: u32,
+    /// Normal in xyz and distance from the origin in w; samples with
+    /// `dot(normal, p) > w` are cut away
+    pub clip_planes: [[f32; 4]; MAX_CLIP_PLANES],
+    /// `RenderMode::index`: whether the raymarch composites rasterized
+    /// geometry, or draws only the membranes
+    pub render_mode: u32,
+    /// Bit `i` set shows phase `i`
+    pub visible_phases: u32,
+    /// Membrane displacement by the ripple noise, in world units
+    pub ripple_amplitude: f32,
+    /// Time scale of the ripple noise
+    pub ripple_speed: f32,
+    /// Tint and thickening by the reaction–diffusion catalyst, 0 to 1
+    pub reaction_strength: f32,
+    /// Glow by the cell temperatures, 0 to 1
+    pub heat_strength: f32,
+    /// Rays stop once their transmittance falls below this
+    pub min_transmittance: f32,
+    /// Strength of the single-scattered light
+    pub scattering: f32,
+    /// Unit vector toward the light
+    pub light_direction: Vec3,
+    /// Brightness of the emitting cells
+    pub emission: f32,
+    /// `Background::index`: what rays that miss the volume show
+    pub background: u32,
+    /// Varies the starfield from world to world
+    pub background_seed: u32,
+    /// How fast steps lengthen and detail drops with distance
+    pub lod_falloff: f32,
+    /// Standard error of an accumulated pixel's luminance below which it
+    /// takes no more samples
+    pub refine_threshold: f32,
+    /// `DebugView::index`: what the raymarch draws instead of the image
+    pub debug_view: u32,
+    pub _pad2: [u32; 3],
+}
+
+/// The world's emitters in one small uniform buffer, the unused slots
+/// zeroed
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct EmitterLights {
+    pub count: u32,
+    pub _pad: [u32; 3],
+    pub emitters: [Emitter; MAX_EMITTERS],
+}
+
+impl EmitterLights {
+    pub fn new(emitters: &[Emitter]) -> Self {
+        let mut lights: Self = bytemuck::Zeroable::zeroed();
+        let count = emitters.len().min(MAX_EMITTERS);
+        lights.emitters[..count].copy_from_slice(&emitters[..count]);
+        lights.count = count as u32;
+        lights
+    }
+}
@@ -0,0 +1,295 @@
+//! Viewer settings in the page URL. The query string configures a viewer
+//! without any JavaScript, say in an iframe's `src`:
+//! `?preset=foam&seed=7&palette=viridis&density=1.5`. With the `syncHash`
+//! option the fragment follows the viewer as well, e.g.
+//! `#seed=7&camera=0,0,0,0.3,0.4,35&density=1.5&membraneGlow=0.8`: the world
+//! seed and, once changed, its cell and phase counts, the camera's focus, yaw, pitch and distance, and every parameter
+//! that differs from its default, so a link shares one view of one world.
+//! Quality settings that depend on the device viewing are left out of it.
+//! Both are read the same way; the fragment wins over the query string,
+//! which wins over options passed to `wasm_main`.
+//!
+//! Fragment updates replace the history entry rather than adding one, so
+//! the back button isn't filled with camera moves, and come a few times a
+//! second at most. A different link pasted into the address bar reloads
+//! the page, since a new seed needs a new world.
+
+use std::fmt::Write;
+
+use glam::Vec3;
+
+use crate::camera::CameraPose;
+use crate::palette::Palette;
+use crate::params::RuntimeParams;
+use crate::preset::Preset;
+use crate::web::{VendekViewer, ViewerOptions};
+
+/// Parameters a link doesn't carry, since they suit one device and not another
+const DEVICE_PARAMS: &[&str] = &["supersample", "targetFps", "maxFps", "lowPower"];
+/// Least time between URL updates, which browsers rate-limit
+const UPDATE_INTERVAL: web_time::Duration = web_time::Duration::from_millis(250);
+
+/// What a URL asks the viewer to show
+#[derive(Debug, Default)]
+pub struct UrlView {
+    pub seed: Option<u64>,
+    pub cells: Option<usize>,
+    pub phases: Option<usize>,
+    pub preset: Option<Preset>,
+    pub attract: Option<f32>,
+    pub camera: Option<CameraPose>,
+    pub params: Vec<(&'static str, f64)>,
+}
+
+impl UrlView {
+    /// Read `key=value` pairs separated by `&`. Pairs that aren't understood
+    /// are skipped, so a link from a newer version still opens.
+    pub fn parse(text: &str) -> Self {
+        let mut view = Self::default();
+        for pair in text.split('&') {
+            let Some((key, value)) = pair.split_once('=') else {
+                continue;
+            };
+            match key {
+                "seed" => view.seed = value.parse().ok().or(view.seed),
+                "cells" => view.cells = value.parse().ok().or(view.cells),
+                "phases" => view.phases = value.parse().ok().or(view.phases),
+                "preset" => view.preset = Preset::from_name(value).or(view.preset),
+                "attract" => view.attract = value.parse().ok().or(view.attract),
+                "camera" => view.camera = parse_camera(value).or(view.camera),
+                _ => {
+                    let name = RuntimeParams::NAMES.iter().find(|name| **name == key);
+                    let value = match value {
+                        "true" => Some(1.0),
+                        "false" => Some(0.0),
+                        _ => value
+                            .parse::<f64>()
+                            .ok()
+                            .filter(|value| value.is_finite())
+                            .or_else(|| {
+                                // Palettes by name, too
+                                Palette::from_name(value)
+                                    .filter(|_| key == "palette")
+                                    .map(|palette| palette.index() as f64)
+                            }),
+                    };
+                    if let (Some(name), Some(value)) = (name, value) {
+                        view.params.push((name, value));
+                    }
+                }
+            }
+        }
+        view
+    }
+
+    /// The fragment for a view, without the `#`. `cells` and `phases` are
+    /// left out when the viewer uses the default counts.
+    pub fn format(
+        seed: u64,
+        cells: Option<usize>,
+        phases: Option<usize>,
+        camera: &CameraPose,
+        params: &RuntimeParams,
+    ) -> String {
+        let mut text = format!("seed={}", seed);
+        if let Some(cells) = cells {
+            let _ = write!(text, "&cells={}", cells);
+        }
+        if let Some(phases) = phases {
+            let _ = write!(text, "&phases={}", phases);
+        }
+        let _ = write!(text, "&camera={}", format_camera(camera));
+        let params = format_params(params, false);
+        if !params.is_empty() {
+            let _ = write!(text, "&{}", params);
+        }
+        text
+    }
+
+    /// Start `viewer` at this view
+    pub fn apply(&self, options: &mut ViewerOptions, viewer: &VendekViewer) {
+        if self.seed.is_some() {
+            options.seed = self.seed;
+        }
+        if self.cells.is_some() {
+            options.cells = self.cells;
+        }
+        if self.phases.is_some() {
+            options.phases = self.phases;
+        }
+        if self.preset.is_some() {
+            options.preset = self.preset;
+        }
+        if self.attract.is_some() {
+            options.attract = self.attract;
+        }
+        if let Some(camera) = self.camera {
+            viewer.move_camera(camera);
+        }
+        for &(name, value) in &self.params {
+            let _ = viewer.set_param(name, value);
+        }
+    }
+}
+
+/// `name=value` pairs joined by `&` for every parameter that differs from
+/// its default, leaving out those that suit only this device unless
+/// `device` is set
+pub fn format_params(params: &RuntimeParams, device: bool) -> String {
+    let defaults = RuntimeParams::default();
+    let mut text = String::new();
+    for name in RuntimeParams::NAMES {
+        if !device && DEVICE_PARAMS.contains(name) {
+            continue;
+        }
+        let value = params.get(name).unwrap_or_default();
+        if defaults.get(name) != Some(value) {
+            if !text.is_empty() {
+                text.push('&');
+            }
+            let _ = write!(text, "{}={}", name, format_number(value));
+        }
+    }
+    text
+}
+
+/// `x,y,z,yaw,pitch,distance`
+pub fn format_camera(camera: &CameraPose) -> String {
+    [
+        camera.focus.x,
+        camera.focus.y,
+        camera.focus.z,
+        camera.yaw,
+        camera.pitch,
+        camera.distance,
+    ]
+    .map(|number| format_number(number as f64))
+    .join(",")
+}
+
+/// Reverses `format_camera`
+pub fn parse_camera(text: &str) -> Option<CameraPose> {
+    let numbers: Vec<f32> = text
+        .split(',')
+        .map(|number| {
+            number
+                .parse()
+                .ok()
+                .filter(|number: &f32| number.is_finite())
+        })
+        .collect::<Option<_>>()?;
+    let [x, y, z, yaw, pitch, distance] = numbers[..] else {
+        return None;
+    };
+    Some(CameraPose {
+        focus: Vec3::new(x, y, z),
+        yaw,
+        pitch,
+        distance,
+    })
+}
+
+/// Short enough for a link: four decimals at most, trailing zeros dropped
+fn format_number(value: f64) -> String {
+    let text = format!("{:.4}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".to_string(),
+        _ => text.to_string(),
+    }
+}
+
+/// The page's query string, without the `?`
+pub fn read_query() -> String {
+    web_sys::window()
+        .and_then(|window| window.location().search().ok())
+        .map(|search| search.trim_start_matches('?').to_string())
+        .unwrap_or_default()
+}
+
+/// The page's URL fragment, without the `#`
+pub fn read_hash() -> String {
+    web_sys::window()
+        .and_then(|window| window.location().hash().ok())
+        .map(|hash| hash.trim_start_matches('#').to_string())
+        .unwrap_or_default()
+}
+
+/// Keeps the page's URL fragment in step with a viewer
+pub struct HashSync {
+    seed: u64,
+    cells: Option<usize>,
+    phases: Option<usize>,
+    /// The fragment last written
+    written: String,
+    last_update: Option<web_time::Instant>,
+}
+
+impl HashSync {
+    /// Also reloads the page when the fragment is changed from outside, say
+    /// by pasting another link into the address bar, since a new seed needs
+    /// a new world
+    pub fn new(seed: u64, cells: Option<usize>, phases: Option<usize>) -> Self {
+        thread_local! {
+            static LISTENING: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+        }
+        if let Some(window) = web_sys::window() {
+            if !LISTENING.replace(true) {
+                let reload = wasm_bindgen::closure::Closure::<dyn FnMut()>::new(|| {
+                    if let Some(window) = web_sys::window() {
+                        let _ = window.location().reload();
+                    }
+                });
+                // Writes below go through replaceState, which doesn't fire
+                // hashchange, so only outside changes get here
+                window.set_onhashchange(Some(wasm_bindgen::JsCast::unchecked_ref(reload.as_ref())));
+                reload.forget();
+            }
+        }
+        Self {
+            seed,
+            cells,
+            phases,
+            written: read_hash(),
+            last_update: None,
+        }
+    }
+
+    /// Follow the viewer onto a world generated from another seed, and
+    /// with other cell and phase counts when `counts` is given
+    pub fn set_world(&mut self, seed: u64, counts: Option<(usize, usize)>) {
+        self.seed = seed;
+        if let Some((cells, phases)) = counts {
+            self.cells = Some(cells);
+            self.phases = Some(phases);
+        }
+    }
+
+    /// Write the view to the fragment if it has changed, at most four times
+    /// a second
+    pub fn update(&mut self, camera: &CameraPose, params: &RuntimeParams) {
+        let now = web_time::Instant::now();
+        if self
+            .last_update
+            .is_some_and(|last| now - last < UPDATE_INTERVAL)
+        {
+            return;
+        }
+        self.last_update = Some(now);
+
+        let hash = UrlView::format(self.seed, self.cells, self.phases, camera, params);
+        if hash == self.written {
+            return;
+        }
+        let Some(window) = web_sys::window() else {
+            return;
+        };
+        // Replacing rather than pushing keeps every camera move out of the
+        // back button's history
+        if let Ok(history) = window.history() {
+            let url = format!("#{}", hash);
+            let _ = history.replace_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some(&url));
+        }
+        self.written = hash;
+    }
+}
@@ -0,0 +1,180 @@
+//! Video recording for the native viewer. Frames are read back as they are
+//! drawn and piped as raw RGBA into an `ffmpeg` process, which encodes them
+//! straight into an MP4, WebM or animated WebP file, so a recording comes out ready to
+//! share rather than as a folder of screenshots. While recording, the
+//! animation advances by exactly one video frame per drawn frame, so the
+//! video plays at the speed the world moves however long each frame took
+//! to render and read back.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use serde::Deserialize;
+
+/// Container and codec of a recording
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoFormat {
+    /// H.264 in MP4, which plays nearly everywhere
+    #[default]
+    Mp4,
+    /// VP9 in WebM
+    Webm,
+    /// Animated WebP that loops forever, which embeds like an image
+    Webp,
+}
+
+impl VideoFormat {
+    /// The format a path's extension names, if any
+    pub fn for_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "mp4" => Some(VideoFormat::Mp4),
+            "webm" => Some(VideoFormat::Webm),
+            "webp" => Some(VideoFormat::Webp),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            VideoFormat::Mp4 => "mp4",
+            VideoFormat::Webm => "webm",
+            VideoFormat::Webp => "webp",
+        }
+    }
+
+    /// `ffmpeg` arguments choosing the encoder
+    fn codec_args(self) -> &'static [&'static str] {
+        match self {
+            VideoFormat::Mp4 => &[
+                "-c:v",
+                "libx264",
+                "-crf",
+                "18",
+                "-preset",
+                "slow",
+                "-movflags",
+                "+faststart",
+            ],
+            VideoFormat::Webm => &["-c:v", "libvpx-vp9", "-crf", "30", "-b:v", "0"],
+            VideoFormat::Webp => &["-c:v", "libwebp_anim", "-quality", "80", "-loop", "0"],
+        }
+    }
+}
+
+/// `[recording]` section
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RecordingConfig {
+    /// Frames per second of the video
+    pub fps: u32,
+    pub format: VideoFormat,
+    /// The `ffmpeg` executable, found on the `PATH` unless a path is given
+    pub ffmpeg: PathBuf,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            fps: 30,
+            format: VideoFormat::default(),
+            ffmpeg: PathBuf::from("ffmpeg"),
+        }
+    }
+}
+
+/// Timestamped output path for a recording in the working directory
+pub fn video_path(format: VideoFormat) -> PathBuf {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    PathBuf::from(format!("vendek-{}.{}", secs, format.extension()))
+}
+
+/// An `ffmpeg` process encoding frames of one size into a file
+pub struct VideoRecorder {
+    child: Child,
+    stdin: ChildStdin,
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    fps: u32,
+    frames: u32,
+}
+
+impl VideoRecorder {
+    /// Start encoding `width`×`height` frames into `path`
+    pub fn start(
+        config: &RecordingConfig,
+        path: &Path,
+        width: u32,
+        height: u32,
+    ) -> io::Result<Self> {
+        let fps = config.fps.max(1);
+        let mut child = Command::new(&config.ffmpeg)
+            .args(["-hide_banner", "-loglevel", "error", "-y"])
+            .args(["-f", "rawvideo", "-pixel_format", "rgba"])
+            .args(["-video_size", &format!("{}x{}", width, height)])
+            .args(["-framerate", &fps.to_string(), "-i", "-"])
+            // 4:2:0 chroma, which players expect, needs even sides
+            .args([
+                "-vf",
+                "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+                "-pix_fmt",
+                "yuv420p",
+            ])
+            .args(config.format.codec_args())
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        Ok(Self {
+            child,
+            stdin,
+            path: path.to_path_buf(),
+            width,
+            height,
+            fps,
+            frames: 0,
+        })
+    }
+
+    /// Size of the frames the video takes
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Seconds of animation each frame covers
+    pub fn frame_time(&self) -> f32 {
+        1.0 / self.fps as f32
+    }
+
+    /// Add a frame of tightly packed RGBA8 rows, top row first
+    pub fn push(&mut self, rgba: &[u8]) -> io::Result<()> {
+        self.stdin.write_all(rgba)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Close the stream and wait for `ffmpeg` to finish the file. Returns
+    /// where it was written and how many frames it holds.
+    pub fn finish(self) -> io::Result<(PathBuf, u32)> {
+        let Self {
+            mut child,
+            stdin,
+            path,
+            frames,
+            ..
+        } = self;
+        drop(stdin);
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("ffmpeg exited with {}", status)));
+        }
+        Ok((path, frames))
+    }
+}
@@ -0,0 +1,202 @@
+//! Voronoi cells as convex polyhedra, for finding which cells share a face.
+//! A cell starts as the world's box and is cut by the plane halfway to each
+//! nearby seed, nearest first, until no seed left is near enough to cut it.
+//! Each face remembers the seed whose plane made it, so the faces left are
+//! the cell's membranes with its neighbours, plus any pieces of the box.
+
+use glam::Vec3;
+
+use crate::world::{Bounds, HoneycombWorld};
+
+/// Seeds tried first for each cell; more are fetched if the cell could still
+/// be cut by one of them
+const FIRST_CANDIDATES: usize = 24;
+
+/// A face of a cell
+struct Face {
+    /// The seed across the face, or `None` for the box
+    seed: Option<usize>,
+    /// Corners in order around the face
+    corners: Vec<Vec3>,
+}
+
+impl Face {
+    fn area(&self) -> f32 {
+        polygon_area(&self.corners)
+    }
+}
+
+/// Neighbours of cell `cell` and the area of the face shared with each
+pub fn cell_faces(world: &HoneycombWorld, cell: usize) -> Vec<(usize, f32)> {
+    let seed = world.cells[cell].position;
+    let extent = world.bounds.size().max_element().max(f32::EPSILON);
+    let epsilon = extent * 1e-5;
+    let mut faces = box_faces(&world.bounds);
+
+    let mut tried = 0;
+    let mut wanted = FIRST_CANDIDATES;
+    loop {
+        let candidates = world.nearest_cells(seed, wanted);
+        for &other in &candidates[tried..] {
+            if other == cell {
+                continue;
+            }
+            let position = world.cells[other].position;
+            let distance = seed.distance(position);
+            // A seed can only cut the cell if its halfway plane is nearer
+            // than the cell's furthest corner
+            if distance > 2.0 * radius(&faces, seed) {
+                return neighbours(&faces, epsilon);
+            }
+            if distance > epsilon {
+                faces = cut(
+                    faces,
+                    other,
+                    (position - seed) / distance,
+                    (seed + position) * 0.5,
+                    epsilon,
+                );
+            }
+        }
+        if candidates.len() < wanted {
+            return neighbours(&faces, epsilon);
+        }
+        tried = candidates.len();
+        wanted *= 2;
+    }
+}
+
+/// Seed faces with more than a sliver of area, merged by seed
+fn neighbours(faces: &[Face], epsilon: f32) -> Vec<(usize, f32)> {
+    let mut neighbours: Vec<(usize, f32)> = Vec::new();
+    for face in faces {
+        let (Some(seed), area) = (face.seed, face.area()) else {
+            continue;
+        };
+        if area <= epsilon * epsilon {
+            continue;
+        }
+        match neighbours.iter_mut().find(|(other, _)| *other == seed) {
+            Some((_, total)) => *total += area,
+            None => neighbours.push((seed, area)),
+        }
+    }
+    neighbours
+}
+
+/// Distance from `seed` to the cell's furthest corner
+fn radius(faces: &[Face], seed: Vec3) -> f32 {
+    faces
+        .iter()
+        .flat_map(|face| &face.corners)
+        .map(|corner| corner.distance(seed))
+        .fold(0.0, f32::max)
+}
+
+/// The six sides of the box, corners in order around each
+fn box_faces(bounds: &Bounds) -> Vec<Face> {
+    let corner = |x: usize, y: usize, z: usize| {
+        Vec3::new(
+            [bounds.min.x, bounds.max.x][x],
+            [bounds.min.y, bounds.max.y][y],
+            [bounds.min.z, bounds.max.z][z],
+        )
+    };
+    let sides = [
+        [(0, 0, 0), (0, 1, 0), (0, 1, 1), (0, 0, 1)],
+        [(1, 0, 0), (1, 0, 1), (1, 1, 1), (1, 1, 0)],
+        [(0, 0, 0), (0, 0, 1), (1, 0, 1), (1, 0, 0)],
+        [(0, 1, 0), (1, 1, 0), (1, 1, 1), (0, 1, 1)],
+        [(0, 0, 0), (1, 0, 0), (1, 1, 0), (0, 1, 0)],
+        [(0, 0, 1), (0, 1, 1), (1, 1, 1), (1, 0, 1)],
+    ];
+    sides
+        .iter()
+        .map(|side| Face {
+            seed: None,
+            corners: side.iter().map(|&(x, y, z)| corner(x, y, z)).collect(),
+        })
+        .collect()
+}
+
+/// Keep the part of the cell on the near side of the plane through `point`
+/// with normal `normal`, closing it with a face for `seed`
+fn cut(faces: Vec<Face>, seed: usize, normal: Vec3, point: Vec3, epsilon: f32) -> Vec<Face> {
+    let side = |p: Vec3| {
+        let distance = normal.dot(p - point);
+        if distance > epsilon {
+            1
+        } else if distance < -epsilon {
+            -1
+        } else {
+            0
+        }
+    };
+    if faces
+        .iter()
+        .flat_map(|face| &face.corners)
+        .all(|&corner| side(corner) <= 0)
+    {
+        return faces;
+    }
+
+    let mut kept = Vec::with_capacity(faces.len() + 1);
+    let mut on_plane = Vec::new();
+    for face in faces {
+        let mut corners = Vec::with_capacity(face.corners.len() + 1);
+        for (i, &a) in face.corners.iter().enumerate() {
+            let b = face.corners[(i + 1) % face.corners.len()];
+            let (side_a, side_b) = (side(a), side(b));
+            if side_a <= 0 {
+                corners.push(a);
+            }
+            if side_a == 0 {
+                on_plane.push(a);
+            }
+            if side_a * side_b < 0 {
+                let (da, db) = (normal.dot(a - point), normal.dot(b - point));
+                let crossing = a + (b - a) * (da / (da - db));
+                corners.push(crossing);
+                on_plane.push(crossing);
+            }
+        }
+        if corners.len() >= 3 {
+            kept.push(Face {
+                seed: face.seed,
+                corners,
+            });
+        }
+    }
+
+    // The new face's corners are where the cut crossed the old faces, in
+    // order around their middle
+    on_plane.dedup_by(|a, b| a.distance(*b) <= epsilon);
+    let mut unique: Vec<Vec3> = Vec::with_capacity(on_plane.len());
+    for p in on_plane {
+        if unique.iter().all(|q| q.distance(p) > epsilon) {
+            unique.push(p);
+        }
+    }
+    if unique.len() >= 3 {
+        let middle = unique.iter().sum::<Vec3>() / unique.len() as f32;
+        let u = normal.any_orthonormal_vector();
+        let v = normal.cross(u);
+        unique.sort_by(|a, b| {
+            let angle = |p: &Vec3| (p - middle).dot(v).atan2((p - middle).dot(u));
+            angle(a).total_cmp(&angle(b))
+        });
+        kept.push(Face {
+            seed: Some(seed),
+            corners: unique,
+        });
+    }
+    kept
+}
+
+/// Area of a flat polygon with corners in order around it
+fn polygon_area(corners: &[Vec3]) -> f32 {
+    let twice: Vec3 = (0..corners.len())
+        .map(|i| corners[i].cross(corners[(i + 1) % corners.len()]))
+        .sum();
+    twice.length() * 0.5
+}
@@ -0,0 +1,1070 @@
+//! JavaScript API for the browser build.
+//!
+//! Each `wasm_main` call creates a viewer and returns a `VendekViewer` handle.
+//! The page pushes parameter changes into the viewer's shared store through
+//! the handle; the render loop reads the store without touching JS objects.
+//! Lifecycle callbacks let the page know when the canvas is live, follow
+//! the frame clock and the camera, and surface GPU errors.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+
+use crate::camera::CameraPose;
+use crate::gpu::LoadStage;
+use crate::palette::{parse_hex_color, Gradient, InvalidGradient, Palette};
+use crate::params::{
+    ClipPlane, ParamStore, RuntimeParams, ALL_PHASES, MAX_CLIP_PLANES, TARGET_FPS,
+};
+use crate::preset::Preset;
+use crate::script::Script;
+use crate::timeline::Timeline;
+use crate::transfer::{InvalidTransferFunction, TransferFunction, TransferPoint};
+
+/// Where and how large to create the canvas, and which world to show,
+/// parsed from the object passed to `wasm_main`
+pub struct ViewerOptions {
+    /// Element the canvas is appended to; defaults to `#canvas-container`, then `<body>`
+    pub container: web_sys::Element,
+    /// Explicit canvas size in CSS pixels; defaults to the container's size
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Follow the container's size after startup (default true)
+    pub auto_resize: bool,
+    /// World generation seed; defaults to the native viewer's seed
+    pub seed: Option<u64>,
+    /// Number of cells in the world; defaults to the preset's, or the native
+    /// viewer's count
+    pub cells: Option<usize>,
+    /// Number of phases; defaults to the preset's, or the native viewer's
+    pub phases: Option<usize>,
+    /// World preset to start from
+    pub preset: Option<Preset>,
+    /// Seconds without input before attract mode starts
+    pub attract: Option<f32>,
+    /// Keep the view in the URL fragment and start from the one there
+    pub sync_hash: bool,
+    /// Keep parameters and camera bookmarks in localStorage and start from
+    /// the ones there
+    pub persist: bool,
+}
+
+impl ViewerOptions {
+    /// Accepts `undefined` or `{ container, width, height, autoResize, seed,
+    /// cells, phases, preset, attract, syncHash, persist }`, where `container` is an
+    /// element id or an `Element` and `preset` a preset name.
+    pub fn from_js(options: &JsValue) -> Result<Self, JsError> {
+        let document = web_sys::window()
+            .and_then(|w| w.document())
+            .ok_or_else(|| JsError::new("no document available"))?;
+
+        let get = |key: &str| {
+            if options.is_object() {
+                js_sys::Reflect::get(options, &key.into()).unwrap_or(JsValue::UNDEFINED)
+            } else {
+                JsValue::UNDEFINED
+            }
+        };
+
+        let container = get("container");
+        let container = if let Some(id) = container.as_string() {
+            document
+                .get_element_by_id(&id)
+                .ok_or_else(|| JsError::new(&format!("no element with id `{}`", id)))?
+        } else if container.is_undefined() || container.is_null() {
+            match document.get_element_by_id("canvas-container") {
+                Some(element) => element,
+                None => document
+                    .body()
+                    .ok_or_else(|| JsError::new("document has no body"))?
+                    .into(),
+            }
+        } else {
+            container
+                .dyn_into::<web_sys::Element>()
+                .map_err(|_| JsError::new("`container` must be an element id or an Element"))?
+        };
+
+        let size = |key: &str| get(key).as_f64().map(|v| v.max(1.0) as u32);
+        let preset = match get("preset").as_string() {
+            Some(name) => Some(
+                Preset::from_name(&name)
+                    .ok_or_else(|| JsError::new(&format!("unknown preset `{}`", name)))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            container,
+            width: size("width"),
+            height: size("height"),
+            auto_resize: get("autoResize").as_bool().unwrap_or(true),
+            seed: get("seed").as_f64().map(|v| v as u64),
+            cells: get("cells").as_f64().map(|v| v as usize),
+            phases: get("phases").as_f64().map(|v| v as usize),
+            preset,
+            attract: get("attract").as_f64().map(|v| v as f32),
+            sync_hash: get("syncHash").as_bool().unwrap_or(false),
+            persist: get("persist").as_bool().unwrap_or(false),
+        })
+    }
+
+    /// Initial canvas size: explicit values first, then the container's layout
+    /// size, then the browser window as a last resort.
+    pub fn initial_size(&self) -> (u32, u32) {
+        let web_window = web_sys::window().unwrap();
+        let fallback = |client: i32, window_size: Result<JsValue, JsValue>| {
+            if client > 0 {
+                client as u32
+            } else {
+                window_size.ok().and_then(|v| v.as_f64()).unwrap_or(0.0) as u32
+            }
+        };
+
+        let width = self
+            .width
+            .unwrap_or_else(|| fallback(self.container.client_width(), web_window.inner_width()));
+        let height = self
+            .height
+            .unwrap_or_else(|| fallback(self.container.client_height(), web_window.inner_height()));
+        (width.max(1), height.max(1))
+    }
+}
+
+/// Replace a viewer's canvas with a message explaining why it can't run
+pub fn show_error_panel(canvas: &web_sys::HtmlCanvasElement, message: &str) {
+    let Some(document) = canvas.owner_document() else {
+        return;
+    };
+    let build = || -> Result<web_sys::Element, JsValue> {
+        let panel = document.create_element("div")?;
+        panel.set_attribute(
+            "style",
+            "color: white; padding: 20px; font-family: sans-serif;",
+        )?;
+        let heading = document.create_element("h2")?;
+        heading.set_text_content(Some("Vendek could not start"));
+        let detail = document.create_element("p")?;
+        detail.set_text_content(Some(message));
+        panel.append_child(&heading)?;
+        panel.append_child(&detail)?;
+        Ok(panel)
+    };
+    if let Err(e) = build().and_then(|panel| canvas.replace_with_with_node_1(&panel)) {
+        tracing::error!("Could not show error panel: {:?}", e);
+    }
+}
+
+/// Ratio of device pixels to CSS pixels for the current display
+pub fn device_pixel_ratio() -> f64 {
+    web_sys::window()
+        .map(|w| w.device_pixel_ratio())
+        .filter(|&ratio| ratio > 0.0)
+        .unwrap_or(1.0)
+}
+
+/// Watches a canvas's layout box with a ResizeObserver and reports its size in
+/// device pixels, so the swapchain tracks the element (not the window) and
+/// stays sharp on HiDPI displays. Disconnects when dropped.
+pub struct CanvasObserver {
+    observer: web_sys::ResizeObserver,
+    _callback: Closure<dyn FnMut(js_sys::Array)>,
+}
+
+impl CanvasObserver {
+    pub fn new(
+        canvas: &web_sys::HtmlCanvasElement,
+        on_resize: impl Fn(u32, u32) + 'static,
+    ) -> Result<Self, JsValue> {
+        let callback = Closure::<dyn FnMut(js_sys::Array)>::new(move |entries: js_sys::Array| {
+            let Ok(entry) = entries.get(0).dyn_into::<web_sys::ResizeObserverEntry>() else {
+                return;
+            };
+            let (width, height) = device_pixel_size(&entry);
+            if width > 0 && height > 0 {
+                on_resize(width, height);
+            }
+        });
+
+        let observer = web_sys::ResizeObserver::new(callback.as_ref().unchecked_ref())?;
+        observer.observe(canvas);
+
+        Ok(Self {
+            observer,
+            _callback: callback,
+        })
+    }
+}
+
+impl Drop for CanvasObserver {
+    fn drop(&mut self) {
+        self.observer.disconnect();
+    }
+}
+
+/// Exact device-pixel size where the browser reports it (Chromium), otherwise
+/// the CSS content box scaled by devicePixelRatio
+fn device_pixel_size(entry: &web_sys::ResizeObserverEntry) -> (u32, u32) {
+    let has_device_box = js_sys::Reflect::get(entry, &"devicePixelContentBoxSize".into())
+        .is_ok_and(|v| !v.is_undefined());
+    if has_device_box {
+        if let Ok(size) = entry
+            .device_pixel_content_box_size()
+            .get(0)
+            .dyn_into::<web_sys::ResizeObserverSize>()
+        {
+            return (size.inline_size() as u32, size.block_size() as u32);
+        }
+    }
+
+    let rect = entry.content_rect();
+    let ratio = device_pixel_ratio();
+    (
+        (rect.width() * ratio).round() as u32,
+        (rect.height() * ratio).round() as u32,
+    )
+}
+
+#[derive(Default)]
+struct Callbacks {
+    ready: Option<js_sys::Function>,
+    frame: Option<js_sys::Function>,
+    error: Option<js_sys::Function>,
+    camera: Option<js_sys::Function>,
+    progress: Option<js_sys::Function>,
+    /// Set once the first frame can render, so late `on_ready` calls fire immediately
+    is_ready: bool,
+    /// Startup stage under way, passed to a late `on_progress` right away
+    stage: Option<LoadStage>,
+}
+
+/// Whether the page is on a phone or tablet, going by the user agent
+fn is_mobile() -> bool {
+    web_sys::window()
+        .and_then(|window| window.navigator().user_agent().ok())
+        .is_some_and(|agent| agent.contains("Mobi") || agent.contains("Android"))
+}
+
+/// Phones and tablets aim for 30 fps, which their GPUs can usually hold at a
+/// reasonable resolution; everything else aims for 60
+fn default_target_fps() -> f32 {
+    if is_mobile() {
+        30.0
+    } else {
+        TARGET_FPS
+    }
+}
+
+/// Resolve/reject pair of a promise handed back to the page
+pub struct PendingPromise {
+    resolve: js_sys::Function,
+    reject: js_sys::Function,
+}
+
+/// State shared between a `VendekViewer` handle and its viewer in the event loop
+#[derive(Default)]
+pub struct Shared {
+    params: ParamStore,
+    callbacks: Callbacks,
+    /// `capture_frame` calls waiting for the next rendered frame
+    captures: Vec<PendingPromise>,
+    /// The viewer's canvas, once created, for `start_recording`
+    canvas: Option<web_sys::HtmlCanvasElement>,
+    recording: Option<Recording>,
+    /// Custom palette gradient not yet uploaded
+    gradient: Option<Gradient>,
+    /// Transfer function changes not yet uploaded, in order: the phase, or
+    /// `None` for all, and the function, or `None` to restore the default
+    transfer_functions: Vec<(Option<usize>, Option<TransferFunction>)>,
+    /// Camera pose set through the JS API and not yet applied
+    camera: Option<CameraPose>,
+    /// Where the camera was heading last frame, for `get_camera`
+    camera_pose: CameraPose,
+    /// Whether the last frame was final, for `converged`
+    converged: bool,
+    /// Script change not yet picked up: the new script, or `None` to stop
+    script: Option<Option<Script>>,
+    /// Timeline change not yet picked up, likewise
+    timeline: Option<Option<Timeline>>,
+    /// Attract mode idle time not yet picked up
+    attract: Option<f32>,
+    /// Pause or play not yet picked up
+    paused: Option<bool>,
+    /// Caption change not yet picked up: the new caption, or `None` to
+    /// remove it
+    caption: Option<Option<String>>,
+    /// New world asked for and not yet generated
+    world_change: Option<WorldChange>,
+    /// Seed and counts of the world shown, for `get_world`
+    seed: u64,
+    cells: usize,
+    phases: usize,
+}
+
+/// A `MediaRecorder` capturing the canvas, and the video it has handed over
+/// so far
+struct Recording {
+    recorder: web_sys::MediaRecorder,
+    chunks: js_sys::Array,
+    _on_data: Closure<dyn FnMut(web_sys::BlobEvent)>,
+}
+
+/// Video types to record, best first
+const RECORDING_TYPES: [&str; 3] = ["video/webm;codecs=vp9", "video/webm", "video/mp4"];
+
+/// A new world asked for through the JS API. What is `None` stays as the
+/// current world has it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WorldChange {
+    pub seed: Option<u64>,
+    pub cells: Option<usize>,
+    pub phases: Option<usize>,
+}
+
+/// The event loop's side of a viewer's link to the page
+#[derive(Clone)]
+pub struct ViewerLink(Rc<RefCell<Shared>>);
+
+impl ViewerLink {
+    /// Parameters set through the JS API, if any changed since the last frame
+    pub fn take_params(&self) -> Option<RuntimeParams> {
+        self.0.borrow_mut().params.take_changed()
+    }
+
+    /// Start from the viewer's own `startup` parameters, such as its quality
+    /// tier for the adapter, but keep those the page, its URL or storage
+    /// set to something other than the default
+    pub fn start_from(&self, startup: &RuntimeParams) {
+        let defaults = RuntimeParams::default();
+        let mut shared = self.0.borrow_mut();
+        let mut params = shared.params.get();
+        for name in RuntimeParams::NAMES {
+            if params.get(name) == defaults.get(name) {
+                if let Some(value) = startup.get(name) {
+                    let _ = params.set(name, value);
+                }
+            }
+        }
+        shared.params.replace(params);
+    }
+
+    /// Mirror parameters the viewer changed itself, e.g. from a key binding,
+    /// so later JS changes don't revert them
+    pub fn set_params(&self, params: RuntimeParams) {
+        self.0.borrow_mut().params.replace(params);
+    }
+
+    /// Custom gradient set since the last frame, if any
+    pub fn take_gradient(&self) -> Option<Gradient> {
+        self.0.borrow_mut().gradient.take()
+    }
+
+    /// Transfer function changes made since the last frame, in order
+    pub fn take_transfer_functions(&self) -> Vec<(Option<usize>, Option<TransferFunction>)> {
+        std::mem::take(&mut self.0.borrow_mut().transfer_functions)
+    }
+
+    /// Camera pose set since the last frame, if any
+    pub fn take_camera(&self) -> Option<CameraPose> {
+        self.0.borrow_mut().camera.take()
+    }
+
+    /// Mirror the camera's pose for `get_camera`
+    pub fn set_camera_pose(&self, pose: CameraPose) {
+        self.0.borrow_mut().camera_pose = pose;
+    }
+
+    /// Script set or cleared since the last frame, if any
+    pub fn take_script(&self) -> Option<Option<Script>> {
+        self.0.borrow_mut().script.take()
+    }
+
+    /// Timeline set or cleared since the last frame, if any
+    pub fn take_timeline(&self) -> Option<Option<Timeline>> {
+        self.0.borrow_mut().timeline.take()
+    }
+
+    /// Attract mode idle time set since the last frame, if any
+    pub fn take_attract(&self) -> Option<f32> {
+        self.0.borrow_mut().attract.take()
+    }
+
+    /// Whether to pause or play, if the page said since the last frame
+    pub fn take_paused(&self) -> Option<bool> {
+        self.0.borrow_mut().paused.take()
+    }
+
+    /// Caption set or cleared since the last frame, if any
+    pub fn take_caption(&self) -> Option<Option<String>> {
+        self.0.borrow_mut().caption.take()
+    }
+
+    /// New world asked for since the last frame, if any
+    pub fn take_world_change(&self) -> Option<WorldChange> {
+        self.0.borrow_mut().world_change.take()
+    }
+
+    /// Mirror the seed and counts of the world shown, for `get_world`
+    pub fn set_world(&self, seed: u64, cells: usize, phases: usize) {
+        let mut shared = self.0.borrow_mut();
+        shared.seed = seed;
+        shared.cells = cells;
+        shared.phases = phases;
+    }
+
+    /// Capture requests made since the last frame
+    pub fn take_captures(&self) -> Vec<PendingPromise> {
+        std::mem::take(&mut self.0.borrow_mut().captures)
+    }
+
+    /// Give `start_recording` the canvas to capture
+    pub fn set_canvas(&self, canvas: web_sys::HtmlCanvasElement) {
+        self.0.borrow_mut().canvas = Some(canvas);
+    }
+
+    pub fn notify_ready(&self) {
+        let callback = {
+            let mut shared = self.0.borrow_mut();
+            shared.callbacks.is_ready = true;
+            shared.callbacks.ready.clone()
+        };
+        if let Some(callback) = callback {
+            call(&callback, &[]);
+        }
+    }
+
+    pub fn notify_progress(&self, stage: LoadStage) {
+        let callback = {
+            let mut shared = self.0.borrow_mut();
+            shared.callbacks.stage = Some(stage);
+            shared.callbacks.progress.clone()
+        };
+        if let Some(callback) = callback {
+            call(&callback, &[stage.name().into(), stage.fraction().into()]);
+        }
+    }
+
+    pub fn notify_frame(&self, time: f32, fps: f32, converged: bool) {
+        let callback = {
+            let mut shared = self.0.borrow_mut();
+            shared.converged = converged;
+            shared.callbacks.frame.clone()
+        };
+        if let Some(callback) = callback {
+            call(&callback, &[time.into(), fps.into(), converged.into()]);
+        }
+    }
+
+    pub fn notify_error(&self, message: &str) {
+        let callback = self.0.borrow().callbacks.error.clone();
+        if let Some(callback) = callback {
+            call(&callback, &[message.into()]);
+        }
+    }
+
+    pub fn notify_camera(&self, pose: CameraPose) {
+        let callback = self.0.borrow().callbacks.camera.clone();
+        if let Some(callback) = callback {
+            call(&callback, &[camera_to_js(&pose).into()]);
+        }
+    }
+}
+
+/// Handle to one viewer on the page, returned by `wasm_main`
+#[wasm_bindgen]
+pub struct VendekViewer {
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl VendekViewer {
+    pub fn new() -> Self {
+        // Phones start in low-power mode, so a page embedding the viewer
+        // doesn't drain them
+        let params = RuntimeParams {
+            target_fps: default_target_fps(),
+            low_power: is_mobile(),
+            ..Default::default()
+        };
+        let shared = Shared {
+            params: ParamStore::new(params),
+            ..Default::default()
+        };
+        Self {
+            shared: Rc::new(RefCell::new(shared)),
+        }
+    }
+
+    pub fn link(&self) -> ViewerLink {
+        ViewerLink(self.shared.clone())
+    }
+
+    /// The shared state, to change through the JS API. Wakes the event loop,
+    /// so a viewer rendering on demand draws the change.
+    fn shared_mut(&self) -> std::cell::RefMut<'_, Shared> {
+        crate::app::wake();
+        self.shared.borrow_mut()
+    }
+
+    /// Glide the camera to `pose`, as `set_camera` does
+    pub fn move_camera(&self, pose: CameraPose) {
+        self.shared_mut().camera = Some(pose);
+    }
+
+    /// The new world asked for so far this frame, to add to
+    fn world_change(&self) -> std::cell::RefMut<'_, WorldChange> {
+        std::cell::RefMut::map(self.shared_mut(), |shared| {
+            shared.world_change.get_or_insert_default()
+        })
+    }
+}
+
+impl Default for VendekViewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl VendekViewer {
+    /// Set a single parameter, e.g. `set_param("membraneGlow", 0.8)`
+    pub fn set_param(&self, name: &str, value: f64) -> Result<(), JsError> {
+        self.shared_mut().params.set(name, value)?;
+        Ok(())
+    }
+
+    /// Set several parameters from a plain object, e.g. `set_params({ density: 2, palette: 1 })`.
+    /// Nothing is applied if any key is unknown or any value is not a number.
+    pub fn set_params(&self, values: &js_sys::Object) -> Result<(), JsError> {
+        let mut updated = self.shared.borrow().params.get();
+        for entry in js_sys::Object::entries(values).iter() {
+            let entry: js_sys::Array = entry.unchecked_into();
+            let name = entry.get(0).as_string().unwrap_or_default();
+            let value = entry
+                .get(1)
+                .as_f64()
+                .ok_or_else(|| JsError::new(&format!("parameter `{}` must be a number", name)))?;
+            updated.set(&name, value)?;
+        }
+        self.shared_mut().params.replace(updated);
+        Ok(())
+    }
+
+    /// All parameters as a plain object keyed by parameter name
+    pub fn get_params(&self) -> js_sys::Object {
+        let current = self.shared.borrow().params.get();
+        let object = js_sys::Object::new();
+        for name in RuntimeParams::NAMES {
+            let value = current.get(name).unwrap_or_default();
+            let _ = js_sys::Reflect::set(&object, &(*name).into(), &value.into());
+        }
+        object
+    }
+
+    /// Define the custom palette from gradient stops and switch to it, e.g.
+    /// `set_palette([{ at: 0, color: "#0b1d51" }, { color: [1, 0.8, 0.2] }])`.
+    /// Colors are `#rrggbb` strings or `[r, g, b]` arrays in 0..1; stops
+    /// without `at` are spaced evenly. Choosing another `palette` value
+    /// switches away, and setting `palette` back to 10 restores the gradient.
+    pub fn set_palette(&self, stops: &js_sys::Array) -> Result<(), JsError> {
+        let stops = stops
+            .iter()
+            .map(|stop| {
+                let field =
+                    |key: &str| js_sys::Reflect::get(&stop, &key.into()).unwrap_or_default();
+                let color = js_color(&field("color"))?
+                    .ok_or_else(|| InvalidGradient("every stop needs a `color`".to_string()))?;
+                Ok((field("at").as_f64().map(|at| at as f32), color))
+            })
+            .collect::<Result<_, InvalidGradient>>()?;
+        let gradient = Gradient::new(stops)?;
+
+        let mut shared = self.shared_mut();
+        shared.gradient = Some(gradient);
+        shared
+            .params
+            .update(|params| params.palette = Palette::Custom.index());
+        Ok(())
+    }
+
+    /// Cut the volume open with clipping plane `index` (0 to 2), e.g.
+    /// `set_clip_plane(0, { position: [0, 0, 0], normal: [1, 0, 0] })`.
+    /// Everything on the side `normal` points to is removed. An optional
+    /// `speed` moves the plane along its normal in world units per second,
+    /// bouncing between the volume bounds.
+    pub fn set_clip_plane(&self, index: usize, plane: &js_sys::Object) -> Result<(), JsError> {
+        if index >= MAX_CLIP_PLANES {
+            return Err(JsError::new(&format!(
+                "clip plane index must be below {}",
+                MAX_CLIP_PLANES
+            )));
+        }
+        let field = |key: &str| js_sys::Reflect::get(plane, &key.into()).unwrap_or_default();
+        let vector = |key: &str| {
+            let value = field(key);
+            let components: Vec<f32> = if js_sys::Array::is_array(&value) {
+                js_sys::Array::from(&value)
+                    .iter()
+                    .filter_map(|c| c.as_f64())
+                    .map(|c| c as f32)
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            match components[..] {
+                [x, y, z] => Ok(glam::Vec3::new(x, y, z)),
+                _ => Err(JsError::new(&format!(
+                    "`{}` must be an [x, y, z] array",
+                    key
+                ))),
+            }
+        };
+        let plane = ClipPlane {
+            position: vector("position")?,
+            normal: vector("normal")?,
+            speed: field("speed").as_f64().unwrap_or_default() as f32,
+        };
+        self.shared_mut()
+            .params
+            .update(|params| params.clip_planes[index] = Some(plane));
+        Ok(())
+    }
+
+    /// Remove clipping plane `index`
+    pub fn clear_clip_plane(&self, index: usize) {
+        self.shared_mut().params.update(|params| {
+            if let Some(slot) = params.clip_planes.get_mut(index) {
+                *slot = None;
+            }
+        });
+    }
+
+    /// Set the transfer function of `phase`, or of every phase when `phase`
+    /// is left out, e.g.
+    /// `set_transfer_function([{ at: 0, opacity: 0 }, { at: 1, color: "#ffd080", opacity: 0.1 }], 3)`.
+    /// Points map a density, 0 at a cell's center to 1 at its membranes, to
+    /// an `opacity` and an optional `color` tint like a palette stop's.
+    pub fn set_transfer_function(
+        &self,
+        points: &js_sys::Array,
+        phase: Option<usize>,
+    ) -> Result<(), JsError> {
+        let points = points
+            .iter()
+            .map(|point| {
+                let field =
+                    |key: &str| js_sys::Reflect::get(&point, &key.into()).unwrap_or_default();
+                let number = |key: &str| {
+                    field(key)
+                        .as_f64()
+                        .map(|value| value as f32)
+                        .ok_or_else(|| {
+                            InvalidTransferFunction(format!(
+                                "every point needs a numeric `{}`",
+                                key
+                            ))
+                        })
+                };
+                let color = js_color(&field("color")).map_err(|e| InvalidTransferFunction(e.0))?;
+                Ok(TransferPoint {
+                    at: number("at")?,
+                    color: color.unwrap_or([1.0; 3]),
+                    opacity: number("opacity")?,
+                })
+            })
+            .collect::<Result<_, InvalidTransferFunction>>()?;
+        let function = TransferFunction::new(points)?;
+        self.shared_mut()
+            .transfer_functions
+            .push((phase, Some(function)));
+        Ok(())
+    }
+
+    /// Restore the default transfer function of `phase`, or of every phase
+    /// when `phase` is left out
+    pub fn clear_transfer_function(&self, phase: Option<usize>) {
+        self.shared_mut().transfer_functions.push((phase, None));
+    }
+
+    /// Show or hide the cells of phase `phase` (0 to 11)
+    pub fn set_phase_visible(&self, phase: u32, visible: bool) {
+        self.shared_mut()
+            .params
+            .update(|params| params.set_phase_visible(phase, visible));
+    }
+
+    /// Show only phase `phase`, or every phase if it is already shown alone
+    pub fn solo_phase(&self, phase: u32) {
+        self.shared_mut()
+            .params
+            .update(|params| params.solo_phase(phase));
+    }
+
+    /// Show every phase again
+    pub fn show_all_phases(&self) {
+        self.shared_mut()
+            .params
+            .update(|params| params.visible_phases = ALL_PHASES);
+    }
+
+    /// Whether the last frame drawn is final: nothing has changed since it
+    /// was drawn, and on the compute path every sample a still view takes
+    /// has accumulated. Captures taken now come out the same as later ones.
+    pub fn converged(&self) -> bool {
+        self.shared.borrow().converged
+    }
+
+    /// Where the camera is heading, as
+    /// `{ focus: [x, y, z], yaw, pitch, distance }` with angles in radians
+    pub fn get_camera(&self) -> js_sys::Object {
+        let shared = self.shared.borrow();
+        camera_to_js(&shared.camera.unwrap_or(shared.camera_pose))
+    }
+
+    /// Move the camera to a pose like `get_camera`'s, smoothly as the mouse
+    /// would. Fields left out keep their current values.
+    pub fn set_camera(&self, camera: &js_sys::Object) -> Result<(), JsError> {
+        let mut shared = self.shared_mut();
+        let mut pose = shared.camera.unwrap_or(shared.camera_pose);
+        let field = |key: &str| js_sys::Reflect::get(camera, &key.into()).unwrap_or_default();
+        let number = |key: &str, current: f32| {
+            let value = field(key);
+            if value.is_undefined() {
+                return Ok(current);
+            }
+            value
+                .as_f64()
+                .map(|value| value as f32)
+                .ok_or_else(|| JsError::new(&format!("`{}` must be a number", key)))
+        };
+        pose.yaw = number("yaw", pose.yaw)?;
+        pose.pitch = number("pitch", pose.pitch)?;
+        pose.distance = number("distance", pose.distance)?;
+        let focus = field("focus");
+        if !focus.is_undefined() {
+            let components: Vec<f32> = if js_sys::Array::is_array(&focus) {
+                js_sys::Array::from(&focus)
+                    .iter()
+                    .filter_map(|c| c.as_f64())
+                    .map(|c| c as f32)
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            pose.focus = match components[..] {
+                [x, y, z] => glam::Vec3::new(x, y, z),
+                _ => return Err(JsError::new("`focus` must be an [x, y, z] array")),
+            };
+        }
+        shared.camera = Some(pose);
+        Ok(())
+    }
+
+    /// Run a frame script, replacing any other; see the README for the
+    /// language. Throws with the line number if it doesn't compile or its
+    /// first run fails.
+    pub fn set_script(&self, source: &str) -> Result<(), JsError> {
+        let script = Script::parse(source)?;
+        self.shared_mut().script = Some(Some(script));
+        Ok(())
+    }
+
+    /// Stop the frame script, leaving parameters and camera where it put them
+    pub fn clear_script(&self) {
+        self.shared_mut().script = Some(None);
+    }
+
+    /// Play a keyframe timeline from the start, replacing any other. Takes
+    /// the TOML a timeline file holds; see the README.
+    pub fn set_timeline(&self, source: &str) -> Result<(), JsError> {
+        let timeline = Timeline::parse(source)?;
+        self.shared_mut().timeline = Some(Some(timeline));
+        Ok(())
+    }
+
+    /// Stop the timeline, leaving parameters and camera where it put them
+    pub fn clear_timeline(&self) {
+        self.shared_mut().timeline = Some(None);
+    }
+
+    /// Start attract mode after `seconds` without input, for kiosks; 0 turns
+    /// it off
+    pub fn set_attract(&self, seconds: f32) {
+        self.shared_mut().attract = Some(seconds);
+    }
+
+    /// Stop the world's clock, as Space does, or start it again. A paused
+    /// viewer rendering on demand draws nothing once its view settles.
+    pub fn set_paused(&self, paused: bool) {
+        self.shared_mut().paused = Some(paused);
+    }
+
+    /// Write `text` along the bottom of the frame, or remove the caption
+    /// when it is left out or empty
+    pub fn set_caption(&self, text: Option<String>) {
+        self.shared_mut().caption = Some(text);
+    }
+
+    /// Generate a new world from `seed`, or a random seed when it is left
+    /// out, keeping the cell count, preset, parameters and camera
+    pub fn regenerate(&self, seed: Option<f64>) {
+        let seed = seed.map_or_else(rand::random, |seed| seed as u64);
+        self.world_change().seed = Some(seed);
+    }
+
+    /// Regenerate the world with `cells` cells, at most 4000, keeping its
+    /// seed and phase count
+    pub fn set_cells(&self, cells: u32) {
+        self.world_change().cells = Some(cells.max(1) as usize);
+    }
+
+    /// Regenerate the world with `phases` phases, 1 to 32, keeping its seed
+    /// and cell count
+    pub fn set_phases(&self, phases: u32) {
+        self.world_change().phases = Some(phases as usize);
+    }
+
+    /// The world shown: `{ seed, cells, phases }`
+    pub fn get_world(&self) -> js_sys::Object {
+        let shared = self.shared.borrow();
+        let object = js_sys::Object::new();
+        for (key, value) in [
+            ("seed", shared.seed as f64),
+            ("cells", shared.cells as f64),
+            ("phases", shared.phases as f64),
+        ] {
+            let _ = js_sys::Reflect::set(&object, &key.into(), &value.into());
+        }
+        object
+    }
+
+    /// Capture the next rendered frame. Resolves with an `image/png` Blob, e.g.
+    /// for `URL.createObjectURL` behind a download link.
+    pub fn capture_frame(&self) -> js_sys::Promise {
+        js_sys::Promise::new(&mut |resolve, reject| {
+            self.shared_mut()
+                .captures
+                .push(PendingPromise { resolve, reject });
+        })
+    }
+
+    /// Start recording the canvas as video, `fps` frames per second at
+    /// most (default 30), with the browser's `MediaRecorder`: WebM where the
+    /// browser can, MP4 otherwise. Frames are recorded as they are shown, so
+    /// the video keeps the pace of the viewer.
+    pub fn start_recording(&self, fps: Option<f64>) -> Result<(), JsError> {
+        let mut shared = self.shared_mut();
+        if shared.recording.is_some() {
+            return Err(JsError::new("already recording"));
+        }
+        let canvas = shared
+            .canvas
+            .clone()
+            .ok_or_else(|| JsError::new("the viewer has no canvas yet"))?;
+        let stream = canvas
+            .capture_stream_with_frame_request_rate(fps.unwrap_or(30.0))
+            .map_err(|e| JsError::new(&format!("could not capture the canvas: {:?}", e)))?;
+        let mime_type = RECORDING_TYPES
+            .into_iter()
+            .find(|mime_type| web_sys::MediaRecorder::is_type_supported(mime_type))
+            .ok_or_else(|| JsError::new("this browser can't record WebM or MP4 video"))?;
+        let options = web_sys::MediaRecorderOptions::new();
+        options.set_mime_type(mime_type);
+        let recorder = web_sys::MediaRecorder::new_with_media_stream_and_media_recorder_options(
+            &stream, &options,
+        )
+        .map_err(|e| JsError::new(&format!("could not start recording: {:?}", e)))?;
+
+        let chunks = js_sys::Array::new();
+        let received = chunks.clone();
+        let on_data =
+            Closure::<dyn FnMut(web_sys::BlobEvent)>::new(move |event: web_sys::BlobEvent| {
+                if let Some(data) = event.data() {
+                    received.push(&data);
+                }
+            });
+        recorder.set_ondataavailable(Some(on_data.as_ref().unchecked_ref()));
+        recorder
+            .start()
+            .map_err(|e| JsError::new(&format!("could not start recording: {:?}", e)))?;
+        shared.recording = Some(Recording {
+            recorder,
+            chunks,
+            _on_data: on_data,
+        });
+        Ok(())
+    }
+
+    /// Stop recording. Resolves with the video as a Blob, of the type the
+    /// browser recorded, e.g. for `URL.createObjectURL` behind a download
+    /// link; rejects if nothing was being recorded.
+    pub fn stop_recording(&self) -> js_sys::Promise {
+        let mut recording = self.shared_mut().recording.take();
+        js_sys::Promise::new(&mut |resolve, reject| {
+            let Some(Recording {
+                recorder,
+                chunks,
+                _on_data: on_data,
+            }) = recording.take()
+            else {
+                call(&reject, &[JsError::new("not recording").into()]);
+                return;
+            };
+            // The last chunk arrives before the recorder reports it stopped
+            let mime_type = recorder.mime_type();
+            let rejected = reject.clone();
+            let on_stop = Closure::once_into_js(move || {
+                drop(on_data);
+                let options = web_sys::BlobPropertyBag::new();
+                options.set_type(&mime_type);
+                match web_sys::Blob::new_with_blob_sequence_and_options(&chunks, &options) {
+                    Ok(blob) => call(&resolve, &[blob.into()]),
+                    Err(e) => call(&rejected, &[e]),
+                }
+            });
+            recorder.set_onstop(Some(on_stop.unchecked_ref()));
+            if let Err(e) = recorder.stop() {
+                call(&reject, &[e]);
+            }
+        })
+    }
+
+    /// Register `callback()` to run once the GPU is initialized and rendering.
+    /// Runs immediately if that has already happened.
+    pub fn on_ready(&self, callback: js_sys::Function) {
+        let already_ready = {
+            let mut shared = self.shared.borrow_mut();
+            shared.callbacks.ready = Some(callback.clone());
+            shared.callbacks.is_ready
+        };
+        if already_ready {
+            call(&callback, &[]);
+        }
+    }
+
+    /// Register `callback(stage, fraction)` to run as each step of startup
+    /// begins: `"world"`, `"adapter"`, `"device"` and `"pipelines"`, with a
+    /// rough share of the work done so far. Startup ends with `on_ready`, or
+    /// `on_error` if it fails. Runs immediately with the current stage if
+    /// startup is under way.
+    pub fn on_progress(&self, callback: js_sys::Function) {
+        let stage = {
+            let mut shared = self.shared.borrow_mut();
+            shared.callbacks.progress = Some(callback.clone());
+            shared
+                .callbacks
+                .stage
+                .filter(|_| !shared.callbacks.is_ready)
+        };
+        if let Some(stage) = stage {
+            call(&callback, &[stage.name().into(), stage.fraction().into()]);
+        }
+    }
+
+    /// Register `callback(time, fps, converged)` to run after every rendered
+    /// frame, with `converged` as `converged` gives it
+    pub fn on_frame(&self, callback: js_sys::Function) {
+        self.shared.borrow_mut().callbacks.frame = Some(callback);
+    }
+
+    /// Register `callback(message)` to run when a GPU or surface error occurs
+    pub fn on_error(&self, callback: js_sys::Function) {
+        self.shared.borrow_mut().callbacks.error = Some(callback);
+    }
+
+    /// Register `callback(camera)` to run when mouse or keyboard input moves
+    /// the camera, with the pose as `get_camera` gives it. Moves made with
+    /// `set_camera` don't call it.
+    pub fn on_camera(&self, callback: js_sys::Function) {
+        self.shared.borrow_mut().callbacks.camera = Some(callback);
+    }
+}
+
+/// Give the browser a turn to paint and handle events before a long
+/// stretch of synchronous work
+pub async fn yield_to_browser() {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        let scheduled = web_sys::window()
+            .is_some_and(|window| window.set_timeout_with_callback(&resolve).is_ok());
+        if !scheduled {
+            let _ = resolve.call0(&JsValue::UNDEFINED);
+        }
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+fn camera_to_js(pose: &CameraPose) -> js_sys::Object {
+    let object = js_sys::Object::new();
+    let focus = js_sys::Array::of3(
+        &pose.focus.x.into(),
+        &pose.focus.y.into(),
+        &pose.focus.z.into(),
+    );
+    let _ = js_sys::Reflect::set(&object, &"focus".into(), &focus);
+    let _ = js_sys::Reflect::set(&object, &"yaw".into(), &pose.yaw.into());
+    let _ = js_sys::Reflect::set(&object, &"pitch".into(), &pose.pitch.into());
+    let _ = js_sys::Reflect::set(&object, &"distance".into(), &pose.distance.into());
+    object
+}
+
+/// Settle capture promises with a frame read back from the GPU
+pub fn finish_captures(
+    captures: Vec<PendingPromise>,
+    width: u32,
+    height: u32,
+    frame: Result<Vec<u8>, wgpu::BufferAsyncError>,
+) {
+    let blob = frame
+        .map_err(|e| JsValue::from(JsError::new(&format!("Frame readback failed: {}", e))))
+        .and_then(|pixels| {
+            crate::capture::encode_png(width, height, &pixels)
+                .map_err(|e| JsError::new(&format!("PNG encoding failed: {}", e)).into())
+        })
+        .and_then(|png| png_blob(&png));
+
+    for capture in captures {
+        match &blob {
+            Ok(blob) => call(&capture.resolve, &[blob.into()]),
+            Err(e) => call(&capture.reject, std::slice::from_ref(e)),
+        }
+    }
+}
+
+fn png_blob(png: &[u8]) -> Result<web_sys::Blob, JsValue> {
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(png));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type("image/png");
+    web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+}
+
+/// Invoke a page callback, logging rather than propagating anything it throws.
+/// Callbacks are cloned out of the store first so they may call back into the viewer.
+fn call(callback: &js_sys::Function, args: &[JsValue]) {
+    let args: js_sys::Array = args.iter().collect();
+    if let Err(e) = callback.apply(&JsValue::NULL, &args) {
+        tracing::error!("Callback threw: {:?}", e);
+    }
+}
+
+/// A `#rrggbb` string or `[r, g, b]` array in 0..1, or `None` when the value
+/// is missing
+fn js_color(value: &JsValue) -> Result<Option<[f32; 3]>, InvalidGradient> {
+    if let Some(hex) = value.as_string() {
+        parse_hex_color(&hex).map(Some)
+    } else if js_sys::Array::is_array(value) {
+        let channels: Vec<f64> = js_sys::Array::from(value)
+            .iter()
+            .filter_map(|c| c.as_f64())
+            .collect();
+        match channels[..] {
+            [r, g, b] => Ok(Some([r as f32, g as f32, b as f32])),
+            _ => Err(InvalidGradient("colors need three channels".to_string())),
+        }
+    } else if value.is_undefined() || value.is_null() {
+        Ok(None)
+    } else {
+        Err(InvalidGradient(
+            "colors are #rrggbb strings or [r, g, b] arrays".to_string(),
+        ))
+    }
+}
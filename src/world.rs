@@ -1,7 +1,18 @@
-use glam::{Mat4, Vec3, Vec4};
+//! The procedural honeycomb: vendek phases with their optical and membrane
+//! properties, and Voronoi cells seeded through a box, or through chunks of
+//! one tiling space, each in one phase.
+//! Generation depends only on the seed and counts, so a seed always gives
+//! the same world. The module needs neither wgpu nor winit, and is all
+//! that builds with the `world-only` feature, for simulations, tests and
+//! offline tools.
+
+use glam::{IVec3, Vec3, Vec4};
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
 
+use crate::kdtree::KdTree;
+use crate::voronoi;
+
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
 pub struct VendekPhase {
@@ -13,7 +24,11 @@ pub struct VendekPhase {
     pub membrane_params: Vec4,
     /// Unique phase identifier
     pub phase_id: u32,
-    pub _pad: [u32; 3],
+    /// Noise over the phase's cells: frequency in cycles per world unit,
+    /// octaves summed, and how far it thins and thickens the density (0 to 1)
+    pub noise_scale: f32,
+    pub noise_octaves: u32,
+    pub noise_strength: f32,
 }
 
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -25,33 +40,150 @@ pub struct HoneycombCell {
     pub phase_index: u32,
 }
 
+/// A cell that glows from within, lighting the volume around it. The
+/// raymarch tints the light by the cell's current phase.
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
-pub struct FrameUniforms {
-    pub view_proj: Mat4,
-    pub inv_view_proj: Mat4,
-    pub camera_position: Vec3,
-    pub time: f32,
-    pub resolution: [f32; 2],
-    pub near: f32,
-    pub far: f32,
+pub struct Emitter {
+    /// The cell's seed
+    pub position: Vec3,
+    /// Distance within which the light is at least half as bright, half the
+    /// way to the nearest other seed
+    pub radius: f32,
+    /// Index into the cells array
+    pub cell: u32,
+    /// Brightness relative to the `emission` parameter
+    pub intensity: f32,
+    pub _pad: [f32; 2],
 }
 
-#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-#[repr(C)]
-pub struct RaymarchParams {
-    pub volume_min: Vec3,
-    pub _pad0: f32,
-    pub volume_max: Vec3,
-    pub _pad1: f32,
-    pub max_steps: u32,
-    pub step_size: f32,
-    pub membrane_thickness: f32,
-    pub membrane_glow: f32,
-    pub density_multiplier: f32,
-    pub enable_coupling: f32,  // 1.0 = enabled, 0.0 = disabled
-    pub palette: u32,
-    pub _pad2: u32,
+/// Emitters a world may have at most; the raymarch visits each per sample
+pub const MAX_EMITTERS: usize = 32;
+
+/// Which cells become emitters: each cell of phase `i` with probability
+/// `phase_probability[i]`, and every cell in `cells`. Phases past the end
+/// of `phase_probability` never emit, and cells past the last are ignored.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EmitterChoice {
+    pub phase_probability: Vec<f32>,
+    pub cells: Vec<usize>,
+}
+
+/// Version of what generation makes of a seed. Bump it with any change
+/// that alters an existing seed's phases or cells, and update the hashes in
+/// `tests/world.rs`, which pin each version's worlds.
+pub const GENERATOR_VERSION: u32 = 1;
+
+/// How the Voronoi seeds are spread through the box
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SeedLayout {
+    /// Independently and uniformly at random, giving cells of very
+    /// different sizes
+    #[default]
+    Uniform,
+    /// Each seed the furthest of several candidates from the seeds before
+    /// it, giving even, bubble-like cells
+    Relaxed,
+    /// On a cubic lattice, filled layer by layer and jittered slightly, giving
+    /// near-identical faceted cells
+    Lattice,
+    /// Gathered around a few random centers, giving small cells in dense
+    /// knots and large ones between them
+    Clustered,
+}
+
+/// What a world looks like beyond its seed and counts. The default is the
+/// viewer's usual world.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldStyle {
+    pub layout: SeedLayout,
+    /// Lowest and highest opacity per world unit a phase may get
+    pub density: (f32, f32),
+}
+
+impl Default for WorldStyle {
+    fn default() -> Self {
+        Self {
+            layout: SeedLayout::Uniform,
+            density: (0.02, 0.08),
+        }
+    }
+}
+
+/// Candidates `SeedLayout::Relaxed` draws for each seed
+const RELAXED_CANDIDATES: usize = 8;
+/// Seeds per cluster in `SeedLayout::Clustered`
+const CLUSTER_SIZE: usize = 12;
+/// Jitter of `SeedLayout::Lattice` seeds, as a share of the spacing
+const LATTICE_JITTER: f32 = 0.15;
+
+impl SeedLayout {
+    /// `count` seed positions inside the box from `low` to `high`
+    fn place(self, rng: &mut ChaCha8Rng, count: usize, low: Vec3, high: Vec3) -> Vec<Vec3> {
+        let uniform = |rng: &mut ChaCha8Rng| {
+            Vec3::new(
+                rng.gen_range(low.x..high.x),
+                rng.gen_range(low.y..high.y),
+                rng.gen_range(low.z..high.z),
+            )
+        };
+        match self {
+            SeedLayout::Uniform => (0..count).map(|_| uniform(rng)).collect(),
+            SeedLayout::Relaxed => {
+                let mut seeds: Vec<Vec3> = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let best = (0..RELAXED_CANDIDATES)
+                        .map(|_| {
+                            let candidate = uniform(rng);
+                            let gap = seeds
+                                .iter()
+                                .map(|seed| seed.distance_squared(candidate))
+                                .fold(f32::MAX, f32::min);
+                            (candidate, gap)
+                        })
+                        .max_by(|a, b| a.1.total_cmp(&b.1))
+                        .map(|(candidate, _)| candidate);
+                    seeds.extend(best);
+                }
+                seeds
+            }
+            SeedLayout::Lattice => {
+                let side = (count as f32).cbrt().ceil().max(1.0) as usize;
+                let spacing = (high - low) / side as f32;
+                (0..count)
+                    .map(|i| {
+                        let index = Vec3::new(
+                            (i % side) as f32,
+                            (i / side % side) as f32,
+                            (i / (side * side)) as f32,
+                        );
+                        let jitter = Vec3::new(
+                            rng.gen_range(-LATTICE_JITTER..LATTICE_JITTER),
+                            rng.gen_range(-LATTICE_JITTER..LATTICE_JITTER),
+                            rng.gen_range(-LATTICE_JITTER..LATTICE_JITTER),
+                        );
+                        low + (index + 0.5 + jitter) * spacing
+                    })
+                    .collect()
+            }
+            SeedLayout::Clustered => {
+                let centers: Vec<Vec3> = (0..count.div_ceil(CLUSTER_SIZE).max(1))
+                    .map(|_| uniform(rng))
+                    .collect();
+                let spread = (high - low) / 8.0;
+                (0..count)
+                    .map(|_| {
+                        let center = centers[rng.gen_range(0..centers.len())];
+                        // Sum of three uniforms: roughly normal, and bounded
+                        let mut offset =
+                            || (0..3).map(|_| rng.gen_range(-1.0f32..1.0)).sum::<f32>() / 1.5;
+                        let offset = Vec3::new(offset(), offset(), offset()) * spread;
+                        (center + offset).clamp(low, high)
+                    })
+                    .collect()
+            }
+        }
+    }
 }
 
 /// Spatial grid for accelerating Voronoi lookups
@@ -67,20 +199,50 @@ pub struct GridCell {
 
 pub struct SpatialGrid {
     pub cells: Vec<GridCell>,
-    pub grid_size: u32,  // cells per dimension
+    pub grid_size: u32, // cells per dimension
 }
 
 impl SpatialGrid {
-    pub fn build(voronoi_cells: &[HoneycombCell], volume_min: Vec3, volume_max: Vec3, grid_size: u32) -> Self {
+    /// Grid cells per side for `cell_count` seeds: about eight grid cells
+    /// to a seed, so a grid cell lists three or four seeds on average and
+    /// crowded ones fill up
+    pub fn size_for(cell_count: usize) -> u32 {
+        ((cell_count as f32 * 8.0).cbrt().ceil() as u32).clamp(2, 64)
+    }
+
+    /// The grid over `world`'s bounds, sized for its cells
+    pub fn for_world(world: &HoneycombWorld) -> Self {
+        Self::build(
+            &world.cells,
+            world.bounds.min,
+            world.bounds.max,
+            Self::size_for(world.cells.len()),
+        )
+    }
+
+    /// Seeds each grid cell lists, x fastest, then y, then z
+    pub fn occupancy(&self) -> Vec<u8> {
+        self.cells.iter().map(|cell| cell.count as u8).collect()
+    }
+
+    pub fn build(
+        voronoi_cells: &[HoneycombCell],
+        volume_min: Vec3,
+        volume_max: Vec3,
+        grid_size: u32,
+    ) -> Self {
         let volume_extent = volume_max - volume_min;
         let cell_size = volume_extent / grid_size as f32;
         let total_cells = (grid_size * grid_size * grid_size) as usize;
 
-        let mut grid_cells = vec![GridCell {
-            cell_indices: [-1; 8],
-            count: 0,
-            _pad: [0; 3],
-        }; total_cells];
+        let mut grid_cells = vec![
+            GridCell {
+                cell_indices: [-1; 8],
+                count: 0,
+                _pad: [0; 3],
+            };
+            total_cells
+        ];
 
         // For each Voronoi cell, add it to nearby grid cells
         for (voronoi_idx, voronoi_cell) in voronoi_cells.iter().enumerate() {
@@ -100,13 +262,20 @@ impl SpatialGrid {
                         let ny = gy as i32 + dy;
                         let nz = gz as i32 + dz;
 
-                        if nx >= 0 && nx < grid_size as i32 &&
-                           ny >= 0 && ny < grid_size as i32 &&
-                           nz >= 0 && nz < grid_size as i32 {
-                            let idx = (nz as u32 * grid_size * grid_size + ny as u32 * grid_size + nx as u32) as usize;
+                        if nx >= 0
+                            && nx < grid_size as i32
+                            && ny >= 0
+                            && ny < grid_size as i32
+                            && nz >= 0
+                            && nz < grid_size as i32
+                        {
+                            let idx = (nz as u32 * grid_size * grid_size
+                                + ny as u32 * grid_size
+                                + nx as u32) as usize;
                             let grid_cell = &mut grid_cells[idx];
                             if (grid_cell.count as usize) < 8 {
-                                grid_cell.cell_indices[grid_cell.count as usize] = voronoi_idx as i32;
+                                grid_cell.cell_indices[grid_cell.count as usize] =
+                                    voronoi_idx as i32;
                                 grid_cell.count += 1;
                             }
                         }
@@ -122,24 +291,112 @@ impl SpatialGrid {
     }
 }
 
+/// Axis-aligned box the world fills and the raymarcher draws
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Bounds {
+    /// The box from `a` to `b`, whichever corners they are
+    pub fn new(a: Vec3, b: Vec3) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// This box moved by `coord` of its own size along each axis, for tiling
+    /// space with it
+    pub fn tiled(&self, coord: IVec3) -> Self {
+        let offset = coord.as_vec3() * self.size();
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    /// The smallest box holding both
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Self {
+            min: Vec3::splat(-12.0),
+            max: Vec3::splat(12.0),
+        }
+    }
+}
+
+/// A generated world; the phases and cells are laid out as the shaders
+/// read them. The default has no phases or cells, in the default bounds.
+#[derive(Default)]
 pub struct HoneycombWorld {
     pub phases: Vec<VendekPhase>,
     pub cells: Vec<HoneycombCell>,
+    pub bounds: Bounds,
+    /// Cells that glow, at most `MAX_EMITTERS`; none unless chosen with
+    /// `choose_emitters`
+    pub emitters: Vec<Emitter>,
+    /// What the world was generated from, which also seeds its simulations
+    pub seed: u64,
     // pub spatial_grid: SpatialGrid, // TODO: re-enable for performance
+    /// Seed positions for spatial queries, as of generation or `reindex`
+    index: KdTree,
 }
 
 impl HoneycombWorld {
-    pub fn generate(seed: u64, cell_count: usize, phase_count: usize) -> Self {
+    /// Generate `phase_count` phases and `cell_count` cells inside `bounds`
+    pub fn generate(seed: u64, cell_count: usize, phase_count: usize, bounds: Bounds) -> Self {
+        Self::generate_styled(
+            seed,
+            cell_count,
+            phase_count,
+            bounds,
+            &WorldStyle::default(),
+        )
+    }
+
+    /// `generate`, with the seeds laid out and the phases as dense as
+    /// `style` says
+    pub fn generate_styled(
+        seed: u64,
+        cell_count: usize,
+        phase_count: usize,
+        bounds: Bounds,
+        style: &WorldStyle,
+    ) -> Self {
+        let _span = tracing::info_span!("generate_world", seed, cell_count, phase_count).entered();
         let mut rng = ChaCha8Rng::seed_from_u64(seed);
 
         // Generate distinct vendek phases with varied visual properties
-        let phases: Vec<VendekPhase> = (0..phase_count)
+        let mut phases: Vec<VendekPhase> = (0..phase_count)
             .map(|i| {
                 let hue = (i as f32) / (phase_count as f32);
                 let (r, g, b) = hsv_to_rgb(hue, 0.7, 0.9);
 
                 VendekPhase {
-                    color_density: Vec4::new(r, g, b, rng.gen_range(0.02..0.08)),
+                    color_density: Vec4::new(
+                        r,
+                        g,
+                        b,
+                        rng.gen_range(style.density.0..style.density.1),
+                    ),
                     scattering: Vec4::new(
                         rng.gen_range(0.1..1.0),
                         rng.gen_range(0.1..1.0),
@@ -153,25 +410,265 @@ impl HoneycombWorld {
                         rng.gen_range(0.1..1.0),  // coupling
                     ),
                     phase_id: i as u32,
-                    _pad: [0; 3],
+                    noise_scale: 0.0,
+                    noise_octaves: 0,
+                    noise_strength: 0.0,
                 }
             })
             .collect();
 
-        // Generate Voronoi seeds
-        let cells: Vec<HoneycombCell> = (0..cell_count)
-            .map(|_| HoneycombCell {
-                position: Vec3::new(
-                    rng.gen_range(-10.0..10.0),
-                    rng.gen_range(-10.0..10.0),
-                    rng.gen_range(-10.0..10.0),
-                ),
-                phase_index: rng.gen_range(0..phase_count as u32),
+        // Generate Voronoi seeds, keeping a twelfth of the box clear at each
+        // side so the outermost cells aren't cut flat by the bounds
+        let margin = bounds.size() / 12.0;
+        let (low, high) = (bounds.min + margin, bounds.max - margin);
+        let cells: Vec<HoneycombCell> = match style.layout {
+            // Drawn interleaved with the phases, as before there were layouts,
+            // so a seed keeps its world
+            SeedLayout::Uniform => (0..cell_count)
+                .map(|_| HoneycombCell {
+                    position: Vec3::new(
+                        rng.gen_range(low.x..high.x),
+                        rng.gen_range(low.y..high.y),
+                        rng.gen_range(low.z..high.z),
+                    ),
+                    phase_index: rng.gen_range(0..phase_count as u32),
+                })
+                .collect(),
+            layout => layout
+                .place(&mut rng, cell_count, low, high)
+                .into_iter()
+                .map(|position| HoneycombCell {
+                    position,
+                    phase_index: rng.gen_range(0..phase_count as u32),
+                })
+                .collect(),
+        };
+
+        // Drawn after the cells so a seed keeps the layout it had before
+        // phases had noise
+        for phase in &mut phases {
+            phase.noise_scale = rng.gen_range(0.3..1.2);
+            phase.noise_octaves = rng.gen_range(1..=4);
+            phase.noise_strength = rng.gen_range(0.3..0.9);
+        }
+
+        Self {
+            index: KdTree::build(&cells),
+            phases,
+            cells,
+            bounds,
+            emitters: Vec::new(),
+            seed,
+        }
+    }
+
+    /// A world of chunks tiling space from `chunk`, the box of chunk zero,
+    /// with the chunks at `coords` loaded: `cells_per_chunk` cells each, in
+    /// the order of `coords`, and bounds holding them all. The phases are
+    /// those of `generate_styled` with the same seed.
+    pub fn generate_chunked(
+        seed: u64,
+        cells_per_chunk: usize,
+        phase_count: usize,
+        chunk: Bounds,
+        coords: &[IVec3],
+        style: &WorldStyle,
+    ) -> Self {
+        let _span = tracing::info_span!("generate_chunked", seed, chunks = coords.len()).entered();
+        let mut world = Self::generate_styled(seed, 0, phase_count, chunk, style);
+        world.cells = coords
+            .iter()
+            .flat_map(|&coord| world.chunk_cells(chunk, coord, cells_per_chunk, style.layout))
+            .collect();
+        world.bounds = coords
+            .iter()
+            .map(|&coord| chunk.tiled(coord))
+            .reduce(|a, b| a.union(&b))
+            .unwrap_or(chunk);
+        world.reindex();
+        world
+    }
+
+    /// The `count` cells of the chunk at `coord` in a world tiled with
+    /// `chunk`, laid out across the whole chunk so they meet its
+    /// neighbours' cells. They depend only on the world's seed and phase
+    /// count and the chunk, so a chunk comes back as it was generated.
+    pub fn chunk_cells(
+        &self,
+        chunk: Bounds,
+        coord: IVec3,
+        count: usize,
+        layout: SeedLayout,
+    ) -> Vec<HoneycombCell> {
+        let bounds = chunk.tiled(coord);
+        let mut rng = ChaCha8Rng::seed_from_u64(chunk_seed(self.seed, coord));
+        let phase_count = self.phases.len() as u32;
+        layout
+            .place(&mut rng, count, bounds.min, bounds.max)
+            .into_iter()
+            .map(|position| HoneycombCell {
+                position,
+                phase_index: rng.gen_range(0..phase_count),
+            })
+            .collect()
+    }
+
+    /// Replace the emitters with the cells `choice` picks, in cell order,
+    /// keeping the first `MAX_EMITTERS`. The draws depend only on the seed,
+    /// so the same choice always picks the same cells.
+    pub fn choose_emitters(&mut self, choice: &EmitterChoice) {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let chosen: Vec<usize> = (0..self.cells.len())
+            .filter(|&i| {
+                let probability = choice
+                    .phase_probability
+                    .get(self.cells[i].phase_index as usize)
+                    .copied()
+                    .unwrap_or(0.0);
+                // Draw for every cell so one phase's odds don't shift another's picks
+                let drawn = rng.gen::<f32>() < probability;
+                drawn || choice.cells.contains(&i)
+            })
+            .take(MAX_EMITTERS)
+            .collect();
+
+        self.emitters = chosen
+            .into_iter()
+            .map(|i| {
+                let position = self.cells[i].position;
+                let spacing = self
+                    .nearest_cells(position, 2)
+                    .get(1)
+                    .map_or(self.bounds.size().min_element(), |&j| {
+                        position.distance(self.cells[j].position)
+                    });
+                Emitter {
+                    position,
+                    radius: spacing * 0.5,
+                    cell: i as u32,
+                    intensity: rng.gen_range(0.5..1.5),
+                    _pad: [0.0; 2],
+                }
             })
             .collect();
+    }
+
+    /// Update the spatial queries after moving seeds. Changing cells'
+    /// phases needs no reindexing.
+    pub fn reindex(&mut self) {
+        self.index = KdTree::build(&self.cells);
+    }
+
+    /// The cell `point` lies in, the one with the nearest seed, as the
+    /// shaders find it; `None` outside the bounds, where the world is empty
+    pub fn cell_at(&self, point: Vec3) -> Option<usize> {
+        let inside = point.cmpge(self.bounds.min).all() && point.cmple(self.bounds.max).all();
+        inside
+            .then(|| self.index.nearest(point, 1).first().copied())
+            .flatten()
+    }
 
-        Self { phases, cells }
+    /// The `k` cells whose seeds are nearest `point`, nearest first, or
+    /// every cell if there are fewer
+    pub fn nearest_cells(&self, point: Vec3, k: usize) -> Vec<usize> {
+        self.index.nearest(point, k)
     }
+
+    /// Which cells share a membrane, found by cutting each cell out of the
+    /// box as a polyhedron. Costs a few cuts per neighbour, so build it once
+    /// and keep it; call again after moving seeds and reindexing.
+    pub fn adjacency(&self) -> Adjacency {
+        let _span = tracing::info_span!("adjacency", cells = self.cells.len()).entered();
+        let found: Vec<Vec<(usize, f32)>> = (0..self.cells.len())
+            .map(|cell| voronoi::cell_faces(self, cell))
+            .collect();
+
+        // Each face is found from both sides, with areas that differ by
+        // rounding. A sliver only one side kept still counts.
+        let mut faces = vec![Vec::new(); self.cells.len()];
+        for (i, list) in found.iter().enumerate() {
+            for &(j, area) in list {
+                let other = found[j]
+                    .iter()
+                    .find(|(k, _)| *k == i)
+                    .map(|&(_, area)| area);
+                if i < j || other.is_none() {
+                    let area = other.map_or(area, |other| (area + other) * 0.5);
+                    faces[i].push((j, area));
+                    faces[j].push((i, area));
+                }
+            }
+        }
+        for list in &mut faces {
+            list.sort_by_key(|&(j, _)| j);
+        }
+        Adjacency { faces }
+    }
+
+    /// For each cell, the cells it shares a membrane with, in index order
+    pub fn neighbours(&self) -> Vec<Vec<usize>> {
+        let adjacency = self.adjacency();
+        (0..adjacency.len())
+            .map(|cell| adjacency.neighbours(cell).collect())
+            .collect()
+    }
+}
+
+/// The Voronoi adjacency graph: which cells meet at a face, and the area of
+/// the membrane between them. Cells that only touch at an edge or corner
+/// aren't neighbours.
+#[derive(Clone, Debug, Default)]
+pub struct Adjacency {
+    /// For each cell, its neighbours and the shared face's area, by index
+    faces: Vec<Vec<(usize, f32)>>,
+}
+
+impl Adjacency {
+    /// Number of cells
+    pub fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    /// The cells `cell` shares a membrane with, in index order
+    pub fn neighbours(&self, cell: usize) -> impl Iterator<Item = usize> + '_ {
+        self.faces[cell].iter().map(|&(j, _)| j)
+    }
+
+    /// The neighbours of `cell` with the area of the membrane to each
+    pub fn faces(&self, cell: usize) -> &[(usize, f32)] {
+        &self.faces[cell]
+    }
+
+    /// Area of the membrane between `a` and `b`, `None` if they don't meet
+    pub fn area(&self, a: usize, b: usize) -> Option<f32> {
+        self.faces[a]
+            .binary_search_by_key(&b, |&(j, _)| j)
+            .ok()
+            .map(|i| self.faces[a][i].1)
+    }
+
+    /// Every membrane once, as the two cells, lower index first, and its area
+    pub fn edges(&self) -> impl Iterator<Item = (usize, usize, f32)> + '_ {
+        self.faces.iter().enumerate().flat_map(|(i, list)| {
+            list.iter()
+                .filter(move |&&(j, _)| i < j)
+                .map(move |&(j, area)| (i, j, area))
+        })
+    }
+}
+
+/// Seed for one chunk's cells, mixing the world seed with the chunk's
+/// coordinates so neighbouring chunks differ
+fn chunk_seed(seed: u64, coord: IVec3) -> u64 {
+    [coord.x, coord.y, coord.z].iter().fold(seed, |hash, &c| {
+        (hash ^ c as u32 as u64)
+            .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            .rotate_left(29)
+    })
 }
 
 fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (f32, f32, f32) {
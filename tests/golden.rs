@@ -0,0 +1,236 @@
+//! Golden-image regression tests: fixed scenes rendered headless and
+//! compared with the reference images in `tests/golden`, so changes to the
+//! shaders or world generation can't alter the picture unnoticed.
+//!
+//! Differences within a small tolerance pass, since adapters and drivers
+//! round differently. On a mismatch the rendered image is written next to
+//! the test binaries (the path is printed) for comparison. After an
+//! intended change, regenerate the references with
+//! `VENDEK_UPDATE_GOLDEN=1 cargo test --test golden` and review them like
+//! any other diff. Without a usable adapter the test is skipped.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use vendek::headless::{GpuError, HeadlessRenderer};
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 90;
+/// A channel may be off by this much before its pixel counts as different
+const CHANNEL_TOLERANCE: u8 = 12;
+/// Share of pixels that may differ before the images count as different
+const PIXEL_TOLERANCE: f64 = 0.005;
+
+struct Scene {
+    name: &'static str,
+    focus: [f32; 3],
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+    time: f32,
+    params: &'static [(&'static str, f64)],
+}
+
+const SCENES: &[Scene] = &[
+    Scene {
+        name: "default",
+        focus: [0.0, 0.0, 0.0],
+        yaw: 0.0,
+        pitch: 0.3,
+        distance: 35.0,
+        time: 0.0,
+        params: &[],
+    },
+    Scene {
+        name: "viridis-glow",
+        focus: [2.0, -1.0, 3.0],
+        yaw: 1.2,
+        pitch: -0.2,
+        distance: 26.0,
+        time: 2.5,
+        params: &[("palette", 7.0), ("membraneGlow", 1.2), ("density", 0.6)],
+    },
+    Scene {
+        name: "isosurface",
+        focus: [0.0, 0.0, 0.0],
+        yaw: -0.8,
+        pitch: 0.6,
+        distance: 30.0,
+        time: 0.0,
+        params: &[("renderMode", 1.0)],
+    },
+    Scene {
+        name: "membranes",
+        focus: [0.0, 0.0, 0.0],
+        yaw: 0.5,
+        pitch: 0.3,
+        distance: 30.0,
+        time: 0.0,
+        params: &[("renderMode", 3.0)],
+    },
+    // Lit from over the camera's shoulder, so the scattered light and the
+    // shadows the cells cast on each other both show
+    Scene {
+        name: "scattering",
+        focus: [0.0, 0.0, 0.0],
+        yaw: 0.4,
+        pitch: 0.3,
+        distance: 32.0,
+        time: 0.0,
+        params: &[("scattering", 1.5), ("lightYaw", 0.4), ("lightPitch", 0.6)],
+    },
+    Scene {
+        name: "starfield",
+        focus: [0.0, 0.0, 0.0],
+        yaw: 2.0,
+        pitch: 0.2,
+        distance: 60.0,
+        time: 0.0,
+        params: &[("background", 1.0)],
+    },
+    Scene {
+        name: "lod-falloff",
+        focus: [0.0, 0.0, 0.0],
+        yaw: 0.9,
+        pitch: 0.4,
+        distance: 40.0,
+        time: 1.0,
+        params: &[("lodFalloff", 2.0)],
+    },
+    Scene {
+        name: "denoise",
+        focus: [0.0, 0.0, 0.0],
+        yaw: -0.4,
+        pitch: 0.3,
+        distance: 32.0,
+        time: 0.5,
+        params: &[("stepSize", 0.5), ("denoise", 1.0)],
+    },
+    Scene {
+        name: "debug-steps",
+        focus: [0.0, 0.0, 0.0],
+        yaw: 0.6,
+        pitch: 0.3,
+        distance: 32.0,
+        time: 0.0,
+        params: &[("debugView", 1.0)],
+    },
+    Scene {
+        name: "debug-normals",
+        focus: [0.0, 0.0, 0.0],
+        yaw: 0.6,
+        pitch: 0.3,
+        distance: 32.0,
+        time: 0.0,
+        params: &[("debugView", 3.0)],
+    },
+    Scene {
+        name: "debug-grid",
+        focus: [0.0, 0.0, 0.0],
+        yaw: 0.6,
+        pitch: 0.3,
+        distance: 32.0,
+        time: 0.0,
+        params: &[("debugView", 5.0)],
+    },
+];
+
+#[test]
+fn golden_images() {
+    let mut renderer = match HeadlessRenderer::new(WIDTH, HEIGHT) {
+        Ok(renderer) => renderer,
+        Err(e @ GpuError::NoAdapter { .. }) => {
+            eprintln!("skipping golden-image tests: {}", e);
+            return;
+        }
+        Err(e) => panic!("could not set up headless rendering: {}", e),
+    };
+    eprintln!("rendering golden images on {}", renderer.adapter());
+
+    let update = std::env::var_os("VENDEK_UPDATE_GOLDEN").is_some();
+    let mut failures = Vec::new();
+    for scene in SCENES {
+        renderer.reset_params();
+        for &(name, value) in scene.params {
+            renderer.set_param(name, value).unwrap();
+        }
+        renderer.set_camera(scene.focus, scene.yaw, scene.pitch, scene.distance);
+        let pixels = renderer.render(scene.time);
+
+        let reference = reference_path(scene.name);
+        if update {
+            write_png(&reference, &pixels);
+            eprintln!("updated {}", reference.display());
+            continue;
+        }
+        let Some(expected) = read_png(&reference) else {
+            failures.push(format!(
+                "{}: no reference image at {}; run with VENDEK_UPDATE_GOLDEN=1 to create it",
+                scene.name,
+                reference.display()
+            ));
+            continue;
+        };
+        let different = pixels
+            .chunks(4)
+            .zip(expected.chunks(4))
+            .filter(|(a, b)| {
+                a.iter()
+                    .zip(b.iter())
+                    .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE)
+            })
+            .count();
+        let share = different as f64 / (WIDTH * HEIGHT) as f64;
+        if expected.len() != pixels.len() || share > PIXEL_TOLERANCE {
+            let actual = actual_path(scene.name);
+            write_png(&actual, &pixels);
+            failures.push(format!(
+                "{}: {:.2}% of pixels differ from {}; rendered image written to {}",
+                scene.name,
+                share * 100.0,
+                reference.display(),
+                actual.display()
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+fn reference_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name))
+}
+
+fn actual_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("golden-{}.png", name))
+}
+
+fn read_png(path: &Path) -> Option<Vec<u8>> {
+    let decoder = png::Decoder::new(File::open(path).ok()?);
+    let mut reader = decoder.read_info().ok()?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).ok()?;
+    if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+        return None;
+    }
+    pixels.truncate(info.buffer_size());
+    Some(pixels)
+}
+
+fn write_png(path: &Path, pixels: &[u8]) {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).unwrap();
+    }
+    let file = BufWriter::new(File::create(path).unwrap());
+    let mut encoder = png::Encoder::new(file, WIDTH, HEIGHT);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    encoder
+        .write_header()
+        .unwrap()
+        .write_image_data(pixels)
+        .unwrap();
+}
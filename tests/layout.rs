@@ -0,0 +1,205 @@
+//! Struct layouts shared between Rust and WGSL. The shaders declare the
+//! uniform and world structs in `types.wgsl`, and the `#[repr(C)]` types the
+//! host uploads must agree with them byte for byte; a field added on one
+//! side only, or padding WGSL inserts where Rust doesn't, shifts every
+//! field after it without any error. This parses `types.wgsl` with naga and
+//! compares each struct's size and named fields' offsets with the Rust
+//! type's. Padding fields, named with a leading underscore, only count
+//! toward the size.
+
+use std::collections::BTreeMap;
+use std::mem::{offset_of, size_of};
+
+use vendek::bvh::BvhNode;
+use vendek::membrane::MembranePair;
+use vendek::uniforms::{EmitterLights, FrameUniforms, RaymarchParams};
+use vendek::world::{Emitter, GridCell, HoneycombCell, VendekPhase};
+
+/// Size and field offsets of a Rust type
+struct Layout {
+    size: usize,
+    offsets: BTreeMap<String, usize>,
+}
+
+macro_rules! layout {
+    ($ty:ty { $($field:ident),* $(,)? }) => {
+        Layout {
+            size: size_of::<$ty>(),
+            offsets: [$((stringify!($field).to_string(), offset_of!($ty, $field))),*]
+                .into_iter()
+                .collect(),
+        }
+    };
+}
+
+fn rust_layouts() -> BTreeMap<&'static str, Layout> {
+    BTreeMap::from([
+        (
+            "FrameUniforms",
+            layout!(FrameUniforms {
+                view_proj,
+                inv_view_proj,
+                camera_position,
+                time,
+                resolution,
+                near,
+                far,
+                viewport_origin,
+                sample_index,
+                sample_limit,
+            }),
+        ),
+        (
+            "RaymarchParams",
+            layout!(RaymarchParams {
+                volume_min,
+                volume_max,
+                max_steps,
+                step_size,
+                membrane_thickness,
+                membrane_glow,
+                density_multiplier,
+                enable_coupling,
+                palette,
+                clip_plane_count,
+                clip_planes,
+                render_mode,
+                visible_phases,
+                ripple_amplitude,
+                ripple_speed,
+                reaction_strength,
+                heat_strength,
+                min_transmittance,
+                scattering,
+                light_direction,
+                emission,
+                background,
+                background_seed,
+                lod_falloff,
+                refine_threshold,
+                debug_view,
+            }),
+        ),
+        (
+            "VendekPhase",
+            layout!(VendekPhase {
+                color_density,
+                scattering,
+                membrane_params,
+                phase_id,
+                noise_scale,
+                noise_octaves,
+                noise_strength,
+            }),
+        ),
+        (
+            "MembranePair",
+            layout!(MembranePair {
+                color,
+                thickness,
+                glow,
+            }),
+        ),
+        (
+            "HoneycombCell",
+            layout!(HoneycombCell {
+                position,
+                phase_index,
+            }),
+        ),
+        (
+            "Emitter",
+            layout!(Emitter {
+                position,
+                radius,
+                cell,
+                intensity,
+            }),
+        ),
+        ("EmitterLights", layout!(EmitterLights { count, emitters })),
+        (
+            "BvhNode",
+            layout!(BvhNode {
+                min,
+                right,
+                max,
+                count,
+                cells,
+            }),
+        ),
+        (
+            "GridCell",
+            layout!(GridCell {
+                cell_indices,
+                count,
+            }),
+        ),
+    ])
+}
+
+/// Size and field offsets of each struct `types.wgsl` declares, as naga
+/// lays them out
+fn wgsl_layouts() -> BTreeMap<String, Layout> {
+    let source = include_str!("../src/shaders/types.wgsl");
+    let module = naga::front::wgsl::parse_str(source).expect("types.wgsl should parse");
+    let mut layouter = naga::proc::Layouter::default();
+    layouter
+        .update(module.to_ctx())
+        .expect("types.wgsl should lay out");
+
+    let mut layouts = BTreeMap::new();
+    for (handle, ty) in module.types.iter() {
+        let (Some(name), naga::TypeInner::Struct { members, .. }) = (&ty.name, &ty.inner) else {
+            continue;
+        };
+        let offsets = members
+            .iter()
+            .filter_map(|member| {
+                let name = member.name.clone()?;
+                (!name.starts_with('_')).then_some((name, member.offset as usize))
+            })
+            .collect();
+        layouts.insert(
+            name.clone(),
+            Layout {
+                size: layouter[handle].size as usize,
+                offsets,
+            },
+        );
+    }
+    layouts
+}
+
+#[test]
+fn rust_structs_match_wgsl() {
+    let wgsl = wgsl_layouts();
+    let mut mismatches = Vec::new();
+    for (name, rust) in rust_layouts() {
+        let Some(shader) = wgsl.get(name) else {
+            mismatches.push(format!("{}: not declared in types.wgsl", name));
+            continue;
+        };
+        if rust.size != shader.size {
+            mismatches.push(format!(
+                "{}: {} bytes in Rust but {} in WGSL",
+                name, rust.size, shader.size
+            ));
+        }
+        for (field, &offset) in &rust.offsets {
+            match shader.offsets.get(field) {
+                Some(&expected) if expected == offset => {}
+                Some(&expected) => mismatches.push(format!(
+                    "{}.{}: at byte {} in Rust but {} in WGSL",
+                    name, field, offset, expected
+                )),
+                None => mismatches.push(format!("{}.{}: missing in WGSL", name, field)),
+            }
+        }
+        for field in shader.offsets.keys() {
+            if !rust.offsets.contains_key(field) {
+                mismatches.push(format!("{}.{}: missing in Rust", name, field));
+            }
+        }
+    }
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
@@ -0,0 +1,149 @@
+//! OSC decoding tests. The listener takes packets from anyone on the
+//! network, so malformed ones must be turned away without losing the good
+//! messages around them or passing values the renderer can't use.
+
+use glam::Vec2;
+use vendek::osc::{decode_packet, OscCommand, OscMessage};
+
+/// A string as OSC writes it: terminated and padded to four bytes
+fn string(text: &str) -> Vec<u8> {
+    let mut bytes = text.as_bytes().to_vec();
+    bytes.push(0);
+    while !bytes.len().is_multiple_of(4) {
+        bytes.push(0);
+    }
+    bytes
+}
+
+/// A message with float arguments
+fn message(address: &str, args: &[f32]) -> Vec<u8> {
+    let tags = format!(",{}", "f".repeat(args.len()));
+    let mut bytes = string(address);
+    bytes.extend(string(&tags));
+    for arg in args {
+        bytes.extend(arg.to_be_bytes());
+    }
+    bytes
+}
+
+/// A bundle of `elements`, each prefixed with its size
+fn bundle(elements: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = string("#bundle");
+    bytes.extend(1u64.to_be_bytes());
+    for element in elements {
+        bytes.extend((element.len() as i32).to_be_bytes());
+        bytes.extend(element);
+    }
+    bytes
+}
+
+fn decoded(packet: &[u8]) -> Vec<Option<OscMessage>> {
+    decode_packet(packet).into_iter().map(Result::ok).collect()
+}
+
+fn glow(value: f64) -> Option<OscMessage> {
+    Some(OscMessage {
+        address: "/vendek/membraneGlow".to_string(),
+        args: vec![value],
+    })
+}
+
+#[test]
+fn plain_message() {
+    let packet = message("/vendek/membraneGlow", &[0.5]);
+    assert_eq!(decoded(&packet), [glow(0.5)]);
+}
+
+#[test]
+fn argument_types() {
+    let mut packet = string("/vendek/camera/pan");
+    packet.extend(string(",idhTF"));
+    packet.extend(3i32.to_be_bytes());
+    packet.extend(0.25f64.to_be_bytes());
+    packet.extend((-7i64).to_be_bytes());
+    let message = decode_packet(&packet).remove(0).unwrap();
+    assert_eq!(message.args, [3.0, 0.25, -7.0, 1.0, 0.0]);
+}
+
+#[test]
+fn message_without_type_tags() {
+    let packet = string("/vendek/camera/zoom");
+    let message = decode_packet(&packet).remove(0).unwrap();
+    assert!(message.args.is_empty());
+}
+
+#[test]
+fn nested_bundles() {
+    let inner = bundle(&[message("/vendek/membraneGlow", &[1.0])]);
+    let packet = bundle(&[message("/vendek/membraneGlow", &[0.5]), inner]);
+    assert_eq!(decoded(&packet), [glow(0.5), glow(1.0)]);
+}
+
+#[test]
+fn bad_element_skipped_alone() {
+    let mut unsupported = string("/vendek/membraneGlow");
+    unsupported.extend(string(",s"));
+    unsupported.extend(string("bright"));
+    let packet = bundle(&[
+        message("/vendek/membraneGlow", &[0.5]),
+        unsupported,
+        string("no-slash"),
+        message("/vendek/membraneGlow", &[1.0]),
+    ]);
+    assert_eq!(decoded(&packet), [glow(0.5), None, None, glow(1.0)]);
+}
+
+#[test]
+fn truncated_packets() {
+    let packet = message("/vendek/membraneGlow", &[0.5]);
+    // Cut just after the address it is a message without type tags, as
+    // very old senders send
+    let address = string("/vendek/membraneGlow").len();
+    for length in (0..packet.len()).filter(|&length| length != address) {
+        let messages = decode_packet(&packet[..length]);
+        assert!(
+            messages.iter().all(|message| message.is_err()),
+            "{} bytes decoded",
+            length
+        );
+    }
+    // A bundle too short for its time tag
+    assert!(decode_packet(&string("#bundle"))[0].is_err());
+}
+
+#[test]
+fn bad_element_sizes() {
+    let good = message("/vendek/membraneGlow", &[0.5]);
+    for size in [-4i32, i32::MIN, 1 << 20] {
+        let mut packet = bundle(std::slice::from_ref(&good));
+        packet.extend(size.to_be_bytes());
+        packet.extend(&good);
+        let messages = decoded(&packet);
+        assert_eq!(messages, [glow(0.5), None], "size {}", size);
+    }
+}
+
+#[test]
+fn commands() {
+    let command = |address: &str, args: &[f64]| {
+        OscCommand::from_message(&OscMessage {
+            address: address.to_string(),
+            args: args.to_vec(),
+        })
+        .ok()
+    };
+    assert_eq!(
+        command("/vendek/density", &[2.0]),
+        Some(OscCommand::SetParam("density".to_string(), 2.0))
+    );
+    assert_eq!(
+        command("/vendek/camera/orbit", &[3.0, -1.0]),
+        Some(OscCommand::Orbit(Vec2::new(3.0, -1.0)))
+    );
+    assert_eq!(command("/vendek/camera/orbit", &[3.0]), None);
+    assert_eq!(command("/other/density", &[2.0]), None);
+    for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+        assert_eq!(command("/vendek/density", &[value]), None);
+        assert_eq!(command("/vendek/camera/zoom", &[value]), None);
+    }
+}
@@ -0,0 +1,119 @@
+//! World snapshot tests: worlds generated from fixed seeds, hashed down to
+//! their phases and cells, so a refactor of seed placement or phase
+//! generation can't quietly change the world an existing seed gives. Seeds
+//! are shared in links, saved settings and session logs, and should keep
+//! meaning the same world.
+//!
+//! The hashes are of the exact bits of each array. When a change to
+//! generation is intended, bump `vendek::world::GENERATOR_VERSION` and
+//! paste the hashes the failure prints over the ones below.
+
+#![cfg(any(feature = "viewer", feature = "world-only"))]
+
+use vendek::world::{Bounds, HoneycombWorld, SeedLayout, WorldStyle, GENERATOR_VERSION};
+
+/// The generator version the hashes below were taken at
+const SNAPSHOT_VERSION: u32 = 1;
+
+struct Snapshot {
+    name: &'static str,
+    seed: u64,
+    cells: usize,
+    phases: usize,
+    layout: SeedLayout,
+    /// FNV-1a hashes of the phases' and the cells' bytes
+    hashes: (u64, u64),
+}
+
+const SNAPSHOTS: &[Snapshot] = &[
+    Snapshot {
+        name: "default",
+        seed: 42,
+        cells: 200,
+        phases: 6,
+        layout: SeedLayout::Uniform,
+        hashes: (0x51a7_6442_13c2_8ba4, 0x5606_d89e_bdba_0e00),
+    },
+    Snapshot {
+        name: "large",
+        seed: 7,
+        cells: 2000,
+        phases: 12,
+        layout: SeedLayout::Uniform,
+        hashes: (0x3d98_33ae_8272_8e14, 0xcb8c_29ef_ec43_5331),
+    },
+    Snapshot {
+        name: "relaxed",
+        seed: 3,
+        cells: 300,
+        phases: 8,
+        layout: SeedLayout::Relaxed,
+        hashes: (0x73bb_737d_d997_e214, 0xafd9_fc1d_93b7_f8d4),
+    },
+    Snapshot {
+        name: "lattice",
+        seed: 11,
+        cells: 250,
+        phases: 5,
+        layout: SeedLayout::Lattice,
+        hashes: (0x23d6_8523_e9fd_9cd6, 0xb781_5898_55cf_f8a8),
+    },
+    Snapshot {
+        name: "clustered",
+        seed: 19,
+        cells: 400,
+        phases: 10,
+        layout: SeedLayout::Clustered,
+        hashes: (0x6d1b_fda9_2cbc_2355, 0x20da_db1d_d4c7_50a7),
+    },
+];
+
+/// 64-bit FNV-1a, which unlike the standard library's hashers is fixed
+/// across Rust versions and platforms
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn hashes(snapshot: &Snapshot) -> (u64, u64) {
+    let style = WorldStyle {
+        layout: snapshot.layout,
+        ..WorldStyle::default()
+    };
+    let world = HoneycombWorld::generate_styled(
+        snapshot.seed,
+        snapshot.cells,
+        snapshot.phases,
+        Bounds::default(),
+        &style,
+    );
+    (
+        fnv1a(bytemuck::cast_slice(&world.phases)),
+        fnv1a(bytemuck::cast_slice(&world.cells)),
+    )
+}
+
+#[test]
+fn seeds_keep_their_worlds() {
+    assert_eq!(
+        GENERATOR_VERSION, SNAPSHOT_VERSION,
+        "GENERATOR_VERSION was bumped; update the hashes and SNAPSHOT_VERSION"
+    );
+    let mut failures = Vec::new();
+    for snapshot in SNAPSHOTS {
+        let actual = hashes(snapshot);
+        if actual != snapshot.hashes {
+            failures.push(format!(
+                "{}: hashes are ({:#018x}, {:#018x}), expected ({:#018x}, {:#018x})",
+                snapshot.name, actual.0, actual.1, snapshot.hashes.0, snapshot.hashes.1
+            ));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "Seeds generate different worlds; if that is intended, bump GENERATOR_VERSION \
+         and update the hashes:\n{}",
+        failures.join("\n")
+    );
+}
@@ -1,19 +1,56 @@
-import init from '../pkg/vendek.js';
+import init, { wasm_main } from '../pkg/vendek.js';
+import { connectLive } from './live.js';
+
+const STAGES = {
+    world: 'Generating world…',
+    adapter: 'Finding a GPU…',
+    device: 'Opening the GPU…',
+    pipelines: 'Compiling shaders…',
+};
+
+// Follow startup in the page's #loading element, if it has one
+function showProgress(viewer) {
+    const loading = document.getElementById('loading');
+    if (!loading) {
+        return;
+    }
+    const stage = document.getElementById('loading-stage');
+    const fill = document.getElementById('loading-fill');
+    viewer.on_progress((name, fraction) => {
+        if (stage) stage.textContent = STAGES[name] ?? name;
+        if (fill) fill.style.width = `${fraction * 100}%`;
+    });
+    viewer.on_ready(() => loading.remove());
+    viewer.on_error(() => loading.remove());
+}
 
 async function run() {
+    let viewer;
     try {
         await init();
+        // The address bar always holds a link to the current view
+        viewer = wasm_main({ container: 'canvas-container', syncHash: true, persist: true });
+        showProgress(viewer);
     } catch (e) {
         console.error("Failed to initialize:", e);
         document.body.innerHTML = `
             <div style="color: white; padding: 20px; font-family: sans-serif;">
-                <h1>WebGPU not available</h1>
-                <p>This application requires WebGPU support.</p>
-                <p>Please use Chrome 113+ or another WebGPU-enabled browser.</p>
+                <h1>Vendek could not start</h1>
+                <p>This application needs a browser with WebGPU or WebGL2 support.</p>
                 <p style="margin-top: 20px; color: #888;">Error: ${e}</p>
             </div>
         `;
+        return;
     }
+
+    // GPU setup finishes asynchronously; if it fails the viewer replaces its
+    // canvas with an explanation and reports the error through on_error
+
+    // Viewer handle for page scripts and the browser console
+    window.vendek = viewer;
+    // Parameter changes shared with other pages through the dev server
+    window.vendekLive = connectLive(viewer);
+    window.onVendekReady?.(viewer);
 }
 
 run();
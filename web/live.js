@@ -0,0 +1,102 @@
+// Live control: shares parameters and the camera between every page open on
+// the dev server (`cargo run --bin serve`) through its /ws WebSocket, so a
+// control page on one device can drive the viewer on another, or a lecturer
+// can steer while the audience follows. Messages are JSON of the form
+// {"params": {"membraneGlow": 0.8}} or {"camera": {"focus": [0, 0, 0],
+// "yaw": 0.3, "pitch": 0.4, "distance": 35}}; whoever wrote last wins. In
+// `--watch` mode the server also sends {"reload": true} after a rebuild.
+
+const RETRY_MIN_MS = 1000;
+const RETRY_MAX_MS = 30000;
+// Camera updates are sent at most this often while the view is moving
+const CAMERA_INTERVAL_MS = 50;
+
+// Connect `viewer` to the channel. Remote changes are applied through
+// set_params and set_camera, and parameters are then passed to
+// window.onVendekRemoteParams, if set. The returned object's `shareCamera`
+// and `followCamera` turn camera sync off in either direction.
+export function connectLive(viewer) {
+    let socket = null;
+    let retry = RETRY_MIN_MS;
+    let everOpened = false;
+
+    const live = {
+        shareCamera: true,
+        followCamera: true,
+
+        // Send parameter changes to the other pages
+        sendParams(params) {
+            send({ params });
+        },
+    };
+
+    function send(message) {
+        if (socket.readyState === WebSocket.OPEN) {
+            socket.send(JSON.stringify(message));
+        }
+    }
+
+    function receive(message) {
+        if (message.reload) {
+            location.reload();
+            return;
+        }
+        if (message.camera && live.followCamera) {
+            try {
+                viewer.set_camera(message.camera);
+            } catch (e) {
+                console.warn('Ignoring remote camera:', e);
+            }
+        }
+        if (!message.params || typeof message.params !== 'object') return;
+        try {
+            viewer.set_params(message.params);
+        } catch (e) {
+            console.warn('Ignoring remote parameters:', e);
+            return;
+        }
+        window.onVendekRemoteParams?.(message.params);
+    }
+
+    // Moves come every frame while the view glides; send the latest one
+    // now and then rather than all of them
+    let pendingCamera = null;
+    viewer.on_camera(camera => {
+        if (!live.shareCamera) return;
+        const waiting = pendingCamera !== null;
+        pendingCamera = camera;
+        if (waiting) return;
+        setTimeout(() => {
+            send({ camera: pendingCamera });
+            pendingCamera = null;
+        }, CAMERA_INTERVAL_MS);
+    });
+
+    function connect() {
+        const scheme = location.protocol === 'https:' ? 'wss:' : 'ws:';
+        socket = new WebSocket(`${scheme}//${location.host}/ws`);
+        socket.addEventListener('open', () => {
+            everOpened = true;
+            retry = RETRY_MIN_MS;
+        });
+        socket.addEventListener('message', event => {
+            let message;
+            try {
+                message = JSON.parse(event.data);
+            } catch {
+                return;
+            }
+            receive(message);
+        });
+        // Reconnect after the server restarts; a host without the channel,
+        // such as a static file server, is left alone after the first try
+        socket.addEventListener('close', () => {
+            if (!everOpened) return;
+            setTimeout(connect, retry);
+            retry = Math.min(retry * 2, RETRY_MAX_MS);
+        });
+    }
+
+    connect();
+    return live;
+}